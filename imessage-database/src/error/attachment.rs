@@ -20,9 +20,18 @@ impl Display for AttachmentError {
             AttachmentError::FileNotFound(path) => {
                 write!(fmt, "File not found at location: {path}")
             }
-            AttachmentError::Unreadable(path, why) => {
-                write!(fmt, "Unable to read file at {path}: {why}")
+            AttachmentError::Unreadable(path, _) => {
+                write!(fmt, "Unable to read file at {path}")
             }
         }
     }
 }
+
+impl std::error::Error for AttachmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AttachmentError::Unreadable(_, why) => Some(why),
+            _ => None,
+        }
+    }
+}
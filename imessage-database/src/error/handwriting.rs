@@ -21,11 +21,11 @@ pub enum HandwritingError {
 impl Display for HandwritingError {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
         match self {
-            HandwritingError::ProtobufError(why) => {
-                write!(fmt, "failed to parse handwriting protobuf: {why}")
+            HandwritingError::ProtobufError(_) => {
+                write!(fmt, "failed to parse handwriting protobuf")
             }
             HandwritingError::InvalidFrameSize(size) => write!(fmt, "expected size 8, got {size}"),
-            HandwritingError::XZError(why) => write!(fmt, "failed to decompress xz: {why}"),
+            HandwritingError::XZError(_) => write!(fmt, "failed to decompress xz"),
             HandwritingError::CompressionUnknown => write!(fmt, "compress method unknown"),
             HandwritingError::InvalidStrokesLength(index, length) => {
                 write!(fmt, "can't access index {index} on array length {length}")
@@ -37,9 +37,20 @@ impl Display for HandwritingError {
             HandwritingError::InvalidDecompressedLength(expected, got) => {
                 write!(fmt, "expected decompressed length of {expected}, got {got}")
             }
-            HandwritingError::ResizeError(why) => {
-                write!(fmt, "failed to resize handwriting coordinates: {why}")
+            HandwritingError::ResizeError(_) => {
+                write!(fmt, "failed to resize handwriting coordinates")
             }
         }
     }
 }
+
+impl std::error::Error for HandwritingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HandwritingError::ProtobufError(why) => Some(why),
+            HandwritingError::XZError(why) => Some(why),
+            HandwritingError::ResizeError(why) => Some(why),
+            _ => None,
+        }
+    }
+}
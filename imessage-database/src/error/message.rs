@@ -17,6 +17,8 @@ pub enum MessageError {
     TypedStreamParseError(TypedStreamError),
     PlistParseError(PlistParseError),
     InvalidTimestamp(i64),
+    NoAssociatedGuid,
+    InvalidAssociatedGuid(String),
 }
 
 impl Display for MessageError {
@@ -24,21 +26,33 @@ impl Display for MessageError {
         match self {
             MessageError::MissingData => write!(fmt, "No attributedBody found!"),
             MessageError::NoText => write!(fmt, "Message has no text!"),
-            MessageError::StreamTypedParseError(why) => {
-                write!(
-                    fmt,
-                    "Failed to parse attributedBody with legacy parser: {why}"
-                )
+            MessageError::StreamTypedParseError(_) => {
+                write!(fmt, "Failed to parse attributedBody with legacy parser")
             }
-            MessageError::TypedStreamParseError(why) => {
-                write!(fmt, "Failed to parse attributedBody: {why}")
+            MessageError::TypedStreamParseError(_) => {
+                write!(fmt, "Failed to parse attributedBody")
             }
-            MessageError::PlistParseError(why) => {
-                write!(fmt, "Failed to parse plist data: {why}")
+            MessageError::PlistParseError(_) => {
+                write!(fmt, "Failed to parse plist data")
             }
             MessageError::InvalidTimestamp(when) => {
                 write!(fmt, "Timestamp is invalid: {when}")
             }
+            MessageError::NoAssociatedGuid => write!(fmt, "Message has no associated GUID!"),
+            MessageError::InvalidAssociatedGuid(guid) => {
+                write!(fmt, "Associated GUID is malformed: {guid}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MessageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MessageError::StreamTypedParseError(why) => Some(why),
+            MessageError::TypedStreamParseError(why) => Some(why),
+            MessageError::PlistParseError(why) => Some(why),
+            _ => None,
         }
     }
 }
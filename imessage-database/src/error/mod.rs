@@ -6,6 +6,7 @@ pub mod attachment;
 pub mod handwriting;
 pub mod message;
 pub mod plist;
+pub mod query_context;
 pub mod streamtyped;
 pub mod table;
 pub mod typedstream;
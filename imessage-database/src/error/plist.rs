@@ -55,3 +55,14 @@ impl Display for PlistParseError {
         }
     }
 }
+
+impl std::error::Error for PlistParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // These variants display the wrapped error, so its source comes next
+        match self {
+            PlistParseError::StreamTypedError(why) => std::error::Error::source(why),
+            PlistParseError::HandwritingError(why) => std::error::Error::source(why),
+            _ => None,
+        }
+    }
+}
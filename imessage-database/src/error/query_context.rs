@@ -0,0 +1,25 @@
+/*!
+ Errors that can happen when building query filters.
+*/
+
+use std::fmt::{Display, Formatter, Result};
+
+/// Errors that can happen when populating a [`QueryContext`](crate::util::query_context::QueryContext)
+#[derive(Debug)]
+pub enum QueryContextError {
+    InvalidDate(String),
+    InvalidColumn(String),
+}
+
+impl Display for QueryContextError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            QueryContextError::InvalidDate(date) => write!(fmt, "Invalid date provided: {date}"),
+            QueryContextError::InvalidColumn(column) => {
+                write!(fmt, "Invalid column name provided: {column}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryContextError {}
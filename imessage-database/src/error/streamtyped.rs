@@ -23,3 +23,5 @@ impl Display for StreamTypedError {
         }
     }
 }
+
+impl std::error::Error for StreamTypedError {}
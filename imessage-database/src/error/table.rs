@@ -14,18 +14,67 @@ pub enum TableError {
     Messages(rusqlite::Error),
     CannotConnect(String),
     CannotRead(std::io::Error),
+    CannotMerge(String),
 }
 
 impl Display for TableError {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
         match self {
-            TableError::Attachment(why) => write!(fmt, "Failed to parse attachment row: {why}"),
-            TableError::ChatToHandle(why) => write!(fmt, "Failed to parse chat handle row: {why}"),
-            TableError::Chat(why) => write!(fmt, "Failed to parse chat row: {why}"),
-            TableError::Handle(why) => write!(fmt, "Failed to parse handle row: {why}"),
-            TableError::Messages(why) => write!(fmt, "Failed to parse messages row: {why}"),
+            TableError::Attachment(_) => write!(fmt, "Failed to parse attachment row"),
+            TableError::ChatToHandle(_) => write!(fmt, "Failed to parse chat handle row"),
+            TableError::Chat(_) => write!(fmt, "Failed to parse chat row"),
+            TableError::Handle(_) => write!(fmt, "Failed to parse handle row"),
+            TableError::Messages(_) => write!(fmt, "Failed to parse messages row"),
             TableError::CannotConnect(why) => write!(fmt, "{why}"),
             TableError::CannotRead(why) => write!(fmt, "{why}"),
+            TableError::CannotMerge(why) => write!(fmt, "{why}"),
         }
     }
 }
+
+impl std::error::Error for TableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TableError::Attachment(why) => Some(why),
+            TableError::ChatToHandle(why) => Some(why),
+            TableError::Chat(why) => Some(why),
+            TableError::Handle(why) => Some(why),
+            TableError::Messages(why) => Some(why),
+            // This variant displays the wrapped error, so its source comes next
+            TableError::CannotRead(why) => std::error::Error::source(why),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use rusqlite::Connection;
+
+    use crate::{
+        error::table::TableError,
+        tables::{messages::Message, table::Table},
+    };
+
+    #[test]
+    fn can_get_source_of_database_error() {
+        // An empty database has no `message` table to query
+        let db = Connection::open_in_memory().unwrap();
+        let err = Message::get(&db).err().unwrap();
+
+        assert!(matches!(err, TableError::Messages(_)));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<rusqlite::Error>().is_some());
+        // The cause is only reported once, by the source
+        assert_eq!(err.to_string(), "Failed to parse messages row");
+        assert!(source.to_string().contains("no such table"));
+    }
+
+    #[test]
+    fn cant_get_source_of_connection_error() {
+        let err = TableError::CannotConnect(String::from("No database found!"));
+        assert!(err.source().is_none());
+    }
+}
@@ -26,12 +26,20 @@ impl Display for TypedStreamError {
                 write!(fmt, "Index {idx:x} is outside of range {len:x}!")
             }
             TypedStreamError::InvalidHeader => write!(fmt, "Invalid typedstream header!"),
-            TypedStreamError::SliceError(why) => {
-                write!(fmt, "Unable to slice source stream: {why}")
-            }
-            TypedStreamError::StringParseError(why) => write!(fmt, "Failed to parse string: {why}"),
+            TypedStreamError::SliceError(_) => write!(fmt, "Unable to slice source stream"),
+            TypedStreamError::StringParseError(_) => write!(fmt, "Failed to parse string"),
             TypedStreamError::InvalidArray => write!(fmt, "Failed to parse array data"),
             TypedStreamError::InvalidPointer(why) => write!(fmt, "Failed to parse pointer: {why}"),
         }
     }
 }
+
+impl std::error::Error for TypedStreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TypedStreamError::SliceError(why) => Some(why),
+            TypedStreamError::StringParseError(why) => Some(why),
+            _ => None,
+        }
+    }
+}
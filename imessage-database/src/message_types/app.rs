@@ -64,6 +64,38 @@ impl<'a> BalloonProvider<'a> for AppMessage<'a> {
 }
 
 impl AppMessage<'_> {
+    /// Describe the balloon the way it reads in the transcript, like `GamePigeon: 8 Ball — Your turn.`
+    ///
+    /// The app name and the balloon's title come first, followed by its captions. Returns `None` if the balloon
+    /// has no caption text, since the app name alone does not describe the message.
+    #[must_use]
+    pub fn caption_summary(&self) -> Option<String> {
+        let captions = [
+            self.caption,
+            self.subcaption,
+            self.trailing_caption,
+            self.trailing_subcaption,
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+        if captions.is_empty() {
+            return None;
+        }
+
+        let heading = match (self.app_name, self.title.or(self.ldtext)) {
+            (Some(app_name), Some(title)) => Some(format!("{app_name}: {title}")),
+            (app_name, title) => app_name.or(title).map(String::from),
+        };
+        Some(match heading {
+            Some(heading) => format!("{heading} — {captions}"),
+            None => captions,
+        })
+    }
+
     /// Parse key/value pairs from the query string in the balloon's a URL
     #[must_use]
     pub fn parse_query_string(&self) -> HashMap<&str, &str> {
@@ -232,6 +264,72 @@ mod tests {
         assert_eq!(balloon, expected);
     }
 
+    #[test]
+    fn test_summarize_game() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/Game.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.caption_summary(),
+            Some("GamePigeon: Dots & Boxes — Your move.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_summarize_captions() {
+        let balloon = AppMessage {
+            image: None,
+            url: None,
+            title: Some("8 Ball"),
+            subtitle: None,
+            caption: Some("Your turn."),
+            subcaption: Some("Round 2"),
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: Some("GamePigeon"),
+            ldtext: Some("Let's play"),
+        };
+        assert_eq!(
+            balloon.caption_summary(),
+            Some("GamePigeon: 8 Ball — Your turn. Round 2".to_string())
+        );
+
+        let balloon = AppMessage {
+            app_name: None,
+            title: None,
+            ldtext: None,
+            ..balloon
+        };
+        assert_eq!(
+            balloon.caption_summary(),
+            Some("Your turn. Round 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cant_summarize_without_caption() {
+        let balloon = AppMessage {
+            image: None,
+            url: None,
+            title: Some("Photos"),
+            subtitle: None,
+            caption: None,
+            subcaption: Some(" "),
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: Some("Photos"),
+            ldtext: None,
+        };
+        assert_eq!(balloon.caption_summary(), None);
+    }
+
     #[test]
     fn test_parse_business() {
         let plist_path = current_dir()
@@ -1,12 +1,15 @@
 /*!
  These are the link previews that iMessage generates when sending Collaboration links, i.e. from Pages or Freeform.
+
+ [`SharedItem`] summarizes these invites, along with other items shared from an app, so they can be described in a
+ single line.
 */
 
 use plist::Value;
 
 use crate::{
     error::plist::PlistParseError,
-    message_types::variants::BalloonProvider,
+    message_types::{app::AppMessage, variants::BalloonProvider},
     util::plist::{get_float_from_nested_dict, get_string_from_dict, get_string_from_nested_dict},
 };
 
@@ -101,10 +104,55 @@ impl<'a> CollaborationMessage<'a> {
     }
 }
 
+/// A short summary of an item shared into a conversation from another app
+#[derive(Debug, PartialEq, Eq)]
+pub struct SharedItem {
+    /// The title of the shared item, i.e. the name of a document
+    pub title: Option<String>,
+    /// The name of the app the item was shared from
+    pub app_name: Option<String>,
+    /// The link used to open the shared item
+    pub url: Option<String>,
+    /// `true` if the item is a [Rich Collaboration](https://developer.apple.com/videos/play/wwdc2022/10095/) invite
+    pub is_collaboration: bool,
+}
+
+impl SharedItem {
+    /// Summarize a collaboration invite
+    #[must_use]
+    pub fn from_collaboration(balloon: &CollaborationMessage) -> Self {
+        Self {
+            title: balloon.title.map(String::from),
+            app_name: balloon.app_name.map(String::from),
+            url: balloon.get_url().map(String::from),
+            is_collaboration: true,
+        }
+    }
+
+    /// Summarize a generic app message, using the text most likely to describe what was shared
+    #[must_use]
+    pub fn from_app(balloon: &AppMessage) -> Self {
+        Self {
+            title: balloon
+                .title
+                .or(balloon.ldtext)
+                .or(balloon.caption)
+                .map(String::from),
+            app_name: balloon.app_name.map(String::from),
+            url: balloon.url.map(String::from),
+            is_collaboration: false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        message_types::{collaboration::CollaborationMessage, variants::BalloonProvider},
+        message_types::{
+            app::AppMessage,
+            collaboration::{CollaborationMessage, SharedItem},
+            variants::BalloonProvider,
+        },
         util::plist::parse_ns_keyed_archiver,
     };
     use plist::Value;
@@ -133,4 +181,51 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn can_summarize_collaboration() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/collaboration_message/Freeform.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = CollaborationMessage::from_map(&parsed).unwrap();
+        let expected = SharedItem {
+            title: Some(String::from("Untitled")),
+            app_name: Some(String::from("Freeform")),
+            url: Some(String::from("https://www.icloud.com/freeform/REDACTED")),
+            is_collaboration: true,
+        };
+
+        assert_eq!(SharedItem::from_collaboration(&balloon), expected);
+    }
+
+    #[test]
+    fn can_summarize_app_message() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/Slideshow.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        // Not a collaboration invite, so only the generic summary is available
+        assert!(CollaborationMessage::from_map(&parsed).is_err());
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+        let expected = SharedItem {
+            title: Some(String::from("Home - 37 Photos")),
+            app_name: Some(String::from("Photos")),
+            url: Some(String::from(
+                "https://share.icloud.com/photos/1337h4x0r_jk#Home",
+            )),
+            is_collaboration: false,
+        };
+
+        assert_eq!(SharedItem::from_app(&balloon), expected);
+    }
 }
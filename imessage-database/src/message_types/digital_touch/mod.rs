@@ -8,3 +8,4 @@ pub use crate::message_types::digital_touch::{
 
 pub(crate) mod digital_touch_proto;
 pub mod models;
+pub mod sketch;
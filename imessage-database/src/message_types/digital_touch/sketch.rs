@@ -0,0 +1,243 @@
+/*!
+Parser for the strokes drawn in [Digital Touch](https://support.apple.com/guide/ipod-touch/send-a-digital-touch-effect-iph3fadba219/ios) sketches.
+
+The `Strokes` field of a `SketchMessage` packs every stroke one after another. Each stroke starts with a 4 byte header
+whose last two bytes are the number of points as a little endian `u16`, followed by that many points. Each point is
+an `x` and a `y` little endian `u16`, scaled so that `u16::MAX` is the far edge of the canvas. The `Colors` field
+holds one RGBA color of 4 bytes for each stroke.
+*/
+
+use std::fmt::Write;
+
+use crate::message_types::digital_touch::digital_touch_proto::{
+    BaseMessage, SketchMessage, TouchKind,
+};
+
+use protobuf::Message;
+
+/// The number of bytes before the points of each stroke
+const STROKE_HEADER_LEN: usize = 4;
+/// The number of bytes used by each point
+const POINT_LEN: usize = 4;
+/// The number of bytes used by each color
+const COLOR_LEN: usize = 4;
+/// The width and height of the rendered canvas, in SVG user units
+const CANVAS_SIZE: f64 = 1000.;
+/// The width of each rendered stroke, in SVG user units
+const STROKE_WIDTH: f64 = 12.;
+
+/// A Digital Touch sketch, drawn with the finger in colored strokes
+#[derive(Debug, PartialEq)]
+pub struct Sketch {
+    /// The ID of the Digital Touch message
+    pub id: String,
+    /// The strokes that make up the drawing, in the order they were drawn
+    pub strokes: Vec<Stroke>,
+}
+
+/// A single line drawn in a [`Sketch`]
+#[derive(Debug, PartialEq)]
+pub struct Stroke {
+    /// The points along the line, in the order they were drawn
+    pub points: Vec<SketchPoint>,
+    /// The color of the line
+    pub color: Color,
+}
+
+/// A point along a [`Stroke`], relative to the size of the canvas
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SketchPoint {
+    /// The distance from the left edge, from `0.0` to `1.0`
+    pub x: f64,
+    /// The distance from the top edge, from `0.0` to `1.0`
+    pub y: f64,
+}
+
+/// The color of a [`Stroke`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    /// Format the color as a CSS hex color, like `#ff00fc`
+    #[must_use]
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+impl Sketch {
+    /// Converts a raw byte payload from the database into a [`Sketch`]
+    ///
+    /// Returns `None` if the payload is not a Digital Touch sketch or its strokes cannot be read.
+    #[must_use]
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let msg = BaseMessage::parse_from_bytes(payload).ok()?;
+        if msg.TouchKind.enum_value_or_default() != TouchKind::Sketch {
+            return None;
+        }
+        let sketch = SketchMessage::parse_from_bytes(&msg.TouchPayload).ok()?;
+
+        Some(Self {
+            id: msg.ID,
+            strokes: parse_strokes(&sketch.Strokes, &sketch.Colors)?,
+        })
+    }
+
+    /// Renders the sketch as an `svg` graphic, with one `path` for each stroke
+    #[must_use]
+    pub fn as_svg(&self) -> String {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg viewBox="0 0 {CANVAS_SIZE} {CANVAS_SIZE}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg">"#
+        );
+        let _ = writeln!(svg, "<title>{}</title>", self.id);
+        svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"black\"/>\n");
+        for stroke in &self.strokes {
+            let mut path = String::new();
+            for (idx, point) in stroke.points.iter().enumerate() {
+                let _ = write!(
+                    path,
+                    "{}{:.1} {:.1}",
+                    if idx == 0 { "M" } else { " L" },
+                    point.x * CANVAS_SIZE,
+                    point.y * CANVAS_SIZE
+                );
+            }
+            let _ = writeln!(
+                svg,
+                r#"<path d="{path}" fill="none" stroke="{}" stroke-opacity="{:.2}" stroke-width="{STROKE_WIDTH}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                stroke.color.hex(),
+                f64::from(stroke.color.alpha) / f64::from(u8::MAX)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Split the packed stroke and color bytes into [`Stroke`]s
+fn parse_strokes(strokes: &[u8], colors: &[u8]) -> Option<Vec<Stroke>> {
+    let mut parsed = vec![];
+    let mut colors = colors.chunks_exact(COLOR_LEN);
+    let mut remaining = strokes;
+
+    while !remaining.is_empty() {
+        let header = remaining.get(..STROKE_HEADER_LEN)?;
+        let num_points = usize::from(u16::from_le_bytes([header[2], header[3]]));
+        let end = STROKE_HEADER_LEN + num_points * POINT_LEN;
+        let points = remaining
+            .get(STROKE_HEADER_LEN..end)?
+            .chunks_exact(POINT_LEN)
+            .map(|point| SketchPoint {
+                x: f64::from(u16::from_le_bytes([point[0], point[1]])) / f64::from(u16::MAX),
+                y: f64::from(u16::from_le_bytes([point[2], point[3]])) / f64::from(u16::MAX),
+            })
+            .collect();
+        let color = colors.next().map_or(
+            Color {
+                red: u8::MAX,
+                green: u8::MAX,
+                blue: u8::MAX,
+                alpha: u8::MAX,
+            },
+            |color| Color {
+                red: color[0],
+                green: color[1],
+                blue: color[2],
+                alpha: color[3],
+            },
+        );
+
+        parsed.push(Stroke { points, color });
+        remaining = &remaining[end..];
+    }
+
+    Some(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::current_dir;
+    use std::fs::File;
+    use std::io::Read;
+
+    use crate::message_types::digital_touch::sketch::{Color, Sketch, parse_strokes};
+
+    fn payload(name: &str) -> Vec<u8> {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/digital_touch_message")
+            .join(name);
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn can_parse_sketch_strokes() {
+        let sketch = Sketch::from_payload(&payload("sketch.bin")).unwrap();
+
+        assert_eq!(sketch.id, "F7D92232-92B3-4C5A-8DC7-2704BE93890E");
+        assert_eq!(sketch.strokes.len(), 1);
+        assert_eq!(sketch.strokes[0].points.len(), 81);
+        assert_eq!(
+            sketch.strokes[0].color,
+            Color {
+                red: 255,
+                green: 0,
+                blue: 252,
+                alpha: 255
+            }
+        );
+        let first = sketch.strokes[0].points[0];
+        assert!((first.x - 14168. / 65535.).abs() < f64::EPSILON);
+        assert!((first.y - 43154. / 65535.).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn can_render_sketch_svg() {
+        let sketch = Sketch::from_payload(&payload("sketch.bin")).unwrap();
+        let svg = sketch.as_svg();
+
+        assert_eq!(svg.matches("<path ").count(), 1);
+        assert!(svg.contains(r#"<path d="M216.2 658.5 L250.0 674.8"#));
+        assert!(svg.contains(r##"stroke="#ff00fc" stroke-opacity="1.00""##));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn can_parse_multiple_strokes() {
+        let strokes = [
+            [0, 0, 1, 0, 0, 0, 0, 0].as_slice(),
+            &[0, 0, 2, 0, 255, 255, 255, 255, 0, 128, 0, 128],
+        ]
+        .concat();
+        let colors = [255, 0, 0, 255, 0, 0, 255, 128];
+
+        let parsed = parse_strokes(&strokes, &colors).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].points.len(), 1);
+        assert_eq!(parsed[1].points.len(), 2);
+        assert_eq!(parsed[1].points[0].x, 1.);
+        assert_eq!(parsed[1].color.hex(), "#0000ff");
+    }
+
+    #[test]
+    fn cant_parse_truncated_strokes() {
+        assert!(parse_strokes(&[0, 0, 2, 0, 1, 2, 3, 4], &[]).is_none());
+    }
+
+    #[test]
+    fn cant_parse_other_kinds() {
+        assert!(Sketch::from_payload(&payload("tap.bin")).is_none());
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
     message_types::variants::BalloonProvider,
     tables::{
         messages::{
-            body::{parse_body_legacy, parse_body_typedstream},
+            body::{parse_body_legacy, parse_body_typedstream, redact_one_time_codes},
             models::BubbleComponent,
         },
         table::AttributedBody,
@@ -64,6 +64,17 @@ impl EditedEvent {
             guid,
         }
     }
+
+    /// Get the text of this version with each one-time code replaced by `replacement`
+    ///
+    /// One-time codes are the ranges of the version's body tagged with
+    /// [`TextEffect::OTP`](crate::message_types::text_effects::TextEffect::OTP). If the version has no text, this
+    /// returns `None`.
+    #[must_use]
+    pub fn text_with_redacted_codes(&self, replacement: &str) -> Option<String> {
+        let text = self.text.as_deref()?;
+        Some(redact_one_time_codes(text, &self.body(), replacement))
+    }
 }
 
 impl AttributedBody for EditedEvent {
@@ -80,6 +91,119 @@ impl AttributedBody for EditedEvent {
     }
 }
 
+/// A single word-level change between two versions of a message part
+#[derive(Debug, PartialEq, Eq)]
+pub enum WordChange<'a> {
+    /// The word exists in both versions
+    Kept(&'a str),
+    /// The word was added in the newer version
+    Added(&'a str),
+    /// The word was removed from the older version
+    Removed(&'a str),
+}
+
+/// The difference between two consecutive [`EditedEvent`]s for a message part
+#[derive(Debug, PartialEq, Eq)]
+pub struct EditDiff<'a> {
+    /// The date the newer version was written
+    pub date: i64,
+    /// The text before the edit
+    pub before: &'a str,
+    /// The text after the edit
+    pub after: &'a str,
+    /// The words kept, added, and removed by the edit, in order
+    pub changes: Vec<WordChange<'a>>,
+}
+
+impl<'a> EditDiff<'a> {
+    fn new(before: &'a EditedEvent, after: &'a EditedEvent) -> Self {
+        let before_text = before.text.as_deref().unwrap_or_default();
+        let after_text = after.text.as_deref().unwrap_or_default();
+        Self {
+            date: after.date,
+            before: before_text,
+            after: after_text,
+            changes: word_diff(before_text, after_text),
+        }
+    }
+
+    /// Describe the edit for readers, i.e. `changed 'Hi' → 'Hello'`
+    ///
+    /// Each run of changed words is described separately, joined by `; `.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut hunks = vec![];
+        let mut removed: Vec<&str> = vec![];
+        let mut added: Vec<&str> = vec![];
+
+        let mut flush = |removed: &mut Vec<&str>, added: &mut Vec<&str>| {
+            match (removed.is_empty(), added.is_empty()) {
+                (false, false) => hunks.push(format!(
+                    "changed '{}' → '{}'",
+                    removed.join(" "),
+                    added.join(" ")
+                )),
+                (false, true) => hunks.push(format!("removed '{}'", removed.join(" "))),
+                (true, false) => hunks.push(format!("added '{}'", added.join(" "))),
+                (true, true) => {}
+            }
+            removed.clear();
+            added.clear();
+        };
+
+        for change in &self.changes {
+            match change {
+                WordChange::Kept(_) => flush(&mut removed, &mut added),
+                WordChange::Added(word) => added.push(word),
+                WordChange::Removed(word) => removed.push(word),
+            }
+        }
+        flush(&mut removed, &mut added);
+
+        if hunks.is_empty() {
+            return String::from("no text changes");
+        }
+        hunks.join("; ")
+    }
+}
+
+/// Compute a word-level diff between two strings using their longest common subsequence
+fn word_diff<'a>(before: &'a str, after: &'a str) -> Vec<WordChange<'a>> {
+    let old: Vec<&str> = before.split_whitespace().collect();
+    let new: Vec<&str> = after.split_whitespace().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(WordChange::Kept(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(WordChange::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(WordChange::Added(new[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|word| WordChange::Removed(word)));
+    changes.extend(new[j..].iter().map(|word| WordChange::Added(word)));
+    changes
+}
+
 /// Tracks the edit status and history for a specific part of a message
 #[derive(Debug, PartialEq)]
 pub struct EditedMessagePart {
@@ -89,6 +213,15 @@ pub struct EditedMessagePart {
     pub edit_history: Vec<EditedEvent>,
 }
 
+impl EditedMessagePart {
+    /// Iterate over the differences between each consecutive pair of edits to this part
+    pub fn diffs(&self) -> impl Iterator<Item = EditDiff<'_>> {
+        self.edit_history
+            .windows(2)
+            .map(|pair| EditDiff::new(&pair[0], &pair[1]))
+    }
+}
+
 impl Default for EditedMessagePart {
     fn default() -> Self {
         Self {
@@ -234,6 +367,31 @@ impl EditedMessage {
         }
     }
 
+    /// Gets the message part a tapback on the given part index applies to
+    ///
+    /// Tapback indexes refer to a part's position in the message body, which edits do not change:
+    /// editing replaces the text of a part but never adds, removes, or reorders parts. A tapback
+    /// therefore always applies to the current version of its part, even if it was sent before the
+    /// edit. Unsent parts are no longer displayed, so tapbacks on them have no anchor.
+    #[must_use]
+    pub fn tapback_anchor(&self, index: usize) -> Option<&EditedMessagePart> {
+        self.parts
+            .get(index)
+            .filter(|part| !matches!(part.status, EditStatus::Unsent))
+    }
+
+    /// Iterate over the GUIDs of other messages referenced by edits, paired with the index of the edited part
+    ///
+    /// Edits that convert a part to or from a link store the GUID of the message that holds the link preview
+    /// in the `bcg` key. These can be resolved with [`Message::edit_references()`](crate::tables::messages::Message::edit_references).
+    pub fn referenced_guids(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.parts.iter().enumerate().flat_map(|(index, part)| {
+            part.edit_history
+                .iter()
+                .filter_map(move |event| Some((index, event.guid.as_deref()?)))
+        })
+    }
+
     /// Gets the number of parts that may or may not have been edited or unsent
     #[must_use]
     pub fn items(&self) -> usize {
@@ -1404,3 +1562,80 @@ mod test_gen {
         }
     }
 }
+
+#[cfg(test)]
+mod test_diff {
+    use std::{env::current_dir, fs::File};
+
+    use plist::Value;
+
+    use crate::message_types::{
+        edited::{EditedMessage, WordChange, word_diff},
+        variants::BalloonProvider,
+    };
+
+    #[test]
+    fn can_diff_words() {
+        assert_eq!(
+            word_diff("Hi there friend", "Hello there"),
+            vec![
+                WordChange::Removed("Hi"),
+                WordChange::Added("Hello"),
+                WordChange::Kept("there"),
+                WordChange::Removed("friend"),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_diff_edited_fixture() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/edited_message/Edited.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = EditedMessage::from_map(&plist).unwrap();
+
+        let diffs: Vec<String> = parsed.parts[0]
+            .diffs()
+            .map(|diff| diff.describe())
+            .collect();
+
+        assert_eq!(
+            diffs,
+            vec![
+                "changed 'First message' → 'Edit 1'",
+                "changed '1' → '2'",
+                "changed 'Edit 2' → 'Edited message'",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_describe_additions_and_removals() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/edited_message/Edited.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let mut parsed = EditedMessage::from_map(&plist).unwrap();
+
+        let history = &mut parsed.parts[0].edit_history;
+        history[0].text = Some("see you soon".to_string());
+        history[1].text = Some("see you very soon".to_string());
+        history[2].text = Some("see you".to_string());
+        history[3].text = Some("see you".to_string());
+
+        let diffs: Vec<String> = parsed.parts[0]
+            .diffs()
+            .map(|diff| diff.describe())
+            .collect();
+
+        assert_eq!(
+            diffs,
+            vec!["added 'very'", "removed 'very soon'", "no text changes"]
+        );
+    }
+}
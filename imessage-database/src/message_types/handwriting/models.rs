@@ -55,6 +55,20 @@ impl HandwrittenMessage {
     /// Renders the handwriting message as an `svg` graphic.
     #[must_use]
     pub fn render_svg(&self) -> String {
+        self.svg_document(generate_strokes)
+    }
+
+    /// Renders the handwriting message as an `svg` graphic built from `path` elements.
+    ///
+    /// Each run of points with the same width along a stroke becomes a single `path`, drawn on a canvas the size of
+    /// the message.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        self.svg_document(generate_paths)
+    }
+
+    /// Wraps the strokes drawn by `draw` in an `svg` document sized to the message's canvas.
+    fn svg_document(&self, draw: fn(&mut String, &[Vec<Point>])) -> String {
         let mut svg = String::new();
         svg.push('\n');
         svg.push_str(format!(r#"<svg viewBox="0 0 {} {}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#, self.width, self.height).as_str());
@@ -75,7 +89,7 @@ impl HandwrittenMessage {
 ",
         );
         svg.push_str("</style>\n");
-        generate_strokes(&mut svg, &self.strokes);
+        draw(&mut svg, &self.strokes);
         svg.push_str("</svg>\n");
         svg
     }
@@ -177,6 +191,28 @@ fn generate_strokes(svg: &mut String, strokes: &[Vec<Point>]) {
     }
 }
 
+/// Generates svg paths from an array of strokes.
+fn generate_paths(svg: &mut String, strokes: &[Vec<Point>]) {
+    for stroke in strokes {
+        for (width, points) in &group_points(stroke) {
+            let mut path = String::with_capacity(points.len() * 8);
+            for (idx, point) in points.iter().enumerate() {
+                let _ = write!(
+                    path,
+                    "{}{} {}",
+                    if idx == 0 { "M" } else { " L" },
+                    point.x,
+                    point.y
+                );
+            }
+            let _ = writeln!(
+                svg,
+                r#"<path class="line" d="{path}" stroke-width="{width}" />"#
+            );
+        }
+    }
+}
+
 /// Group points along a stroke together by width
 fn group_points(stroke: &[Point]) -> Vec<(u16, Vec<&Point>)> {
     let mut groups = vec![];
@@ -11162,6 +11198,28 @@ mod tests {
         assert_eq!(balloon.render_svg(), expected);
     }
 
+    #[test]
+    fn test_parse_handwritten_as_svg_paths() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/hello.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let svg = balloon.to_svg();
+
+        assert!(svg.contains(r#"<svg viewBox="0 0 405 161" "#));
+        assert!(svg.contains(r#"<path class="line" d="M8 149 L25 139" stroke-width="10" />"#));
+        assert_eq!(
+            svg.matches("<path ").count(),
+            balloon.render_svg().matches("<polyline ").count()
+        );
+        assert!(!svg.contains("<polyline"));
+    }
+
     #[test]
     fn test_parse_handwritten_as_svg_old() {
         let protobuf_path = current_dir()
@@ -10,7 +10,9 @@ pub mod edited;
 pub mod expressives;
 pub mod handwriting;
 pub mod music;
+pub mod payment;
 pub mod placemark;
+pub mod poll;
 pub mod sticker;
 pub mod text_effects;
 pub mod url;
@@ -0,0 +1,269 @@
+/*!
+ These are the Apple Pay and Apple Cash payments sent as `com.apple.PassbookUIService.PeerPaymentMessagesExtension`
+ app messages.
+
+ The amount is read from a numeric field when the payload has one. Most payloads only describe the payment in
+ their captions, like `Sent $265 with Apple Pay.`, so the amount and currency are parsed from that text instead.
+*/
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    util::plist::{get_string_from_dict, get_value_from_dict},
+};
+
+/// Keys that may contain the numeric amount of a payment
+const AMOUNT_KEYS: [&str; 2] = ["amount", "paymentAmount"];
+/// Keys that may contain the ISO 4217 code of a payment's currency
+const CURRENCY_KEYS: [&str; 2] = ["currency", "currencyCode"];
+/// Keys that may contain text describing a payment, in the order they are checked
+const CAPTION_KEYS: [&str; 3] = ["ldtext", "subcaption", "caption"];
+/// Currency symbols and the currency codes they represent
+const CURRENCY_SYMBOLS: [(&str, &str); 4] =
+    [("$", "USD"), ("€", "EUR"), ("£", "GBP"), ("¥", "JPY")];
+
+/// Whether money was sent, received, or requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentDirection {
+    /// The sender of the message asked for money
+    Request,
+    /// The sender of the message paid the recipient
+    Send,
+    /// The database owner was paid by the sender of the message
+    Receive,
+}
+
+/// A payment sent with Apple Pay or Apple Cash
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplePayMessage {
+    /// The amount of money, in units of the currency
+    pub amount: f64,
+    /// The ISO 4217 code of the currency, like `USD`
+    pub currency: String,
+    /// Whether the money was sent, received, or requested
+    pub direction: PaymentDirection,
+}
+
+impl ApplePayMessage {
+    /// Parse a payment from a message's `payload_data`, after resolving any `NSKeyedArchiver` pointers
+    ///
+    /// The payload does not record who is reading it, so the direction is from the perspective of the message's
+    /// sender: it is never [`PaymentDirection::Receive`].
+    pub fn from_payload(payload: &Value) -> Result<Self, PlistParseError> {
+        let user_info = get_value_from_dict(payload, "userInfo");
+        let dicts = [Some(payload), user_info];
+        let captions: Vec<&str> = CAPTION_KEYS
+            .iter()
+            .flat_map(|key| dicts.iter().flatten().map(move |dict| (dict, *key)))
+            .filter_map(|(dict, key)| get_string_from_dict(dict, key))
+            .collect();
+        let from_captions = captions.iter().find_map(|caption| parse_amount(caption));
+
+        let numeric = AMOUNT_KEYS
+            .iter()
+            .flat_map(|key| dicts.iter().flatten().map(move |dict| (dict, *key)))
+            .find_map(|(dict, key)| get_value_from_dict(dict, key));
+        let (amount, currency) = match numeric {
+            Some(Value::Real(amount)) => (*amount, None),
+            Some(Value::Integer(amount)) => (
+                amount.as_signed().ok_or_else(|| {
+                    PlistParseError::InvalidType("amount".to_string(), "integer".to_string())
+                })? as f64,
+                None,
+            ),
+            Some(Value::String(amount)) => parse_amount(amount)
+                .map(|(amount, currency)| (amount, Some(currency)))
+                .or_else(|| amount.trim().parse().ok().map(|amount| (amount, None)))
+                .ok_or_else(|| {
+                    PlistParseError::InvalidType("amount".to_string(), "number".to_string())
+                })?,
+            Some(_) => {
+                return Err(PlistParseError::InvalidType(
+                    "amount".to_string(),
+                    "number".to_string(),
+                ));
+            }
+            None => from_captions
+                .clone()
+                .map(|(amount, currency)| (amount, Some(currency)))
+                .ok_or_else(|| PlistParseError::MissingKey("amount".to_string()))?,
+        };
+
+        let currency = CURRENCY_KEYS
+            .iter()
+            .flat_map(|key| dicts.iter().flatten().map(move |dict| (dict, *key)))
+            .find_map(|(dict, key)| get_string_from_dict(dict, key))
+            .map(String::from)
+            .or(currency)
+            .or_else(|| from_captions.map(|(_, currency)| currency))
+            .ok_or_else(|| PlistParseError::MissingKey("currency".to_string()))?;
+
+        Ok(Self {
+            amount,
+            currency,
+            direction: parse_direction(&captions),
+        })
+    }
+
+    /// Render the amount with its currency symbol, like `$20.00`, or with its code if it has no known symbol
+    #[must_use]
+    pub fn formatted_amount(&self) -> String {
+        match CURRENCY_SYMBOLS
+            .iter()
+            .find(|(_, code)| *code == self.currency)
+        {
+            Some((symbol, _)) => format!("{symbol}{:.2}", self.amount),
+            None => format!("{:.2} {}", self.amount, self.currency),
+        }
+    }
+}
+
+impl Display for ApplePayMessage {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        let verb = match self.direction {
+            PaymentDirection::Request => "Requested",
+            PaymentDirection::Send => "Sent",
+            PaymentDirection::Receive => "Received",
+        };
+        write!(fmt, "{verb} {}", self.formatted_amount())
+    }
+}
+
+/// Find the first amount of money in a caption, like `$265` in `Sent $265 with Apple Pay.`
+///
+/// Numbers without a currency, like the dates in `Sending you $1 weekly starting Nov 18, 2023`, are skipped.
+fn parse_amount(text: &str) -> Option<(f64, String)> {
+    text.split(char::is_whitespace).find_map(|word| {
+        let word = word.trim_end_matches(['.', ',', '!', '?']);
+        let first_digit = word.find(|c: char| c.is_ascii_digit())?;
+        let last_digit = word.rfind(|c: char| c.is_ascii_digit())?;
+        let (prefix, rest) = word.split_at(first_digit);
+        let (number, suffix) = rest.split_at(last_digit - first_digit + 1);
+
+        let currency = [prefix, suffix].into_iter().find_map(|marker| {
+            CURRENCY_SYMBOLS
+                .iter()
+                .find(|(symbol, _)| marker.ends_with(symbol) || marker.starts_with(symbol))
+                .map(|(_, code)| (*code).to_string())
+                .or_else(|| {
+                    (marker.len() == 3 && marker.chars().all(|c| c.is_ascii_uppercase()))
+                        .then(|| marker.to_string())
+                })
+        })?;
+        let amount = number.replace(',', "").parse().ok()?;
+        Some((amount, currency))
+    })
+}
+
+/// Determine whether a payment was sent or requested from the text that describes it
+fn parse_direction(captions: &[&str]) -> PaymentDirection {
+    if captions
+        .iter()
+        .any(|caption| caption.to_lowercase().contains("request"))
+    {
+        PaymentDirection::Request
+    } else {
+        PaymentDirection::Send
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File};
+
+    use plist::{Dictionary, Value};
+
+    use crate::{
+        message_types::payment::{ApplePayMessage, PaymentDirection},
+        util::plist::parse_ns_keyed_archiver,
+    };
+
+    fn fixture(name: &str) -> Value {
+        let plist_path = current_dir()
+            .unwrap()
+            .join("test_data/app_message")
+            .join(name);
+        let plist = Value::from_reader(File::open(plist_path).unwrap()).unwrap();
+        parse_ns_keyed_archiver(&plist).unwrap()
+    }
+
+    fn payload(entries: &[(&str, Value)]) -> Value {
+        let mut dict = Dictionary::new();
+        for (key, value) in entries {
+            dict.insert((*key).to_string(), value.clone());
+        }
+        Value::Dictionary(dict)
+    }
+
+    #[test]
+    fn can_parse_sent_payment() {
+        let payment = ApplePayMessage::from_payload(&fixture("Sent265.plist")).unwrap();
+
+        let expected = ApplePayMessage {
+            amount: 265.0,
+            currency: "USD".to_string(),
+            direction: PaymentDirection::Send,
+        };
+        assert_eq!(payment, expected);
+        assert_eq!(payment.to_string(), "Sent $265.00");
+    }
+
+    #[test]
+    fn can_parse_recurring_payment() {
+        let payment = ApplePayMessage::from_payload(&fixture("ApplePayRecurring.plist")).unwrap();
+
+        assert_eq!(payment.amount, 1.0);
+        assert_eq!(payment.currency, "USD");
+        assert_eq!(payment.direction, PaymentDirection::Send);
+    }
+
+    #[test]
+    fn can_parse_requested_payment() {
+        let payment = ApplePayMessage::from_payload(&payload(&[(
+            "ldtext",
+            Value::String("Requested €1,250.50 with Apple\u{a0}Pay.".to_string()),
+        )]))
+        .unwrap();
+
+        assert_eq!(payment.amount, 1250.5);
+        assert_eq!(payment.currency, "EUR");
+        assert_eq!(payment.to_string(), "Requested €1250.50");
+    }
+
+    #[test]
+    fn can_parse_numeric_amount() {
+        let payment = ApplePayMessage::from_payload(&payload(&[
+            ("amount", Value::Real(20.0)),
+            ("currencyCode", Value::String("CHF".to_string())),
+        ]))
+        .unwrap();
+
+        assert_eq!(payment.amount, 20.0);
+        assert_eq!(payment.to_string(), "Sent 20.00 CHF");
+    }
+
+    #[test]
+    fn can_parse_string_amount() {
+        let payment = ApplePayMessage::from_payload(&payload(&[(
+            "amount",
+            Value::String("£7.25".to_string()),
+        )]))
+        .unwrap();
+
+        assert_eq!(payment.amount, 7.25);
+        assert_eq!(payment.currency, "GBP");
+    }
+
+    #[test]
+    fn cant_parse_payment_without_amount() {
+        let payment = ApplePayMessage::from_payload(&payload(&[(
+            "ldtext",
+            Value::String("Apple\u{a0}Cash".to_string()),
+        )]));
+
+        assert!(payment.is_err());
+    }
+}
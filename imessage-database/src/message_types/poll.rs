@@ -0,0 +1,195 @@
+/*!
+ These are the polls introduced in iOS 26, sent as `com.apple.messages.Polls` app messages.
+
+ Apple does not document the payload, so parsing is best-effort: if the data does not look like a poll,
+ callers get nothing instead of partial data.
+*/
+
+use plist::Value;
+
+use crate::{
+    error::plist::PlistParseError,
+    util::plist::{get_string_from_dict, get_value_from_dict},
+};
+
+/// Keys that may contain the question a poll asks
+const QUESTION_KEYS: [&str; 2] = ["question", "title"];
+/// Keys that may contain the list of choices in a poll
+const OPTION_KEYS: [&str; 2] = ["options", "choices"];
+/// Keys that may contain the text of a single choice
+const OPTION_TEXT_KEYS: [&str; 2] = ["text", "title"];
+/// Keys that may contain the participants that voted for a choice
+const VOTER_KEYS: [&str; 2] = ["voters", "votes"];
+
+/// A single choice in a [`Poll`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PollOption {
+    /// The text of the choice
+    pub text: String,
+    /// The handles of the participants who voted for the choice
+    pub voters: Vec<String>,
+}
+
+/// A poll sent to a conversation, with the votes cast for each choice
+#[derive(Debug, PartialEq, Eq)]
+pub struct Poll {
+    /// The question the poll asks, if any
+    pub question: Option<String>,
+    /// The choices participants can vote for
+    pub options: Vec<PollOption>,
+}
+
+impl Poll {
+    /// Parse a poll from a message's `payload_data`, after resolving any `NSKeyedArchiver` pointers
+    pub fn from_payload(payload: &Value) -> Result<Self, PlistParseError> {
+        // Some payloads nest the poll under its own key
+        let body = get_value_from_dict(payload, "poll").unwrap_or(payload);
+
+        let options = OPTION_KEYS
+            .iter()
+            .find_map(|key| get_value_from_dict(body, key))
+            .ok_or_else(|| PlistParseError::MissingKey("options".to_string()))?
+            .as_array()
+            .ok_or_else(|| {
+                PlistParseError::InvalidType("options".to_string(), "array".to_string())
+            })?
+            .iter()
+            .filter_map(PollOption::from_payload)
+            .collect::<Vec<_>>();
+
+        if options.is_empty() {
+            return Err(PlistParseError::WrongMessageType);
+        }
+
+        Ok(Self {
+            question: QUESTION_KEYS
+                .iter()
+                .find_map(|key| get_string_from_dict(body, key))
+                .map(String::from),
+            options,
+        })
+    }
+
+    /// The total number of votes cast across every choice
+    #[must_use]
+    pub fn total_votes(&self) -> usize {
+        self.options.iter().map(|option| option.voters.len()).sum()
+    }
+}
+
+impl PollOption {
+    /// Parse a single choice, skipping entries that have no text
+    fn from_payload(payload: &Value) -> Option<Self> {
+        let text = match payload {
+            Value::String(text) => text.as_str(),
+            _ => OPTION_TEXT_KEYS
+                .iter()
+                .find_map(|key| get_string_from_dict(payload, key))?,
+        };
+
+        let voters = VOTER_KEYS
+            .iter()
+            .find_map(|key| get_value_from_dict(payload, key))
+            .and_then(Value::as_array)
+            .map(|voters| {
+                voters
+                    .iter()
+                    .filter_map(Value::as_string)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            text: text.to_string(),
+            voters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::message_types::poll::{Poll, PollOption};
+
+    fn option(text: &str, voters: &[&str]) -> Value {
+        let mut dict = Dictionary::new();
+        dict.insert("text".to_string(), Value::String(text.to_string()));
+        dict.insert(
+            "voters".to_string(),
+            Value::Array(
+                voters
+                    .iter()
+                    .map(|voter| Value::String((*voter).to_string()))
+                    .collect(),
+            ),
+        );
+        Value::Dictionary(dict)
+    }
+
+    fn poll(question: &str, options: Vec<Value>) -> Value {
+        let mut dict = Dictionary::new();
+        dict.insert("question".to_string(), Value::String(question.to_string()));
+        dict.insert("options".to_string(), Value::Array(options));
+        Value::Dictionary(dict)
+    }
+
+    #[test]
+    fn can_parse_poll() {
+        let payload = poll(
+            "Dinner?",
+            vec![
+                option("Tacos", &["+15558675309", "person@example.com"]),
+                option("Pizza", &[]),
+            ],
+        );
+
+        let expected = Poll {
+            question: Some("Dinner?".to_string()),
+            options: vec![
+                PollOption {
+                    text: "Tacos".to_string(),
+                    voters: vec!["+15558675309".to_string(), "person@example.com".to_string()],
+                },
+                PollOption {
+                    text: "Pizza".to_string(),
+                    voters: vec![],
+                },
+            ],
+        };
+
+        let parsed = Poll::from_payload(&payload).unwrap();
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.total_votes(), 2);
+    }
+
+    #[test]
+    fn can_parse_nested_poll() {
+        let mut root = Dictionary::new();
+        root.insert(
+            "poll".to_string(),
+            poll("Where?", vec![option("Here", &["+15558675309"])]),
+        );
+
+        let parsed = Poll::from_payload(&Value::Dictionary(root)).unwrap();
+        assert_eq!(parsed.question.as_deref(), Some("Where?"));
+        assert_eq!(parsed.options.len(), 1);
+    }
+
+    #[test]
+    fn cant_parse_non_poll() {
+        let mut dict = Dictionary::new();
+        dict.insert(
+            "URL".to_string(),
+            Value::String("https://example.com".to_string()),
+        );
+
+        assert!(Poll::from_payload(&Value::Dictionary(dict)).is_err());
+    }
+
+    #[test]
+    fn cant_parse_poll_without_choices() {
+        assert!(Poll::from_payload(&poll("Empty?", vec![])).is_err());
+    }
+}
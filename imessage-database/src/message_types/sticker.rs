@@ -4,7 +4,9 @@ These are [sticker messages](https://support.apple.com/guide/iphone/send-sticker
 
 use std::fmt::Display;
 
-use crate::util::bundle_id::parse_balloon_bundle_id;
+use plist::Value;
+
+use crate::util::{bundle_id::parse_balloon_bundle_id, plist::plist_as_dictionary};
 
 /// Bytes for `stickerEffect:type="`
 const STICKER_EFFECT_PREFIX: [u8; 20] = [
@@ -12,14 +14,20 @@ const STICKER_EFFECT_PREFIX: [u8; 20] = [
 ];
 /// Bytes for `"/>`
 const STICKER_EFFECT_SUFFIX: [u8; 3] = [34, 47, 62];
+/// The `sticker_user_info` key that holds the bundle ID of the app that created a sticker
+const STICKER_BUNDLE_ID_KEY: &str = "pid";
+/// The `sticker_user_info` key that holds a sticker's accessibility label, which names a Memoji's pose
+const STICKER_LABEL_KEY: &str = "ali";
+/// The `attribution_info` key that holds the name of the app or sticker pack a sticker came from
+const ATTRIBUTION_NAME_KEY: &str = "name";
 
 /// Represents the source that created a sticker attachment
 #[derive(Debug, PartialEq, Eq)]
 pub enum StickerSource {
     /// A [Genmoji](https://support.apple.com/guide/iphone/create-genmoji-with-apple-intelligence-iph4e76f5667/ios)
     Genmoji,
-    /// A [Memoji](https://support.apple.com/en-us/111115)
-    Memoji,
+    /// A [Memoji](https://support.apple.com/en-us/111115), with the pose or expression it was made with, if known
+    Memoji(Option<String>),
     /// User-created stickers
     UserGenerated,
     /// Application provided stickers
@@ -42,7 +50,7 @@ impl StickerSource {
             Some("com.apple.messages.genmoji") => Some(StickerSource::Genmoji),
             Some(
                 "com.apple.Animoji.StickersApp.MessagesExtension" | "com.apple.Jellyfish.Animoji",
-            ) => Some(StickerSource::Memoji),
+            ) => Some(StickerSource::Memoji(None)),
             Some("com.apple.Stickers.UserGenerated.MessagesExtension") => {
                 Some(StickerSource::UserGenerated)
             }
@@ -50,6 +58,121 @@ impl StickerSource {
             None => None,
         }
     }
+
+    /// Determine the source of a sticker from its `sticker_user_info` `plist`
+    ///
+    /// Unlike [`Self::from_bundle_id()`], this also reads the pose of Memoji stickers, like `surprised`.
+    #[must_use]
+    pub fn from_sticker_info(sticker_info: &Value) -> Option<Self> {
+        let plist = plist_as_dictionary(sticker_info).ok()?;
+        let source = Self::from_bundle_id(plist.get(STICKER_BUNDLE_ID_KEY)?.as_string()?)?;
+
+        match source {
+            StickerSource::Memoji(_) => Some(StickerSource::Memoji(
+                plist
+                    .get(STICKER_LABEL_KEY)
+                    .and_then(Value::as_string)
+                    .map(str::trim)
+                    .filter(|pose| !pose.is_empty())
+                    .map(String::from),
+            )),
+            source => Some(source),
+        }
+    }
+}
+
+impl Display for StickerSource {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StickerSource::Genmoji => write!(fmt, "Genmoji"),
+            StickerSource::Memoji(Some(pose)) => write!(fmt, "Memoji ({pose})"),
+            StickerSource::Memoji(None) => write!(fmt, "Memoji"),
+            StickerSource::UserGenerated => write!(fmt, "User Generated"),
+            StickerSource::App(bundle_id) => write!(fmt, "{bundle_id}"),
+        }
+    }
+}
+
+/// Where a sticker overlay was placed on the message bubble it targets
+///
+/// Read from the `sxs`, `sys`, `ssa`, and `srt` keys of an attachment's `sticker_user_info` `plist`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StickerPosition {
+    /// Horizontal position of the sticker's center, as a fraction of the bubble's width
+    pub x: f64,
+    /// Vertical position of the sticker's center, as a fraction of the bubble's height
+    pub y: f64,
+    /// How much the sender resized the sticker, where `1.0` is the original size
+    pub scale: f64,
+    /// How much the sender rotated the sticker, in radians
+    pub rotation: f64,
+}
+
+impl StickerPosition {
+    /// Parse the position of a sticker from its `sticker_user_info` `plist`
+    ///
+    /// Returns `None` if the sticker was not placed on a message bubble.
+    #[must_use]
+    pub fn from_sticker_info(sticker_info: &Value) -> Option<Self> {
+        let plist = plist_as_dictionary(sticker_info).ok()?;
+        let number = |key: &str| {
+            let value = plist.get(key)?;
+            value
+                .as_real()
+                .or_else(|| value.as_signed_integer().map(|value| value as f64))
+        };
+
+        Some(Self {
+            x: number("sxs")?,
+            y: number("sys")?,
+            scale: number("ssa").unwrap_or(1.),
+            rotation: number("srt").unwrap_or(0.),
+        })
+    }
+}
+
+/// The app and sticker pack that a sticker attachment came from
+#[derive(Debug, PartialEq, Eq)]
+pub struct StickerAttribution {
+    /// The bundle ID of the app that created the sticker, like `com.apple.Animoji.StickersApp.MessagesExtension`
+    pub bundle_id: String,
+    /// The name of the sticker pack or app, if the sticker has `attribution_info`
+    pub pack_name: Option<String>,
+}
+
+impl StickerAttribution {
+    /// Read where a sticker came from out of its `sticker_user_info` and `attribution_info` `plist`s
+    ///
+    /// Returns `None` if `sticker_info` does not record the bundle ID of the app that created the sticker.
+    #[must_use]
+    pub fn from_info(sticker_info: &Value, attribution_info: Option<&Value>) -> Option<Self> {
+        let plist = plist_as_dictionary(sticker_info).ok()?;
+        let bundle_id =
+            parse_balloon_bundle_id(Some(plist.get(STICKER_BUNDLE_ID_KEY)?.as_string()?))?;
+
+        Some(Self {
+            bundle_id: bundle_id.to_string(),
+            pack_name: attribution_info.and_then(attribution_name),
+        })
+    }
+
+    /// The [`StickerSource`] for the app that created the sticker
+    #[must_use]
+    pub fn source(&self) -> Option<StickerSource> {
+        StickerSource::from_bundle_id(&self.bundle_id)
+    }
+}
+
+/// Get the name of the app or sticker pack from a sticker's `attribution_info` `plist`
+#[must_use]
+pub fn attribution_name(attribution_info: &Value) -> Option<String> {
+    let plist = plist_as_dictionary(attribution_info).ok()?;
+    plist
+        .get(ATTRIBUTION_NAME_KEY)?
+        .as_string()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
 }
 
 /// Represents different types of [sticker effects](https://www.macrumors.com/how-to/add-effects-to-stickers-in-messages/) that can be applied to sticker iMessage balloons.
@@ -137,7 +260,130 @@ mod tests {
     use std::fs::File;
     use std::io::Read;
 
-    use crate::message_types::sticker::{StickerEffect, get_sticker_effect};
+    use plist::Value;
+
+    use crate::message_types::sticker::{
+        StickerAttribution, StickerEffect, StickerPosition, StickerSource, get_sticker_effect,
+    };
+
+    #[test]
+    fn can_parse_memoji_pose() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/stickers/memoji_user_info.plist");
+        let plist = Value::from_file(plist_path).unwrap();
+
+        let source = StickerSource::from_sticker_info(&plist).unwrap();
+        assert_eq!(
+            source,
+            StickerSource::Memoji(Some(String::from("surprised")))
+        );
+        assert_eq!(source.to_string(), "Memoji (surprised)");
+    }
+
+    #[test]
+    fn can_parse_memoji_without_pose() {
+        let plist = Value::Dictionary(plist::Dictionary::from_iter([(
+            String::from("pid"),
+            Value::String(String::from(
+                "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.Animoji.StickersApp.MessagesExtension",
+            )),
+        )]));
+
+        let source = StickerSource::from_sticker_info(&plist).unwrap();
+        assert_eq!(source, StickerSource::Memoji(None));
+        assert_eq!(source.to_string(), "Memoji");
+    }
+
+    #[test]
+    fn can_parse_source_of_other_stickers() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/stickers/overlay_user_info.plist");
+        let plist = Value::from_file(plist_path).unwrap();
+
+        let source = StickerSource::from_sticker_info(&plist).unwrap();
+        assert_eq!(source, StickerSource::UserGenerated);
+        assert_eq!(source.to_string(), "User Generated");
+    }
+
+    #[test]
+    fn can_parse_sticker_attribution() {
+        let test_data = current_dir().unwrap().join("test_data/stickers");
+        let sticker_info = Value::from_file(test_data.join("app_user_info.plist")).unwrap();
+        let attribution_info = Value::from_file(test_data.join("attribution_info.plist")).unwrap();
+
+        let attribution =
+            StickerAttribution::from_info(&sticker_info, Some(&attribution_info)).unwrap();
+        assert_eq!(
+            attribution,
+            StickerAttribution {
+                bundle_id: String::from("com.cutecats.stickers.MessagesExtension"),
+                pack_name: Some(String::from("Cute Cats")),
+            }
+        );
+        assert_eq!(
+            attribution.source(),
+            Some(StickerSource::App(String::from(
+                "com.cutecats.stickers.MessagesExtension"
+            )))
+        );
+    }
+
+    #[test]
+    fn can_parse_sticker_attribution_without_pack_name() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/stickers/memoji_user_info.plist");
+        let plist = Value::from_file(plist_path).unwrap();
+
+        let attribution = StickerAttribution::from_info(&plist, None).unwrap();
+        assert_eq!(
+            attribution.bundle_id,
+            "com.apple.Animoji.StickersApp.MessagesExtension"
+        );
+        assert_eq!(attribution.pack_name, None);
+        assert_eq!(attribution.source(), Some(StickerSource::Memoji(None)));
+    }
+
+    #[test]
+    fn cant_parse_attribution_without_bundle_id() {
+        let plist = Value::Dictionary(plist::Dictionary::new());
+
+        assert_eq!(StickerAttribution::from_info(&plist, None), None);
+    }
+
+    #[test]
+    fn can_parse_sticker_overlay_position() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/stickers/overlay_user_info.plist");
+        let plist = Value::from_file(plist_path).unwrap();
+
+        assert_eq!(
+            StickerPosition::from_sticker_info(&plist),
+            Some(StickerPosition {
+                x: 0.25,
+                y: 0.75,
+                scale: 1.5,
+                rotation: 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn cant_parse_position_of_unplaced_sticker() {
+        let plist = Value::Dictionary(plist::Dictionary::from_iter([(
+            String::from("pid"),
+            Value::String(String::from("com.apple.messages.genmoji")),
+        )]));
+
+        assert_eq!(StickerPosition::from_sticker_info(&plist), None);
+    }
 
     #[test]
     fn test_parse_sticker_normal() {
@@ -38,6 +38,11 @@ pub struct URLMessage<'a> {
     pub icons: Vec<&'a str>,
     /// The name of a website
     pub site_name: Option<&'a str>,
+    /// The position of the preview image among the message's attachments, if the image was sent as a file transfer
+    ///
+    /// The file transfer's GUID is the [`guid`](crate::tables::messages::models::AttachmentMeta::guid) of the
+    /// attachment at this position in the message body.
+    pub image_attachment_index: Option<usize>,
     pub placeholder: bool,
 }
 
@@ -55,6 +60,7 @@ impl<'a> BalloonProvider<'a> for URLMessage<'a> {
             icons: URLMessage::get_array_from_nested_dict(url_metadata, "icons")
                 .unwrap_or_default(),
             site_name: get_string_from_dict(url_metadata, "siteName"),
+            image_attachment_index: URLMessage::get_image_attachment_index(url_metadata),
             placeholder: get_bool_from_dict(url_metadata, "richLinkIsPlaceholder").unwrap_or(false),
         })
     }
@@ -128,6 +134,29 @@ impl<'a> URLMessage<'a> {
             .collect()
     }
 
+    /// Get the index of the attachment that holds the preview image
+    ///
+    /// The image is archived as a `RichLinkImageAttachmentSubstitute` with the scalars `imageType`
+    /// and `richLinkImageAttachmentSubstituteIndex`. Like the `images` key above, the parser collapses
+    /// scalars under the parent key, and since the index is archived last it is the value that remains:
+    /// ```json
+    /// "image": {
+    ///     "MIMEType": String("image/png"),
+    ///     "image": Integer(1)
+    /// }
+    /// ```
+    ///
+    /// Images embedded in the payload also collapse `imageType` under `image`, but they carry their
+    /// bytes under `data`, so those are skipped.
+    fn get_image_attachment_index(payload: &'a Value) -> Option<usize> {
+        let image = payload.as_dictionary()?.get("image")?.as_dictionary()?;
+        if image.contains_key("data") {
+            return None;
+        }
+        let index = image.get("image")?.as_unsigned_integer()?;
+        usize::try_from(index).ok()
+    }
+
     /// Get the redirected URL from a URL message, falling back to the original URL, if it exists
     #[must_use]
     pub fn get_url(&self) -> Option<&str> {
@@ -165,6 +194,7 @@ mod url_tests {
             images: vec![],
             icons: vec!["https://chrissardegna.com/favicon.ico"],
             site_name: None,
+            image_attachment_index: None,
             placeholder: false,
         };
 
@@ -195,6 +225,7 @@ mod url_tests {
                 "https://chrissardegna.com/ddc-icon-16x16.png",
             ],
             site_name: Some("Christopher Sardegna"),
+            image_attachment_index: None,
             placeholder: false,
         };
 
@@ -228,12 +259,32 @@ mod url_tests {
                 "https://abs.twimg.com/favicons/favicon.ico",
             ],
             site_name: Some("Twitter"),
+            image_attachment_index: Some(1),
             placeholder: false,
         };
 
         assert_eq!(balloon, expected);
     }
 
+    #[test]
+    fn test_parse_url_preview_card() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/url_message/Twitter.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = URLMessage::from_map(&parsed).unwrap();
+
+        assert!(balloon.title.is_some_and(|title| !title.is_empty()));
+        assert!(balloon.summary.is_some_and(|summary| !summary.is_empty()));
+        assert!(balloon.get_url().is_some_and(|url| !url.is_empty()));
+        // The icon is the first file transfer, so the preview image is the second
+        assert_eq!(balloon.image_attachment_index, Some(1));
+    }
+
     #[test]
     fn test_parse_url_reminder() {
         let plist_path = current_dir()
@@ -256,6 +307,7 @@ mod url_tests {
             images: vec![],
             icons: vec![],
             site_name: None,
+            image_attachment_index: None,
             placeholder: false,
         };
 
@@ -273,6 +325,7 @@ mod url_tests {
             images: vec![],
             icons: vec!["https://chrissardegna.com/favicon.ico"],
             site_name: None,
+            image_attachment_index: None,
             placeholder: false,
         };
         assert_eq!(expected.get_url(), Some("https://chrissardegna.com/"));
@@ -289,6 +342,7 @@ mod url_tests {
             images: vec![],
             icons: vec!["https://chrissardegna.com/favicon.ico"],
             site_name: None,
+            image_attachment_index: None,
             placeholder: false,
         };
         assert_eq!(expected.get_url(), Some("https://chrissardegna.com"));
@@ -305,6 +359,7 @@ mod url_tests {
             images: vec![],
             icons: vec!["https://chrissardegna.com/favicon.ico"],
             site_name: None,
+            image_attachment_index: None,
             placeholder: false,
         };
         assert_eq!(expected.get_url(), None);
@@ -2,7 +2,11 @@
  Variants represent the different types of iMessages that exist in the `messages` table.
 */
 
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    mem::discriminant,
+};
 
 use plist::Value;
 
@@ -13,6 +17,7 @@ use crate::{
         placemark::PlacemarkMessage, url::URLMessage,
     },
     tables::messages::models::GroupAction,
+    util::graphemes::first_cluster,
 };
 
 /// # Tapbacks
@@ -43,7 +48,10 @@ use crate::{
 /// - 3 is the text of the message
 ///
 /// In this example, a Like on `p:2/` is a like on the third image.
-#[derive(Debug)]
+///
+/// Two tapbacks are equal if they are the same kind and render the same [`emoji()`](Tapback::emoji), so custom
+/// emoji tapbacks can be grouped together even when the emoji spans many codepoints.
+#[derive(Debug, Clone, Copy)]
 pub enum Tapback<'a> {
     /// Heart
     Loved,
@@ -63,13 +71,50 @@ pub enum Tapback<'a> {
     Sticker,
 }
 
+/// Shown in place of a custom emoji tapback when the database did not store the emoji
+pub const UNKNOWN_EMOJI_TAPBACK: &str = "❔";
+
+impl Tapback<'_> {
+    /// The emoji that represents the tapback
+    ///
+    /// Custom emoji tapbacks use the first full grapheme cluster the sender reacted with, so skin tones and
+    /// ZWJ sequences are never split, or [`UNKNOWN_EMOJI_TAPBACK`] if it is missing.
+    #[must_use]
+    pub fn emoji(&self) -> &str {
+        match self {
+            Tapback::Loved => "❤️",
+            Tapback::Liked => "👍",
+            Tapback::Disliked => "👎",
+            Tapback::Laughed => "😂",
+            Tapback::Emphasized => "‼️",
+            Tapback::Questioned => "❓",
+            Tapback::Emoji(emoji) => emoji
+                .and_then(|emoji| first_cluster(emoji.trim()))
+                .unwrap_or(UNKNOWN_EMOJI_TAPBACK),
+            Tapback::Sticker => "🖼️",
+        }
+    }
+}
+
+impl PartialEq for Tapback<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        discriminant(self) == discriminant(other) && self.emoji() == other.emoji()
+    }
+}
+
+impl Eq for Tapback<'_> {}
+
+impl Hash for Tapback<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+        self.emoji().hash(state);
+    }
+}
+
 impl Display for Tapback<'_> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Tapback::Emoji(emoji) => match emoji {
-                Some(e) => write!(fmt, "{e}"),
-                None => write!(fmt, "Unknown emoji!"),
-            },
+            Tapback::Emoji(_) => write!(fmt, "{}", self.emoji()),
             _ => write!(fmt, "{self:?}"),
         }
     }
@@ -99,6 +144,8 @@ pub enum CustomBalloon<'a> {
     CheckIn,
     /// Find My messages
     FindMy,
+    /// [Poll](crate::message_types::poll) messages
+    Poll,
 }
 
 /// URL Message Types
@@ -131,10 +178,23 @@ pub enum Announcement<'a> {
     GroupAction(GroupAction<'a>),
     /// A user kept an audio message
     AudioMessageKept,
+    /// A user pinned or unpinned a message in the chat
+    MessagePin(PinAction),
     /// Types that may occur in the future
     Unknown(&'a i32),
 }
 
+/// Pin Action Container
+///
+/// Messages can either be pinned to or unpinned from a chat; this enum represents those states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinAction {
+    /// The message was pinned to the chat
+    Pinned,
+    /// The message was unpinned from the chat
+    Unpinned,
+}
+
 /// Tapback Action Container
 ///
 /// Tapbacks can either be added or removed; this enum represents those states
@@ -3,27 +3,33 @@
 */
 
 use plist::Value;
-use rusqlite::{Connection, Error, Result, Row, Statement, blob::Blob};
+use rusqlite::{Connection, Error, Result, Row, Statement, blob::Blob, params_from_iter};
 use sha1::{Digest, Sha1};
 
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
-    io::Read,
+    io::{Read, copy},
     path::{Path, PathBuf},
 };
 
 use crate::{
     error::{attachment::AttachmentError, table::TableError},
-    message_types::sticker::{StickerEffect, StickerSource, get_sticker_effect},
+    message_types::sticker::{
+        StickerAttribution, StickerEffect, StickerPosition, StickerSource, attribution_name,
+        get_sticker_effect,
+    },
     tables::{
         messages::Message,
-        table::{ATTACHMENT, ATTRIBUTION_INFO, GetBlob, STICKER_USER_INFO, Table},
+        table::{
+            ATTACHMENT, ATTRIBUTION_INFO, CHAT_MESSAGE_JOIN, GetBlob, MESSAGE,
+            MESSAGE_ATTACHMENT_JOIN, RECENTLY_DELETED, STICKER_USER_INFO, Table,
+        },
     },
     util::{
         dirs::home,
-        output::{done_processing, processing},
+        output::{diagnostic, done_processing, processing},
         platform::Platform,
-        plist::plist_as_dictionary,
         query_context::QueryContext,
         size::format_file_size,
     },
@@ -72,7 +78,7 @@ impl MediaType<'_> {
 }
 
 /// Represents a single row in the `attachment` table.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Attachment {
     pub rowid: i32,
     /// The path to the file on disk
@@ -87,6 +93,7 @@ pub struct Attachment {
     pub total_bytes: i64,
     /// `true` if the attachment was a sticker, else `false`
     pub is_sticker: bool,
+    /// Non-zero if the attachment is not displayed in the conversation, i.e. a link preview's thumbnail
     pub hide_attachment: i32,
     /// The prompt used to generate a Genmoji
     pub emoji_description: Option<String>,
@@ -175,10 +182,126 @@ impl Attachment {
                 let m = Attachment::extract(attachment)?;
                 out_l.push(m);
             }
+            Attachment::remove_duplicates(&mut out_l);
         }
         Ok(out_l)
     }
 
+    /// Remove attachments that share a `ROWID` with an earlier attachment, returning how many were removed
+    ///
+    /// Each attachment keeps the position of its first occurrence. Sharing the same file twice in one message
+    /// creates two attachment rows with distinct `ROWID`s, so those are not treated as duplicates. A single
+    /// `ROWID` joined to a message more than once is a data issue: current databases prevent it with a `UNIQUE`
+    /// constraint on `message_attachment_join`, but databases from older versions or that were merged during a
+    /// sync or restore can still contain repeated rows.
+    pub fn remove_duplicates(attachments: &mut Vec<Attachment>) -> usize {
+        let before = attachments.len();
+        let mut seen = HashSet::with_capacity(before);
+        attachments.retain(|attachment| seen.insert(attachment.rowid));
+        before - attachments.len()
+    }
+
+    /// Build a map of message `ROWID`s to the attachments associated with them in a single query
+    ///
+    /// Only messages that match the filters in the provided [`QueryContext`] are included.
+    /// The order of each message's attachments matches [`Attachment::from_message()`], so
+    /// the map can be consulted with [`Message::attachments_without_query()`] instead of
+    /// querying the database once per message.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::attachment::Attachment;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let context = QueryContext::default();
+    /// let attachments = Attachment::cache_by_message(&conn, &context).unwrap();
+    /// ```
+    pub fn cache_by_message(
+        db: &Connection,
+        context: &QueryContext,
+    ) -> Result<HashMap<i32, Vec<Attachment>>, TableError> {
+        let mut map: HashMap<i32, Vec<Attachment>> = HashMap::new();
+
+        let filter = |include_recoverable: bool| {
+            let message_filter = Message::generate_filter_statement(context, include_recoverable);
+            if !message_filter.is_empty() {
+                format!(
+                    "WHERE j.message_id IN (
+                        SELECT m.ROWID
+                        FROM {MESSAGE} as m
+                        LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+                        {}
+                        {}
+                    )",
+                    if include_recoverable {
+                        format!("LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id")
+                    } else {
+                        String::new()
+                    },
+                    message_filter
+                )
+            } else {
+                String::new()
+            }
+        };
+
+        let mut statement = db
+            .prepare(&format!(
+                "
+                    SELECT j.message_id, {COLS}
+                    FROM {MESSAGE_ATTACHMENT_JOIN} j
+                    LEFT JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID
+                    {}
+                    ORDER BY j.message_id, j.ROWID
+                ",
+                filter(true)
+            ))
+            .or_else(|_| {
+                db.prepare(&format!(
+                    "
+                        SELECT j.message_id, {COLS}
+                        FROM {MESSAGE_ATTACHMENT_JOIN} j
+                        LEFT JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID
+                        {}
+                        ORDER BY j.message_id, j.ROWID
+                    ",
+                    filter(false)
+                ))
+            })
+            .map_err(TableError::Attachment)?;
+
+        let iter = statement
+            .query_map(params_from_iter(context.predicate_params()), |row| {
+                Ok((row.get::<_, i32>("message_id"), Attachment::from_row(row)))
+            })
+            .map_err(TableError::Attachment)?;
+
+        for row in iter {
+            let (message_id, attachment) = row.map_err(TableError::Attachment)?;
+            let message_id = message_id.map_err(TableError::Attachment)?;
+            let attachment = Attachment::extract(Ok(attachment))?;
+            map.entry(message_id).or_default().push(attachment);
+        }
+        map.values_mut().for_each(|attachments| {
+            Attachment::remove_duplicates(attachments);
+        });
+
+        Ok(map)
+    }
+
+    /// `true` if the attachment is not displayed in the conversation on its own, else `false`
+    ///
+    /// Hidden attachments hold data used to render another part of a message, like the thumbnail of a link preview.
+    #[must_use]
+    pub fn is_hidden(&self) -> bool {
+        self.hide_attachment != 0
+    }
+
     /// Get the media type of an attachment
     #[must_use]
     pub fn mime_type(&'_ self) -> MediaType<'_> {
@@ -205,6 +328,9 @@ impl Attachment {
                         // This type is for audio messages, which are sent in `caf` format
                         // https://developer.apple.com/library/archive/documentation/MusicAudio/Reference/CAFSpec/CAF_overview/CAF_overview.html
                         "com.apple.coreaudio-format" => MediaType::Audio("x-caf; codecs=opus"),
+                        // Photos taken on an iPhone are stored as HEIC without a MIME type
+                        "public.heic" => MediaType::Image("heic"),
+                        "public.heif" => MediaType::Image("heif"),
                         _ => MediaType::Unknown,
                     }
                 } else {
@@ -238,6 +364,32 @@ impl Attachment {
         Ok(None)
     }
 
+    /// `true` if the attachment's file is on disk, else `false`
+    ///
+    /// Attachments that were offloaded to iCloud leave behind an empty stub file, or no file at all, so an
+    /// attachment only counts as downloaded if its file exists and is not empty. If the attachment was copied,
+    /// the copy is checked instead.
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    #[must_use]
+    pub fn is_downloaded(
+        &self,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> bool {
+        self.copied_path
+            .clone()
+            .or_else(|| {
+                self.resolved_attachment_path(platform, db_path, custom_attachment_root)
+                    .map(PathBuf::from)
+            })
+            .and_then(|path| path.metadata().ok())
+            // Some attachments are stored as directories
+            .is_some_and(|metadata| metadata.is_dir() || metadata.len() > 0)
+    }
+
     /// Determine the [`StickerEffect`] of a sticker message
     ///
     /// `db_path` is the path to the root of the backup directory.
@@ -297,6 +449,19 @@ impl Attachment {
         format_file_size(u64::try_from(self.total_bytes).unwrap_or(0))
     }
 
+    /// Get the hex SHA-1 digest of the attachment's file, to find identical files across messages and chats
+    ///
+    /// The file is read from [`filename`](Self::filename), with a leading `~` expanded to the home directory. The
+    /// digest is computed each time this is called. Returns `None` if there is no path or the file cannot be read.
+    #[must_use]
+    pub fn sha1(&self) -> Option<String> {
+        let path = Self::gen_macos_attachment(self.filename.as_deref()?);
+        let mut file = File::open(path).ok()?;
+        let mut hasher = Sha1::new();
+        copy(&mut file, &mut hasher).ok()?;
+        Some(format!("{:x}", hasher.finalize()))
+    }
+
     /// Get the total attachment bytes referenced in the table
     pub fn get_total_attachment_bytes(
         db: &Connection,
@@ -304,15 +469,15 @@ impl Attachment {
     ) -> Result<u64, TableError> {
         let mut bytes_query = match context.limit {
             Some(limit) => {
-                let statement = format!("SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT} a LIMIT {limit}"); // TODO: Who knows if this works!
+                let statement =
+                    format!("SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT} a LIMIT {limit}"); // TODO: Who knows if this works!
                 db.prepare(&statement).map_err(TableError::Attachment)?
             }
-            None => {
-                db.prepare(&format!(
+            None => db
+                .prepare(&format!(
                     "SELECT IFNULL(SUM(total_bytes), 0) FROM {ATTACHMENT}"
                 ))
-                .map_err(TableError::Attachment)?
-            }
+                .map_err(TableError::Attachment)?,
         };
         bytes_query
             .query_row([], |r| -> Result<i64> { r.get(0) })
@@ -331,6 +496,7 @@ impl Attachment {
     /// Use the optional `custom_attachment_root` parameter when the attachments are not stored in
     /// the same place as the database expects.The expected location is [`DEFAULT_ATTACHMENT_ROOT`].
     /// A custom attachment root like `/custom/path` will overwrite a path like `~/Library/Messages/Attachments/3d/...` to `/custom/path/3d/...`
+    /// See [`Self::rewrite_attachment_root()`] for the paths that are rewritten.
     #[must_use]
     pub fn resolved_attachment_path(
         &self,
@@ -340,8 +506,10 @@ impl Attachment {
     ) -> Option<String> {
         if let Some(mut path_str) = self.filename.clone() {
             // Apply custom attachment path
-            if let Some(custom_attachment_path) = custom_attachment_root {
-                path_str = path_str.replace(DEFAULT_ATTACHMENT_ROOT, custom_attachment_path);
+            if let Some(rewritten) = custom_attachment_root.and_then(|custom_attachment_path| {
+                Attachment::rewrite_attachment_root(&path_str, custom_attachment_path)
+            }) {
+                path_str = rewritten;
             }
             return match platform {
                 Platform::macOS => Some(Attachment::gen_macos_attachment(&path_str)),
@@ -351,6 +519,41 @@ impl Attachment {
         None
     }
 
+    /// Rewrite a stored attachment path so it points into a relocated attachment folder
+    ///
+    /// Paths are stored either relative to the home directory, like `~/Library/Messages/Attachments/3d/...`,
+    /// or as absolute paths into a user's home directory, like `/Users/name/Library/Messages/Attachments/3d/...`.
+    /// Both are rewritten to `custom_root/3d/...`. Returns [`None`] if the path is not in the default
+    /// attachment folder.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::tables::attachment::Attachment;
+    ///
+    /// let rewritten = Attachment::rewrite_attachment_root("~/Library/Messages/Attachments/3d/13/file.jpeg", "/Volumes/Backup");
+    /// assert_eq!(rewritten, Some("/Volumes/Backup/3d/13/file.jpeg".to_string()));
+    /// ```
+    #[must_use]
+    pub fn rewrite_attachment_root(path: &str, custom_root: &str) -> Option<String> {
+        // The part of the default root that follows the home directory
+        let relative_root = DEFAULT_ATTACHMENT_ROOT.strip_prefix('~')?;
+
+        let remainder = match path.strip_prefix(DEFAULT_ATTACHMENT_ROOT) {
+            Some(remainder) => remainder,
+            // Absolute paths contain the home directory of the user who owned the database
+            None => {
+                let idx = path.find(relative_root)?;
+                if !path.starts_with('/') {
+                    return None;
+                }
+                &path[idx + relative_root.len()..]
+            }
+        };
+
+        Some(format!("{}{remainder}", custom_root.trim_end_matches('/')))
+    }
+
     /// Emit diagnostic data for the Attachments table
     ///
     /// This is defined outside of [`Diagnostic`](crate::tables::table::Diagnostic) because it requires additional data.
@@ -427,35 +630,86 @@ impl Attachment {
 
         let total_bytes =
             Attachment::get_total_attachment_bytes(db, &QueryContext::default()).unwrap_or(0);
+        let (missing_from_disk, missing_bytes) =
+            Attachment::missing_from_disk(db, db_path, platform)?;
 
         done_processing();
 
         if total_attachments > 0 {
-            println!("\rAttachment diagnostic data:");
-            println!("    Total attachments: {total_attachments}");
-            println!(
+            diagnostic("Attachment diagnostic data:");
+            diagnostic(&format!("    Total attachments: {total_attachments}"));
+            diagnostic(&format!(
                 "        Data referenced in table: {}",
                 format_file_size(total_bytes)
-            );
-            println!(
+            ));
+            diagnostic(&format!(
                 "        Data present on disk: {}",
                 format_file_size(size_on_disk)
-            );
+            ));
             if missing_files > 0 && total_attachments > 0 {
-                println!(
+                diagnostic(&format!(
                     "    Missing files: {missing_files:?} ({:.0}%)",
                     (missing_files as f64 / f64::from(total_attachments)) * 100f64
-                );
-                println!("        No path provided: {null_attachments}");
-                println!(
+                ));
+                diagnostic(&format!("        No path provided: {null_attachments}"));
+                diagnostic(&format!(
                     "        No file located: {}",
                     missing_files.saturating_sub(null_attachments)
-                );
+                ));
+            }
+            if missing_from_disk > 0 {
+                diagnostic(&format!(
+                    "    Attachments missing from disk: {missing_from_disk}"
+                ));
+                diagnostic(&format!(
+                    "        Data missing from disk: {}",
+                    format_file_size(missing_bytes)
+                ));
             }
         }
         Ok(())
     }
 
+    /// Count the attachments whose file is no longer on disk, and the bytes the table says they used
+    ///
+    /// Rows without a `filename` are skipped, since they never pointed to a file. Returns the number of missing
+    /// files and the sum of their `total_bytes`, the data that would be lost from an export.
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn missing_from_disk(
+        db: &Connection,
+        db_path: &Path,
+        platform: &Platform,
+    ) -> Result<(u64, u64), TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT filename, total_bytes FROM {ATTACHMENT} WHERE filename IS NOT NULL"
+            ))
+            .map_err(TableError::Attachment)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+            })
+            .map_err(TableError::Attachment)?;
+
+        let mut missing_files = 0;
+        let mut missing_bytes: u64 = 0;
+        for row in rows {
+            let (filepath, total_bytes) = row.map_err(TableError::Attachment)?;
+            let path = match platform {
+                Platform::macOS => Some(Attachment::gen_macos_attachment(&filepath)),
+                Platform::iOS => Attachment::gen_ios_attachment(&filepath, db_path),
+            };
+            if !path.is_some_and(|path| Path::new(&path).exists()) {
+                missing_files += 1;
+                missing_bytes = missing_bytes
+                    .saturating_add(u64::try_from(total_bytes.unwrap_or(0)).unwrap_or(0));
+            }
+        }
+        Ok((missing_files, missing_bytes))
+    }
+
     /// Generate a macOS path for an attachment
     fn gen_macos_attachment(path: &str) -> String {
         if path.starts_with('~') {
@@ -498,15 +752,20 @@ impl Attachment {
 
     /// Parse a sticker's source from the Bundle ID stored in [`STICKER_USER_INFO`] `plist` data
     ///
+    /// Memoji stickers also carry the pose they were made with, if the `plist` records it.
+    ///
     /// Calling this hits the database, so it is expensive and should
     /// only get invoked when needed.
     pub fn get_sticker_source(&self, db: &Connection) -> Option<StickerSource> {
-        if let Some(sticker_info) = self.sticker_info(db) {
-            let plist = plist_as_dictionary(&sticker_info).ok()?;
-            let bundle_id = plist.get("pid")?.as_string()?;
-            return StickerSource::from_bundle_id(bundle_id);
-        }
-        None
+        StickerSource::from_sticker_info(&self.sticker_info(db)?)
+    }
+
+    /// Parse where a sticker was placed on a message bubble from [`STICKER_USER_INFO`] `plist` data
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    pub fn get_sticker_position(&self, db: &Connection) -> Option<StickerPosition> {
+        StickerPosition::from_sticker_info(&self.sticker_info(db)?)
     }
 
     /// Parse a sticker's application name stored in [`ATTRIBUTION_INFO`] `plist` data
@@ -514,11 +773,18 @@ impl Attachment {
     /// Calling this hits the database, so it is expensive and should
     /// only get invoked when needed.
     pub fn get_sticker_source_application_name(&self, db: &Connection) -> Option<String> {
-        if let Some(attribution_info) = self.attribution_info(db) {
-            let plist = plist_as_dictionary(&attribution_info).ok()?;
-            return Some(plist.get("name")?.as_string()?.to_owned());
-        }
-        None
+        attribution_name(&self.attribution_info(db)?)
+    }
+
+    /// Get the bundle ID of the app a sticker came from and the name of its sticker pack
+    ///
+    /// The bundle ID is read from [`STICKER_USER_INFO`] and the pack name from [`ATTRIBUTION_INFO`]. Returns `None` if
+    /// the attachment has no [`STICKER_USER_INFO`] data.
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    pub fn get_sticker_attribution(&self, db: &Connection) -> Option<StickerAttribution> {
+        StickerAttribution::from_info(&self.sticker_info(db)?, self.attribution_info(db).as_ref())
     }
 }
 
@@ -527,7 +793,8 @@ mod tests {
     use crate::{
         tables::{
             attachment::{Attachment, DEFAULT_ATTACHMENT_ROOT, MediaType},
-            table::get_connection,
+            messages::Message,
+            table::{Table, get_connection, get_empty_test_connection},
         },
         util::{platform::Platform, query_context::QueryContext},
     };
@@ -592,6 +859,14 @@ mod tests {
         assert_eq!(attachment.mime_type(), MediaType::Image("heic"));
     }
 
+    #[test]
+    fn can_get_mime_type_heic_uti() {
+        let mut attachment = sample_attachment();
+        attachment.mime_type = None;
+        attachment.uti = Some("public.heic".to_string());
+        assert_eq!(attachment.mime_type(), MediaType::Image("heic"));
+    }
+
     #[test]
     fn can_get_mime_type_fake() {
         let mut attachment = sample_attachment();
@@ -658,6 +933,135 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_rewrite_absolute_attachment_root() {
+        assert_eq!(
+            Attachment::rewrite_attachment_root(
+                "/Users/someone/Library/Messages/Attachments/a/b/c.png",
+                "custom/root/"
+            ),
+            Some("custom/root/a/b/c.png".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_rewrite_path_outside_attachment_root() {
+        assert_eq!(
+            Attachment::rewrite_attachment_root("/tmp/a/b/c.png", "custom/root"),
+            None
+        );
+        assert_eq!(
+            Attachment::rewrite_attachment_root(
+                "a/Library/Messages/Attachments/b/c.png",
+                "custom/root"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn can_resolve_rewritten_path_to_relocated_file() {
+        let root = std::env::temp_dir().join("imessage-database-relocated-attachments");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::write(root.join("a/b/c.png"), b"png").unwrap();
+
+        let db_path = PathBuf::from("fake_root");
+        let mut attachment = sample_attachment();
+        attachment.filename = Some(format!("{DEFAULT_ATTACHMENT_ROOT}/a/b/c.png"));
+
+        let resolved = attachment
+            .resolved_attachment_path(&Platform::macOS, &db_path, root.to_str())
+            .unwrap();
+        assert_eq!(PathBuf::from(&resolved), root.join("a/b/c.png"));
+        assert!(Path::new(&resolved).exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn can_detect_downloaded_attachment() {
+        let root = std::env::temp_dir().join("imessage-database-downloaded-attachments");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("full.png"), b"png").unwrap();
+        // Attachments offloaded to iCloud leave an empty stub behind
+        std::fs::write(root.join("stub.png"), b"").unwrap();
+
+        let db_path = PathBuf::from("fake_root");
+        let mut attachment = sample_attachment();
+
+        attachment.filename = Some(root.join("full.png").display().to_string());
+        assert!(attachment.is_downloaded(&Platform::macOS, &db_path, None));
+
+        attachment.filename = Some(root.join("stub.png").display().to_string());
+        assert!(!attachment.is_downloaded(&Platform::macOS, &db_path, None));
+
+        attachment.filename = Some(root.join("missing.png").display().to_string());
+        assert!(!attachment.is_downloaded(&Platform::macOS, &db_path, None));
+
+        // A copy of the file takes precedence over the original
+        attachment.copied_path = Some(root.join("full.png"));
+        assert!(attachment.is_downloaded(&Platform::macOS, &db_path, None));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn can_remove_duplicate_attachments() {
+        let attachment = |rowid| {
+            let mut attachment = sample_attachment();
+            attachment.rowid = rowid;
+            attachment
+        };
+        let mut attachments = vec![attachment(1), attachment(2), attachment(1), attachment(2)];
+
+        assert_eq!(Attachment::remove_duplicates(&mut attachments), 2);
+        assert_eq!(
+            attachments.iter().map(|a| a.rowid).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(Attachment::remove_duplicates(&mut attachments), 0);
+    }
+
+    #[test]
+    fn can_get_message_joined_to_attachment_twice() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let copy = std::env::temp_dir().join("imessage-database-duplicate-join.db");
+        std::fs::copy(&db_path, &copy).unwrap();
+        // Older databases do not have a `UNIQUE` constraint on the join table
+        let connection = rusqlite::Connection::open(&copy).unwrap();
+        connection
+            .execute_batch(
+                "
+                CREATE TABLE join_copy AS SELECT * FROM message_attachment_join;
+                DROP TABLE message_attachment_join;
+                CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+                INSERT INTO message_attachment_join SELECT * FROM join_copy;
+                INSERT INTO message_attachment_join SELECT * FROM join_copy;
+                ",
+            )
+            .unwrap();
+
+        let message =
+            Message::from_guid("0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", &connection).unwrap();
+        let attachments = Attachment::from_message(&connection, &message).unwrap();
+        assert_eq!(
+            attachments.iter().map(|a| a.rowid).collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        let mut cache =
+            Attachment::cache_by_message(&connection, &QueryContext::default()).unwrap();
+        assert_eq!(message.attachments_without_query(&mut cache).len(), 1);
+
+        std::fs::remove_file(&copy).unwrap();
+    }
+
     #[test]
     fn can_get_resolved_path_macos_raw() {
         let db_path = PathBuf::from("fake_root");
@@ -818,4 +1222,158 @@ mod tests {
 
         assert_eq!(attachment.file_size(), String::from("8388608.00 TB"));
     }
+
+    #[test]
+    fn can_count_attachments_missing_from_disk() {
+        let db = get_empty_test_connection();
+        let present = current_dir()
+            .unwrap()
+            .join("test_data/exif/DateTimeOriginal.jpg");
+        db.execute(
+            "INSERT INTO attachment (ROWID, guid, original_guid, filename, total_bytes) VALUES
+                (1, 'a', 'a', ?1, 115),
+                (2, 'b', 'b', '/missing/IMG_0001.jpeg', 2048),
+                (3, 'c', 'c', '~/Library/Messages/Attachments/missing.heic', 1024),
+                (4, 'd', 'd', NULL, 4096);",
+            [present.to_string_lossy()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            Attachment::missing_from_disk(&db, Path::new("/"), &Platform::macOS).unwrap(),
+            (2, 3072)
+        );
+    }
+
+    #[test]
+    fn can_get_sticker_attribution() {
+        let db = get_empty_test_connection();
+        let test_data = current_dir().unwrap().join("test_data/stickers");
+        let sticker_info = std::fs::read(test_data.join("app_user_info.plist")).unwrap();
+        let attribution_info = std::fs::read(test_data.join("attribution_info.plist")).unwrap();
+        db.execute(
+            "INSERT INTO attachment (ROWID, guid, original_guid, is_sticker, sticker_user_info, attribution_info) VALUES
+                (1, 'a', 'a', 1, ?1, ?2),
+                (2, 'b', 'b', 1, ?1, NULL),
+                (3, 'c', 'c', 0, NULL, NULL);",
+            (&sticker_info, &attribution_info),
+        )
+        .unwrap();
+
+        let mut attachment = sample_attachment();
+        attachment.rowid = 1;
+        let attribution = attachment.get_sticker_attribution(&db).unwrap();
+        assert_eq!(
+            attribution.bundle_id,
+            "com.cutecats.stickers.MessagesExtension"
+        );
+        assert_eq!(attribution.pack_name, Some(String::from("Cute Cats")));
+
+        attachment.rowid = 2;
+        assert_eq!(
+            attachment.get_sticker_attribution(&db).unwrap().pack_name,
+            None
+        );
+
+        attachment.rowid = 3;
+        assert!(attachment.get_sticker_attribution(&db).is_none());
+    }
+
+    #[test]
+    fn can_get_sha1() {
+        let mut attachment = sample_attachment();
+        attachment.filename = Some(
+            current_dir()
+                .unwrap()
+                .join("test_data/exif/DateTimeOriginal.jpg")
+                .to_string_lossy()
+                .to_string(),
+        );
+
+        assert_eq!(
+            attachment.sha1().as_deref(),
+            Some("82d1eb1905b87e8a3ffce6f70882a3d7f0d6e7f7")
+        );
+    }
+
+    #[test]
+    fn cant_get_sha1_missing_file() {
+        let mut attachment = sample_attachment();
+        assert_eq!(attachment.sha1(), None);
+
+        attachment.filename = None;
+        assert_eq!(attachment.sha1(), None);
+    }
+
+    #[test]
+    fn can_prefetch_attachments_by_message() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let connection = get_connection(&db_path).unwrap();
+
+        let context = QueryContext::default();
+        let mut cache = Attachment::cache_by_message(&connection, &context).unwrap();
+
+        let mut statement = Message::stream_rows(&connection, &context).unwrap();
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap();
+
+        for message in messages {
+            let message = Message::extract(message).unwrap();
+            let queried = Attachment::from_message(&connection, &message).unwrap();
+            let batched = message.attachments_without_query(&mut cache);
+
+            assert!(!queried.is_empty());
+            assert_eq!(
+                queried.iter().map(|a| a.rowid).collect::<Vec<_>>(),
+                batched.iter().map(|a| a.rowid).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                queried.iter().map(|a| &a.filename).collect::<Vec<_>>(),
+                batched.iter().map(|a| &a.filename).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn can_prefetch_attachments_by_message_chat_filter() {
+        let connection = get_empty_test_connection();
+        connection
+            .execute_batch(
+                "
+                INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                    (1, 'included', 'a', 1, 0, 0),
+                    (2, 'excluded', 'b', 2, 0, 0),
+                    (3, 'deleted', 'c', 3, 0, 0);
+                INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2);
+                INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date) VALUES (1, 3, 4);
+                INSERT INTO attachment (ROWID, guid, original_guid, filename) VALUES
+                    (10, 'a1', 'a1', 'one.png'),
+                    (20, 'a2', 'a2', 'two.png'),
+                    (30, 'a3', 'a3', 'three.png');
+                INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 10), (2, 20), (3, 30);
+                ",
+            )
+            .unwrap();
+
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1]));
+
+        // Messages in the selected chat, or recently deleted from it, keep their attachments
+        let cache = Attachment::cache_by_message(&connection, &context).unwrap();
+        assert_eq!(
+            cache[&1].iter().map(|a| a.rowid).collect::<Vec<_>>(),
+            vec![10]
+        );
+        assert_eq!(
+            cache[&3].iter().map(|a| a.rowid).collect::<Vec<_>>(),
+            vec![30]
+        );
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.len(), 2);
+    }
 }
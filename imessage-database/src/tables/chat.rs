@@ -2,7 +2,7 @@
  This module represents common (but not all) columns in the `chat` table.
 */
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
@@ -10,10 +10,15 @@ use crate::{
     error::table::TableError,
     tables::{
         messages::models::Service,
-        table::{CHAT, Cacheable, Table},
+        table::{CHAT, CHAT_MESSAGE_JOIN, Cacheable, MESSAGE, Table},
     },
 };
 
+/// The `style` of a chat with more than one other participant
+pub const GROUP_CHAT_STYLE: i32 = 43;
+/// The `style` of a chat with a single other participant
+pub const DIRECT_CHAT_STYLE: i32 = 45;
+
 /// Represents a single row in the `chat` table.
 #[derive(Debug)]
 pub struct Chat {
@@ -23,6 +28,10 @@ pub struct Chat {
     pub service_name: Option<String>,
     /// Optional custom name created created for the chat
     pub display_name: Option<String>,
+    /// Whether the chat is a group or one-on-one, see [`GROUP_CHAT_STYLE`] and [`DIRECT_CHAT_STYLE`]
+    pub style: Option<i32>,
+    /// The ID shared by every `chat` row that belongs to the same group
+    pub group_id: Option<String>,
 }
 
 impl Table for Chat {
@@ -32,6 +41,8 @@ impl Table for Chat {
             chat_identifier: row.get("chat_identifier")?,
             service_name: row.get("service_name")?,
             display_name: row.get("display_name").unwrap_or(None),
+            style: row.get("style").unwrap_or(None),
+            group_id: row.get("group_id").unwrap_or(None),
         })
     }
 
@@ -108,9 +119,291 @@ impl Chat {
         }
     }
 
+    /// `true` if the chat is a group conversation, else `false`
+    ///
+    /// Databases that do not record the chat's `style` fall back to its identifier, since group chat identifiers
+    /// look like `chat123456789` while one-on-one chats use a phone number or email address.
+    #[must_use]
+    pub fn is_group(&self) -> bool {
+        match self.style {
+            Some(style) => style == GROUP_CHAT_STYLE,
+            None => self.chat_identifier.starts_with("chat"),
+        }
+    }
+
+    /// Get the identifier that uniquely names the conversation
+    ///
+    /// Group chats are named by their `group_id`, which is shared by every `chat` row for the group, even when
+    /// the group is split across services. One-on-one chats are named by their `chat_identifier`, which is the
+    /// other participant's phone number or email address. Groups without a `group_id` use their `chat_identifier`.
+    #[must_use]
+    pub fn canonical_identifier(&self) -> &str {
+        match &self.group_id {
+            Some(group_id) if self.is_group() && !group_id.is_empty() => group_id,
+            _ => &self.chat_identifier,
+        }
+    }
+
     /// Get the service used by the chat, i.e. iMessage, SMS, IRC, etc.
     #[must_use]
     pub fn service(&self) -> Service {
         Service::from(self.service_name.as_deref())
     }
+
+    /// Build a map of chat IDs to the number of messages written by a person in that chat
+    ///
+    /// System messages, like [announcements](crate::message_types::variants::Announcement), are not counted,
+    /// so a chat that only contains group actions maps to `0`. Chats without any messages are also included.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat::Chat;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let counts = Chat::user_message_counts(&conn);
+    /// ```
+    pub fn user_message_counts(db: &Connection) -> Result<HashMap<i32, u64>, TableError> {
+        let mut map = HashMap::new();
+
+        // Messages with an `item_type` other than `0` are group actions or other system events
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     ch.ROWID as chat_id,
+                     COUNT(m.ROWID) as num_messages
+                 FROM {CHAT} as ch
+                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON ch.ROWID = c.chat_id
+                 LEFT JOIN {MESSAGE} as m ON c.message_id = m.ROWID AND m.item_type = 0
+                 GROUP BY ch.ROWID"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let counts = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(TableError::Chat)?;
+
+        for count in counts {
+            let (chat_id, num_messages) = count.map_err(TableError::Chat)?;
+            map.insert(chat_id, num_messages);
+        }
+
+        Ok(map)
+    }
+
+    /// Get the IDs of chats whose most recent message was sent at or after `since`
+    ///
+    /// `since` is a timestamp in the same format as [`Message::date`](crate::tables::messages::Message::date).
+    /// Chats without any messages are never included.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat::Chat;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let active = Chat::active_since(&conn, 674526582885055488);
+    /// ```
+    pub fn active_since(db: &Connection, since: i64) -> Result<BTreeSet<i32>, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT c.chat_id
+                 FROM {CHAT_MESSAGE_JOIN} as c
+                 JOIN {MESSAGE} as m ON c.message_id = m.ROWID
+                 GROUP BY c.chat_id
+                 HAVING MAX(m.date) >= ?1"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let chat_ids = statement
+            .query_map([since], |row| row.get(0))
+            .map_err(TableError::Chat)?;
+
+        chat_ids
+            .collect::<Result<BTreeSet<i32>, _>>()
+            .map_err(TableError::Chat)
+    }
+
+    /// Get the IDs of the chats a person would know by `identifier`, like a phone number, email address, or group name
+    ///
+    /// A chat matches if its `chat_identifier` or `display_name` is `identifier`. A group can be stored as several
+    /// `chat` rows that share a [`group_id`](Self::group_id), so every row in a matching group is included. The IDs
+    /// are sorted, and are empty if no chat matches.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat::Chat;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let chat_ids = Chat::get_ids_for_identifier(&conn, "+15558675309");
+    /// ```
+    pub fn get_ids_for_identifier(
+        db: &Connection,
+        identifier: &str,
+    ) -> Result<Vec<i32>, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT ROWID
+                 FROM {CHAT}
+                 WHERE chat_identifier = ?1
+                     OR display_name = ?1
+                     OR group_id IN (
+                         SELECT group_id FROM {CHAT}
+                         WHERE (chat_identifier = ?1 OR display_name = ?1) AND group_id IS NOT NULL
+                     )
+                 ORDER BY ROWID"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let chat_ids = statement
+            .query_map([identifier], |row| row.get(0))
+            .map_err(TableError::Chat)?;
+
+        chat_ids
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(TableError::Chat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::tables::{
+        chat::Chat,
+        table::{Cacheable, get_empty_test_connection},
+    };
+
+    #[test]
+    fn can_get_canonical_identifier() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, style, chat_identifier, group_id) VALUES
+                (1, 'iMessage;+;chat123456789', 43, 'chat123456789', '6B5B4B1B-0000-4000-8000-000000000001'),
+                (2, 'iMessage;-;+15558675309', 45, '+15558675309', '6B5B4B1B-0000-4000-8000-000000000002'),
+                (3, 'SMS;+;chat987654321', NULL, 'chat987654321', NULL);
+            ",
+        )
+        .unwrap();
+        let chats = Chat::cache(&db).unwrap();
+
+        // Groups are named by their group ID
+        let group = chats.get(&1).unwrap();
+        assert!(group.is_group());
+        assert_eq!(
+            group.canonical_identifier(),
+            "6B5B4B1B-0000-4000-8000-000000000001"
+        );
+
+        // One-on-one chats are named by the other participant's address
+        let direct = chats.get(&2).unwrap();
+        assert!(!direct.is_group());
+        assert_eq!(direct.canonical_identifier(), "+15558675309");
+
+        // Groups without a group ID fall back to the chat identifier
+        let legacy = chats.get(&3).unwrap();
+        assert!(legacy.is_group());
+        assert_eq!(legacy.canonical_identifier(), "chat987654321");
+    }
+
+    #[test]
+    fn can_count_user_messages() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'a', 'announcements'), (2, 'b', 'people'), (3, 'c', 'empty');
+            INSERT INTO message (ROWID, guid, item_type, group_action_type, other_handle) VALUES (1, 'm1', 1, 0, 2);
+            INSERT INTO message (ROWID, guid, item_type) VALUES (2, 'm2', 0), (3, 'm3', 0), (4, 'm4', 2);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2), (2, 3), (2, 4);
+            ",
+        )
+        .unwrap();
+
+        let counts = Chat::user_message_counts(&db).unwrap();
+        assert_eq!(counts.get(&1), Some(&0));
+        assert_eq!(counts.get(&2), Some(&2));
+        assert_eq!(counts.get(&3), Some(&0));
+    }
+
+    #[test]
+    fn can_select_recently_active_chats() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'a', 'stale'), (2, 'b', 'recent'), (3, 'c', 'empty');
+            INSERT INTO message (ROWID, guid, date) VALUES (1, 'm1', 100), (2, 'm2', 200), (3, 'm3', 150), (4, 'm4', 900);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (2, 3), (2, 4);
+            ",
+        )
+        .unwrap();
+
+        // The stale chat's newest message is older than the cutoff, even though the recent chat has older messages
+        assert_eq!(Chat::active_since(&db, 500).unwrap(), BTreeSet::from([2]));
+        assert_eq!(
+            Chat::active_since(&db, 200).unwrap(),
+            BTreeSet::from([1, 2])
+        );
+        assert!(Chat::active_since(&db, 1000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn can_get_ids_for_identifier() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier, display_name, group_id) VALUES
+                (1, 'iMessage;-;+15558675309', '+15558675309', NULL, 'A'),
+                (2, 'SMS;-;+15558675309', '+15558675309', NULL, 'B'),
+                (3, 'iMessage;+;chat123', 'chat123', 'Family', 'C'),
+                (4, 'SMS;+;chat456', 'chat456', NULL, 'C'),
+                (5, 'iMessage;-;bob@example.com', 'bob@example.com', NULL, NULL);
+            ",
+        )
+        .unwrap();
+
+        // The same person over iMessage and SMS
+        assert_eq!(
+            Chat::get_ids_for_identifier(&db, "+15558675309").unwrap(),
+            vec![1, 2]
+        );
+        // Every row of a group, by its name or its identifier
+        assert_eq!(
+            Chat::get_ids_for_identifier(&db, "Family").unwrap(),
+            vec![3, 4]
+        );
+        assert_eq!(
+            Chat::get_ids_for_identifier(&db, "chat456").unwrap(),
+            vec![3, 4]
+        );
+        assert_eq!(
+            Chat::get_ids_for_identifier(&db, "bob@example.com").unwrap(),
+            vec![5]
+        );
+    }
+
+    #[test]
+    fn cant_get_ids_for_missing_identifier() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'a', '+15558675309');",
+        )
+        .unwrap();
+
+        assert!(
+            Chat::get_ids_for_identifier(&db, "+15550000000")
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -9,7 +9,7 @@ use crate::{
     tables::table::{
         CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, Cacheable, Deduplicate, Diagnostic, Table,
     },
-    util::output::{done_processing, processing},
+    util::output::{diagnostic, done_processing, processing},
 };
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
@@ -180,8 +180,10 @@ impl Diagnostic for ChatToHandle {
             .difference(&unique_chats_from_handles)
             .count();
         if chats_with_no_handles > 0 {
-            println!("Thread diagnostic data:");
-            println!("    Chats with no handles: {chats_with_no_handles:?}");
+            diagnostic("Thread diagnostic data:");
+            diagnostic(&format!(
+                "    Chats with no handles: {chats_with_no_handles:?}"
+            ));
         }
 
         Ok(())
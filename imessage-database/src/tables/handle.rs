@@ -7,8 +7,8 @@ use std::collections::{BTreeSet, HashMap};
 
 use crate::{
     error::table::TableError,
-    tables::table::{Cacheable, Deduplicate, Diagnostic, HANDLE, ME, Table},
-    util::output::{done_processing, processing},
+    tables::table::{Cacheable, Deduplicate, Diagnostic, HANDLE, ME, MESSAGE, Table},
+    util::output::{diagnostic, done_processing, processing},
 };
 
 /// Represents a single row in the `handle` table.
@@ -21,6 +21,23 @@ pub struct Handle {
     pub person_centric_id: Option<String>,
 }
 
+/// Describes the messages exchanged with a single handle, built by [`Handle::summaries()`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct HandleSummary {
+    /// The `ROWID` of the handle
+    pub rowid: i32,
+    /// Identifier for the contact, i.e. a phone number or email address
+    pub id: String,
+    /// The number of messages sent to or received from the handle
+    pub messages: u64,
+    /// The date of the first message with the handle, or `None` if there are no messages
+    pub first_message: Option<i64>,
+    /// The date of the most recent message with the handle, or `None` if there are no messages
+    pub last_message: Option<i64>,
+    /// The services the handle was reached over, such as `iMessage` or `SMS`
+    pub services: BTreeSet<String>,
+}
+
 impl Table for Handle {
     fn from_row(row: &Row) -> Result<Handle> {
         Ok(Handle {
@@ -180,8 +197,8 @@ impl Diagnostic for Handle {
 
             if let Some(dupes) = count_dupes {
                 if dupes > 0 {
-                    println!("Handle diagnostic data:");
-                    println!("    Contacts with more than one ID: {dupes}");
+                    diagnostic("Handle diagnostic data:");
+                    diagnostic(&format!("    Contacts with more than one ID: {dupes}"));
                 }
             }
         }
@@ -191,6 +208,65 @@ impl Diagnostic for Handle {
 }
 
 impl Handle {
+    /// Summarize the messages exchanged with every handle in the table, ordered by `ROWID`
+    ///
+    /// Handles with no messages are included. The services include the service recorded on the handle
+    /// as well as the service of each of its messages.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::handle::Handle;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// for summary in Handle::summaries(&conn).unwrap() {
+    ///     println!("{}: {} messages", summary.id, summary.messages);
+    /// }
+    /// ```
+    pub fn summaries(db: &Connection) -> Result<Vec<HandleSummary>, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     h.ROWID,
+                     h.id,
+                     h.service,
+                     COUNT(m.ROWID),
+                     MIN(m.date),
+                     MAX(m.date),
+                     GROUP_CONCAT(DISTINCT m.service)
+                 FROM {HANDLE} as h
+                 LEFT JOIN {MESSAGE} as m ON m.handle_id = h.ROWID
+                 GROUP BY h.ROWID
+                 ORDER BY h.ROWID"
+            ))
+            .map_err(TableError::Handle)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                let mut services: BTreeSet<String> = row
+                    .get::<_, Option<String>>(6)?
+                    .map(|services| services.split(',').map(String::from).collect())
+                    .unwrap_or_default();
+                if let Some(service) = row.get::<_, Option<String>>(2)? {
+                    services.insert(service);
+                }
+                Ok(HandleSummary {
+                    rowid: row.get(0)?,
+                    id: row.get(1)?,
+                    messages: row.get(3)?,
+                    first_message: row.get(4)?,
+                    last_message: row.get(5)?,
+                    services,
+                })
+            })
+            .map_err(TableError::Handle)?;
+
+        rows.collect::<Result<Vec<_>>>().map_err(TableError::Handle)
+    }
+
     /// The handles table does not have a lot of information and can have many duplicate values.
     ///
     /// This method generates a hashmap of each separate item in this table to a combined string
@@ -260,8 +336,62 @@ impl Handle {
 
 #[cfg(test)]
 mod tests {
-    use crate::tables::{handle::Handle, table::Deduplicate};
-    use std::collections::{HashMap, HashSet};
+    use crate::tables::{
+        handle::{Handle, HandleSummary},
+        table::{Deduplicate, get_empty_test_connection},
+    };
+    use std::collections::{BTreeSet, HashMap, HashSet};
+
+    #[test]
+    fn can_summarize_handles() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO handle (ROWID, id, service) VALUES
+                (1, '+15558675309', 'iMessage'),
+                (2, 'alice@example.com', 'iMessage'),
+                (3, '+15550000000', 'SMS');
+            INSERT INTO message (ROWID, guid, handle_id, service, date, date_read, date_delivered) VALUES
+                (1, 'a', 1, 'iMessage', 30, 0, 0),
+                (2, 'b', 1, 'SMS', 10, 0, 0),
+                (3, 'c', 1, 'iMessage', 20, 0, 0),
+                (4, 'd', 2, 'iMessage', 40, 0, 0);
+            ",
+        )
+        .unwrap();
+
+        let summaries = Handle::summaries(&db).unwrap();
+
+        assert_eq!(
+            summaries,
+            vec![
+                HandleSummary {
+                    rowid: 1,
+                    id: String::from("+15558675309"),
+                    messages: 3,
+                    first_message: Some(10),
+                    last_message: Some(30),
+                    services: BTreeSet::from([String::from("SMS"), String::from("iMessage")]),
+                },
+                HandleSummary {
+                    rowid: 2,
+                    id: String::from("alice@example.com"),
+                    messages: 1,
+                    first_message: Some(40),
+                    last_message: Some(40),
+                    services: BTreeSet::from([String::from("iMessage")]),
+                },
+                HandleSummary {
+                    rowid: 3,
+                    id: String::from("+15550000000"),
+                    messages: 0,
+                    first_message: None,
+                    last_message: None,
+                    services: BTreeSet::from([String::from("SMS")]),
+                },
+            ]
+        );
+    }
 
     #[test]
     fn test_can_dedupe() {
@@ -19,6 +19,37 @@ pub enum BubbleResult<'a> {
     Continuation(TextAttributes<'a>),
 }
 
+/// Replace each range of `text` tagged with [`TextEffect::OTP`] in `body` with `replacement`
+pub(crate) fn redact_one_time_codes(
+    text: &str,
+    body: &[BubbleComponent],
+    replacement: &str,
+) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last_end = 0;
+
+    for component in body {
+        if let BubbleComponent::Text(attributes) = component {
+            for attribute in attributes {
+                if !matches!(attribute.effect, TextEffect::OTP) || attribute.start < last_end {
+                    continue;
+                }
+                if let (Some(before), Some(_)) = (
+                    text.get(last_end..attribute.start),
+                    text.get(attribute.start..attribute.end),
+                ) {
+                    redacted.push_str(before);
+                    redacted.push_str(replacement);
+                    last_end = attribute.end;
+                }
+            }
+        }
+    }
+
+    redacted.push_str(text.get(last_end..).unwrap_or_default());
+    redacted
+}
+
 /// Logic to use deserialized typedstream data to parse the message body
 pub(crate) fn parse_body_typedstream<'a>(
     components: Option<&'a Vec<Archivable>>,
@@ -80,6 +111,13 @@ pub(crate) fn parse_body_typedstream<'a>(
         }
     }
 
+    // Text runs should cover each text bubble exactly once
+    for component in &mut out_v {
+        if let BubbleComponent::Text(runs) = component {
+            tile_runs(runs);
+        }
+    }
+
     // Add retracted components into the body
     if let Some(edited_message) = &edited_parts {
         for (idx, edited_message_part) in edited_message.parts.iter().enumerate() {
@@ -95,6 +133,44 @@ pub(crate) fn parse_body_typedstream<'a>(
     (!out_v.is_empty()).then_some(out_v)
 }
 
+/// Make a text bubble's runs tile its text: ordered, without gaps or overlaps, and with adjacent identical runs merged
+///
+/// Gaps between runs are filled with [`TextEffect::Default`], and a run that overlaps the one before it
+/// starts where the previous run ends.
+fn tile_runs(runs: &mut Vec<TextAttributes>) {
+    // A bubble with no text, like the caption slot before an attachment, keeps its empty run
+    if runs.iter().all(|run| run.start >= run.end) {
+        return;
+    }
+    runs.sort_by_key(|run| run.start);
+
+    let mut tiled: Vec<TextAttributes> = Vec::with_capacity(runs.len());
+    for mut run in runs.drain(..) {
+        if let Some(previous) = tiled.last() {
+            if run.start < previous.end {
+                run.start = previous.end;
+            } else if run.start > previous.end {
+                let gap = TextAttributes::new(previous.end, run.start, TextEffect::Default);
+                push_run(&mut tiled, gap);
+            }
+        }
+        if run.start < run.end {
+            push_run(&mut tiled, run);
+        }
+    }
+    *runs = tiled;
+}
+
+/// Add a run to the end of a tiled list, extending the last run instead if it has the same effect
+fn push_run<'a>(tiled: &mut Vec<TextAttributes<'a>>, run: TextAttributes<'a>) {
+    match tiled.last_mut() {
+        Some(previous) if previous.end == run.start && previous.effect == run.effect => {
+            previous.end = run.end;
+        }
+        _ => tiled.push(run),
+    }
+}
+
 fn get_range(component: &Archivable) -> Option<(&i64, &u64)> {
     if let Archivable::Data(items) = component {
         if items.len() == 2 {
@@ -295,7 +371,7 @@ mod typedstream_tests {
         },
         tables::messages::{
             Message,
-            body::parse_body_typedstream,
+            body::{parse_body_typedstream, tile_runs},
             models::{AttachmentMeta, BubbleComponent, TextAttributes},
         },
         util::typedstream::parser::TypedStreamReader,
@@ -1062,6 +1138,72 @@ mod typedstream_tests {
         );
     }
 
+    #[test]
+    fn can_tile_text_effects_styles_mixed() {
+        let mut m = Message::blank();
+        m.text = Some("Underline normal jitter normal".to_string());
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/TextStylesMixed");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        m.components = parser.parse().ok();
+
+        let body = parse_body_typedstream(
+            m.components.as_ref(),
+            m.text.as_deref(),
+            m.edited_parts.as_ref(),
+        )
+        .unwrap();
+        let [BubbleComponent::Text(runs)] = body.as_slice() else {
+            panic!("Expected a single text bubble, got {body:?}");
+        };
+
+        // The runs start at the beginning of the text, end at the end, and each starts where the last ended
+        assert_eq!(runs.first().unwrap().start, 0);
+        assert_eq!(runs.last().unwrap().end, m.text.as_ref().unwrap().len());
+        for pair in runs.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_ne!(pair[0].effect, pair[1].effect);
+        }
+        assert!(runs.iter().all(|run| run.start < run.end));
+    }
+
+    #[test]
+    fn can_tile_runs_with_gaps_and_overlaps() {
+        let mut runs = vec![
+            TextAttributes::new(10, 15, TextEffect::Styles(vec![Style::Bold])),
+            TextAttributes::new(0, 4, TextEffect::Default),
+            TextAttributes::new(4, 6, TextEffect::Default),
+            TextAttributes::new(12, 20, TextEffect::Styles(vec![Style::Bold])),
+            TextAttributes::new(20, 20, TextEffect::OTP),
+        ];
+
+        tile_runs(&mut runs);
+
+        assert_eq!(
+            runs,
+            vec![
+                TextAttributes::new(0, 10, TextEffect::Default),
+                TextAttributes::new(10, 20, TextEffect::Styles(vec![Style::Bold])),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_keep_empty_run() {
+        let mut runs = vec![TextAttributes::new(0, 0, TextEffect::Default)];
+
+        tile_runs(&mut runs);
+
+        assert_eq!(runs, vec![TextAttributes::new(0, 0, TextEffect::Default)]);
+    }
+
     #[test]
     fn can_get_message_body_audio_message() {
         let mut m = Message::blank();
@@ -0,0 +1,171 @@
+/*!
+ An iterator over message rows that manages its own prepared statement, built by [`Message::iter()`].
+*/
+
+use std::collections::VecDeque;
+
+use rusqlite::{Connection, Statement, ToSql};
+
+use crate::{
+    error::table::TableError,
+    tables::{
+        messages::{Message, models::PageCursor},
+        table::Table,
+    },
+    util::predicate::PredicateValue,
+};
+
+/// The number of rows read from the database at a time
+pub(crate) const ITER_PAGE_SIZE: usize = 500;
+
+/// Iterates over the messages that match a [`QueryContext`](crate::util::query_context::QueryContext), newest first
+///
+/// Rows are read a page at a time with a single prepared statement that the iterator owns, so messages can be
+/// streamed without keeping a `rusqlite` statement alive in the caller. A message that is joined to both a chat
+/// and the recently deleted collection is only yielded once.
+///
+/// # Example:
+///
+/// ```no_run
+/// use imessage_database::util::dirs::default_db_path;
+/// use imessage_database::tables::table::get_connection;
+/// use imessage_database::tables::messages::Message;
+/// use imessage_database::util::query_context::QueryContext;
+///
+/// let db_path = default_db_path();
+/// let conn = get_connection(&db_path).unwrap();
+/// let context = QueryContext::default();
+///
+/// for message in Message::iter(&conn, &context).unwrap().with_text(true) {
+///     let message = message.unwrap();
+///     println!("{:?}", message.text);
+/// }
+/// ```
+pub struct MessageIter<'a> {
+    /// The connection used to generate message text
+    db: &'a Connection,
+    /// The query for a single page, bound to a new cursor for each page
+    statement: Statement<'a>,
+    /// The values of the context's predicates, bound before the cursor
+    params: Vec<PredicateValue>,
+    /// Messages from the current page that have not been yielded yet
+    buffer: VecDeque<Message>,
+    /// Where the next page starts, or `None` once the last page was read
+    cursor: Option<PageCursor>,
+    /// The number of messages that can still be yielded under the context's limit
+    remaining: Option<usize>,
+    /// The number of messages that still have to be skipped under the context's offset
+    skip: usize,
+    /// If `true`, call [`Message::generate_text()`] on each message before it is yielded
+    with_text: bool,
+    /// The number of rows read from the database at a time
+    pub(crate) page_size: usize,
+}
+
+impl<'a> MessageIter<'a> {
+    /// Create an iterator from a statement that reads a page of messages older than a cursor
+    ///
+    /// The statement's parameters must be `params`, then the cursor's date, the cursor's `ROWID`, and the page size.
+    pub(crate) fn new(
+        db: &'a Connection,
+        statement: Statement<'a>,
+        params: Vec<PredicateValue>,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Self {
+        Self {
+            db,
+            statement,
+            params,
+            buffer: VecDeque::new(),
+            cursor: Some(PageCursor {
+                date: i64::MAX,
+                rowid: i32::MAX,
+            }),
+            remaining: limit,
+            skip: offset,
+            with_text: false,
+            page_size: ITER_PAGE_SIZE,
+        }
+    }
+
+    /// Set whether each message's text is generated before it is yielded
+    ///
+    /// Text is not generated by default, which avoids parsing every message's `attributedBody` when only the
+    /// other columns are needed.
+    #[must_use]
+    pub fn with_text(mut self, with_text: bool) -> Self {
+        self.with_text = with_text;
+        self
+    }
+
+    /// Read the next page of messages into the buffer
+    fn read_page(&mut self) -> Result<(), TableError> {
+        let Some(cursor) = self.cursor.take() else {
+            return Ok(());
+        };
+        let page_size = self.page_size as i64;
+        let mut params: Vec<&dyn ToSql> = self
+            .params
+            .iter()
+            .map(|value| value as &dyn ToSql)
+            .collect();
+        params.extend([&cursor.date as &dyn ToSql, &cursor.rowid, &page_size]);
+        let rows = self
+            .statement
+            .query_map(params.as_slice(), |row| Ok(Message::from_row(row)))
+            .map_err(TableError::Messages)?;
+
+        let mut fetched = 0;
+        for row in rows {
+            let message = Message::extract(row)?;
+            fetched += 1;
+            if self
+                .buffer
+                .back()
+                .is_none_or(|last| last.rowid != message.rowid)
+            {
+                self.buffer.push_back(message);
+            }
+        }
+
+        // A short page means the oldest message was reached
+        if fetched == self.page_size {
+            self.cursor = self.buffer.back().map(|oldest| PageCursor {
+                date: oldest.date,
+                rowid: oldest.rowid,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for MessageIter<'_> {
+    type Item = Result<Message, TableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let mut message = loop {
+            if self.buffer.is_empty()
+                && let Err(why) = self.read_page()
+            {
+                self.cursor = None;
+                return Some(Err(why));
+            }
+            let message = self.buffer.pop_front()?;
+            if self.skip == 0 {
+                break message;
+            }
+            self.skip -= 1;
+        };
+        if self.with_text {
+            let _ = message.generate_text(self.db);
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        Some(Ok(message))
+    }
+}
@@ -3,7 +3,8 @@
 
  # Iterating over Message Data
 
- Generally, use [`Message::get()`] or [`Message::stream_rows()`] to iterate over message rows.
+ Generally, use [`Message::iter()`] to iterate over messages, or [`Message::get()`] or [`Message::stream_rows()`]
+ to iterate over message rows.
 
  ## Example
  ```rust
@@ -109,35 +110,56 @@
  ```
 */
 
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    ops::Range,
+    sync::LazyLock,
+};
 
 use chrono::{DateTime, offset::Local};
 use plist::Value;
-use rusqlite::{Connection, Error, Result, Row, Statement, blob::Blob};
+use rusqlite::{Connection, Error, Result, Row, Statement, blob::Blob, params_from_iter};
 
 use crate::{
     error::{message::MessageError, table::TableError},
     message_types::{
+        app::AppMessage,
+        collaboration::{CollaborationMessage, SharedItem},
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
-        variants::{Announcement, BalloonProvider, CustomBalloon, Tapback, TapbackAction, Variant},
+        payment::{ApplePayMessage, PaymentDirection},
+        poll::Poll,
+        text_effects::TextEffect,
+        variants::{
+            Announcement, BalloonProvider, CustomBalloon, PinAction, Tapback, TapbackAction,
+            Variant,
+        },
     },
     tables::{
+        attachment::{Attachment, MediaType},
         messages::{
-            body::{parse_body_legacy, parse_body_typedstream},
-            models::{BubbleComponent, GroupAction, Service},
-            query_parts::{ios_13_older_query, ios_14_15_query, ios_16_newer_query},
+            body::{parse_body_legacy, parse_body_typedstream, redact_one_time_codes},
+            iter::MessageIter,
+            models::{
+                AttachmentMeta, AttachmentMismatch, BubbleComponent, EditReference, GroupAction,
+                ItemType, Mention, MessagePage, OriginatorStatus, PageCursor, RenderedPart,
+                RenderedParts, ReplyContext, ReplyKind, ReplyNode, Service, StickerOverlay,
+            },
+            query_parts::{newest_first_stable, oldest_first, one_row_per_message},
         },
         table::{
             ATTRIBUTED_BODY, AttributedBody, CHAT_MESSAGE_JOIN, Cacheable, Diagnostic, GetBlob,
             MESSAGE, MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD, MESSAGE_SUMMARY_INFO,
-            RECENTLY_DELETED, Table,
+            RECENTLY_DELETED, SchemaVersion, Table,
         },
     },
     util::{
         bundle_id::parse_balloon_bundle_id,
-        dates::{get_local_time, readable_diff},
-        output::{done_processing, processing},
+        dates::{TIMESTAMP_FACTOR, get_local_time, get_offset, readable_diff},
+        output::{diagnostic, done_processing, processing},
+        plist::parse_ns_keyed_archiver,
+        predicate::PredicateValue,
         query_context::QueryContext,
         streamtyped,
         typedstream::{models::Archivable, parser::TypedStreamReader},
@@ -145,7 +167,48 @@ use crate::{
 };
 
 /// The required columns, interpolated into the most recent schema due to performance considerations
-pub(crate) const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji";
+pub(crate) const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, associated_message_range_location, associated_message_range_length, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji, is_audio_message, is_played";
+
+/// Excludes tapbacks and stickers (`associated_message_type` 1000-3999) and announcements, i.e. group
+/// actions (`item_type` 1-3) and kept audio messages (`item_type` 5)
+const CONVERSATIONAL_PREDICATES: &str = "COALESCE(m.associated_message_type, 0) NOT BETWEEN 1000 AND 3999 AND m.item_type NOT IN (1, 2, 3, 5)";
+
+/// Excludes messages where every part was unsent, which are edited but have no remaining body
+const NOT_UNSENT_PREDICATE: &str =
+    "NOT (m.date_edited > 0 AND m.text IS NULL AND m.attributedBody IS NULL)";
+
+/// Selects messages that are the target of at least one added tapback (`associated_message_type` 2000-2999),
+/// stripping the `p:0/` and `bp:` prefixes the same way as [`Message::clean_associated_guid()`]
+///
+/// A tapback does not count if its sender later removed it, i.e. sent a tapback of the same kind plus 1000
+/// (`associated_message_type` 3000-3999) on the same part.
+static HAS_TAPBACKS_PREDICATE: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "m.guid IN (
+    SELECT
+        CASE
+            WHEN t.associated_message_guid LIKE 'p:%' THEN substr(t.associated_message_guid, instr(t.associated_message_guid, '/') + 1)
+            WHEN t.associated_message_guid LIKE 'bp:%' THEN substr(t.associated_message_guid, 4)
+            ELSE t.associated_message_guid
+        END
+    FROM {MESSAGE} as t
+    WHERE t.associated_message_type BETWEEN 2000 AND 2999
+        AND NOT EXISTS (
+            SELECT 1 FROM {MESSAGE} as r
+            WHERE r.associated_message_guid = t.associated_message_guid
+                AND r.associated_message_type = t.associated_message_type + 1000
+                AND r.handle_id IS t.handle_id
+                AND r.is_from_me IS t.is_from_me
+                AND r.date > t.date
+        )
+)"
+    )
+});
+
+/// Selects messages that begin a thread
+static HAS_REPLIES_PREDICATE: LazyLock<String> = LazyLock::new(|| {
+    format!("EXISTS (SELECT 1 FROM {MESSAGE} as r WHERE r.thread_originator_guid = m.guid)")
+});
 
 /// Represents a single row in the `message` table.
 ///
@@ -175,7 +238,7 @@ pub struct Message {
     pub is_from_me: bool,
     /// `true` if the message was read by the recipient, else `false`
     pub is_read: bool,
-    /// Intermediate data for determining the [`Variant`] of a message
+    /// Intermediate data for determining the [`Variant`] of a message, see [`ItemType`]
     pub item_type: i32,
     /// Optional handle for the recipient of a message that includes shared content
     pub other_handle: Option<i32>,
@@ -191,6 +254,10 @@ pub struct Message {
     pub associated_message_guid: Option<String>,
     /// Intermediate data for determining the [`Variant`] of a message
     pub associated_message_type: Option<i32>,
+    /// The start of the range of text a [`Tapback`] applies to, in characters of the target message's text
+    pub associated_message_range_location: i32,
+    /// The length of the range of text a [`Tapback`] applies to, or `0` if it applies to the whole part
+    pub associated_message_range_length: i32,
     /// The [bundle ID](https://developer.apple.com/help/app-store-connect/reference/app-bundle-information) of the app that generated the [`AppMessage`](crate::message_types::app::AppMessage)
     pub balloon_bundle_id: Option<String>,
     /// Intermediate data for determining the [`expressive`](crate::message_types::expressives) of a message
@@ -203,6 +270,10 @@ pub struct Message {
     pub date_edited: i64,
     /// If present, this is the emoji associated with a custom emoji tapback
     pub associated_message_emoji: Option<String>,
+    /// `true` if the message is a voice message recorded in the Messages app, else `false`
+    pub is_audio_message: bool,
+    /// `true` if the message was played by its recipient, if the database tracks it
+    pub is_played: Option<bool>,
     /// The [`identifier`](crate::tables::chat::Chat::chat_identifier) of the chat the message belongs to
     pub chat_id: Option<i32>,
     /// The number of attached files included in the message
@@ -240,12 +311,20 @@ impl Table for Message {
             group_action_type: row.get("group_action_type").unwrap_or(0),
             associated_message_guid: row.get("associated_message_guid").unwrap_or(None),
             associated_message_type: row.get("associated_message_type").unwrap_or(None),
+            associated_message_range_location: row
+                .get("associated_message_range_location")
+                .unwrap_or(0),
+            associated_message_range_length: row
+                .get("associated_message_range_length")
+                .unwrap_or(0),
             balloon_bundle_id: row.get("balloon_bundle_id").unwrap_or(None),
             expressive_send_style_id: row.get("expressive_send_style_id").unwrap_or(None),
             thread_originator_guid: row.get("thread_originator_guid").unwrap_or(None),
             thread_originator_part: row.get("thread_originator_part").unwrap_or(None),
             date_edited: row.get("date_edited").unwrap_or(0),
             associated_message_emoji: row.get("associated_message_emoji").unwrap_or(None),
+            is_audio_message: row.get("is_audio_message").unwrap_or(false),
+            is_played: row.get("is_played").unwrap_or(None),
             chat_id: row.get("chat_id").unwrap_or(None),
             num_attachments: row.get("num_attachments")?,
             deleted_from: row.get("deleted_from").unwrap_or(None),
@@ -255,12 +334,10 @@ impl Table for Message {
         })
     }
 
-    /// Convert data from the messages table to native Rust data structures, falling back to
-    /// more compatible queries to ensure compatibility with older database schemas
+    /// Convert data from the messages table to native Rust data structures, using the query
+    /// that matches the database's [`SchemaVersion`](crate::tables::table::SchemaVersion)
     fn get(db: &Connection) -> Result<Statement, TableError> {
-        db.prepare(&ios_16_newer_query(None, None))
-            .or_else(|_| db.prepare(&ios_14_15_query(None, None)))
-            .or_else(|_| db.prepare(&ios_13_older_query(None, None)))
+        db.prepare(&SchemaVersion::detect(db).query(None, None))
             .map_err(TableError::Messages)
     }
 
@@ -342,17 +419,26 @@ impl Diagnostic for Message {
 
         let total_messages: i64 = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
 
+        let malformed_associations = Self::count_malformed_associations(db).unwrap_or(0);
+
         done_processing();
 
-        println!("Message diagnostic data:");
-        println!("    Total messages: {total_messages}");
+        diagnostic("Message diagnostic data:");
+        diagnostic(&format!("    Total messages: {total_messages}"));
         if num_dangling > 0 {
-            println!("    Messages not associated with a chat: {num_dangling}");
+            diagnostic(&format!(
+                "    Messages not associated with a chat: {num_dangling}"
+            ));
         }
         if messages_in_more_than_one_chat > 0 {
-            println!(
+            diagnostic(&format!(
                 "    Messages belonging to more than one chat: {messages_in_more_than_one_chat}"
-            );
+            ));
+        }
+        if malformed_associations > 0 {
+            diagnostic(&format!(
+                "    Messages with a malformed associated message GUID: {malformed_associations}"
+            ));
         }
         Ok(())
     }
@@ -587,6 +673,15 @@ impl Message {
         matches!(self.variant(), Variant::Tapback(..))
     }
 
+    /// `true` if the message is a sticker placed on top of another message's bubble, else `false`
+    ///
+    /// These are stored like [`Tapback::Sticker`] tapbacks, but render on the target bubble instead of
+    /// next to it; see [`Self::sticker_overlay()`].
+    #[must_use]
+    pub fn is_sticker_overlay(&self) -> bool {
+        self.associated_message_type == Some(1000)
+    }
+
     /// `true` if the message has an [`Expressive`], else `false`
     #[must_use]
     pub fn is_expressive(&self) -> bool {
@@ -652,16 +747,60 @@ impl Message {
         self.num_replies > 0
     }
 
+    /// Get the kind of event the message represents from its `item_type`
+    #[must_use]
+    pub fn item_kind(&self) -> ItemType {
+        ItemType::from(self.item_type)
+    }
+
     /// `true` if the message indicates a sent audio message was kept, else `false`
     #[must_use]
     pub fn is_kept_audio_message(&self) -> bool {
-        self.item_type == 5
+        matches!(self.item_kind(), ItemType::KeptAudio)
+    }
+
+    /// Get the action of a message pin event, or `None` if the message is not a pin event
+    ///
+    /// Pin events are assumed to be stored with an `item_type` of `7`, with a `group_action_type` of `0` when a
+    /// message is pinned and `1` when it is unpinned. This mapping is unverified: none of the sample databases
+    /// this library is tested against contain a pin event, so it may change if real data disagrees.
+    #[must_use]
+    pub fn pin_action(&self) -> Option<PinAction> {
+        match (self.item_kind(), self.group_action_type) {
+            (ItemType::MessagePin, 0) => Some(PinAction::Pinned),
+            (ItemType::MessagePin, 1) => Some(PinAction::Unpinned),
+            _ => None,
+        }
+    }
+
+    /// Get the message a pin event applies to, with its text generated
+    ///
+    /// The pinned message's GUID is stored in the `associated_message_guid` column. Returns `None` if the
+    /// message is not a pin event, the database does not record the pinned message, or it no longer exists.
+    pub fn pinned_message(&self, db: &Connection) -> Option<Self> {
+        self.pin_action()?;
+        let (_, guid) = self.clean_associated_guid()?;
+        let mut pinned = Self::from_guid(guid, db).ok()?;
+        let _ = pinned.generate_text(db);
+        Some(pinned)
+    }
+
+    /// Determine if a voice message was played by its recipient
+    ///
+    /// Returns [`None`] if the message is not a voice message or the database does not track played status.
+    #[must_use]
+    pub fn voice_message_played(&self) -> Option<bool> {
+        if self.is_audio_message {
+            self.is_played
+        } else {
+            None
+        }
     }
 
     /// `true` if the message is a [SharePlay/FaceTime](crate::message_types::variants::Variant::SharePlay) message, else `false`
     #[must_use]
     pub fn is_shareplay(&self) -> bool {
-        self.item_type == 6
+        matches!(self.item_kind(), ItemType::SharePlay)
     }
 
     /// `true` if the message was sent by the database owner, else `false`
@@ -685,13 +824,17 @@ impl Message {
     /// `true` if the message indicates a sender started sharing their location, else `false`
     #[must_use]
     pub fn started_sharing_location(&self) -> bool {
-        self.item_type == 4 && self.group_action_type == 0 && !self.share_status
+        matches!(self.item_kind(), ItemType::LocationShare)
+            && self.group_action_type == 0
+            && !self.share_status
     }
 
     /// `true` if the message indicates a sender stopped sharing their location, else `false`
     #[must_use]
     pub fn stopped_sharing_location(&self) -> bool {
-        self.item_type == 4 && self.group_action_type == 0 && self.share_status
+        matches!(self.item_kind(), ItemType::LocationShare)
+            && self.group_action_type == 0
+            && self.share_status
     }
 
     /// `true` if the message was deleted and is recoverable, else `false`
@@ -726,6 +869,9 @@ impl Message {
     /// If `include_recoverable` is `true`, the filter includes messages from the recently deleted messages
     /// table that match the chat IDs. This allows recovery of deleted messages that are still
     /// present in the database but no longer visible in the Messages app.
+    ///
+    /// The search term, date range, and each [`Predicate`](crate::util::predicate::Predicate) are numbered from `?1`,
+    /// so the statement must be bound with [`QueryContext::predicate_params()`].
     pub(crate) fn generate_filter_statement(
         context: &QueryContext,
         include_recoverable: bool,
@@ -742,25 +888,93 @@ impl Message {
                 .join(", ");
 
             if include_recoverable {
-                filters.push_str(&format!(
-                    "WHERE (c.chat_id IN ({ids}) OR d.chat_id IN ({ids}))"
-                ));
+                filters.push_str(&format!("(c.chat_id IN ({ids}) OR d.chat_id IN ({ids}))"));
             } else {
-                filters.push_str(&format!("WHERE c.chat_id IN ({ids})"));
+                filters.push_str(&format!("c.chat_id IN ({ids})"));
+            }
+        }
+
+        // Calendar day filter, evaluated in the selected time zone or the local one
+        if let Some((month, day)) = context.on_this_day {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            let date = format!("m.date / {TIMESTAMP_FACTOR} + {}", get_offset());
+            match context.utc_offset {
+                Some(seconds) => filters.push_str(&format!(
+                    "strftime('%m-%d', {date} + {seconds}, 'unixepoch') = '{month:02}-{day:02}'"
+                )),
+                None => filters.push_str(&format!(
+                    "strftime('%m-%d', {date}, 'unixepoch', 'localtime') = '{month:02}-{day:02}'"
+                )),
+            }
+        }
+
+        // Engagement filters
+        for (enabled, predicate) in [
+            (context.only_with_tapbacks, HAS_TAPBACKS_PREDICATE.as_str()),
+            (context.only_with_replies, HAS_REPLIES_PREDICATE.as_str()),
+        ] {
+            if enabled {
+                if !filters.is_empty() {
+                    filters.push_str(" AND ");
+                }
+                filters.push_str(predicate);
+            }
+        }
+
+        // Incremental export lower bound
+        if let Some(rowid) = context.since_rowid {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!("m.ROWID > {rowid}"));
+        }
+
+        // Text search, matched literally against the `text` column only and bound as the first parameter
+        let mut next_param = 1;
+        if context.search_term.is_some() {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!("m.text LIKE ?{next_param} ESCAPE '\\'"));
+            next_param += 1;
+        }
+
+        // Date range filters, bound after the search term
+        for (bound, comparison) in [(context.start, ">="), (context.end, "<=")] {
+            if bound.is_some() {
+                if !filters.is_empty() {
+                    filters.push_str(" AND ");
+                }
+                filters.push_str(&format!("m.date {comparison} ?{next_param}"));
+                next_param += 1;
+            }
+        }
+
+        // Typed predicates, compiled into bound parameters
+        for predicate in &context.predicates {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
             }
+            filters.push_str(&predicate.to_sql(next_param));
+            next_param += predicate.values().len();
         }
 
+        if !filters.is_empty() {
+            return format!("WHERE {filters}");
+        }
         filters
     }
 
     pub(crate) fn generate_limit_statement(context: &QueryContext) -> String {
-        let mut limit_statement = String::new();
-        // limit filter
-        if let Some(limit) = context.limit {
-            limit_statement.push_str(&format!("LIMIT {limit}"));
+        // SQLite only allows an offset after a limit, and a negative limit selects every row
+        match (context.limit, context.offset) {
+            (Some(limit), Some(offset)) => format!("LIMIT {limit} OFFSET {offset}"),
+            (Some(limit), None) => format!("LIMIT {limit}"),
+            (None, Some(offset)) => format!("LIMIT -1 OFFSET {offset}"),
+            (None, None) => String::new(),
         }
-
-        limit_statement
     }
 
     /// Get the number of messages in the database
@@ -805,13 +1019,82 @@ impl Message {
                 .map_err(TableError::Messages)?
         };
         // Execute query, defaulting to zero if it fails
-        let count: u64 = statement.query_row([], |r| r.get(0)).unwrap_or(0);
+        let count: u64 = statement
+            .query_row(params_from_iter(context.predicate_params()), |r| r.get(0))
+            .unwrap_or(0);
 
         Ok(count)
     }
 
+    /// Get the number of messages a person wrote, excluding tapbacks, announcements, and fully unsent messages
+    ///
+    /// Unlike [`Self::get_count()`], this reflects how many "real" messages were exchanged.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let context = QueryContext::default();
+    /// Message::count_conversational(&conn, &context);
+    /// ```
+    pub fn count_conversational(
+        db: &Connection,
+        context: &QueryContext,
+    ) -> Result<u64, TableError> {
+        // Combine the conversational predicates with any filters from the query context
+        let with_filters = |filters: String, predicates: &str| {
+            if filters.is_empty() {
+                format!("WHERE {predicates}")
+            } else {
+                format!("{filters} AND {predicates}")
+            }
+        };
+
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     COUNT(*)
+                 FROM {MESSAGE} as m
+                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+                 LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id
+                 {}",
+                with_filters(
+                    Self::generate_filter_statement(context, true),
+                    &format!("{CONVERSATIONAL_PREDICATES} AND {NOT_UNSENT_PREDICATE}")
+                )
+            ))
+            // Older schemas have no recently deleted messages and do not support unsending
+            .or_else(|_| {
+                db.prepare(&format!(
+                    "SELECT
+                         COUNT(*)
+                     FROM {MESSAGE} as m
+                     LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+                     {}",
+                    with_filters(
+                        Self::generate_filter_statement(context, false),
+                        CONVERSATIONAL_PREDICATES
+                    )
+                ))
+            })
+            .map_err(TableError::Messages)?;
+
+        statement
+            .query_row(params_from_iter(context.predicate_params()), |r| r.get(0))
+            .map_err(TableError::Messages)
+    }
+
     /// Stream messages from the database with optional filters.
     ///
+    /// If the context has [`predicates`](QueryContext::predicates), the statement must be bound with
+    /// [`QueryContext::predicate_params()`].
+    ///
     /// # Example:
     ///
     /// ```
@@ -826,7 +1109,8 @@ impl Message {
     ///
     /// let mut statement = Message::stream_rows(&conn, &context).unwrap();
     ///
-    /// let messages = statement.query_map([], |row| Ok(Message::from_row(row))).unwrap();
+    /// let params = rusqlite::params_from_iter(context.predicate_params());
+    /// let messages = statement.query_map(params, |row| Ok(Message::from_row(row))).unwrap();
     ///
     /// messages.map(|msg| println!("{:#?}", Message::extract(msg)));
     /// ```
@@ -837,25 +1121,287 @@ impl Message {
         if !context.has_filters() {
             return Self::get(db);
         }
-        db.prepare(&ios_16_newer_query(
-            Some(&Self::generate_filter_statement(context, true)),
+        // A limit or offset counts rows, so messages in more than one chat would otherwise fill several slots
+        let filters = |include_recoverable: bool| {
+            let filters = Self::generate_filter_statement(context, include_recoverable);
+            if context.limit.is_some() || context.offset.is_some() {
+                one_row_per_message(&filters)
+            } else {
+                filters
+            }
+        };
+        let version = SchemaVersion::detect(db);
+        // Break ties between messages sent at the same time so pages selected with an offset never overlap
+        db.prepare(&newest_first_stable(&version.query(
+            Some(&filters(version == SchemaVersion::Ios16OrNewer)),
             Some(&Self::generate_limit_statement(context)),
-        ))
-        .or_else(|_| {
-            db.prepare(&ios_14_15_query(
-                Some(&Self::generate_filter_statement(context, false)),
-                Some(&Self::generate_limit_statement(context)),
-            ))
-        })
-        .or_else(|_| {
-            db.prepare(&ios_13_older_query(
-                Some(&Self::generate_filter_statement(context, false)),
-                Some(&Self::generate_limit_statement(context)),
-            ))
-        })
+        )))
         .map_err(TableError::Messages)
     }
 
+    /// Iterate over the messages that match the filters in a [`QueryContext`], newest first
+    ///
+    /// Unlike [`Self::stream_rows()`], the returned [`MessageIter`] owns its statement and yields messages that are
+    /// already extracted, so no rows need to be extracted by hand. Use [`MessageIter::with_text()`] to generate each
+    /// message's text before it is yielded. The context's limit and offset are applied to messages, not rows.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let context = QueryContext::default();
+    ///
+    /// for message in Message::iter(&conn, &context).unwrap().with_text(true) {
+    ///     println!("{:?}", message.unwrap().text);
+    /// }
+    /// ```
+    pub fn iter<'a>(
+        db: &'a Connection,
+        context: &QueryContext,
+    ) -> Result<MessageIter<'a>, TableError> {
+        let version = SchemaVersion::detect(db);
+        let filters =
+            Self::generate_filter_statement(context, version == SchemaVersion::Ios16OrNewer);
+        // The cursor's parameters follow the search term's and predicates' parameters
+        let params: Vec<PredicateValue> = context.predicate_params().collect();
+        let date = params.len() + 1;
+        let cursor_filter = format!(
+            "(m.date < ?{date} OR (m.date = ?{date} AND m.ROWID < ?{}))",
+            date + 1
+        );
+        let filters = if filters.is_empty() {
+            format!("WHERE {cursor_filter}")
+        } else {
+            format!("{filters} AND {cursor_filter}")
+        };
+        let limit = format!("LIMIT ?{}", date + 2);
+        let query = newest_first_stable(&version.query(Some(&filters), Some(&limit)));
+
+        let statement = db.prepare(&query).map_err(TableError::Messages)?;
+        let limit = context
+            .limit
+            .map(|limit| usize::try_from(limit).unwrap_or_default());
+        let offset = context
+            .offset
+            .map_or(0, |offset| usize::try_from(offset).unwrap_or(usize::MAX));
+        Ok(MessageIter::new(db, statement, params, limit, offset))
+    }
+
+    /// Get the `n` oldest messages that match the filters in a [`QueryContext`], oldest first
+    ///
+    /// The context's own limit is ignored.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let context = QueryContext::default();
+    /// let oldest = Message::first_n(&conn, 10, &context).unwrap();
+    /// ```
+    pub fn first_n(
+        db: &Connection,
+        n: usize,
+        context: &QueryContext,
+    ) -> Result<Vec<Self>, TableError> {
+        Self::bounded(db, n, context, true)
+    }
+
+    /// Get the `n` newest messages that match the filters in a [`QueryContext`], oldest first
+    ///
+    /// The context's own limit is ignored.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let context = QueryContext::default();
+    /// let newest = Message::last_n(&conn, 10, &context).unwrap();
+    /// ```
+    pub fn last_n(
+        db: &Connection,
+        n: usize,
+        context: &QueryContext,
+    ) -> Result<Vec<Self>, TableError> {
+        let mut messages = Self::bounded(db, n, context, false)?;
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Get the `page_size` messages in a chat that were sent before a cursor, newest first
+    ///
+    /// Pass `None` as the cursor to get the newest messages, then pass the returned cursor to get the
+    /// next, older page. Only one page is read from the database at a time, so a conversation can be paged
+    /// through without loading it into memory. Messages in the recently deleted collection are included in
+    /// the chat they were deleted from.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    ///
+    /// let mut cursor = None;
+    /// loop {
+    ///     let page = Message::page_before(&conn, 1, cursor, 50).unwrap();
+    ///     println!("Loaded {} messages", page.messages.len());
+    ///     match page.cursor {
+    ///         Some(next) => cursor = Some(next),
+    ///         None => break,
+    ///     }
+    /// }
+    /// ```
+    pub fn page_before(
+        db: &Connection,
+        chat_id: i32,
+        cursor: Option<PageCursor>,
+        page_size: usize,
+    ) -> Result<MessagePage, TableError> {
+        let version = SchemaVersion::detect(db);
+        let chat_filter = if version == SchemaVersion::Ios16OrNewer {
+            "(c.chat_id = ?1 OR d.chat_id = ?1)"
+        } else {
+            "c.chat_id = ?1"
+        };
+        // A message can be joined to the chat and the recently deleted collection at the same time
+        let filters = one_row_per_message(&format!(
+            "WHERE {chat_filter} AND (m.date < ?2 OR (m.date = ?2 AND m.ROWID < ?3))"
+        ));
+        let query = newest_first_stable(&version.query(Some(&filters), Some("LIMIT ?4")));
+
+        let cursor = cursor.unwrap_or(PageCursor {
+            date: i64::MAX,
+            rowid: i32::MAX,
+        });
+        let mut statement = db.prepare(&query).map_err(TableError::Messages)?;
+        let rows = statement
+            .query_map(
+                rusqlite::params![chat_id, cursor.date, cursor.rowid, page_size as i64],
+                |row| Ok(Self::from_row(row)),
+            )
+            .map_err(TableError::Messages)?;
+
+        let messages = rows
+            .map(Self::extract)
+            .collect::<Result<Vec<Self>, TableError>>()?;
+
+        // A short page means the oldest message in the chat was reached
+        let cursor = if messages.len() < page_size {
+            None
+        } else {
+            messages.last().map(|oldest| PageCursor {
+                date: oldest.date,
+                rowid: oldest.rowid,
+            })
+        };
+
+        Ok(MessagePage { messages, cursor })
+    }
+
+    /// Get up to `n` messages from one end of the filtered messages, in query order
+    fn bounded(
+        db: &Connection,
+        n: usize,
+        context: &QueryContext,
+        oldest: bool,
+    ) -> Result<Vec<Self>, TableError> {
+        let limit = format!("LIMIT {n}");
+        let order = |query: String| if oldest { oldest_first(&query) } else { query };
+
+        let version = SchemaVersion::detect(db);
+        // Messages in more than one chat would otherwise be returned once per chat
+        let filters = one_row_per_message(&Self::generate_filter_statement(
+            context,
+            version == SchemaVersion::Ios16OrNewer,
+        ));
+        let mut statement = db
+            .prepare(&order(version.query(Some(&filters), Some(&limit))))
+            .map_err(TableError::Messages)?;
+
+        let rows = statement
+            .query_map(params_from_iter(context.predicate_params()), |row| {
+                Ok(Self::from_row(row))
+            })
+            .map_err(TableError::Messages)?;
+
+        rows.map(Self::extract).collect()
+    }
+
+    /// Build a map of message `ROWID`s to their 1-based position within their chat
+    ///
+    /// Positions increase with the message date and are independent of any [`QueryContext`] filters,
+    /// so a message keeps the same number across exports. Tapbacks do not occupy a position.
+    /// Messages in the recently deleted collection are numbered within the chat they were deleted from.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let sequence = Message::cache_chat_sequence(&conn).unwrap();
+    /// ```
+    pub fn cache_chat_sequence(db: &Connection) -> Result<HashMap<i32, u64>, TableError> {
+        let mut map = HashMap::new();
+
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     m.ROWID,
+                     ROW_NUMBER() OVER (PARTITION BY COALESCE(c.chat_id, d.chat_id) ORDER BY m.date, m.ROWID)
+                 FROM {MESSAGE} as m
+                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+                 LEFT JOIN {RECENTLY_DELETED} as d ON m.ROWID = d.message_id
+                 WHERE m.associated_message_guid IS NULL"
+            ))
+            .or_else(|_| {
+                db.prepare(&format!(
+                    "SELECT
+                         m.ROWID,
+                         ROW_NUMBER() OVER (PARTITION BY c.chat_id ORDER BY m.date, m.ROWID)
+                     FROM {MESSAGE} as m
+                     LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+                     WHERE m.associated_message_guid IS NULL"
+                ))
+            })
+            .map_err(TableError::Messages)?;
+
+        let positions = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(TableError::Messages)?;
+
+        for position in positions {
+            let (rowid, sequence) = position.map_err(TableError::Messages)?;
+            map.insert(rowid, sequence);
+        }
+
+        Ok(map)
+    }
+
     /// See [`Tapback`] for details on this data.
     #[must_use]
     pub fn clean_associated_guid(&self) -> Option<(usize, &str)> {
@@ -875,6 +1421,94 @@ impl Message {
         None
     }
 
+    /// Parse the associated GUID field, surfacing malformed data instead of defaulting the index to `0`
+    ///
+    /// Unlike [`Self::clean_associated_guid()`], this fails if a `p:` prefixed GUID has an index that is
+    /// not a number, so tapbacks are never attached to the wrong part of a message.
+    pub fn try_clean_associated_guid(&self) -> Result<(usize, &str), MessageError> {
+        match &self.associated_message_guid {
+            Some(guid) => parse_associated_guid(guid),
+            None => Err(MessageError::NoAssociatedGuid),
+        }
+    }
+
+    /// Count the messages with an associated GUID that cannot be parsed by [`Self::try_clean_associated_guid()`]
+    pub(crate) fn count_malformed_associations(db: &Connection) -> Result<u64, TableError> {
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT associated_message_guid FROM {MESSAGE} WHERE associated_message_guid IS NOT NULL"
+            ))
+            .map_err(TableError::Messages)?;
+
+        let guids = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(TableError::Messages)?;
+
+        let mut count = 0;
+        for guid in guids {
+            if parse_associated_guid(&guid.map_err(TableError::Messages)?).is_err() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Get the index of the part of this message that a tapback is anchored to
+    ///
+    /// Returns `None` if the tapback does not target this message. For edited messages, see
+    /// [`EditedMessage::tapback_anchor()`] for how tapbacks are anchored after edits.
+    #[must_use]
+    pub fn tapback_anchor(&self, tapback: &Self) -> Option<usize> {
+        let (index, guid) = tapback.clean_associated_guid()?;
+        if guid != self.guid {
+            return None;
+        }
+        match &self.edited_parts {
+            Some(edited_parts) => edited_parts.tapback_anchor(index).map(|_| index),
+            None => Some(index),
+        }
+    }
+
+    /// The range of the target message's text that a tapback applies to, in UTF-16 code units
+    ///
+    /// The range is an `NSRange`, so characters outside the Basic Multilingual Plane, like most emoji, count as two
+    /// units. Read from the `associated_message_range_location` and `associated_message_range_length` columns.
+    /// Returns `None` if the message is not a tapback or the range is empty, in which case the tapback
+    /// applies to the whole part given by its associated GUID.
+    #[must_use]
+    pub fn tapback_range(&self) -> Option<Range<usize>> {
+        if !self.is_tapback() {
+            return None;
+        }
+        let start = usize::try_from(self.associated_message_range_location).ok()?;
+        let length = usize::try_from(self.associated_message_range_length).ok()?;
+        (length > 0).then_some(start..start + length)
+    }
+
+    /// Get the text a tapback applies to in the message it reacts to
+    ///
+    /// Uses [`Self::tapback_range()`] if it lies within the target's text, otherwise falls back to the
+    /// text of the whole part the tapback applies to. Returns `None` if that part is not text.
+    #[must_use]
+    pub fn tapback_target_text<'a>(&self, target: &'a Message) -> Option<&'a str> {
+        let text = target.text.as_deref()?;
+
+        if let Some(range) = self.tapback_range() {
+            let start = utf16_to_byte_offset(text, range.start);
+            let end = utf16_to_byte_offset(text, range.end);
+            if let (Some(start), Some(end)) = (start, end) {
+                return text.get(start..end);
+            }
+        }
+
+        match target.body().get(self.tapback_index())? {
+            BubbleComponent::Text(attributes) => {
+                text.get(attributes.first()?.start..attributes.last()?.end)
+            }
+            _ => None,
+        }
+    }
+
     /// Parse the index of a tapback from it's associated GUID field
     fn tapback_index(&self) -> usize {
         match self.clean_associated_guid() {
@@ -889,20 +1523,7 @@ impl Message {
 
         // No need to hit the DB if we know we don't have replies
         if self.has_replies() {
-            let filters = format!("WHERE m.thread_originator_guid = \"{}\"", self.guid);
-
-            // No iOS 13 and prior used here because `thread_originator_guid` is not present in that schema
-            let mut statement = db
-                .prepare(&ios_16_newer_query(Some(&filters), None))
-                .or_else(|_| db.prepare(&ios_14_15_query(Some(&filters), None)))
-                .map_err(TableError::Messages)?;
-
-            let iter = statement
-                .query_map([], |row| Ok(Message::from_row(row)))
-                .map_err(TableError::Messages)?;
-
-            for message in iter {
-                let m = Message::extract(message)?;
+            for m in Self::replies_to(db, &self.guid)? {
                 let idx = m.get_reply_index();
                 match out_h.get_mut(&idx) {
                     Some(body_part) => body_part.push(m),
@@ -916,6 +1537,302 @@ impl Message {
         Ok(out_h)
     }
 
+    /// Build the tree of replies beneath this message, including replies to replies
+    ///
+    /// Each reply is placed under the message its `thread_originator_guid` points to. A message is only placed in
+    /// the tree once, so a cycle in the reply chain ends that branch instead of repeating forever.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let message = Message::from_guid("example-guid", &conn).unwrap();
+    /// let thread = message.reply_thread_tree(&conn).unwrap();
+    /// println!("{} direct replies", thread.children.len());
+    /// ```
+    pub fn reply_thread_tree(self, db: &Connection) -> Result<ReplyNode, TableError> {
+        let mut visited = HashSet::from([self.guid.clone()]);
+        self.build_reply_node(db, &mut visited)
+    }
+
+    /// Place this message in a [`ReplyNode`] along with the replies that have not been visited yet
+    fn build_reply_node(
+        self,
+        db: &Connection,
+        visited: &mut HashSet<String>,
+    ) -> Result<ReplyNode, TableError> {
+        let mut children = vec![];
+        // Replies are selected newest first
+        for reply in Self::replies_to(db, &self.guid)?.into_iter().rev() {
+            if visited.insert(reply.guid.clone()) {
+                children.push(reply.build_reply_node(db, visited)?);
+            }
+        }
+        Ok(ReplyNode {
+            message: self,
+            children,
+        })
+    }
+
+    /// Get the messages whose `thread_originator_guid` is the given GUID
+    fn replies_to(db: &Connection, guid: &str) -> Result<Vec<Self>, TableError> {
+        let filters = format!("WHERE m.thread_originator_guid = \"{guid}\"");
+
+        // iOS 13 and prior fail here because `thread_originator_guid` is not present in that schema
+        let mut statement = db
+            .prepare(&SchemaVersion::detect(db).query(Some(&filters), None))
+            .map_err(TableError::Messages)?;
+
+        let iter = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(TableError::Messages)?;
+
+        iter.map(Message::extract).collect()
+    }
+
+    /// Resolve the messages referenced by this message's edits
+    ///
+    /// When an edit converts a message part to or from a link, the edit event references the message that
+    /// holds the link preview. References to messages that no longer exist are kept with no message, so
+    /// callers can still follow the rest of the chain. Requires [`Message::generate_text()`] to have parsed
+    /// the edit history first.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let mut message = Message::from_guid("example-guid", &conn).unwrap();
+    /// let _ = message.generate_text(&conn);
+    /// for reference in message.edit_references(&conn).unwrap() {
+    ///     println!("Part {} references {}: {:?}", reference.part_index, reference.guid, reference.message);
+    /// }
+    /// ```
+    pub fn edit_references(&self, db: &Connection) -> Result<Vec<EditReference>, TableError> {
+        let Some(edited_parts) = &self.edited_parts else {
+            return Ok(vec![]);
+        };
+
+        edited_parts
+            .referenced_guids()
+            .map(|(part_index, guid)| {
+                let message = match Message::from_guid(guid, db) {
+                    Ok(message) => Some(message),
+                    Err(TableError::Messages(Error::QueryReturnedNoRows)) => None,
+                    Err(why) => return Err(why),
+                };
+                Ok(EditReference {
+                    part_index,
+                    guid: guid.to_string(),
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    /// Describe how a reply relates to the message that started its thread
+    ///
+    /// The database does not record whether a reply was sent into a thread or as an inline quote, so the
+    /// [`ReplyKind`] is a heuristic based on the originator's [`num_replies`](Self::num_replies): a reply is part
+    /// of a [`ReplyKind::Thread`] when the originator has more than one reply, otherwise it is a
+    /// [`ReplyKind::Quote`] of the originator. If the originator is missing, its replies cannot be counted and the
+    /// reply is treated as a quote. Exporters can use the [`OriginatorStatus`] to decide whether the quoted message
+    /// can be shown. Returns [`None`] if the message is not a reply.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::messages::Message;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let message = Message::from_guid("example-guid", &conn).unwrap();
+    /// if let Ok(Some(context)) = message.reply_context(&conn) {
+    ///     println!("{:?} of a message that is {:?}", context.kind, context.originator);
+    /// }
+    /// ```
+    pub fn reply_context(&self, db: &Connection) -> Result<Option<ReplyContext>, TableError> {
+        let Some(originator_guid) = &self.thread_originator_guid else {
+            return Ok(None);
+        };
+
+        let (replies, originator) = match Message::from_guid(originator_guid, db) {
+            Ok(mut originator) => {
+                let _ = originator.generate_text(db);
+                let status = if originator.is_deleted() || originator.is_fully_unsent() {
+                    OriginatorStatus::Deleted
+                } else {
+                    OriginatorStatus::Present
+                };
+                (originator.num_replies, status)
+            }
+            Err(TableError::Messages(Error::QueryReturnedNoRows)) => (0, OriginatorStatus::Missing),
+            Err(why) => return Err(why),
+        };
+        let kind = if replies > 1 {
+            ReplyKind::Thread
+        } else {
+            ReplyKind::Quote
+        };
+
+        Ok(Some(ReplyContext { kind, originator }))
+    }
+
+    /// Take the attachments for this message from a map built by [`Attachment::cache_by_message()`]
+    ///
+    /// This yields the same result as [`Attachment::from_message()`] without querying the database.
+    /// The attachments are removed from the map, as each message is only rendered once.
+    pub fn attachments_without_query(
+        &self,
+        attachments: &mut HashMap<i32, Vec<Attachment>>,
+    ) -> Vec<Attachment> {
+        if !self.has_attachments() {
+            return vec![];
+        }
+        attachments.remove(&self.rowid).unwrap_or_default()
+    }
+
+    /// Pair each component of the message body with the attachment it displays
+    ///
+    /// `attachments` should be in the order returned by [`Attachment::from_message()`]. Attachments that repeat
+    /// a `ROWID` are [removed](Attachment::remove_duplicates) so they are not rendered twice. Attachments are
+    /// assigned to the `U+FFFC` placeholders in the body in order. Malformed data can leave the body with a
+    /// different number of placeholders than attachments; when that happens, placeholders without an
+    /// attachment are left empty, attachments without a placeholder are appended to the end, and the
+    /// result's [`mismatch`](RenderedParts::mismatch) describes the problem so it can be reported.
+    ///
+    /// App messages do not contain placeholders, so their attachments are always appended.
+    #[must_use]
+    pub fn rendered_parts(&self, mut attachments: Vec<Attachment>) -> RenderedParts<'_> {
+        Attachment::remove_duplicates(&mut attachments);
+        let body = self.body();
+
+        let is_app = matches!(body.as_slice(), [BubbleComponent::App]);
+        let placeholders = body
+            .iter()
+            .filter(|component| matches!(component, BubbleComponent::Attachment(_)))
+            .count();
+        let mismatch =
+            (!is_app && placeholders != attachments.len()).then_some(AttachmentMismatch {
+                placeholders,
+                attachments: attachments.len(),
+            });
+
+        let mut attachments = attachments.into_iter();
+        let mut parts: Vec<RenderedPart> = body
+            .into_iter()
+            .map(|component| {
+                let attachment = match component {
+                    BubbleComponent::Attachment(_) => attachments.next(),
+                    _ => None,
+                };
+                RenderedPart {
+                    component,
+                    attachment,
+                }
+            })
+            .collect();
+
+        // Attachments without a placeholder go at the end instead of being dropped
+        parts.extend(attachments.map(|attachment| RenderedPart {
+            component: BubbleComponent::Attachment(AttachmentMeta::default()),
+            attachment: Some(attachment),
+        }));
+
+        RenderedParts { parts, mismatch }
+    }
+
+    /// Remove the thumbnail of a link preview from a message's attachments and return it
+    ///
+    /// Link previews store their thumbnail as a [hidden](Attachment::is_hidden) image attachment. It should only
+    /// be rendered as part of the preview card, so taking it out of `attachments` leaves only the media the
+    /// sender shared. Returns `None` if the message is not a [link preview](Self::is_url) or has no hidden image.
+    pub fn take_link_preview_image(&self, attachments: &mut Vec<Attachment>) -> Option<Attachment> {
+        if !self.is_url() {
+            return None;
+        }
+        let index = attachments.iter().position(|attachment| {
+            attachment.is_hidden() && matches!(attachment.mime_type(), MediaType::Image(_))
+        })?;
+        Some(attachments.remove(index))
+    }
+
+    /// Get the sticker this message placed on another message's bubble, and where it was placed
+    ///
+    /// Returns `None` if the message is not a [sticker overlay](Self::is_sticker_overlay) or its sticker
+    /// attachment is missing. Calling this hits the database, so it should only get invoked when needed.
+    pub fn sticker_overlay(&self, db: &Connection) -> Result<Option<StickerOverlay>, TableError> {
+        if !self.is_sticker_overlay() {
+            return Ok(None);
+        }
+        let mut attachments = Attachment::from_message(db, self)?;
+        let Some(index) = attachments
+            .iter()
+            .position(|attachment| attachment.is_sticker)
+            .or_else(|| (!attachments.is_empty()).then_some(0))
+        else {
+            return Ok(None);
+        };
+        let attachment = attachments.swap_remove(index);
+
+        Ok(Some(StickerOverlay {
+            position: attachment.get_sticker_position(db),
+            attachment,
+        }))
+    }
+
+    /// Get the message text with each one-time code replaced by `replacement`
+    ///
+    /// One-time codes are the ranges of the body tagged with [`TextEffect::OTP`]. If the text has not been
+    /// captured with [`Self::generate_text()`], this returns `None`.
+    #[must_use]
+    pub fn text_with_redacted_codes(&self, replacement: &str) -> Option<String> {
+        let text = self.text.as_deref()?;
+        Some(redact_one_time_codes(text, &self.body(), replacement))
+    }
+
+    /// Get the handles mentioned in the message, in the order they appear
+    ///
+    /// Mentions are the ranges of the body tagged with [`TextEffect::Mention`]. Each range counts `char`s, not bytes,
+    /// so it can be used with [`str::chars()`] even when the text before or inside a mention has multi-byte
+    /// characters. If the text has not been captured with [`Self::generate_text()`], this is empty.
+    #[must_use]
+    pub fn mentions(&self) -> Vec<Mention> {
+        let Some(text) = self.text.as_deref() else {
+            return vec![];
+        };
+        let char_idx = |byte_idx: usize| text.get(..byte_idx).map(|prefix| prefix.chars().count());
+
+        let mut mentions = vec![];
+        for component in self.body() {
+            if let BubbleComponent::Text(attributes) = component {
+                for attribute in attributes {
+                    if let TextEffect::Mention(handle) = attribute.effect
+                        && let (Some(start), Some(end)) =
+                            (char_idx(attribute.start), char_idx(attribute.end))
+                    {
+                        mentions.push(Mention {
+                            range: start..end,
+                            handle: handle.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        mentions
+    }
+
     /// Get the variant of a message, see [`variants`](crate::message_types::variants) for detail.
     #[must_use]
     pub fn variant(&self) -> Variant {
@@ -950,6 +1867,7 @@ impl Message {
                             Variant::App(CustomBalloon::CheckIn)
                         }
                         "com.apple.findmy.FindMyMessagesApp" => Variant::App(CustomBalloon::FindMy),
+                        "com.apple.messages.Polls" => Variant::App(CustomBalloon::Poll),
                         _ => Variant::App(CustomBalloon::Application(bundle_id)),
                     },
                     // This is the most common case
@@ -1059,6 +1977,10 @@ impl Message {
             return Some(Announcement::AudioMessageKept);
         }
 
+        if let Some(action) = self.pin_action() {
+            return Some(Announcement::MessagePin(action));
+        }
+
         None
     }
 
@@ -1068,6 +1990,19 @@ impl Message {
         Service::from(self.service.as_deref())
     }
 
+    /// Determine the service the message was sent from, using the service of its chat when the message does not have one
+    ///
+    /// Some SMS and RCS messages are stored with a `NULL` service, but the chat they belong to still records that it
+    /// is an SMS or RCS conversation. `chat_service` is the [`service_name`](crate::tables::chat::Chat::service_name)
+    /// of the message's chat.
+    #[must_use]
+    pub fn service_in<'a>(&'a self, chat_service: Option<&'a str>) -> Service<'a> {
+        match self.service() {
+            Service::Unknown => Service::from(chat_service),
+            service => service,
+        }
+    }
+
     /// Get a message's plist from the [`MESSAGE_PAYLOAD`] BLOB column
     ///
     /// Calling this hits the database, so it is expensive and should
@@ -1079,18 +2014,117 @@ impl Message {
         Value::from_reader(self.get_blob(db, MESSAGE_PAYLOAD)?).ok()
     }
 
+    /// Parse a [`Poll`] from the message's payload, if the message is a poll
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed. Polls use an undocumented schema, so any payload that
+    /// cannot be parsed yields [`None`].
+    pub fn poll(&self, db: &Connection) -> Option<Poll> {
+        if !matches!(self.variant(), Variant::App(CustomBalloon::Poll)) {
+            return None;
+        }
+
+        let payload = self.payload_data(db)?;
+        let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+        Poll::from_payload(&payload).ok()
+    }
+
+    /// Parse an [`ApplePayMessage`] from the message's payload, if the message is an Apple Pay payment
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed. Payments sent to the database owner by other people are marked as
+    /// [`PaymentDirection::Receive`], and any payload that cannot be parsed yields [`None`].
+    pub fn apple_pay(&self, db: &Connection) -> Option<ApplePayMessage> {
+        if !matches!(self.variant(), Variant::App(CustomBalloon::ApplePay)) {
+            return None;
+        }
+
+        let payload = self.payload_data(db)?;
+        let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+        let mut payment = ApplePayMessage::from_payload(&payload).ok()?;
+        if !self.is_from_me() && payment.direction == PaymentDirection::Send {
+            payment.direction = PaymentDirection::Receive;
+        }
+        Some(payment)
+    }
+
+    /// Summarize an item shared from another app, like a collaboration invite from Pages or Freeform
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed. URL previews only yield a summary if they are collaboration invites.
+    /// App messages whose payload is not a collaboration invite fall back to the generic app summary,
+    /// and any payload that cannot be parsed yields [`None`].
+    pub fn shared_item(&self, db: &Connection) -> Option<SharedItem> {
+        let is_url = match self.variant() {
+            Variant::App(CustomBalloon::URL) => true,
+            Variant::App(CustomBalloon::Application(_) | CustomBalloon::Slideshow) => false,
+            _ => return None,
+        };
+
+        let payload = self.payload_data(db)?;
+        let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+        if let Ok(balloon) = CollaborationMessage::from_map(&payload) {
+            return Some(SharedItem::from_collaboration(&balloon));
+        }
+        if is_url {
+            return None;
+        }
+        AppMessage::from_map(&payload)
+            .ok()
+            .map(|balloon| SharedItem::from_app(&balloon))
+    }
+
+    /// Describe a third-party app balloon, like a game, by its captions
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed. Only messages from app extensions yield a summary, and collaboration invites
+    /// or balloons without caption text yield [`None`]; see [`AppMessage::caption_summary()`].
+    pub fn app_summary(&self, db: &Connection) -> Option<String> {
+        if !matches!(self.variant(), Variant::App(CustomBalloon::Application(_))) {
+            return None;
+        }
+
+        let payload = self.payload_data(db)?;
+        let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+        if CollaborationMessage::from_map(&payload).is_ok() {
+            return None;
+        }
+        AppMessage::from_map(&payload).ok()?.caption_summary()
+    }
+
     /// Get a message's raw data from the [`MESSAGE_PAYLOAD`] BLOB column
     ///
     /// Calling this hits the database, so it is expensive and should
     /// only get invoked when needed.
     ///
     /// This column contains data used by [`HandwrittenMessage`](crate::message_types::handwriting::HandwrittenMessage)s.
+    /// For app balloons, the bytes are a binary plist that [`Self::payload_data()`] parses.
+    /// Returns [`None`] if the column is `NULL` or cannot be read.
     pub fn raw_payload_data(&self, db: &Connection) -> Option<Vec<u8>> {
-        let mut buf = Vec::new();
-        self.get_blob(db, MESSAGE_PAYLOAD)?
-            .read_to_end(&mut buf)
-            .ok()?;
-        Some(buf)
+        self.read_blob(db, MESSAGE_PAYLOAD)
+    }
+
+    /// Get a message's raw data from the [`MESSAGE_SUMMARY_INFO`] BLOB column
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    ///
+    /// The bytes are a binary plist that [`Self::message_summary_info()`] parses.
+    /// Returns [`None`] if the column is `NULL` or cannot be read.
+    pub fn raw_message_summary_info(&self, db: &Connection) -> Option<Vec<u8>> {
+        self.read_blob(db, MESSAGE_SUMMARY_INFO)
+    }
+
+    /// Get a message's raw [typedstream](crate::util::typedstream) from the [`ATTRIBUTED_BODY`] BLOB column
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    ///
+    /// The bytes are unparsed, which is useful for debugging or reverse engineering the format.
+    /// [`Self::generate_text()`] parses them into the message's text and components.
+    /// Returns [`None`] if the column is `NULL` or cannot be read.
+    pub fn raw_typedstream(&self, db: &Connection) -> Option<Vec<u8>> {
+        self.read_blob(db, ATTRIBUTED_BODY)
     }
 
     /// Get a message's plist from the [`MESSAGE_SUMMARY_INFO`] BLOB column
@@ -1109,6 +2143,7 @@ impl Message {
     /// only get invoked when needed.
     ///
     /// This column contains the message's body text with any other attributes.
+    /// Unlike [`Self::raw_typedstream()`], a column that cannot be read yields empty data.
     pub fn attributed_body(&self, db: &Connection) -> Option<Vec<u8>> {
         let mut body = vec![];
         self.get_blob(db, ATTRIBUTED_BODY)?
@@ -1117,6 +2152,13 @@ impl Message {
         Some(body)
     }
 
+    /// Read the full contents of a BLOB column
+    fn read_blob(&self, db: &Connection, column: &str) -> Option<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.get_blob(db, column)?.read_to_end(&mut buf).ok()?;
+        Some(buf)
+    }
+
     /// Determine which [`Expressive`] the message was sent with
     #[must_use]
     pub fn get_expressive(&self) -> Expressive {
@@ -1189,15 +2231,42 @@ impl Message {
         let filters = format!("WHERE m.guid = \"{guid}\"");
 
         let mut statement = db
-            .prepare(&ios_16_newer_query(Some(&filters), None))
-            .or_else(|_| db.prepare(&ios_14_15_query(Some(&filters), None)))
-            .or_else(|_| db.prepare(&ios_13_older_query(Some(&filters), None)))
+            .prepare(&SchemaVersion::detect(db).query(Some(&filters), None))
             .map_err(TableError::Messages)?;
 
         Message::extract(statement.query_row([], |row| Ok(Message::from_row(row))))
     }
 }
 
+/// Parse an associated GUID like `p:0/GUID`, `bp:GUID`, or `GUID` into its body part index and GUID
+fn parse_associated_guid(guid: &str) -> Result<(usize, &str), MessageError> {
+    let invalid = || MessageError::InvalidAssociatedGuid(guid.to_string());
+
+    if let Some(part) = guid.strip_prefix("p:") {
+        let (index, message_id) = part.split_once('/').ok_or_else(invalid)?;
+        let index = index.parse::<usize>().map_err(|_| invalid())?;
+        return Ok((index, message_id.get(0..36).ok_or_else(invalid)?));
+    } else if let Some(message_id) = guid.strip_prefix("bp:") {
+        return Ok((0, message_id.get(0..36).ok_or_else(invalid)?));
+    }
+
+    Ok((0, guid.get(0..36).ok_or_else(invalid)?))
+}
+
+/// Convert an offset in UTF-16 code units into a byte offset in `text`
+///
+/// Returns `None` if the offset is past the end of the text or splits a surrogate pair.
+fn utf16_to_byte_offset(text: &str, offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (idx, c) in text.char_indices() {
+        if units >= offset {
+            return (units == offset).then_some(idx);
+        }
+        units += c.len_utf16();
+    }
+    (units == offset).then_some(text.len())
+}
+
 #[cfg(test)]
 impl Message {
     #[must_use]
@@ -1223,12 +2292,16 @@ impl Message {
             group_action_type: 0,
             associated_message_guid: None,
             associated_message_type: None,
+            associated_message_range_location: 0,
+            associated_message_range_length: 0,
             balloon_bundle_id: None,
             expressive_send_style_id: None,
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
             associated_message_emoji: None,
+            is_audio_message: false,
+            is_played: None,
             chat_id: None,
             num_attachments: 0,
             deleted_from: None,
@@ -5,7 +5,9 @@
 pub use message::Message;
 
 pub(crate) mod body;
+pub mod iter;
 pub mod message;
 pub mod models;
 pub(crate) mod query_parts;
+pub mod tapbacks;
 mod tests;
@@ -2,10 +2,14 @@
  This module contains Data structures and models that represent message data.
 */
 
-use std::fmt::{Display, Formatter, Result};
+use std::{
+    fmt::{Display, Formatter, Result},
+    ops::Range,
+};
 
 use crate::{
-    message_types::text_effects::TextEffect, tables::messages::message::Message,
+    message_types::{sticker::StickerPosition, text_effects::TextEffect},
+    tables::{attachment::Attachment, messages::message::Message},
     util::typedstream::models::Archivable,
 };
 
@@ -28,15 +32,141 @@ pub enum BubbleComponent<'a> {
     Retracted,
 }
 
-/// Defines different types of [services](https://support.apple.com/en-us/104972) we can receive messages from.
+/// A message body component paired with the attachment it displays, built by [`Message::rendered_parts()`]
+#[derive(Debug)]
+pub struct RenderedPart<'a> {
+    /// The component of the message body
+    pub component: BubbleComponent<'a>,
+    /// The attachment shown by an attachment component, if one exists
+    pub attachment: Option<Attachment>,
+}
+
+/// The components of a message body paired with the message's attachments
+#[derive(Debug)]
+pub struct RenderedParts<'a> {
+    /// The parts of the message, in display order
+    pub parts: Vec<RenderedPart<'a>>,
+    /// Present if the number of attachment placeholders in the body did not match the number of attachments
+    pub mismatch: Option<AttachmentMismatch>,
+}
+
+/// Describes a message whose body and attachment table disagree on how many attachments it has
+#[derive(Debug, PartialEq, Eq)]
+pub struct AttachmentMismatch {
+    /// The number of `U+FFFC` placeholders in the message body
+    pub placeholders: usize,
+    /// The number of attachments joined to the message
+    pub attachments: usize,
+}
+
+impl Display for AttachmentMismatch {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        write!(
+            fmt,
+            "Message body has {} attachment placeholder(s) but {} attachment(s); extra attachments are shown at the end",
+            self.placeholders, self.attachments
+        )
+    }
+}
+
+/// A message referenced by an edit, built by [`Message::edit_references()`]
 #[derive(Debug)]
+pub struct EditReference {
+    /// The index of the edited message part
+    pub part_index: usize,
+    /// The GUID stored in the edit event
+    pub guid: String,
+    /// The referenced message, or `None` if it no longer exists in the database
+    pub message: Option<Message>,
+}
+
+/// Describes how a reply relates to the message that started its thread, built by [`Message::reply_context()`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplyContext {
+    /// Whether the reply is part of a conversation in the thread or only quotes the originator
+    pub kind: ReplyKind,
+    /// Whether the message the reply responds to can still be displayed
+    pub originator: OriginatorStatus,
+}
+
+/// Distinguishes replies that form a thread from replies that quote a single message
+///
+/// This is inferred from the number of replies, see [`Message::reply_context()`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplyKind {
+    /// The originator has more than one reply, so the reply is part of a threaded conversation
+    Thread,
+    /// The reply is the only response to the originator, so it only quotes the message it responds to
+    Quote,
+}
+
+/// A message and every reply beneath it, built by [`Message::reply_thread_tree()`]
+#[derive(Debug)]
+pub struct ReplyNode {
+    /// The message at this point in the thread
+    pub message: Message,
+    /// The replies to the message, oldest first
+    pub children: Vec<ReplyNode>,
+}
+
+/// Describes the state of the message that started a thread
+#[derive(Debug, PartialEq, Eq)]
+pub enum OriginatorStatus {
+    /// The originator is still in the chat
+    Present,
+    /// The originator was unsent or moved to Recently Deleted
+    Deleted,
+    /// The originator no longer exists in the database
+    Missing,
+}
+
+/// Marks where [`Message::page_before()`] should resume paging through a chat
+///
+/// Messages are ordered by date, then by `ROWID` for messages sent at the same time, so pages never overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    /// The date of the oldest message on the previous page
+    pub date: i64,
+    /// The `ROWID` of the oldest message on the previous page
+    pub rowid: i32,
+}
+
+/// A batch of messages from a chat, built by [`Message::page_before()`]
+#[derive(Debug)]
+pub struct MessagePage {
+    /// The messages on the page, newest first
+    pub messages: Vec<Message>,
+    /// The cursor used to get the next, older page, or `None` if there are no older messages
+    pub cursor: Option<PageCursor>,
+}
+
+/// A sticker placed on top of another message's bubble, built by [`Message::sticker_overlay()`]
+#[derive(Debug)]
+pub struct StickerOverlay {
+    /// The sticker image
+    pub attachment: Attachment,
+    /// Where the sticker was placed on the target bubble, or `None` if the database does not record it
+    pub position: Option<StickerPosition>,
+}
+
+/// A handle mentioned in a message's text, built by [`Message::mentions()`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mention {
+    /// The range of the mention in the message text, in `char`s rather than bytes
+    pub range: Range<usize>,
+    /// The phone number or email address of the person mentioned
+    pub handle: String,
+}
+
+/// Defines different types of [services](https://support.apple.com/en-us/104972) we can receive messages from.
+#[derive(Debug, PartialEq, Eq)]
 pub enum Service<'a> {
     /// An iMessage
     #[allow(non_camel_case_types)]
     iMessage,
     /// A message sent as SMS
     SMS,
-    /// A message sent as RCS
+    /// A message sent as [RCS](https://support.apple.com/en-us/104972), stored as `rcs` or `RCS`
     RCS,
     /// A message sent via [satellite](https://support.apple.com/en-us/120930)
     Satellite,
@@ -54,7 +184,7 @@ impl<'a> Service<'a> {
                 "iMessage" => Service::iMessage,
                 "iMessageLite" => Service::Satellite,
                 "SMS" => Service::SMS,
-                "rcs" | "RCS" => Service::RCS,
+                name if name.eq_ignore_ascii_case("rcs") => Service::RCS,
                 service_name => Service::Other(service_name),
             };
         }
@@ -197,7 +327,53 @@ impl<'a> AttachmentMeta<'a> {
     }
 }
 
+/// The kind of event a message represents, mapped from the `item_type` column
+///
+/// Most messages are [`ItemType::Normal`]. The other types are system events that are displayed in the
+/// conversation but are not written by a person.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemType {
+    /// A message written by a person, `0`
+    Normal,
+    /// A participant was added to or removed from a group, `1`
+    GroupMembership,
+    /// The group was renamed, `2`
+    GroupRename,
+    /// A participant left the group, or the group icon changed, `3`
+    GroupEvent,
+    /// A participant started or stopped sharing their location, `4`
+    LocationShare,
+    /// A sent audio message was kept, `5`
+    KeptAudio,
+    /// A [SharePlay/FaceTime](crate::message_types::variants::Variant::SharePlay) event, `6`
+    SharePlay,
+    /// A message was pinned or unpinned in the chat, `7` (unverified, see [`Message::pin_action()`])
+    MessagePin,
+    /// A value this library does not recognize
+    Unknown(i32),
+}
+
+impl From<i32> for ItemType {
+    fn from(item_type: i32) -> Self {
+        match item_type {
+            0 => Self::Normal,
+            1 => Self::GroupMembership,
+            2 => Self::GroupRename,
+            3 => Self::GroupEvent,
+            4 => Self::LocationShare,
+            5 => Self::KeptAudio,
+            6 => Self::SharePlay,
+            7 => Self::MessagePin,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
 /// Represents different types of group message actions that can occur in a chat system
+///
+/// The participant who performed the action is the sender of the message, so it is resolved from the message's
+/// `handle_id` and `is_from_me` columns. Actions that affect another
+/// participant also carry that participant's handle, available from [`GroupAction::target()`].
 #[derive(Debug)]
 pub enum GroupAction<'a> {
     /// A new participant has been added to the group
@@ -219,17 +395,28 @@ impl<'a> GroupAction<'a> {
     #[must_use]
     pub fn from_message(message: &'a Message) -> Option<Self> {
         match (
-            message.item_type,
+            message.item_kind(),
             message.group_action_type,
             message.other_handle,
             &message.group_title,
         ) {
-            (1, 0, Some(who), _) => Some(Self::ParticipantAdded(who)),
-            (1, 1, Some(who), _) => Some(Self::ParticipantRemoved(who)),
-            (2, _, _, Some(name)) => Some(Self::NameChange(name)),
-            (3, 0, _, _) => Some(Self::ParticipantLeft),
-            (3, 1, _, _) => Some(Self::GroupIconChanged),
-            (3, 2, _, _) => Some(Self::GroupIconRemoved),
+            (ItemType::GroupMembership, 0, Some(who), _) => Some(Self::ParticipantAdded(who)),
+            (ItemType::GroupMembership, 1, Some(who), _) => Some(Self::ParticipantRemoved(who)),
+            (ItemType::GroupRename, _, _, Some(name)) => Some(Self::NameChange(name)),
+            (ItemType::GroupEvent, 0, _, _) => Some(Self::ParticipantLeft),
+            (ItemType::GroupEvent, 1, _, _) => Some(Self::GroupIconChanged),
+            (ItemType::GroupEvent, 2, _, _) => Some(Self::GroupIconRemoved),
+            _ => None,
+        }
+    }
+
+    /// The `ROWID` of the [`Handle`](crate::tables::handle::Handle) of the participant that was added or removed
+    ///
+    /// Returns `None` for actions that do not affect another participant.
+    #[must_use]
+    pub fn target(&self) -> Option<i32> {
+        match self {
+            Self::ParticipantAdded(who) | Self::ParticipantRemoved(who) => Some(*who),
             _ => None,
         }
     }
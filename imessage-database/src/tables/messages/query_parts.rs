@@ -63,6 +63,33 @@ ORDER BY
     m.date DESC
 ";
 
+/// Collapse the rows a message gets for each chat or recently deleted collection it is joined to, so a `LIMIT`
+/// counts every message once
+pub(crate) fn one_row_per_message(filters: &str) -> String {
+    format!("{filters}\nGROUP BY\n    m.ROWID")
+}
+
+const ORDER_BY_OLDEST_FIRST: &str = "
+ORDER BY
+    m.date ASC
+";
+
+/// Sort a query generated by this module by ascending message date instead of descending
+pub(crate) fn oldest_first(query: &str) -> String {
+    query.replacen(ORDER_BY, ORDER_BY_OLDEST_FIRST, 1)
+}
+
+const ORDER_BY_NEWEST_FIRST_STABLE: &str = "
+ORDER BY
+    m.date DESC,
+    m.ROWID DESC
+";
+
+/// Sort a query generated by this module by descending message date, breaking ties by descending `ROWID`
+pub(crate) fn newest_first_stable(query: &str) -> String {
+    query.replacen(ORDER_BY, ORDER_BY_NEWEST_FIRST_STABLE, 1)
+}
+
 /// Generate a SQL Query compatible with the macOS Ventura+ and i0S 16+ schema
 pub(crate) fn ios_16_newer_query(filters: Option<&str>, limit: Option<&str>) -> String {
     format!(
@@ -0,0 +1,86 @@
+/*!
+ Helpers that summarize the tapbacks built by [`Message::cache()`](crate::tables::table::Cacheable::cache).
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    message_types::variants::{Tapback, TapbackAction, Variant},
+    tables::messages::Message,
+};
+
+/// Get the tapbacks that are still applied to each part of a message, ordered by part and then by date
+///
+/// `parts` is the value cached for a single target message. Each person has at most one tapback on each part, so a
+/// tapback is dropped if the same person later replaced or removed their tapback on that part.
+#[must_use]
+pub fn applied_tapbacks(parts: &HashMap<usize, Vec<Message>>) -> Vec<(usize, &Message)> {
+    let mut events: Vec<(usize, &Message)> = parts
+        .iter()
+        .flat_map(|(part, tapbacks)| tapbacks.iter().map(|tapback| (*part, tapback)))
+        .collect();
+    events.sort_by_key(|(part, tapback)| (*part, tapback.date, tapback.rowid));
+
+    let mut applied: Vec<(usize, &Message)> = vec![];
+    for (part, tapback) in events {
+        let Variant::Tapback(_, action, _) = tapback.variant() else {
+            continue;
+        };
+        let sender = (tapback.handle_id, tapback.is_from_me());
+        applied.retain(|(p, t)| *p != part || (t.handle_id, t.is_from_me()) != sender);
+        if matches!(action, TapbackAction::Added) {
+            applied.push((part, tapback));
+        }
+    }
+    applied
+}
+
+/// Count the tapbacks still applied to each part of the message with the given `guid`
+///
+/// A removed tapback cancels the tapback its sender added earlier, so a message that was loved and then un-loved
+/// has no tapbacks. The tapbacks on each part are ordered from most to least common, with ties in the order they
+/// were first added.
+///
+/// # Example:
+///
+/// ```no_run
+/// use imessage_database::util::dirs::default_db_path;
+/// use imessage_database::tables::table::{Cacheable, get_connection};
+/// use imessage_database::tables::messages::{Message, tapbacks::tapback_counts};
+///
+/// let db_path = default_db_path();
+/// let conn = get_connection(&db_path).unwrap();
+/// let tapbacks = Message::cache(&conn).unwrap();
+///
+/// for (part, counts) in tapback_counts(&tapbacks, "message_guid") {
+///     for (tapback, count) in counts {
+///         println!("Part {part}: {count} {tapback}");
+///     }
+/// }
+/// ```
+#[must_use]
+pub fn tapback_counts<'a>(
+    tapbacks: &'a HashMap<String, HashMap<usize, Vec<Message>>>,
+    guid: &str,
+) -> HashMap<usize, Vec<(Tapback<'a>, usize)>> {
+    let mut counts: HashMap<usize, Vec<(Tapback<'a>, usize)>> = HashMap::new();
+    let Some(parts) = tapbacks.get(guid) else {
+        return counts;
+    };
+
+    for (part, message) in applied_tapbacks(parts) {
+        let Variant::Tapback(_, _, tapback) = message.variant() else {
+            continue;
+        };
+        let part_counts = counts.entry(part).or_default();
+        match part_counts.iter_mut().find(|(kind, _)| *kind == tapback) {
+            Some((_, count)) => *count += 1,
+            None => part_counts.push((tapback, 1)),
+        }
+    }
+
+    counts
+        .values_mut()
+        .for_each(|part_counts| part_counts.sort_by(|(_, a), (_, b)| b.cmp(a)));
+    counts
+}
@@ -1,6 +1,10 @@
 #[cfg(test)]
 mod group_action_tests {
-    use crate::tables::messages::{message::Message, models::GroupAction};
+    use crate::tables::{
+        handle::Handle,
+        messages::{message::Message, models::GroupAction},
+        table::{Cacheable, get_empty_test_connection},
+    };
 
     #[test]
     fn test_group_action_participant_added() {
@@ -15,6 +19,40 @@ mod group_action_tests {
         ));
     }
 
+    #[test]
+    fn test_group_action_participant_added_actor_and_target() {
+        let mut msg = Message::blank();
+        msg.item_type = 1;
+        msg.group_action_type = 0;
+        msg.handle_id = Some(1);
+        msg.other_handle = Some(2);
+
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "INSERT INTO handle (ROWID, id, service) VALUES
+                (1, '+15555550101', 'iMessage'),
+                (2, '+15555550102', 'iMessage');",
+        )
+        .unwrap();
+        let handles = Handle::cache(&db).unwrap();
+
+        let action = msg.group_action().unwrap();
+        let actor = handles.get(&msg.handle_id.unwrap());
+        let target = handles.get(&action.target().unwrap());
+        assert_eq!(actor.map(String::as_str), Some("+15555550101"));
+        assert_eq!(target.map(String::as_str), Some("+15555550102"));
+    }
+
+    #[test]
+    fn test_group_action_without_target() {
+        let mut msg = Message::blank();
+        msg.item_type = 3;
+        msg.group_action_type = 0;
+        msg.handle_id = Some(1);
+
+        assert_eq!(msg.group_action().unwrap().target(), None);
+    }
+
     #[test]
     fn test_group_action_participant_added_missing_handle() {
         let mut msg = Message::blank();
@@ -111,7 +149,7 @@ mod announcement_tests {
     use crate::{
         message_types::{
             edited::{EditStatus, EditedMessage, EditedMessagePart},
-            variants::Announcement,
+            variants::{Announcement, PinAction},
         },
         tables::messages::{message::Message, models::GroupAction},
     };
@@ -292,4 +330,84 @@ mod announcement_tests {
             Some(Announcement::AudioMessageKept)
         ));
     }
+
+    #[test]
+    fn test_announcement_message_pinned() {
+        let mut msg = Message::blank();
+        msg.item_type = 7;
+        msg.group_action_type = 0;
+
+        assert!(matches!(
+            msg.get_announcement(),
+            Some(Announcement::MessagePin(PinAction::Pinned))
+        ));
+    }
+
+    #[test]
+    fn test_announcement_message_unpinned() {
+        let mut msg = Message::blank();
+        msg.item_type = 7;
+        msg.group_action_type = 1;
+
+        assert!(matches!(
+            msg.get_announcement(),
+            Some(Announcement::MessagePin(PinAction::Unpinned))
+        ));
+    }
+}
+
+#[cfg(test)]
+mod pin_tests {
+    use crate::{
+        message_types::variants::PinAction,
+        tables::{messages::Message, table::get_empty_test_connection},
+    };
+
+    const PINNED: &str = "5D3B0C2E-8F1A-4B6C-9D7E-0A1B2C3D4E5F";
+
+    #[test]
+    fn can_resolve_pinned_message() {
+        let db = get_empty_test_connection();
+        db.execute_batch(&format!(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, '{PINNED}', 'Gate code is 1234', 1, 0, 0);
+            INSERT INTO message (ROWID, guid, item_type, group_action_type, associated_message_guid, date, date_read, date_delivered) VALUES
+                (2, 'pin', 7, 0, '{PINNED}', 2, 0, 0);
+            "
+        ))
+        .unwrap();
+
+        let pin = Message::from_guid("pin", &db).unwrap();
+        assert_eq!(pin.pin_action(), Some(PinAction::Pinned));
+        assert!(pin.is_announcement());
+
+        let pinned = pin.pinned_message(&db).unwrap();
+        assert_eq!(pinned.guid, PINNED);
+        assert_eq!(pinned.text.as_deref(), Some("Gate code is 1234"));
+    }
+
+    #[test]
+    fn can_handle_missing_pinned_message() {
+        let db = get_empty_test_connection();
+        let mut pin = Message::blank();
+        pin.item_type = 7;
+        pin.associated_message_guid = Some(PINNED.to_string());
+
+        assert_eq!(pin.pin_action(), Some(PinAction::Pinned));
+        assert!(pin.pinned_message(&db).is_none());
+
+        // The schema may not record which message was pinned
+        pin.associated_message_guid = None;
+        assert!(pin.pinned_message(&db).is_none());
+    }
+
+    #[test]
+    fn cant_get_pin_action_for_normal_message() {
+        let mut msg = Message::blank();
+        msg.associated_message_guid = Some(PINNED.to_string());
+
+        assert_eq!(msg.pin_action(), None);
+        assert!(msg.pinned_message(&get_empty_test_connection()).is_none());
+    }
 }
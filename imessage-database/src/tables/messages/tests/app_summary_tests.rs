@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    const GAME_PIGEON_BUNDLE_ID: &str = "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.gamerdelights.gamepigeon.ext";
+
+    fn game_db() -> rusqlite::Connection {
+        let payload = read(
+            current_dir()
+                .unwrap()
+                .join("test_data/app_message/Game.plist"),
+        )
+        .unwrap();
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered, is_from_me, associated_message_type, balloon_bundle_id, payload_data) VALUES
+                (1, 'game', 0, 0, 0, 0, 0, ?1, ?2),
+                (2, 'other', 0, 0, 0, 0, 0, NULL, ?2)",
+            (GAME_PIGEON_BUNDLE_ID, &payload),
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_summarize_game_balloon() {
+        let db = game_db();
+
+        let message = Message::from_guid("game", &db).unwrap();
+
+        assert_eq!(
+            message.app_summary(&db),
+            Some("GamePigeon: Dots & Boxes — Your move.".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_summarize_other_messages() {
+        let db = game_db();
+
+        let message = Message::from_guid("other", &db).unwrap();
+        assert!(message.app_summary(&db).is_none());
+    }
+}
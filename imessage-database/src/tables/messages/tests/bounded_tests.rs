@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tables::{messages::Message, table::get_empty_test_connection},
+        util::query_context::QueryContext,
+    };
+
+    fn timeline() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'first', 'a', 10, 0, 0),
+                (2, 'second', 'b', 20, 0, 0),
+                (3, 'third', 'c', 30, 0, 0),
+                (4, 'fourth', 'd', 40, 0, 0),
+                (5, 'fifth', 'e', 50, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (2, 3), (1, 4), (2, 5);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn guids(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.guid.as_str()).collect()
+    }
+
+    #[test]
+    fn can_get_last_n() {
+        let db = timeline();
+        let messages = Message::last_n(&db, 3, &QueryContext::default()).unwrap();
+
+        assert_eq!(guids(&messages), vec!["third", "fourth", "fifth"]);
+    }
+
+    #[test]
+    fn can_get_first_n() {
+        let db = timeline();
+        let messages = Message::first_n(&db, 2, &QueryContext::default()).unwrap();
+
+        assert_eq!(guids(&messages), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn can_get_last_n_with_filters() {
+        let db = timeline();
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1]));
+
+        let messages = Message::last_n(&db, 2, &context).unwrap();
+
+        assert_eq!(guids(&messages), vec!["second", "fourth"]);
+    }
+
+    #[test]
+    fn can_get_last_n_messages_in_several_chats() {
+        let db = timeline();
+        db.execute(
+            "INSERT INTO chat_message_join (chat_id, message_id) VALUES (2, 4)",
+            [],
+        )
+        .unwrap();
+
+        let messages = Message::last_n(&db, 3, &QueryContext::default()).unwrap();
+
+        assert_eq!(guids(&messages), vec!["third", "fourth", "fifth"]);
+    }
+
+    #[test]
+    fn can_get_fewer_than_n() {
+        let db = timeline();
+        let messages = Message::first_n(&db, 100, &QueryContext::default()).unwrap();
+
+        assert_eq!(messages.len(), 5);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tables::{messages::Message, table::get_empty_test_connection},
+        util::query_context::QueryContext,
+    };
+
+    #[test]
+    fn can_count_conversational_messages() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, item_type, associated_message_type) VALUES
+                (1, 'a', 'Hello', 1, 0, 0),
+                (2, 'b', 'Hi', 2, 0, 0),
+                (3, 'c', 'How are you?', 3, 0, 0);
+            INSERT INTO message (ROWID, guid, text, date, associated_message_guid, associated_message_type) VALUES
+                (4, 'd', 'Loved \"Hello\"', 4, 'p:0/a', 2000),
+                (5, 'e', 'Removed a like', 5, 'p:0/b', 3001);
+            INSERT INTO message (ROWID, guid, date, item_type, group_title) VALUES (6, 'f', 6, 2, 'New Name');
+            INSERT INTO message (ROWID, guid, date, date_edited, associated_message_type) VALUES (7, 'g', 7, 8, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (1, 6), (2, 7);
+            ",
+        )
+        .unwrap();
+
+        let context = QueryContext::default();
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 7);
+        assert_eq!(Message::count_conversational(&db, &context).unwrap(), 3);
+
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([2]));
+        assert_eq!(Message::count_conversational(&db, &context).unwrap(), 0);
+    }
+
+    #[test]
+    fn can_count_messages_for_chat_identifier() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES
+                (1, 'iMessage;-;+15558675309', '+15558675309'),
+                (2, 'SMS;-;+15558675309', '+15558675309'),
+                (3, 'iMessage;-;bob@example.com', 'bob@example.com');
+            INSERT INTO message (ROWID, guid, text, date) VALUES (1, 'a', 'Hi', 1), (2, 'b', 'Yo', 2), (3, 'c', 'Hey', 3);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2), (3, 3);
+            ",
+        )
+        .unwrap();
+
+        let mut context = QueryContext::default();
+        context.set_chat_identifier(&db, "+15558675309").unwrap();
+        assert_eq!(context.selected_chat_ids, Some(BTreeSet::from([1, 2])));
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 2);
+
+        // An identifier without chats selects nothing instead of everything
+        let mut context = QueryContext::default();
+        context
+            .set_chat_identifier(&db, "nobody@example.com")
+            .unwrap();
+        assert_eq!(context.selected_chat_ids, Some(BTreeSet::new()));
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 0);
+    }
+}
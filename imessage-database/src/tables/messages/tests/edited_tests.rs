@@ -1,10 +1,32 @@
 #[cfg(test)]
 mod tests {
+    use std::{env::current_dir, fs::File};
+
+    use plist::Value;
+
     use crate::{
-        message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
-        tables::messages::Message,
+        message_types::{
+            edited::{EditStatus, EditedEvent, EditedMessage, EditedMessagePart},
+            variants::BalloonProvider,
+        },
+        tables::{messages::Message, table::get_empty_test_connection},
     };
 
+    /// GUID referenced by the second edit in the `EditedToLink` fixture
+    const LINK_GUID: &str = "292BF9C6-C9B8-4827-BE65-6EA1C9B5B384";
+
+    fn edited_to_link() -> Message {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/edited_message/EditedToLink.plist");
+        let plist = Value::from_reader(File::open(plist_path).unwrap()).unwrap();
+
+        let mut m = Message::blank();
+        m.edited_parts = Some(EditedMessage::from_map(&plist).unwrap());
+        m
+    }
+
     #[test]
     fn can_get_fully_unsent_true_single() {
         let mut m = Message::blank();
@@ -164,4 +186,121 @@ mod tests {
 
         assert!(!m.is_fully_unsent());
     }
+
+    fn edited_with_tapback() -> (Message, Message) {
+        let mut m = Message::blank();
+        m.guid = String::from("4A2B9F6C-1D3E-4F5A-8B7C-9D0E1F2A3B4C");
+        m.edited_parts = Some(EditedMessage {
+            parts: vec![
+                EditedMessagePart {
+                    status: EditStatus::Edited,
+                    edit_history: vec![
+                        EditedEvent::new(100, Some("Hello wrold".to_string()), None, None),
+                        EditedEvent::new(300, Some("Hello world".to_string()), None, None),
+                    ],
+                },
+                EditedMessagePart {
+                    status: EditStatus::Unsent,
+                    edit_history: vec![],
+                },
+            ],
+        });
+
+        // Sent between the original message and the edit
+        let mut tapback = Message::blank();
+        tapback.associated_message_type = Some(2000);
+        tapback.date = 200;
+        tapback.associated_message_guid =
+            Some(String::from("p:0/4A2B9F6C-1D3E-4F5A-8B7C-9D0E1F2A3B4C"));
+
+        (m, tapback)
+    }
+
+    #[test]
+    fn can_anchor_tapback_to_edited_part() {
+        let (m, tapback) = edited_with_tapback();
+
+        assert_eq!(m.tapback_anchor(&tapback), Some(0));
+        assert_eq!(
+            m.edited_parts
+                .as_ref()
+                .and_then(|parts| parts.tapback_anchor(0))
+                .and_then(|part| part.edit_history.last())
+                .and_then(|event| event.text.as_deref()),
+            Some("Hello world")
+        );
+    }
+
+    #[test]
+    fn cant_anchor_tapback_to_unsent_part() {
+        let (m, mut tapback) = edited_with_tapback();
+        tapback.associated_message_guid =
+            Some(String::from("p:1/4A2B9F6C-1D3E-4F5A-8B7C-9D0E1F2A3B4C"));
+
+        assert_eq!(m.tapback_anchor(&tapback), None);
+    }
+
+    #[test]
+    fn cant_anchor_tapback_to_other_message() {
+        let (mut m, tapback) = edited_with_tapback();
+        m.guid = String::from("00000000-0000-0000-0000-000000000000");
+
+        assert_eq!(m.tapback_anchor(&tapback), None);
+    }
+
+    #[test]
+    fn can_anchor_tapback_to_unedited_message() {
+        let (mut m, tapback) = edited_with_tapback();
+        m.edited_parts = None;
+
+        assert_eq!(m.tapback_anchor(&tapback), Some(0));
+    }
+
+    #[test]
+    fn can_list_referenced_guids() {
+        let m = edited_to_link();
+
+        let guids: Vec<(usize, &str)> = m
+            .edited_parts
+            .as_ref()
+            .unwrap()
+            .referenced_guids()
+            .collect();
+        assert_eq!(guids, vec![(1, LINK_GUID)]);
+    }
+
+    #[test]
+    fn can_resolve_edit_references() {
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES (7, ?1, 'https://github.com/ReagentX/imessage-exporter/issues/10', 0, 0, 0)",
+            [LINK_GUID],
+        )
+        .unwrap();
+
+        let references = edited_to_link().edit_references(&db).unwrap();
+
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].part_index, 1);
+        assert_eq!(references[0].guid, LINK_GUID);
+        assert_eq!(references[0].message.as_ref().map(|m| m.rowid), Some(7));
+    }
+
+    #[test]
+    fn can_keep_dangling_edit_references() {
+        let db = get_empty_test_connection();
+
+        let references = edited_to_link().edit_references(&db).unwrap();
+
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].guid, LINK_GUID);
+        assert!(references[0].message.is_none());
+    }
+
+    #[test]
+    fn can_skip_edit_references_for_unedited_message() {
+        let db = get_empty_test_connection();
+
+        assert!(Message::blank().edit_references(&db).unwrap().is_empty());
+    }
 }
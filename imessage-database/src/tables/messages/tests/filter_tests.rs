@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tables::{
+            messages::Message,
+            table::{Table, get_empty_test_connection},
+        },
+        util::{
+            predicate::{Predicate, PredicateValue},
+            query_context::QueryContext,
+        },
+    };
+
+    fn engagement_db() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, associated_message_type) VALUES
+                (1, 'loved', 'Hello', 1, 0, 0, 0),
+                (2, 'ignored', 'Hi', 2, 0, 0, 0),
+                (3, 'emphasized', 'How are you?', 3, 0, 0, 0),
+                (4, 'unliked', 'Good', 4, 0, 0, 0);
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (5, 'tapback_1', 'Loved \"Hello\"', 5, 0, 0, 'p:0/loved', 2000),
+                (6, 'tapback_2', 'Emphasized \"How are you?\"', 6, 0, 0, 'bp:emphasized', 2004),
+                (7, 'tapback_3', 'Removed a like from \"Good\"', 7, 0, 0, 'p:0/unliked', 3001);
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, thread_originator_guid, associated_message_type) VALUES
+                (8, 'reply', 'Not bad', 8, 0, 0, 'unliked', 0);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn selected_guids(db: &rusqlite::Connection, context: &QueryContext) -> Vec<String> {
+        let mut statement = Message::stream_rows(db, context).unwrap();
+        let mut guids: Vec<String> = statement
+            .query_map(
+                rusqlite::params_from_iter(context.predicate_params()),
+                |row| Ok(Message::from_row(row)),
+            )
+            .unwrap()
+            .map(|message| Message::extract(message).unwrap().guid)
+            .collect();
+        guids.sort();
+        guids
+    }
+
+    #[test]
+    fn can_select_messages_with_tapbacks() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.set_only_with_tapbacks(true);
+
+        assert_eq!(selected_guids(&db, &context), vec!["emphasized", "loved"]);
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 2);
+    }
+
+    #[test]
+    fn cant_select_messages_whose_tapbacks_were_removed() {
+        let db = engagement_db();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type) VALUES
+                (9, 'unloved', 'Bye', 1, 0, 9, 0, 0, 0),
+                (10, 'reloved', 'See you', 1, 0, 10, 0, 0, 0);
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (11, 'tapback_4', 'Loved \"Bye\"', 2, 0, 11, 0, 0, 'p:0/unloved', 2000),
+                (12, 'tapback_5', 'Removed a heart from \"Bye\"', 2, 0, 12, 0, 0, 'p:0/unloved', 3000),
+                (13, 'tapback_6', 'Loved \"See you\"', 2, 0, 13, 0, 0, 'p:0/reloved', 2000),
+                (14, 'tapback_7', 'Removed a heart from \"See you\"', 2, 0, 14, 0, 0, 'p:0/reloved', 3000),
+                (15, 'tapback_8', 'Loved \"See you\"', 2, 0, 15, 0, 0, 'p:0/reloved', 2000),
+                (16, 'tapback_9', 'Removed a heart from \"Hello\"', 3, 0, 16, 0, 0, 'p:0/loved', 3000);
+            ",
+        )
+        .unwrap();
+        let mut context = QueryContext::default();
+        context.set_only_with_tapbacks(true);
+
+        // A removal only cancels the same sender's earlier tapback, and a tapback added again counts
+        assert_eq!(
+            selected_guids(&db, &context),
+            vec!["emphasized", "loved", "reloved"]
+        );
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 3);
+    }
+
+    #[test]
+    fn can_select_messages_with_replies() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.set_only_with_replies(true);
+
+        assert_eq!(selected_guids(&db, &context), vec!["unliked"]);
+    }
+
+    #[test]
+    fn can_combine_engagement_filters() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.set_only_with_tapbacks(true);
+        context.set_only_with_replies(true);
+
+        assert!(selected_guids(&db, &context).is_empty());
+    }
+
+    #[test]
+    fn can_select_messages_since_rowid() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.set_since_rowid(6);
+
+        assert_eq!(selected_guids(&db, &context), vec!["reply", "tapback_3"]);
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 2);
+        assert_eq!(
+            Message::generate_filter_statement(&context, false),
+            "WHERE m.ROWID > 6"
+        );
+    }
+
+    #[test]
+    fn can_combine_since_rowid_with_filters() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.set_since_rowid(2);
+        context.set_only_with_tapbacks(true);
+
+        assert_eq!(selected_guids(&db, &context), vec!["emphasized"]);
+        assert!(Message::generate_filter_statement(&context, false).ends_with(" AND m.ROWID > 2"));
+    }
+
+    #[test]
+    fn can_generate_predicate_statement() {
+        let mut context = QueryContext::default();
+        context.set_since_rowid(2);
+        context.add_predicate(Predicate::eq("associated_message_type", 0).unwrap());
+        context.add_predicate(Predicate::is_in("guid", ["loved", "reply"]).unwrap());
+        context.add_predicate(Predicate::is_null("thread_originator_guid").unwrap());
+
+        assert_eq!(
+            Message::generate_filter_statement(&context, false),
+            "WHERE m.ROWID > 2 AND m.associated_message_type = ?1 AND m.guid IN (?2, ?3) AND m.thread_originator_guid IS NULL"
+        );
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![
+                PredicateValue::Int(0),
+                PredicateValue::from("loved"),
+                PredicateValue::from("reply")
+            ]
+        );
+    }
+
+    #[test]
+    fn can_select_messages_with_predicates() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.add_predicate(Predicate::eq("associated_message_type", 0).unwrap());
+        context.add_predicate(Predicate::like("text", "H%").unwrap());
+
+        assert_eq!(
+            selected_guids(&db, &context),
+            vec!["emphasized", "ignored", "loved"]
+        );
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 3);
+    }
+
+    #[test]
+    fn can_iterate_messages_with_predicates() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.add_predicate(Predicate::gt("associated_message_type", 1000).unwrap());
+        context.add_predicate(Predicate::not_eq("guid", "tapback_2").unwrap());
+
+        let guids: Vec<String> = Message::iter(&db, &context)
+            .unwrap()
+            .map(|message| message.unwrap().guid)
+            .collect();
+        assert_eq!(guids, vec!["tapback_3", "tapback_1"]);
+    }
+
+    #[test]
+    fn cant_inject_through_predicate_values() {
+        let db = engagement_db();
+        let mut context = QueryContext::default();
+        context.add_predicate(Predicate::eq("guid", "loved' OR '1' = '1").unwrap());
+
+        assert!(selected_guids(&db, &context).is_empty());
+    }
+}
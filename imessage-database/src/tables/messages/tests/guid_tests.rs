@@ -84,3 +84,75 @@ mod tests {
         );
     }
 }
+
+#[cfg(test)]
+mod strict_tests {
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    #[test]
+    fn can_try_get_valid_guid_p() {
+        let mut m = Message::blank();
+        m.associated_message_guid = Some("p:2/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        assert_eq!(
+            (2usize, "A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A"),
+            m.try_clean_associated_guid().unwrap()
+        );
+    }
+
+    #[test]
+    fn can_try_get_valid_guid_bp() {
+        let mut m = Message::blank();
+        m.associated_message_guid = Some("bp:A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        assert_eq!(
+            (0usize, "A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A"),
+            m.try_clean_associated_guid().unwrap()
+        );
+    }
+
+    #[test]
+    fn cant_try_get_malformed_index_p() {
+        let mut m = Message::blank();
+        m.associated_message_guid = Some("p:x/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        // The lenient parser silently attaches the tapback to the first part
+        assert_eq!(
+            Some((0usize, "A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A")),
+            m.clean_associated_guid()
+        );
+        assert!(m.try_clean_associated_guid().is_err());
+    }
+
+    #[test]
+    fn cant_try_get_missing_separator_p() {
+        let mut m = Message::blank();
+        m.associated_message_guid = Some("p:1A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        assert!(m.try_clean_associated_guid().is_err());
+    }
+
+    #[test]
+    fn cant_try_get_missing_guid() {
+        let m = Message::blank();
+
+        assert!(m.try_clean_associated_guid().is_err());
+    }
+
+    #[test]
+    fn can_count_malformed_associations() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, associated_message_guid) VALUES
+                (1, 'a', 'p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A'),
+                (2, 'b', 'p:x/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A'),
+                (3, 'c', 'bp:FAKE'),
+                (4, 'd', NULL);
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(Message::count_malformed_associations(&db).unwrap(), 2);
+    }
+}
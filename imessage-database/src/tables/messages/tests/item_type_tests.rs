@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::messages::{
+        Message,
+        models::{GroupAction, ItemType},
+    };
+
+    #[test]
+    fn can_map_known_item_types() {
+        assert_eq!(ItemType::from(0), ItemType::Normal);
+        assert_eq!(ItemType::from(1), ItemType::GroupMembership);
+        assert_eq!(ItemType::from(2), ItemType::GroupRename);
+        assert_eq!(ItemType::from(3), ItemType::GroupEvent);
+        assert_eq!(ItemType::from(4), ItemType::LocationShare);
+        assert_eq!(ItemType::from(5), ItemType::KeptAudio);
+        assert_eq!(ItemType::from(6), ItemType::SharePlay);
+        assert_eq!(ItemType::from(7), ItemType::MessagePin);
+    }
+
+    #[test]
+    fn can_map_unknown_item_type() {
+        assert_eq!(ItemType::from(42), ItemType::Unknown(42));
+        assert_eq!(ItemType::from(-1), ItemType::Unknown(-1));
+    }
+
+    #[test]
+    fn can_get_item_kind() {
+        let mut m = Message::blank();
+        assert_eq!(m.item_kind(), ItemType::Normal);
+
+        m.item_type = 5;
+        assert_eq!(m.item_kind(), ItemType::KeptAudio);
+        assert!(m.is_kept_audio_message());
+
+        m.item_type = 6;
+        assert!(m.is_shareplay());
+        assert!(!m.is_kept_audio_message());
+    }
+
+    #[test]
+    fn can_get_location_sharing() {
+        let mut m = Message::blank();
+        m.item_type = 4;
+
+        assert!(m.started_sharing_location());
+        assert!(!m.stopped_sharing_location());
+
+        m.share_status = true;
+        assert!(!m.started_sharing_location());
+        assert!(m.stopped_sharing_location());
+    }
+
+    #[test]
+    fn can_get_group_action_from_item_type() {
+        let mut m = Message::blank();
+        m.item_type = 1;
+        m.other_handle = Some(3);
+        assert!(matches!(
+            m.group_action(),
+            Some(GroupAction::ParticipantAdded(3))
+        ));
+
+        m.item_type = 3;
+        m.group_action_type = 2;
+        assert!(matches!(
+            m.group_action(),
+            Some(GroupAction::GroupIconRemoved)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use crate::{
+        tables::{messages::Message, table::get_empty_test_connection},
+        util::query_context::QueryContext,
+    };
+
+    fn database() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'one', 'a', 10, 0, 0),
+                (2, 'two', 'b', 20, 0, 0),
+                (3, 'three', 'c', 20, 0, 0),
+                (4, 'four', 'd', 30, 0, 0),
+                (5, 'five', 'e', 40, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (2, 4), (1, 5);
+            INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date) VALUES (1, 5, 50);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn guids(db: &rusqlite::Connection, context: &QueryContext, page_size: usize) -> Vec<String> {
+        let mut iter = Message::iter(db, context).unwrap();
+        iter.page_size = page_size;
+        iter.map(|message| message.unwrap().guid).collect()
+    }
+
+    #[test]
+    fn can_iterate_newest_first() {
+        let db = database();
+
+        // `five` is joined to a chat and the recently deleted collection, but is only yielded once
+        assert_eq!(
+            guids(&db, &QueryContext::default(), 500),
+            vec!["five", "four", "three", "two", "one"]
+        );
+    }
+
+    #[test]
+    fn can_iterate_across_pages() {
+        let db = database();
+
+        // Messages sent at the same time are split across pages without being repeated or skipped
+        for page_size in 1..=3 {
+            assert_eq!(
+                guids(&db, &QueryContext::default(), page_size),
+                vec!["five", "four", "three", "two", "one"]
+            );
+        }
+    }
+
+    #[test]
+    fn can_iterate_with_filters() {
+        let db = database();
+
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids([1].into());
+        context.limit = Some(3);
+
+        assert_eq!(guids(&db, &context, 2), vec!["five", "three", "two"]);
+    }
+
+    #[test]
+    fn can_iterate_with_offset() {
+        let db = database();
+
+        // The offset skips messages, so `five` only takes up one of the skipped slots
+        let mut context = QueryContext::default();
+        context.set_page(1, 2);
+        for page_size in 1..=3 {
+            assert_eq!(guids(&db, &context, page_size), vec!["three", "two"]);
+        }
+    }
+
+    #[test]
+    fn can_skip_generating_text() {
+        let typedstream = read(
+            current_dir()
+                .unwrap()
+                .join("test_data/typedstream/AttributedBodyTextOnly"),
+        )
+        .unwrap();
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, text, attributedBody, date, date_read, date_delivered) VALUES (1, 'one', NULL, ?1, 10, 0, 0)",
+            [&typedstream],
+        )
+        .unwrap();
+
+        let with_text: Vec<Message> = Message::iter(&db, &QueryContext::default())
+            .unwrap()
+            .with_text(true)
+            .map(Result::unwrap)
+            .collect();
+        let without_text: Vec<Message> = Message::iter(&db, &QueryContext::default())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(with_text.len(), 1);
+        assert_eq!(without_text.len(), 1);
+        assert!(with_text[0].text.is_some());
+        assert!(without_text[0].text.is_none());
+    }
+}
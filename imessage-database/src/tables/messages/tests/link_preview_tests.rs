@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{
+        attachment::Attachment, messages::Message, table::get_empty_test_connection,
+    };
+
+    fn link_preview_db() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, balloon_bundle_id) VALUES
+                (1, 'link', 'https://example.com', 1, 0, 0, 'com.apple.messages.URLBalloonProvider'),
+                (2, 'photo', NULL, 2, 0, 0, NULL);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes, is_sticker, hide_attachment) VALUES
+                (1, 'a1', 'a1', 'a/preview.pluginPayloadAttachment', 'image/jpeg', 'preview.pluginPayloadAttachment', 10, 0, 1),
+                (2, 'a2', 'a2', 'a/shared.png', 'image/png', 'shared.png', 10, 0, 0),
+                (3, 'a3', 'a3', 'a/photo.png', 'image/png', 'photo.png', 10, 0, 1);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1), (1, 2), (2, 3);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn message(db: &rusqlite::Connection, guid: &str) -> Message {
+        let mut message = Message::from_guid(guid, db).unwrap();
+        message.num_attachments = 1;
+        message
+    }
+
+    #[test]
+    fn can_take_link_preview_image() {
+        let db = link_preview_db();
+        let message = message(&db, "link");
+        let mut attachments = Attachment::from_message(&db, &message).unwrap();
+
+        let preview = message.take_link_preview_image(&mut attachments).unwrap();
+
+        assert_eq!(preview.rowid, 1);
+        assert!(preview.is_hidden());
+        // Only the media the sender shared is left
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].rowid, 2);
+    }
+
+    #[test]
+    fn cant_take_link_preview_image_from_other_messages() {
+        let db = link_preview_db();
+        let message = message(&db, "photo");
+        let mut attachments = Attachment::from_message(&db, &message).unwrap();
+
+        assert!(message.take_link_preview_image(&mut attachments).is_none());
+        assert_eq!(attachments.len(), 1);
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File, io::Read};
+
+    use crate::{
+        tables::messages::{Message, models::Mention},
+        util::typedstream::parser::TypedStreamReader,
+    };
+
+    fn mention_message(text: &str) -> Message {
+        let mut m = Message::blank();
+        m.text = Some(text.to_string());
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        m.components = parser.parse().ok();
+        m
+    }
+
+    #[test]
+    fn can_get_mentions() {
+        let m = mention_message("Test Dad ");
+        assert_eq!(
+            m.mentions(),
+            vec![Mention {
+                range: 5..8,
+                handle: "+15558675309".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn can_get_mentions_after_multibyte_text() {
+        let text = "Tést Dàd ";
+        let m = mention_message(text);
+        let mentions = m.mentions();
+
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].range, 5..8);
+        let mentioned: String = text
+            .chars()
+            .skip(mentions[0].range.start)
+            .take(mentions[0].range.len())
+            .collect();
+        assert_eq!(mentioned, "Dàd");
+    }
+
+    #[test]
+    fn cant_get_mentions_without_mention() {
+        let mut m = Message::blank();
+        m.text = Some("Hello world".to_string());
+        assert!(m.mentions().is_empty());
+    }
+
+    #[test]
+    fn cant_get_mentions_missing_text() {
+        let m = Message::blank();
+        assert!(m.mentions().is_empty());
+    }
+}
@@ -1,7 +1,29 @@
 mod announcement;
+mod app_summary_tests;
+mod bounded_tests;
+mod count_tests;
 mod date_tests;
 mod edited_tests;
 mod expressive_tests;
+mod filter_tests;
 mod guid_tests;
+mod item_type_tests;
+mod iter_tests;
+mod link_preview_tests;
+mod mention_tests;
+mod otp_tests;
+mod page_tests;
+mod payment_tests;
+mod poll_tests;
 mod query_tests;
+mod raw_tests;
+mod rendered_parts_tests;
+mod reply_context_tests;
+mod reply_thread_tests;
+mod sequence_tests;
+mod service_tests;
+mod sticker_overlay_tests;
+mod tapback_count_tests;
+mod tapback_range_tests;
 mod variant;
+mod voice_tests;
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File, io::Read};
+
+    use crate::{tables::messages::Message, util::typedstream::parser::TypedStreamReader};
+
+    fn code_message() -> Message {
+        let mut m = Message::blank();
+        m.text = Some("000123 is your security code. Don't share your code.".to_string());
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Code");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        m.components = parser.parse().ok();
+        m
+    }
+
+    #[test]
+    fn can_redact_one_time_code() {
+        let m = code_message();
+        assert_eq!(
+            m.text_with_redacted_codes("[one-time code]"),
+            Some("[one-time code] is your security code. Don't share your code.".to_string())
+        );
+    }
+
+    #[test]
+    fn can_preserve_text_without_code() {
+        let mut m = Message::blank();
+        m.text = Some("Hello world".to_string());
+        assert_eq!(
+            m.text_with_redacted_codes("[one-time code]"),
+            Some("Hello world".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_redact_missing_text() {
+        let m = Message::blank();
+        assert_eq!(m.text_with_redacted_codes("[one-time code]"), None);
+    }
+}
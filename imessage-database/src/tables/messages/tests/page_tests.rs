@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tables::{
+            messages::{Message, models::PageCursor},
+            table::{Table, get_empty_test_connection},
+        },
+        util::query_context::QueryContext,
+    };
+
+    fn conversation() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'one', 'a', 10, 0, 0),
+                (2, 'two', 'b', 20, 0, 0),
+                (3, 'three', 'c', 20, 0, 0),
+                (4, 'other chat', 'd', 30, 0, 0),
+                (5, 'four', 'e', 40, 0, 0),
+                (6, 'five', 'f', 50, 0, 0),
+                (7, 'deleted', 'g', 60, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (2, 4), (1, 5), (1, 6);
+            INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date) VALUES (1, 7, 70);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn guids(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.guid.as_str()).collect()
+    }
+
+    #[test]
+    fn can_page_newest_first() {
+        let db = conversation();
+
+        let first = Message::page_before(&db, 1, None, 3).unwrap();
+        assert_eq!(guids(&first.messages), vec!["deleted", "five", "four"]);
+        assert_eq!(first.cursor, Some(PageCursor { date: 40, rowid: 5 }));
+
+        // Messages sent at the same time are split across pages without being repeated or skipped
+        let second = Message::page_before(&db, 1, first.cursor, 2).unwrap();
+        assert_eq!(guids(&second.messages), vec!["three", "two"]);
+
+        let third = Message::page_before(&db, 1, second.cursor, 2).unwrap();
+        assert_eq!(guids(&third.messages), vec!["one"]);
+        assert_eq!(third.cursor, None);
+    }
+
+    #[test]
+    fn can_page_through_whole_chat() {
+        let db = conversation();
+
+        let mut seen = vec![];
+        let mut cursor = None;
+        loop {
+            let page = Message::page_before(&db, 1, cursor, 2).unwrap();
+            seen.extend(page.messages.into_iter().map(|m| m.rowid));
+            match page.cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen, vec![7, 6, 5, 3, 2, 1]);
+    }
+
+    #[test]
+    fn can_fill_page_with_messages_joined_twice() {
+        let db = conversation();
+        db.execute(
+            "INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 7)",
+            [],
+        )
+        .unwrap();
+
+        // The deleted message is still in the chat, but only takes up one slot on the page
+        let page = Message::page_before(&db, 1, None, 3).unwrap();
+        assert_eq!(guids(&page.messages), vec!["deleted", "five", "four"]);
+        assert_eq!(page.cursor, Some(PageCursor { date: 40, rowid: 5 }));
+    }
+
+    #[test]
+    fn can_page_empty_chat() {
+        let db = conversation();
+        let page = Message::page_before(&db, 3, None, 10).unwrap();
+
+        assert!(page.messages.is_empty());
+        assert_eq!(page.cursor, None);
+    }
+
+    fn offset_page(db: &rusqlite::Connection, page: u64, page_size: i32) -> Vec<i32> {
+        let mut context = QueryContext::default();
+        context.set_page(page, page_size);
+        context.set_selected_chat_ids(BTreeSet::from([1]));
+
+        let mut statement = Message::stream_rows(db, &context).unwrap();
+        statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap()
+            .map(|message| Message::extract(message).unwrap().rowid)
+            .collect()
+    }
+
+    #[test]
+    fn can_page_with_offset() {
+        let db = conversation();
+
+        // Messages sent at the same time are ordered by ROWID, so pages do not overlap
+        assert_eq!(offset_page(&db, 0, 2), vec![7, 6]);
+        assert_eq!(offset_page(&db, 1, 2), vec![5, 3]);
+        assert_eq!(offset_page(&db, 2, 2), vec![2, 1]);
+        assert!(offset_page(&db, 3, 2).is_empty());
+    }
+
+    #[test]
+    fn can_page_with_offset_messages_joined_twice() {
+        let db = conversation();
+        db.execute(
+            "INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 7)",
+            [],
+        )
+        .unwrap();
+
+        // The deleted message is still in the chat, but only takes up one slot on the first page
+        assert_eq!(offset_page(&db, 0, 2), vec![7, 6]);
+        assert_eq!(offset_page(&db, 1, 2), vec![5, 3]);
+        assert_eq!(offset_page(&db, 2, 2), vec![2, 1]);
+    }
+
+    #[test]
+    fn can_generate_limit_statement_offset() {
+        let mut context = QueryContext::default();
+        context.set_page(3, 20);
+        assert_eq!(
+            Message::generate_limit_statement(&context),
+            "LIMIT 20 OFFSET 60"
+        );
+
+        let context = QueryContext {
+            offset: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            Message::generate_limit_statement(&context),
+            "LIMIT -1 OFFSET 5"
+        );
+
+        let mut context = QueryContext::default();
+        context.set_limit(10);
+        assert_eq!(Message::generate_limit_statement(&context), "LIMIT 10");
+    }
+}
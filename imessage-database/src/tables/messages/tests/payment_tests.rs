@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use crate::{
+        message_types::payment::PaymentDirection,
+        tables::{messages::Message, table::get_empty_test_connection},
+    };
+
+    const APPLE_PAY_BUNDLE_ID: &str = "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.PassbookUIService.PeerPaymentMessagesExtension";
+
+    fn payment_db() -> rusqlite::Connection {
+        let payload = read(
+            current_dir()
+                .unwrap()
+                .join("test_data/app_message/Sent265.plist"),
+        )
+        .unwrap();
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered, is_from_me, associated_message_type, balloon_bundle_id, payload_data) VALUES
+                (1, 'sent', 0, 0, 0, 1, 0, ?1, ?2),
+                (2, 'received', 0, 0, 0, 0, 0, ?1, ?2),
+                (3, 'other', 0, 0, 0, 0, 0, NULL, ?2)",
+            (APPLE_PAY_BUNDLE_ID, &payload),
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_get_sent_payment() {
+        let db = payment_db();
+
+        let message = Message::from_guid("sent", &db).unwrap();
+        let payment = message.apple_pay(&db).unwrap();
+
+        assert_eq!(payment.direction, PaymentDirection::Send);
+        assert_eq!(payment.to_string(), "Sent $265.00");
+    }
+
+    #[test]
+    fn can_get_received_payment() {
+        let db = payment_db();
+
+        let message = Message::from_guid("received", &db).unwrap();
+        let payment = message.apple_pay(&db).unwrap();
+
+        assert_eq!(payment.direction, PaymentDirection::Receive);
+        assert_eq!(payment.to_string(), "Received $265.00");
+    }
+
+    #[test]
+    fn cant_get_payment_from_other_messages() {
+        let db = payment_db();
+
+        let message = Message::from_guid("other", &db).unwrap();
+        assert!(message.apple_pay(&db).is_none());
+    }
+}
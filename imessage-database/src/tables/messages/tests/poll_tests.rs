@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    const POLL_BUNDLE_ID: &str =
+        "com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.messages.Polls";
+
+    fn poll_payload() -> Vec<u8> {
+        let mut tacos = Dictionary::new();
+        tacos.insert("text".to_string(), Value::String("Tacos".to_string()));
+        tacos.insert(
+            "voters".to_string(),
+            Value::Array(vec![
+                Value::String("+15558675309".to_string()),
+                Value::String("person@example.com".to_string()),
+            ]),
+        );
+
+        let mut pizza = Dictionary::new();
+        pizza.insert("text".to_string(), Value::String("Pizza".to_string()));
+
+        let mut poll = Dictionary::new();
+        poll.insert("question".to_string(), Value::String("Dinner?".to_string()));
+        poll.insert(
+            "options".to_string(),
+            Value::Array(vec![Value::Dictionary(tacos), Value::Dictionary(pizza)]),
+        );
+
+        let mut buf = vec![];
+        Value::Dictionary(poll).to_writer_binary(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn can_get_poll_from_payload() {
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered, associated_message_type, balloon_bundle_id, payload_data)
+             VALUES (1, 'poll', 0, 0, 0, 0, ?1, ?2)",
+            (POLL_BUNDLE_ID, poll_payload()),
+        )
+        .unwrap();
+
+        let message = Message::from_guid("poll", &db).unwrap();
+        let poll = message.poll(&db).unwrap();
+
+        assert_eq!(poll.question.as_deref(), Some("Dinner?"));
+        assert_eq!(poll.options.len(), 2);
+        assert_eq!(poll.options[0].text, "Tacos");
+        assert_eq!(poll.options[0].voters.len(), 2);
+        assert_eq!(poll.options[1].text, "Pizza");
+        assert!(poll.options[1].voters.is_empty());
+    }
+
+    #[test]
+    fn cant_get_poll_from_invalid_payload() {
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered, associated_message_type, balloon_bundle_id, payload_data)
+             VALUES (1, 'poll', 0, 0, 0, 0, ?1, X'DEADBEEF')",
+            [POLL_BUNDLE_ID],
+        )
+        .unwrap();
+
+        let message = Message::from_guid("poll", &db).unwrap();
+        assert!(message.poll(&db).is_none());
+    }
+
+    #[test]
+    fn cant_get_poll_from_normal_message() {
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered, associated_message_type, payload_data)
+             VALUES (1, 'normal', 0, 0, 0, 0, ?1)",
+            [poll_payload()],
+        )
+        .unwrap();
+
+        let message = Message::from_guid("normal", &db).unwrap();
+        assert!(message.poll(&db).is_none());
+    }
+}
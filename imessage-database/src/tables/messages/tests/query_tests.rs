@@ -37,10 +37,7 @@ mod exclude_recoverable_tests {
         context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
 
         let statement = Message::generate_filter_statement(&context, false);
-        assert_eq!(
-            statement,
-            "WHERE c.chat_id IN (1, 2, 3)"
-        );
+        assert_eq!(statement, "WHERE c.chat_id IN (1, 2, 3)");
     }
 
     #[test]
@@ -167,15 +164,13 @@ mod guid_query_tests {
 
 #[cfg(test)]
 mod query_string_tests {
-    use crate::{
-        tables::messages::query_parts,
-    };
+    use crate::tables::messages::query_parts;
 
     #[test]
     fn can_generate_no_filters_16() {
         let query_string = query_parts::ios_16_newer_query(None, Some("LIMIT 10"));
         let expected = "\nSELECT
-    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, associated_message_range_location, associated_message_range_length, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji, is_audio_message, is_played,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     d.chat_id as deleted_from,
@@ -187,15 +182,16 @@ LEFT JOIN chat_recoverable_message_join as d ON m.ROWID = d.message_id
 
 ORDER BY
     m.date DESC
-LIMIT 10;"; 
+LIMIT 10;";
         assert_eq!(query_string, expected);
     }
 
     #[test]
     fn can_generate_filters_16() {
-        let query_string = query_parts::ios_16_newer_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let query_string =
+            query_parts::ios_16_newer_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
         let expected = "\nSELECT
-    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji,
+    rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, associated_message_range_location, associated_message_range_length, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, associated_message_emoji, is_audio_message, is_played,
     c.chat_id,
     (SELECT COUNT(*) FROM message_attachment_join a WHERE m.ROWID = a.message_id) as num_attachments,
     d.chat_id as deleted_from,
@@ -233,7 +229,8 @@ ORDER BY
 
     #[test]
     fn can_generate_filters_14_15() {
-        let query_string = query_parts::ios_14_15_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let query_string =
+            query_parts::ios_14_15_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
         let expected = "\nSELECT
     *,
     c.chat_id,
@@ -272,7 +269,8 @@ LIMIT 10;";
 
     #[test]
     fn can_generate_filters_13() {
-        let query_string = query_parts::ios_13_older_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
+        let query_string =
+            query_parts::ios_13_older_query(Some("WHERE m.guid = \"fake\""), Some("LIMIT 10"));
         let expected = "\nSELECT
     *,
     c.chat_id,
@@ -289,3 +287,285 @@ LIMIT 10;";
         assert_eq!(query_string, expected);
     }
 }
+
+#[cfg(test)]
+mod on_this_day_tests {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        tables::{
+            messages::Message,
+            table::{Table, get_empty_test_connection},
+        },
+        util::query_context::QueryContext,
+    };
+
+    #[test]
+    fn can_generate_filter_statement_on_this_day() {
+        let mut context = QueryContext::default();
+        context.set_on_this_day(5, 17).unwrap();
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE strftime('%m-%d', m.date / 1000000000 + 978307200, 'unixepoch', 'localtime') = '05-17'"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_on_this_day_chat_ids() {
+        let mut context = QueryContext::default();
+        context.set_on_this_day(1, 2).unwrap();
+        context.set_selected_chat_ids(BTreeSet::from([1, 2]));
+
+        let statement = Message::generate_filter_statement(&context, true);
+        assert_eq!(
+            statement,
+            "WHERE (c.chat_id IN (1, 2) OR d.chat_id IN (1, 2)) AND strftime('%m-%d', m.date / 1000000000 + 978307200, 'unixepoch', 'localtime') = '01-02'"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_on_this_day_utc_offset() {
+        let mut context = QueryContext::default();
+        context.set_on_this_day(5, 17).unwrap();
+        context.set_utc_offset(-14400);
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE strftime('%m-%d', m.date / 1000000000 + 978307200 + -14400, 'unixepoch') = '05-17'"
+        );
+    }
+
+    #[test]
+    fn can_select_messages_on_this_day_utc_offset() {
+        let db = get_empty_test_connection();
+        // 2021-05-17 02:00 UTC is still May 16th in New York, and 2021-05-17 23:00 UTC is May 18th in Tokyo
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, date, date_read, date_delivered) VALUES
+                (1, 'early', 642909600000000000, 0, 0),
+                (2, 'late', 642985200000000000, 0, 0);
+            ",
+        )
+        .unwrap();
+        let guids = |utc_offset: i32| -> Vec<String> {
+            let mut context = QueryContext::default();
+            context.set_on_this_day(5, 17).unwrap();
+            context.set_utc_offset(utc_offset);
+            let mut statement = Message::stream_rows(&db, &context).unwrap();
+            statement
+                .query_map([], |row| Ok(Message::from_row(row)))
+                .unwrap()
+                .map(|message| Message::extract(message).unwrap().guid)
+                .collect()
+        };
+
+        assert_eq!(guids(0), vec!["late", "early"]);
+        assert_eq!(guids(-4 * 3600), vec!["late"]);
+        assert_eq!(guids(9 * 3600), vec!["early"]);
+    }
+
+    #[test]
+    fn can_select_messages_on_this_day() {
+        let db = get_empty_test_connection();
+        // Dates are at noon UTC so the local calendar day matches in most time zones
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, date, date_read, date_delivered) VALUES
+                (1, '2021-05-17', 642945600000000000, 0, 0),
+                (2, '2022-05-17', 674481600000000000, 0, 0),
+                (3, '2022-05-18', 674568000000000000, 0, 0),
+                (4, '2021-06-17', 645624000000000000, 0, 0);
+            ",
+        )
+        .unwrap();
+
+        let mut context = QueryContext::default();
+        context.set_on_this_day(5, 17).unwrap();
+
+        let mut statement = Message::stream_rows(&db, &context).unwrap();
+        let guids: Vec<String> = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap()
+            .map(|message| Message::extract(message).unwrap().guid)
+            .collect();
+
+        assert_eq!(guids, vec!["2022-05-17", "2021-05-17"]);
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use crate::{
+        tables::{messages::Message, table::get_empty_test_connection},
+        util::{
+            predicate::{Predicate, PredicateValue},
+            query_context::QueryContext,
+        },
+    };
+
+    fn search_db() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'dinner', 'Dinner at 8?', 1, 0, 0),
+                (2, 'percent', '100% sure', 2, 0, 0),
+                (3, 'digits', '1000 sure', 3, 0, 0),
+                (4, 'quote', 'It''s dinner time', 4, 0, 0),
+                (5, 'body_only', NULL, 5, 0, 0);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn searched_guids(db: &rusqlite::Connection, term: &str) -> Vec<String> {
+        let mut context = QueryContext::default();
+        context.set_search(term);
+        Message::iter(db, &context)
+            .unwrap()
+            .map(|message| message.unwrap().guid)
+            .collect()
+    }
+
+    #[test]
+    fn can_generate_filter_statement_search() {
+        let mut context = QueryContext::default();
+        context.set_search("50%_off 'now' \\");
+        context.add_predicate(Predicate::eq("is_from_me", 1).unwrap());
+
+        // The search term is bound as the first parameter, before the predicates
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE m.text LIKE ?1 ESCAPE '\\' AND m.is_from_me = ?2"
+        );
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![
+                PredicateValue::from("%50\\%\\_off 'now' \\\\%"),
+                PredicateValue::Int(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn can_search_text() {
+        let db = search_db();
+
+        assert_eq!(searched_guids(&db, "dinner"), vec!["quote", "dinner"]);
+        let mut context = QueryContext::default();
+        context.set_search("dinner");
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 2);
+    }
+
+    #[test]
+    fn can_search_special_characters_literally() {
+        let db = search_db();
+
+        assert_eq!(searched_guids(&db, "0%"), vec!["percent"]);
+        assert_eq!(searched_guids(&db, "It's"), vec!["quote"]);
+    }
+
+    #[test]
+    fn cant_search_null_text() {
+        let db = search_db();
+
+        // Every message with text contains a space, but `body_only` has no `text` to match
+        assert_eq!(
+            searched_guids(&db, " "),
+            vec!["quote", "digits", "percent", "dinner"]
+        );
+        let mut context = QueryContext::default();
+        context.set_search(" ");
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 4);
+    }
+}
+
+#[cfg(test)]
+mod date_range_tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::{
+        tables::{messages::Message, table::get_empty_test_connection},
+        util::{
+            predicate::{Predicate, PredicateValue},
+            query_context::QueryContext,
+        },
+    };
+
+    #[test]
+    fn can_generate_filter_statement_date_range() {
+        let context = QueryContext {
+            start: Some(100),
+            end: Some(200),
+            ..Default::default()
+        };
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(statement, "WHERE m.date >= ?1 AND m.date <= ?2");
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![PredicateValue::Int(100), PredicateValue::Int(200)]
+        );
+    }
+
+    #[test]
+    fn can_bind_date_range_after_search_term() {
+        let mut context = QueryContext {
+            end: Some(200),
+            ..Default::default()
+        };
+        context.set_search("dinner");
+        context.add_predicate(Predicate::eq("is_from_me", 1).unwrap());
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE m.text LIKE ?1 ESCAPE '\\' AND m.date <= ?2 AND m.is_from_me = ?3"
+        );
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![
+                PredicateValue::Text(String::from("%dinner%")),
+                PredicateValue::Int(200),
+                PredicateValue::Int(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn can_select_messages_in_date_range() {
+        let db = get_empty_test_connection();
+        // Midnight local time on 2021-05-17, 2021-05-18, and 2021-05-19
+        let day = |day| {
+            let mut context = QueryContext::default();
+            context.set_start(&Local.with_ymd_and_hms(2021, 5, day, 0, 0, 0).unwrap());
+            context.start.unwrap()
+        };
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, date_read, date_delivered) VALUES
+                (1, 'before', ?1 - 1, 0, 0),
+                (2, 'start', ?1, 0, 0),
+                (3, 'middle', ?2, 0, 0),
+                (4, 'end', ?3, 0, 0),
+                (5, 'after', ?3 + 1, 0, 0)",
+            [day(17), day(18), day(19)],
+        )
+        .unwrap();
+
+        let mut context = QueryContext::default();
+        context.set_start(&Local.with_ymd_and_hms(2021, 5, 17, 0, 0, 0).unwrap());
+        context.set_end(&Local.with_ymd_and_hms(2021, 5, 19, 0, 0, 0).unwrap());
+        let guids: Vec<String> = Message::iter(&db, &context)
+            .unwrap()
+            .map(|message| message.unwrap().guid)
+            .collect();
+
+        assert_eq!(guids, vec!["end", "middle", "start"]);
+        assert_eq!(Message::get_count(&db, &context).unwrap(), 3);
+    }
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    fn fixture(path: &str) -> Vec<u8> {
+        read(current_dir().unwrap().join("test_data").join(path)).unwrap()
+    }
+
+    #[test]
+    fn can_get_raw_blobs() {
+        let typedstream = fixture("typedstream/AttributedBodyTextOnly");
+        let summary_info = fixture("edited_message/Edited.plist");
+        let payload = fixture("app_message/FindMy.plist");
+
+        let db = get_empty_test_connection();
+        db.execute(
+            "INSERT INTO message (ROWID, guid, date, attributedBody, message_summary_info, payload_data)
+             VALUES (1, 'raw', 0, ?1, ?2, ?3)",
+            (&typedstream, &summary_info, &payload),
+        )
+        .unwrap();
+
+        let message = Message::from_guid("raw", &db).unwrap();
+
+        assert_eq!(message.raw_typedstream(&db), Some(typedstream));
+        assert_eq!(message.raw_message_summary_info(&db), Some(summary_info));
+        assert_eq!(message.raw_payload_data(&db), Some(payload));
+        assert!(message.message_summary_info(&db).is_some());
+        assert!(message.payload_data(&db).is_some());
+    }
+
+    #[test]
+    fn cant_get_missing_raw_blobs() {
+        let db = get_empty_test_connection();
+        db.execute_batch("INSERT INTO message (ROWID, guid, date) VALUES (1, 'empty', 0);")
+            .unwrap();
+
+        let message = Message::from_guid("empty", &db).unwrap();
+
+        assert_eq!(message.raw_typedstream(&db), None);
+        assert_eq!(message.raw_message_summary_info(&db), None);
+        assert_eq!(message.raw_payload_data(&db), None);
+    }
+}
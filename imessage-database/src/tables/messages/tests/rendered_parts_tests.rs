@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{
+        attachment::Attachment,
+        messages::{
+            Message,
+            models::{AttachmentMismatch, BubbleComponent},
+        },
+    };
+
+    fn attachment(rowid: i32) -> Attachment {
+        Attachment {
+            rowid,
+            filename: Some(format!("a/b/{rowid}.png")),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some(format!("{rowid}.png")),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            emoji_description: None,
+            copied_path: None,
+        }
+    }
+
+    /// The `ROWID`s of the attachment assigned to each attachment component
+    fn slots(m: &Message, attachments: Vec<Attachment>) -> Vec<Option<i32>> {
+        m.rendered_parts(attachments)
+            .parts
+            .iter()
+            .filter(|part| matches!(part.component, BubbleComponent::Attachment(_)))
+            .map(|part| part.attachment.as_ref().map(|a| a.rowid))
+            .collect()
+    }
+
+    #[test]
+    fn can_pair_matching_attachments() {
+        let mut m = Message::blank();
+        m.text = Some("\u{FFFC}test 1\u{FFFC}test 2".to_string());
+
+        let rendered = m.rendered_parts(vec![attachment(1), attachment(2)]);
+
+        assert!(rendered.mismatch.is_none());
+        assert_eq!(rendered.parts.len(), 4);
+        assert_eq!(
+            slots(&m, vec![attachment(1), attachment(2)]),
+            vec![Some(1), Some(2)]
+        );
+        assert!(matches!(
+            rendered.parts[1].component,
+            BubbleComponent::Text(_)
+        ));
+        assert!(rendered.parts[1].attachment.is_none());
+    }
+
+    #[test]
+    fn can_append_extra_attachments() {
+        let mut m = Message::blank();
+        m.text = Some("\u{FFFC}test 1".to_string());
+
+        let rendered = m.rendered_parts(vec![attachment(1), attachment(2), attachment(3)]);
+
+        assert_eq!(
+            rendered.mismatch,
+            Some(AttachmentMismatch {
+                placeholders: 1,
+                attachments: 3
+            })
+        );
+        // The text stays after the first attachment and the extras are shown last
+        assert_eq!(rendered.parts.len(), 4);
+        assert!(matches!(
+            rendered.parts[1].component,
+            BubbleComponent::Text(_)
+        ));
+        assert_eq!(
+            slots(&m, vec![attachment(1), attachment(2), attachment(3)]),
+            vec![Some(1), Some(2), Some(3)]
+        );
+    }
+
+    #[test]
+    fn can_leave_extra_placeholders_empty() {
+        let mut m = Message::blank();
+        m.text = Some("\u{FFFC}test 1\u{FFFC}".to_string());
+
+        let rendered = m.rendered_parts(vec![attachment(1)]);
+
+        assert_eq!(
+            rendered.mismatch,
+            Some(AttachmentMismatch {
+                placeholders: 2,
+                attachments: 1
+            })
+        );
+        assert_eq!(slots(&m, vec![attachment(1)]), vec![Some(1), None]);
+    }
+
+    #[test]
+    fn can_append_app_attachments_without_mismatch() {
+        let mut m = Message::blank();
+        m.text = Some("\u{FFFC}".to_string());
+        m.balloon_bundle_id = Some("com.apple.messages.URLBalloonProvider".to_string());
+
+        let rendered = m.rendered_parts(vec![attachment(1)]);
+
+        assert!(rendered.mismatch.is_none());
+        assert!(matches!(rendered.parts[0].component, BubbleComponent::App));
+        assert_eq!(
+            rendered.parts[1].attachment.as_ref().map(|a| a.rowid),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn can_describe_mismatch() {
+        let mismatch = AttachmentMismatch {
+            placeholders: 1,
+            attachments: 2,
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "Message body has 1 attachment placeholder(s) but 2 attachment(s); extra attachments are shown at the end"
+        );
+    }
+
+    #[test]
+    fn can_render_duplicate_attachment_once() {
+        let mut m = Message::blank();
+        m.text = Some("\u{FFFC}test 1\u{FFFC}test 2".to_string());
+
+        let rendered = m.rendered_parts(vec![attachment(1), attachment(1), attachment(2)]);
+
+        assert!(rendered.mismatch.is_none());
+        assert_eq!(
+            slots(&m, vec![attachment(1), attachment(2), attachment(1)]),
+            vec![Some(1), Some(2)]
+        );
+    }
+}
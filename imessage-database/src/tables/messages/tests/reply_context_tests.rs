@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{
+        messages::{
+            Message,
+            models::{OriginatorStatus, ReplyContext, ReplyKind},
+        },
+        table::get_empty_test_connection,
+    };
+
+    fn thread() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, thread_originator_guid) VALUES
+                (1, 'originator', 'hello', 10, 0, 0, NULL),
+                (2, 'quote', 'hi', 20, 0, 0, 'originator'),
+                (3, 'deleted', 'gone', 30, 0, 0, NULL),
+                (4, 'first', 'one', 40, 0, 0, 'deleted'),
+                (5, 'second', 'two', 50, 0, 0, 'deleted'),
+                (6, 'orphan', 'three', 60, 0, 0, 'missing');
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 4), (1, 5), (1, 6);
+            INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date) VALUES (1, 3, 1);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_get_reply_to_present_originator() {
+        let db = thread();
+        let reply = Message::from_guid("quote", &db).unwrap();
+
+        assert_eq!(
+            reply.reply_context(&db).unwrap(),
+            Some(ReplyContext {
+                kind: ReplyKind::Quote,
+                originator: OriginatorStatus::Present,
+            })
+        );
+    }
+
+    #[test]
+    fn can_get_reply_to_deleted_originator() {
+        let db = thread();
+        let reply = Message::from_guid("first", &db).unwrap();
+
+        assert_eq!(
+            reply.reply_context(&db).unwrap(),
+            Some(ReplyContext {
+                kind: ReplyKind::Thread,
+                originator: OriginatorStatus::Deleted,
+            })
+        );
+    }
+
+    #[test]
+    fn can_get_reply_to_missing_originator() {
+        let db = thread();
+        let reply = Message::from_guid("orphan", &db).unwrap();
+
+        assert_eq!(
+            reply.reply_context(&db).unwrap(),
+            Some(ReplyContext {
+                kind: ReplyKind::Quote,
+                originator: OriginatorStatus::Missing,
+            })
+        );
+    }
+
+    #[test]
+    fn can_skip_non_replies() {
+        let db = thread();
+        let message = Message::from_guid("originator", &db).unwrap();
+
+        assert_eq!(message.reply_context(&db).unwrap(), None);
+    }
+}
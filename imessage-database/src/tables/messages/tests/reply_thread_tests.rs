@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{
+        messages::{Message, models::ReplyNode},
+        table::get_empty_test_connection,
+    };
+
+    fn thread() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered, thread_originator_guid) VALUES
+                (1, 'root', 'Dinner?', 10, 0, 0, NULL),
+                (2, 'reply', 'Where?', 20, 0, 0, 'root'),
+                (3, 'nested', 'The usual', 30, 0, 0, 'reply'),
+                (4, 'deepest', 'See you there', 40, 0, 0, 'nested'),
+                (5, 'second', 'I am in', 50, 0, 0, 'root'),
+                (6, 'loop_a', 'a', 60, 0, 0, 'loop_b'),
+                (7, 'loop_b', 'b', 70, 0, 0, 'loop_a');
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (1, 6), (1, 7);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    fn guids(node: &ReplyNode) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.message.guid.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn can_build_nested_reply_tree() {
+        let db = thread();
+        let root = Message::from_guid("root", &db).unwrap();
+
+        let tree = root.reply_thread_tree(&db).unwrap();
+
+        assert_eq!(tree.message.guid, "root");
+        assert_eq!(guids(&tree), vec!["reply", "second"]);
+
+        let reply = &tree.children[0];
+        assert_eq!(guids(reply), vec!["nested"]);
+        assert_eq!(guids(&reply.children[0]), vec!["deepest"]);
+        assert!(reply.children[0].children[0].children.is_empty());
+        assert!(tree.children[1].children.is_empty());
+    }
+
+    #[test]
+    fn can_build_tree_without_replies() {
+        let db = thread();
+        let leaf = Message::from_guid("deepest", &db).unwrap();
+
+        let tree = leaf.reply_thread_tree(&db).unwrap();
+
+        assert_eq!(tree.message.guid, "deepest");
+        assert!(tree.children.is_empty());
+    }
+
+    #[test]
+    fn can_stop_at_reply_cycle() {
+        let db = thread();
+        let start = Message::from_guid("loop_a", &db).unwrap();
+
+        let tree = start.reply_thread_tree(&db).unwrap();
+
+        assert_eq!(guids(&tree), vec!["loop_b"]);
+        assert!(tree.children[0].children.is_empty());
+    }
+}
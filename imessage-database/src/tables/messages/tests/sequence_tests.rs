@@ -0,0 +1,31 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    #[test]
+    fn can_number_messages_per_chat() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, date) VALUES (1, 'a', 300), (2, 'b', 100), (3, 'c', 200), (4, 'd', 50), (5, 'e', 400);
+            INSERT INTO message (ROWID, guid, date, associated_message_guid, associated_message_type) VALUES (6, 'f', 150, 'p:0/b', 2000);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (2, 4), (2, 5), (1, 6);
+            ",
+        )
+        .unwrap();
+
+        let sequence = Message::cache_chat_sequence(&db).unwrap();
+
+        // Chat 1, ordered by date
+        assert_eq!(sequence.get(&2), Some(&1));
+        assert_eq!(sequence.get(&3), Some(&2));
+        assert_eq!(sequence.get(&1), Some(&3));
+
+        // Chat 2 starts over
+        assert_eq!(sequence.get(&4), Some(&1));
+        assert_eq!(sequence.get(&5), Some(&2));
+
+        // Tapbacks are not numbered
+        assert_eq!(sequence.get(&6), None);
+    }
+}
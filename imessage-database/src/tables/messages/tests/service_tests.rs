@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::messages::{Message, models::Service};
+
+    #[test]
+    fn can_get_imessage_service() {
+        let mut m = Message::blank();
+        m.service = Some("iMessage".to_string());
+
+        assert_eq!(m.service(), Service::iMessage);
+        assert_eq!(m.service().to_string(), "iMessage");
+    }
+
+    #[test]
+    fn can_get_sms_service() {
+        let mut m = Message::blank();
+        m.service = Some("SMS".to_string());
+
+        assert_eq!(m.service(), Service::SMS);
+        assert_eq!(m.service().to_string(), "SMS");
+    }
+
+    #[test]
+    fn can_get_rcs_service() {
+        let mut m = Message::blank();
+        m.service = Some("rcs".to_string());
+        assert_eq!(m.service(), Service::RCS);
+
+        m.service = Some("RCS".to_string());
+        assert_eq!(m.service(), Service::RCS);
+
+        m.service = Some("Rcs".to_string());
+        assert_eq!(m.service(), Service::RCS);
+        assert_eq!(m.service().to_string(), "RCS");
+    }
+
+    #[test]
+    fn can_get_missing_service() {
+        let mut m = Message::blank();
+        m.service = None;
+
+        assert_eq!(m.service(), Service::Unknown);
+    }
+
+    #[test]
+    fn can_get_service_from_chat() {
+        let mut m = Message::blank();
+        m.service = None;
+        assert_eq!(m.service_in(Some("SMS")), Service::SMS);
+        assert_eq!(m.service_in(Some("RCS")), Service::RCS);
+        assert_eq!(m.service_in(None), Service::Unknown);
+
+        // The message's own service wins
+        m.service = Some("iMessage".to_string());
+        assert_eq!(m.service_in(Some("SMS")), Service::iMessage);
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use std::env::current_dir;
+
+    use crate::tables::{messages::Message, table::get_connection};
+
+    #[test]
+    fn can_detect_sticker_overlay() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(1000);
+        assert!(m.is_sticker_overlay());
+        assert!(m.is_tapback());
+    }
+
+    #[test]
+    fn cant_detect_sticker_tapback_as_overlay() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(2007);
+        assert!(!m.is_sticker_overlay());
+
+        m.associated_message_type = Some(0);
+        assert!(!m.is_sticker_overlay());
+    }
+
+    #[test]
+    fn can_get_sticker_overlay_attachment() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let conn = get_connection(&db_path).unwrap();
+
+        let mut message =
+            Message::from_guid("0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", &conn).unwrap();
+        assert!(message.sticker_overlay(&conn).unwrap().is_none());
+
+        message.associated_message_type = Some(1000);
+        let overlay = message.sticker_overlay(&conn).unwrap().unwrap();
+        assert_eq!(overlay.attachment.rowid, 1);
+        assert!(overlay.attachment.is_sticker);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        message_types::variants::Tapback,
+        tables::{
+            messages::{
+                Message,
+                tapbacks::{applied_tapbacks, tapback_counts},
+            },
+            table::{Cacheable, get_empty_test_connection},
+        },
+    };
+
+    const TARGET: &str = "A1B2C3D4-E5F6-4A5B-8C7D-9E0F1A2B3C4D";
+
+    fn database() -> rusqlite::Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(&format!(
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (1, '{TARGET}', 'Dinner?', 0, 1, 10, 0, 0, NULL, 0),
+                (2, 'love-1', NULL, 1, 0, 20, 0, 0, 'p:0/{TARGET}', 2000),
+                (3, 'love-2', NULL, 2, 0, 30, 0, 0, 'p:0/{TARGET}', 2000),
+                (4, 'laugh-3', NULL, 3, 0, 40, 0, 0, 'p:0/{TARGET}', 2003),
+                (5, 'love-me', NULL, 0, 1, 50, 0, 0, 'p:1/{TARGET}', 2000),
+                (6, 'unlove-me', NULL, 0, 1, 60, 0, 0, 'p:1/{TARGET}', 3000),
+                (7, 'like-1', NULL, 1, 0, 70, 0, 0, 'p:1/{TARGET}', 2001);
+            "
+        ))
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_count_tapbacks_per_part() {
+        let db = database();
+        let tapbacks = Message::cache(&db).unwrap();
+
+        let counts = tapback_counts(&tapbacks, TARGET);
+
+        let expected = HashMap::from([
+            (0, vec![(Tapback::Loved, 2), (Tapback::Laughed, 1)]),
+            (1, vec![(Tapback::Liked, 1)]),
+        ]);
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn can_cancel_removed_tapback() {
+        let db = database();
+        let tapbacks = Message::cache(&db).unwrap();
+
+        let applied: Vec<&str> = applied_tapbacks(&tapbacks[TARGET])
+            .into_iter()
+            .filter(|(part, _)| *part == 1)
+            .map(|(_, tapback)| tapback.guid.as_str())
+            .collect();
+
+        // The loved tapback was removed, so only the liked tapback from someone else is left
+        assert_eq!(applied, vec!["like-1"]);
+    }
+
+    #[test]
+    fn can_replace_earlier_tapback_from_same_sender() {
+        let db = database();
+        db.execute_batch(&format!(
+            "INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (8, 'laugh-1', NULL, 1, 0, 80, 0, 0, 'p:0/{TARGET}', 2003);"
+        ))
+        .unwrap();
+        let tapbacks = Message::cache(&db).unwrap();
+
+        let counts = tapback_counts(&tapbacks, TARGET);
+
+        assert_eq!(counts[&0], vec![(Tapback::Laughed, 2), (Tapback::Loved, 1)]);
+    }
+
+    #[test]
+    fn cant_count_tapbacks_for_unknown_message() {
+        let db = database();
+        let tapbacks = Message::cache(&db).unwrap();
+
+        assert!(tapback_counts(&tapbacks, "missing").is_empty());
+    }
+}
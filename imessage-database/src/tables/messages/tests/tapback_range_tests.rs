@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    const TARGET: &str = "A1B2C3D4-E5F6-4A5B-8C7D-9E0F1A2B3C4D";
+
+    fn tapback(range_location: i32, range_length: i32) -> Message {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(2001);
+        m.associated_message_guid = Some(format!("p:0/{TARGET}"));
+        m.associated_message_range_location = range_location;
+        m.associated_message_range_length = range_length;
+        m
+    }
+
+    fn target(text: &str) -> Message {
+        let mut m = Message::blank();
+        m.guid = TARGET.to_string();
+        m.text = Some(text.to_string());
+        m
+    }
+
+    #[test]
+    fn can_get_tapback_sub_range() {
+        let tapback = tapback(10, 6);
+        let target = target("Dinner at Luigi’s tonight?");
+
+        assert_eq!(tapback.tapback_range(), Some(10..16));
+        assert_eq!(tapback.tapback_target_text(&target), Some("Luigi’"));
+    }
+
+    #[test]
+    fn can_get_tapback_sub_range_after_emoji() {
+        // The emoji is two UTF-16 code units, so `Luigi’` starts at unit 13
+        let tapback = tapback(13, 6);
+        let target = target("😂 Dinner at Luigi’s tonight?");
+
+        assert_eq!(tapback.tapback_target_text(&target), Some("Luigi’"));
+    }
+
+    #[test]
+    fn can_fall_back_when_range_splits_emoji() {
+        let tapback = tapback(1, 3);
+        let target = target("😂 Dinner");
+
+        assert_eq!(tapback.tapback_target_text(&target), Some("😂 Dinner"));
+    }
+
+    #[test]
+    fn can_fall_back_to_whole_part() {
+        let tapback = tapback(0, 0);
+        let target = target("Dinner at Luigi’s tonight?");
+
+        assert_eq!(tapback.tapback_range(), None);
+        assert_eq!(
+            tapback.tapback_target_text(&target),
+            Some("Dinner at Luigi’s tonight?")
+        );
+    }
+
+    #[test]
+    fn can_fall_back_when_range_is_out_of_bounds() {
+        let tapback = tapback(4, 100);
+        let target = target("Hello");
+
+        assert_eq!(tapback.tapback_target_text(&target), Some("Hello"));
+    }
+
+    #[test]
+    fn cant_get_range_for_normal_message() {
+        let mut message = Message::blank();
+        message.associated_message_range_location = 2;
+        message.associated_message_range_length = 3;
+
+        assert_eq!(message.tapback_range(), None);
+    }
+
+    #[test]
+    fn can_read_range_from_table() {
+        let db = get_empty_test_connection();
+        db.execute_batch(&format!(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, '{TARGET}', 'Hello world', 1, 0, 0);
+            INSERT INTO message (ROWID, guid, associated_message_guid, associated_message_type, associated_message_range_location, associated_message_range_length, date, date_read, date_delivered) VALUES
+                (2, 'tapback', 'p:0/{TARGET}', 2000, 6, 5, 2, 0, 0);
+            "
+        ))
+        .unwrap();
+
+        let target = Message::from_guid(TARGET, &db).unwrap();
+        let tapback = Message::from_guid("tapback", &db).unwrap();
+
+        assert_eq!(tapback.tapback_range(), Some(6..11));
+        assert_eq!(tapback.tapback_target_text(&target), Some("world"));
+    }
+}
@@ -1,7 +1,11 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use crate::{
-        message_types::variants::{CustomBalloon, Tapback, TapbackAction, Variant},
+        message_types::variants::{
+            CustomBalloon, Tapback, TapbackAction, UNKNOWN_EMOJI_TAPBACK, Variant,
+        },
         tables::messages::Message,
     };
 
@@ -45,6 +49,17 @@ mod tests {
         assert!(matches!(m.variant(), Variant::App(CustomBalloon::FindMy)));
     }
 
+    #[test]
+    fn test_built_in_app_poll() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(0);
+        m.balloon_bundle_id = Some(
+            "com.apple.messages.MSMessageExtensionBalloonPlugin:XXX:com.apple.messages.Polls"
+                .to_string(),
+        );
+        assert!(matches!(m.variant(), Variant::App(CustomBalloon::Poll)));
+    }
+
     #[test]
     fn test_tapback_added_heart() {
         let mut m = Message::blank();
@@ -93,6 +108,59 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn can_render_custom_emoji_tapback() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(2006);
+        m.associated_message_emoji = Some("🎉".to_owned());
+        let Variant::Tapback(_, _, tapback) = m.variant() else {
+            panic!("Not a tapback!");
+        };
+        assert_eq!(tapback.to_string(), "🎉");
+        assert_eq!(tapback.emoji(), "🎉");
+    }
+
+    #[test]
+    fn can_render_custom_emoji_tapback_without_emoji() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(2006);
+        let Variant::Tapback(_, _, tapback) = m.variant() else {
+            panic!("Not a tapback!");
+        };
+        assert_eq!(tapback.to_string(), UNKNOWN_EMOJI_TAPBACK);
+        assert_eq!(tapback.emoji(), UNKNOWN_EMOJI_TAPBACK);
+    }
+
+    #[test]
+    fn can_group_zwj_sequence_emoji_tapbacks() {
+        let emoji = ["👩🏽‍💻", "👩🏽‍💻 ", "👩‍💻", "👩🏽‍💻"];
+        let messages: Vec<Message> = emoji
+            .iter()
+            .map(|emoji| {
+                let mut m = Message::blank();
+                m.associated_message_type = Some(2006);
+                m.associated_message_emoji = Some((*emoji).to_owned());
+                m
+            })
+            .collect();
+
+        let mut counts: HashMap<Tapback, usize> = HashMap::new();
+        for m in &messages {
+            let Variant::Tapback(_, _, tapback) = m.variant() else {
+                panic!("Not a tapback!");
+            };
+            // The whole sequence is rendered, not just its first codepoint
+            assert_eq!(tapback.to_string(), tapback.emoji());
+            *counts.entry(tapback).or_default() += 1;
+        }
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&Tapback::Emoji(Some("👩🏽‍💻"))], 3);
+        assert_eq!(counts[&Tapback::Emoji(Some("👩‍💻"))], 1);
+        assert_ne!(Tapback::Emoji(Some("👩🏽‍💻")), Tapback::Emoji(Some("👩")));
+        assert_ne!(Tapback::Emoji(Some("❤️")), Tapback::Loved);
+    }
+
     #[test]
     fn can_get_variant_third_party_app() {
         let mut m = Message::blank();
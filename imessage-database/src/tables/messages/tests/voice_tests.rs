@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::tables::{messages::Message, table::get_empty_test_connection};
+
+    #[test]
+    fn can_get_played_voice_message() {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, date, date_read, date_delivered, is_audio_message, is_played) VALUES
+                (1, 'played', 0, 0, 0, 1, 1),
+                (2, 'unplayed', 0, 0, 0, 1, 0),
+                (3, 'not_audio', 0, 0, 0, 0, 0);
+            ",
+        )
+        .unwrap();
+
+        let played = Message::from_guid("played", &db).unwrap();
+        assert_eq!(played.voice_message_played(), Some(true));
+
+        let unplayed = Message::from_guid("unplayed", &db).unwrap();
+        assert_eq!(unplayed.voice_message_played(), Some(false));
+
+        let text = Message::from_guid("not_audio", &db).unwrap();
+        assert_eq!(text.voice_message_played(), None);
+    }
+
+    #[test]
+    fn cant_get_played_without_column() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "
+            CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, date INTEGER, is_from_me INTEGER, is_audio_message INTEGER);
+            CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+            CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+            INSERT INTO message (ROWID, guid, date, is_from_me, is_audio_message) VALUES (1, 'audio', 0, 0, 1);
+            ",
+        )
+        .unwrap();
+
+        let message = Message::from_guid("audio", &db).unwrap();
+        assert!(message.is_audio_message);
+        assert_eq!(message.voice_message_played(), None);
+    }
+
+    #[test]
+    fn cant_get_played_for_blank_message() {
+        let mut message = Message::blank();
+        message.is_played = Some(true);
+        assert_eq!(message.voice_message_played(), None);
+    }
+}
@@ -2,11 +2,23 @@
  This module defines traits for table representations and stores some shared table constants.
 */
 
-use std::{collections::HashMap, fs::metadata, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::metadata,
+    path::Path,
+};
 
 use rusqlite::{Connection, Error, OpenFlags, Result, Row, Statement, blob::Blob};
 
-use crate::{error::table::TableError, tables::messages::models::BubbleComponent};
+use crate::{
+    error::table::TableError,
+    tables::messages::{
+        message::COLS,
+        models::BubbleComponent,
+        query_parts::{ios_13_older_query, ios_14_15_query, ios_16_newer_query},
+    },
+};
 
 /// Defines behavior for SQL Table data
 pub trait Table {
@@ -133,6 +145,241 @@ pub fn get_connection(path: &Path) -> Result<Connection, TableError> {
     )))
 }
 
+/// Describes which tables and columns exist in a database, and the features they enable
+///
+/// The schema of the iMessage database varies between OS versions, so this can be used
+/// to warn about missing data before an export starts instead of failing partway through.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    /// The `message` table exists
+    pub has_message: bool,
+    /// The `chat_message_join` table exists
+    pub has_chat_message_join: bool,
+    /// The `message` table has an `attributedBody` column
+    pub has_attributed_body: bool,
+    /// The `message` table has a `thread_originator_guid` column
+    pub has_thread_originator_guid: bool,
+    /// The `message` table has `date_edited` and `message_summary_info` columns
+    pub has_edit_history: bool,
+    /// The `chat_recoverable_message_join` table exists
+    pub has_recently_deleted: bool,
+    /// The OS release that most likely created the database
+    pub version: SchemaVersion,
+}
+
+impl SchemaReport {
+    /// Probe a database for the tables and columns the crate relies on
+    ///
+    /// The schema is read once, and the same probe decides both the supported features and the [`SchemaVersion`].
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::{
+    ///     util::dirs::default_db_path,
+    ///     tables::table::{SchemaReport, get_connection}
+    /// };
+    ///
+    /// let db_path = default_db_path();
+    /// let connection = get_connection(&db_path).unwrap();
+    /// let report = SchemaReport::check(&connection);
+    /// println!("Database created by {}", report.version);
+    /// for feature in report.unsupported_features() {
+    ///     println!("Not supported: {feature}");
+    /// }
+    /// ```
+    pub fn check(db: &Connection) -> Self {
+        let tables = names(db, "SELECT name FROM sqlite_master WHERE type = 'table'");
+        let message_columns = names(
+            db,
+            &format!("SELECT name FROM pragma_table_info('{MESSAGE}')"),
+        );
+        let has_thread_originator_guid = message_columns.contains("thread_originator_guid");
+        let has_recently_deleted = tables.contains(RECENTLY_DELETED);
+
+        SchemaReport {
+            has_message: tables.contains(MESSAGE),
+            has_chat_message_join: tables.contains(CHAT_MESSAGE_JOIN),
+            has_attributed_body: message_columns.contains(ATTRIBUTED_BODY),
+            has_thread_originator_guid,
+            has_edit_history: message_columns.contains("date_edited")
+                && message_columns.contains(MESSAGE_SUMMARY_INFO),
+            has_recently_deleted,
+            version: SchemaVersion::from_columns(
+                &message_columns,
+                has_thread_originator_guid,
+                has_recently_deleted,
+            ),
+        }
+    }
+
+    /// `true` if messages can be read and assigned to chats, else `false`
+    #[must_use]
+    pub fn supports_export(&self) -> bool {
+        self.has_message && self.has_chat_message_join
+    }
+
+    /// `true` if threaded replies can be parsed, else `false`
+    #[must_use]
+    pub fn supports_replies(&self) -> bool {
+        self.has_thread_originator_guid
+    }
+
+    /// `true` if recently deleted messages can be recovered, else `false`
+    #[must_use]
+    pub fn supports_recovery(&self) -> bool {
+        self.has_recently_deleted
+    }
+
+    /// `true` if edited and unsent messages can be parsed, else `false`
+    #[must_use]
+    pub fn supports_edits(&self) -> bool {
+        self.has_edit_history
+    }
+
+    /// `true` if message text can include formatting and attachment placement, else `false`
+    #[must_use]
+    pub fn supports_rich_text(&self) -> bool {
+        self.has_attributed_body
+    }
+
+    /// Names of the features this database does not support, for warning users
+    #[must_use]
+    pub fn unsupported_features(&self) -> Vec<&'static str> {
+        [
+            (self.supports_replies(), "threaded replies"),
+            (self.supports_recovery(), "recently deleted messages"),
+            (self.supports_edits(), "edited and unsent messages"),
+            (self.supports_rich_text(), "formatted message text"),
+        ]
+        .into_iter()
+        .filter_map(|(supported, feature)| (!supported).then_some(feature))
+        .collect()
+    }
+}
+
+/// The approximate OS release that created a database, based on the shape of its schema
+///
+/// Each version corresponds to one of the message query tiers, from newest to oldest.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaVersion {
+    /// macOS Catalina, iOS 13 and older: no threaded replies
+    #[default]
+    Ios13OrOlder,
+    /// macOS Big Sur to Monterey, iOS 14 to iOS 15: threaded replies, but no edits or recently deleted messages
+    Ios14To15,
+    /// macOS Ventura+ and iOS 16+: edits, emoji tapbacks, and recently deleted messages
+    Ios16OrNewer,
+}
+
+impl SchemaVersion {
+    /// Guess which OS release created a database from the columns in its `message` table
+    ///
+    /// This runs the same probe as [`SchemaReport::check()`]; use that instead if the supported features are
+    /// needed too.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::{
+    ///     util::dirs::default_db_path,
+    ///     tables::table::{SchemaVersion, get_connection}
+    /// };
+    ///
+    /// let db_path = default_db_path();
+    /// let connection = get_connection(&db_path).unwrap();
+    /// println!("Database created by {}", SchemaVersion::detect(&connection));
+    /// ```
+    pub fn detect(db: &Connection) -> Self {
+        SchemaReport::check(db).version
+    }
+
+    /// Pick the version from a probed schema
+    ///
+    /// - If every column read by the newest query exists, such as `date_edited` and
+    ///   `associated_message_emoji`, along with `chat_recoverable_message_join`, the database is from iOS 16+
+    /// - If `thread_originator_guid` exists, the database is from iOS 14 to 15
+    /// - Otherwise, the database is from iOS 13 or older
+    fn from_columns(
+        message_columns: &HashSet<String>,
+        has_thread_originator_guid: bool,
+        has_recently_deleted: bool,
+    ) -> Self {
+        // `rowid` is implicit, so it is not listed as a column
+        let has_newest_columns = COLS
+            .split(", ")
+            .filter(|column| !column.eq_ignore_ascii_case("rowid"))
+            .all(|column| message_columns.contains(column));
+
+        if has_newest_columns && has_recently_deleted {
+            SchemaVersion::Ios16OrNewer
+        } else if has_thread_originator_guid {
+            SchemaVersion::Ios14To15
+        } else {
+            SchemaVersion::Ios13OrOlder
+        }
+    }
+
+    /// Generate the message query that is compatible with this schema
+    pub(crate) fn query(self, filters: Option<&str>, limit: Option<&str>) -> String {
+        match self {
+            SchemaVersion::Ios16OrNewer => ios_16_newer_query(filters, limit),
+            SchemaVersion::Ios14To15 => ios_14_15_query(filters, limit),
+            SchemaVersion::Ios13OrOlder => ios_13_older_query(filters, limit),
+        }
+    }
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SchemaVersion::Ios16OrNewer => write!(fmt, "macOS Ventura+ or iOS 16+"),
+            SchemaVersion::Ios14To15 => write!(fmt, "macOS Big Sur to Monterey or iOS 14 to 15"),
+            SchemaVersion::Ios13OrOlder => write!(fmt, "macOS Catalina or iOS 13 and older"),
+        }
+    }
+}
+
+/// Collect the first column of a query into a set, treating any failure as an empty result
+fn names(db: &Connection, query: &str) -> HashSet<String> {
+    db.prepare(query)
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<HashSet<String>>>()
+        })
+        .unwrap_or_default()
+}
+
+/// Create an empty, writable, in-memory database that uses the same schema as the test database
+#[cfg(test)]
+pub(crate) fn get_empty_test_connection() -> Connection {
+    let source = get_connection(
+        &std::env::current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db"),
+    )
+    .unwrap();
+    let db = Connection::open_in_memory().unwrap();
+    // Tests only insert the rows they need, so related rows may not exist
+    db.execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+
+    // Triggers are skipped because they call functions that only exist in the Messages app
+    let mut statement = source
+        .prepare("SELECT sql FROM sqlite_master WHERE type IN ('table', 'index') AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'")
+        .unwrap();
+    let schema = statement
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap();
+    for sql in schema {
+        db.execute_batch(&sql.unwrap()).unwrap();
+    }
+
+    db
+}
+
 /// Get the size of the database on the disk
 // # Example:
 ///
@@ -196,3 +443,141 @@ pub const ORPHANED: &str = "orphaned";
 pub const FITNESS_RECEIVER: &str = "$(kIMTranscriptPluginBreadcrumbTextReceiverIdentifier)";
 /// Name for attachments directory in exports
 pub const ATTACHMENTS_DIR: &str = "attachments";
+
+#[cfg(test)]
+mod schema_tests {
+    use rusqlite::Connection;
+
+    use crate::tables::{
+        messages::message::COLS,
+        table::{SchemaReport, SchemaVersion, get_empty_test_connection},
+    };
+
+    #[test]
+    fn can_check_current_schema() {
+        let db = get_empty_test_connection();
+        let report = SchemaReport::check(&db);
+
+        assert!(report.supports_export());
+        assert!(report.supports_replies());
+        assert!(report.supports_recovery());
+        assert!(report.supports_edits());
+        assert!(report.supports_rich_text());
+        assert!(report.unsupported_features().is_empty());
+        assert_eq!(report.version, SchemaVersion::Ios16OrNewer);
+    }
+
+    #[test]
+    fn can_check_schema_without_replies() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "
+            CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, attributedBody BLOB);
+            CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+            ",
+        )
+        .unwrap();
+
+        let report = SchemaReport::check(&db);
+
+        assert!(report.supports_export());
+        assert!(report.supports_rich_text());
+        assert!(!report.supports_replies());
+        assert!(!report.supports_recovery());
+        assert!(!report.supports_edits());
+        assert_eq!(
+            report.unsupported_features(),
+            vec![
+                "threaded replies",
+                "recently deleted messages",
+                "edited and unsent messages"
+            ]
+        );
+    }
+
+    /// Create a database whose `message` table has only the given columns
+    fn synthesize(columns: &str, recently_deleted: bool) -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(&format!(
+            "CREATE TABLE message ({columns}); CREATE TABLE chat_message_join (chat_id, message_id); CREATE TABLE message_attachment_join (message_id, attachment_id);"
+        ))
+        .unwrap();
+        if recently_deleted {
+            db.execute_batch(
+                "CREATE TABLE chat_recoverable_message_join (chat_id, message_id, delete_date);",
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn can_detect_ios_16_schema() {
+        let db = synthesize(COLS, true);
+        assert_eq!(SchemaVersion::detect(&db), SchemaVersion::Ios16OrNewer);
+        assert!(
+            db.prepare(&SchemaVersion::detect(&db).query(None, None))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn can_detect_test_database_schema() {
+        let db = get_empty_test_connection();
+        assert_eq!(SchemaVersion::detect(&db), SchemaVersion::Ios16OrNewer);
+    }
+
+    #[test]
+    fn can_detect_ios_14_15_schema() {
+        // Threaded replies exist, but edits, emoji tapbacks, and recently deleted messages do not
+        let columns = COLS
+            .split(", ")
+            .filter(|column| !matches!(*column, "date_edited" | "associated_message_emoji"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let db = synthesize(&columns, false);
+
+        assert_eq!(SchemaVersion::detect(&db), SchemaVersion::Ios14To15);
+        assert!(
+            db.prepare(&SchemaVersion::detect(&db).query(None, None))
+                .is_ok()
+        );
+        // The newest query does not work with this schema
+        assert!(
+            db.prepare(&SchemaVersion::Ios16OrNewer.query(None, None))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_detect_ios_13_schema() {
+        let db = synthesize(
+            "ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, date INTEGER",
+            false,
+        );
+
+        assert_eq!(SchemaVersion::detect(&db), SchemaVersion::Ios13OrOlder);
+        assert!(
+            db.prepare(&SchemaVersion::detect(&db).query(None, None))
+                .is_ok()
+        );
+        assert!(
+            db.prepare(&SchemaVersion::Ios14To15.query(None, None))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_order_schema_versions() {
+        assert!(SchemaVersion::Ios13OrOlder < SchemaVersion::Ios14To15);
+        assert!(SchemaVersion::Ios14To15 < SchemaVersion::Ios16OrNewer);
+    }
+
+    #[test]
+    fn cant_export_empty_database() {
+        let db = Connection::open_in_memory().unwrap();
+        let report = SchemaReport::check(&db);
+
+        assert!(!report.supports_export());
+    }
+}
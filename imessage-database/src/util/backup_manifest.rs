@@ -0,0 +1,192 @@
+/*!
+ Contains logic for finding files in an unencrypted iOS backup using its `Manifest.db`.
+
+ An iOS backup stores each file under a name derived from the SHA-1 hash of the file's domain and path, like
+ `3d/3d0d7e5fb2ce288813306e4d4636395e047a3d28` for `HomeDomain-Library/SMS/sms.db`. The `Files` table in the
+ backup's `Manifest.db` maps each domain and path to the `fileID` it is stored under, so files can be found even when
+ they were not backed up to the domain the Messages database expects.
+*/
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+
+use crate::{error::table::TableError, tables::table::get_connection};
+
+/// The name of the database that lists every file in an iOS backup
+pub const MANIFEST_DB: &str = "Manifest.db";
+/// The domain attachments are usually backed up to
+pub const MEDIA_DOMAIN: &str = "MediaDomain";
+/// The directory attachment paths are relative to, for paths stored as absolute paths on the device
+const LIBRARY_DIR: &str = "Library/";
+
+/// The file index of an unencrypted iOS backup
+pub struct BackupManifest {
+    /// The root of the backup directory, which contains `Manifest.db`
+    root: PathBuf,
+    /// The connection to `Manifest.db`
+    db: Connection,
+}
+
+impl BackupManifest {
+    /// Open the `Manifest.db` in the root of an iOS backup
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use imessage_database::util::backup_manifest::BackupManifest;
+    ///
+    /// let manifest = BackupManifest::open(Path::new("/path/to/backup")).unwrap();
+    /// let path = manifest.attachment_path("~/Library/SMS/Attachments/a1/01/IMG_0001/IMG_0001.jpeg");
+    /// println!("{path:?}");
+    /// ```
+    pub fn open(backup_root: &Path) -> Result<Self, TableError> {
+        Ok(Self {
+            root: backup_root.to_path_buf(),
+            db: get_connection(&backup_root.join(MANIFEST_DB))?,
+        })
+    }
+
+    /// Get the `fileID` a file from the device is stored under, or `None` if it is not in the backup
+    #[must_use]
+    pub fn file_id(&self, domain: &str, relative_path: &str) -> Option<String> {
+        self.db
+            .query_row(
+                "SELECT fileID FROM Files WHERE domain = ?1 AND relativePath = ?2",
+                [domain, relative_path],
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    /// Map an attachment's `filename` to the path it is stored at in the backup
+    ///
+    /// The file is looked up in [`MEDIA_DOMAIN`] first, then in any other domain with the same path. Returns `None`
+    /// if the backup does not contain the file.
+    #[must_use]
+    pub fn attachment_path(&self, filename: &str) -> Option<PathBuf> {
+        let relative_path = backup_relative_path(filename)?;
+        let file_id = self.file_id(MEDIA_DOMAIN, relative_path).or_else(|| {
+            self.db
+                .query_row(
+                    "SELECT fileID FROM Files WHERE relativePath = ?1 ORDER BY domain LIMIT 1",
+                    [relative_path],
+                    |row| row.get::<_, String>(0),
+                )
+                .ok()
+        })?;
+        Some(self.root.join(file_id.get(0..2)?).join(&file_id))
+    }
+}
+
+/// Get the path a file is stored under in the backup, relative to its domain
+///
+/// Attachment paths are usually stored relative to the home directory, like `~/Library/SMS/...`, but can also be
+/// absolute paths on the device, like `/var/mobile/Library/SMS/...`.
+fn backup_relative_path(filename: &str) -> Option<&str> {
+    if let Some(relative_path) = filename.strip_prefix("~/") {
+        return Some(relative_path);
+    }
+    filename
+        .find(LIBRARY_DIR)
+        .and_then(|idx| filename.get(idx..))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use rusqlite::Connection;
+
+    use crate::util::backup_manifest::{BackupManifest, MANIFEST_DB, backup_relative_path};
+
+    fn fake_backup(name: &str) -> PathBuf {
+        let root = temp_dir().join(name);
+        let _ = remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+
+        let db = Connection::open(root.join(MANIFEST_DB)).unwrap();
+        db.execute_batch(
+            "
+            CREATE TABLE Files (fileID TEXT PRIMARY KEY, domain TEXT, relativePath TEXT, flags INTEGER, file BLOB);
+            INSERT INTO Files (fileID, domain, relativePath, flags) VALUES
+                ('3d0d7e5fb2ce288813306e4d4636395e047a3d28', 'HomeDomain', 'Library/SMS/sms.db', 1),
+                ('b1e8a5bdc63d01e1c1c7d0f4e6a2b5c3d4e5f607', 'MediaDomain', 'Library/SMS/Attachments/a1/01/IMG_0001/IMG_0001.jpeg', 1),
+                ('c2f9b6ced74e12f2d2d8e1a5f7b3c6d4e5f6a718', 'AppDomainGroup-group.com.apple.messages', 'Library/SMS/Attachments/b2/02/IMG_0002/IMG_0002.heic', 1);
+            ",
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn can_get_file_id() {
+        let root = fake_backup("imessage-database-manifest-file-id");
+        let manifest = BackupManifest::open(&root).unwrap();
+
+        assert_eq!(
+            manifest.file_id("HomeDomain", "Library/SMS/sms.db"),
+            Some("3d0d7e5fb2ce288813306e4d4636395e047a3d28".to_string())
+        );
+        assert_eq!(manifest.file_id("MediaDomain", "Library/SMS/sms.db"), None);
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn can_resolve_attachment_path() {
+        let root = fake_backup("imessage-database-manifest-attachment");
+        let manifest = BackupManifest::open(&root).unwrap();
+
+        assert_eq!(
+            manifest.attachment_path("~/Library/SMS/Attachments/a1/01/IMG_0001/IMG_0001.jpeg"),
+            Some(
+                root.join("b1")
+                    .join("b1e8a5bdc63d01e1c1c7d0f4e6a2b5c3d4e5f607")
+            )
+        );
+        // Files backed up to another domain are still found
+        assert_eq!(
+            manifest.attachment_path(
+                "/var/mobile/Library/SMS/Attachments/b2/02/IMG_0002/IMG_0002.heic"
+            ),
+            Some(
+                root.join("c2")
+                    .join("c2f9b6ced74e12f2d2d8e1a5f7b3c6d4e5f6a718")
+            )
+        );
+        assert_eq!(
+            manifest.attachment_path("~/Library/SMS/Attachments/missing.jpeg"),
+            None
+        );
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cant_open_missing_manifest() {
+        let root = temp_dir().join("imessage-database-manifest-missing");
+        let _ = remove_dir_all(&root);
+
+        assert!(BackupManifest::open(&root).is_err());
+    }
+
+    #[test]
+    fn can_get_relative_path() {
+        assert_eq!(
+            backup_relative_path("~/Library/SMS/Attachments/a.jpeg"),
+            Some("Library/SMS/Attachments/a.jpeg")
+        );
+        assert_eq!(
+            backup_relative_path("/var/mobile/Library/SMS/Attachments/a.jpeg"),
+            Some("Library/SMS/Attachments/a.jpeg")
+        );
+        assert_eq!(backup_relative_path("a.jpeg"), None);
+    }
+}
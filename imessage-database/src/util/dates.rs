@@ -4,7 +4,10 @@
  Most dates are stored as nanosecond-precision unix timestamps with an epoch of `1/1/2001 00:00:00` in the local time zone.
 */
 
-use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use std::fmt::Display;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
 
 use crate::error::message::MessageError;
 
@@ -50,6 +53,55 @@ pub fn get_local_time(date_stamp: &i64, offset: &i64) -> Result<DateTime<Local>,
     Ok(Local.from_utc_datetime(&utc_stamp))
 }
 
+/// Create a `DateTime` in a specific time zone from an arbitrary date and offset
+///
+/// This is like [`get_local_time()`], but for rendering dates in the time zone the database came from instead of the
+/// time zone of the current machine.
+///
+/// # Example:
+///
+/// ```
+/// use chrono_tz::Tz;
+/// use imessage_database::util::dates::{get_time_in_zone, get_offset};
+///
+/// let current_offset = get_offset();
+/// let london = get_time_in_zone(&674526582885055488, &current_offset, Tz::Europe__London).unwrap();
+/// ```
+pub fn get_time_in_zone(
+    date_stamp: &i64,
+    offset: &i64,
+    tz: Tz,
+) -> Result<DateTime<Tz>, MessageError> {
+    let utc_stamp = DateTime::from_timestamp((date_stamp / TIMESTAMP_FACTOR) + offset, 0)
+        .ok_or(MessageError::InvalidTimestamp(*date_stamp))?
+        .naive_utc();
+    Ok(tz.from_utc_datetime(&utc_stamp))
+}
+
+/// Get the offset from UTC, in seconds, that a time zone uses on a calendar day this year
+///
+/// Calendar day filters match every year with this one offset, so messages sent within an hour of midnight in a
+/// year where daylight saving time started on a different date can land on the neighboring day. If the day does not
+/// exist this year, i.e. February 29th, the current offset is used.
+///
+/// # Example:
+///
+/// ```
+/// use chrono_tz::Tz;
+/// use imessage_database::util::dates::utc_offset_on_day;
+///
+/// assert_eq!(utc_offset_on_day(Tz::Asia__Tokyo, 5, 17), 9 * 3600);
+/// ```
+#[must_use]
+pub fn utc_offset_on_day(tz: Tz, month: u32, day: u32) -> i32 {
+    let now = Utc::now().with_timezone(&tz);
+    NaiveDate::from_ymd_opt(now.year(), month, day)
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .and_then(|noon| tz.from_local_datetime(&noon).earliest())
+        .map_or(now.offset().fix(), |date| date.offset().fix())
+        .local_minus_utc()
+}
+
 /// Format a date from the iMessage table for reading
 ///
 /// # Example:
@@ -62,7 +114,10 @@ pub fn get_local_time(date_stamp: &i64, offset: &i64) -> Result<DateTime<Local>,
 /// println!("{date}");
 /// ```
 #[must_use]
-pub fn format(date: &Result<DateTime<Local>, MessageError>) -> String {
+pub fn format<T: TimeZone>(date: &Result<DateTime<T>, MessageError>) -> String
+where
+    T::Offset: Display,
+{
     match date {
         Ok(d) => DateTime::format(d, "%b %d, %Y %l:%M:%S %p").to_string(),
         Err(why) => why.to_string(),
@@ -149,9 +204,10 @@ pub fn readable_diff(
 mod tests {
     use crate::{
         error::message::MessageError,
-        util::dates::{format, readable_diff},
+        util::dates::{format, get_offset, get_time_in_zone, readable_diff, utc_offset_on_day},
     };
     use chrono::prelude::*;
+    use chrono_tz::Tz;
 
     #[test]
     fn can_format_date_single_digit() {
@@ -171,6 +227,30 @@ mod tests {
         assert_eq!(format(&date), "May 20, 2020 10:10:11 AM");
     }
 
+    #[test]
+    fn can_format_date_in_zone() {
+        // 2022-05-17 12:00:00 UTC
+        let stamp = 674481600000000000;
+        let new_york = get_time_in_zone(&stamp, &get_offset(), Tz::America__New_York);
+        let london = get_time_in_zone(&stamp, &get_offset(), Tz::Europe__London);
+
+        assert_eq!(format(&new_york), "May 17, 2022  8:00:00 AM");
+        assert_eq!(format(&london), "May 17, 2022  1:00:00 PM");
+    }
+
+    #[test]
+    fn can_get_utc_offset_on_day() {
+        // Daylight saving time is in effect in July, but not in January
+        assert_eq!(utc_offset_on_day(Tz::America__New_York, 7, 4), -4 * 3600);
+        assert_eq!(utc_offset_on_day(Tz::America__New_York, 1, 2), -5 * 3600);
+        assert_eq!(utc_offset_on_day(Tz::UTC, 2, 29), 0);
+    }
+
+    #[test]
+    fn cant_get_time_in_zone_invalid() {
+        assert!(get_time_in_zone(&0, &(i64::MAX / 2), Tz::UTC).is_err());
+    }
+
     #[test]
     fn cant_format_diff_backwards() {
         let end = Ok(Local.with_ymd_and_hms(2020, 5, 20, 9, 10, 11).unwrap());
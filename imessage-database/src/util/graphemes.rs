@@ -0,0 +1,117 @@
+/*!
+ Contains logic for splitting text into user-perceived characters without breaking up emoji.
+
+ A single emoji can be made of many codepoints: skin tones, variation selectors, and
+ [ZWJ sequences](https://unicode.org/emoji/charts/emoji-zwj-sequences.html) like `👩🏽‍💻` all render as one
+ glyph. This is not a full implementation of [UAX #29](https://www.unicode.org/reports/tr29/), but it keeps
+ every emoji sequence Messages can store together.
+*/
+
+/// Zero width joiner, which glues two emoji into one glyph
+const ZWJ: char = '\u{200D}';
+
+/// Determine if `c` attaches to the character before it
+fn extends(c: char) -> bool {
+    matches!(c,
+        // Combining marks
+        '\u{0300}'..='\u{036F}'
+        // Variation selectors
+        | '\u{FE00}'..='\u{FE0F}'
+        // Combining enclosing keycap
+        | '\u{20E3}'
+        // Skin tone modifiers
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        // Tag characters, used by subdivision flags
+        | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Determine if `c` is half of a flag
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+/// Split text into grapheme clusters, keeping emoji sequences intact
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::graphemes::clusters;
+///
+/// assert_eq!(clusters("a👩🏽‍💻"), vec!["a", "👩🏽‍💻"]);
+/// ```
+#[must_use]
+pub fn clusters(text: &str) -> Vec<&str> {
+    let mut clusters = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, first)) = chars.next() {
+        let mut end = start + first.len_utf8();
+        let mut joined = false;
+        let mut flag = is_regional_indicator(first);
+
+        while let Some(&(idx, next)) = chars.peek() {
+            let attaches =
+                joined || next == ZWJ || extends(next) || (flag && is_regional_indicator(next));
+            if !attaches {
+                break;
+            }
+            // A pair of regional indicators is one flag; a third starts a new one
+            flag = false;
+            joined = next == ZWJ;
+            end = idx + next.len_utf8();
+            chars.next();
+        }
+
+        clusters.push(&text[start..end]);
+    }
+
+    clusters
+}
+
+/// Get the first grapheme cluster in `text`, or `None` if it is empty
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::graphemes::first_cluster;
+///
+/// assert_eq!(first_cluster("👍🏽 "), Some("👍🏽"));
+/// ```
+#[must_use]
+pub fn first_cluster(text: &str) -> Option<&str> {
+    clusters(text).into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::graphemes::{clusters, first_cluster};
+
+    #[test]
+    fn can_keep_zwj_sequence() {
+        assert_eq!(clusters("👨‍👩‍👧‍👦"), vec!["👨‍👩‍👧‍👦"]);
+        assert_eq!(clusters("🏳️‍🌈!"), vec!["🏳️‍🌈", "!"]);
+    }
+
+    #[test]
+    fn can_keep_skin_tone() {
+        assert_eq!(clusters("👍🏽👍"), vec!["👍🏽", "👍"]);
+    }
+
+    #[test]
+    fn can_keep_flags() {
+        assert_eq!(clusters("🇺🇸🇨🇦🇲"), vec!["🇺🇸", "🇨🇦", "🇲"]);
+        assert_eq!(clusters("🏴󠁧󠁢󠁳󠁣󠁴󠁿"), vec!["🏴󠁧󠁢󠁳󠁣󠁴󠁿"]);
+    }
+
+    #[test]
+    fn can_keep_keycap() {
+        assert_eq!(clusters("1️⃣2"), vec!["1️⃣", "2"]);
+    }
+
+    #[test]
+    fn can_split_plain_text() {
+        assert_eq!(clusters("héllo"), vec!["h", "é", "l", "l", "o"]);
+        assert_eq!(first_cluster(""), None);
+    }
+}
@@ -0,0 +1,118 @@
+/*!
+ Contains logic for grouping consecutive messages from the same sender, like the Messages app does for chat bubbles.
+*/
+
+use std::collections::HashMap;
+
+use crate::{tables::messages::Message, util::dates::TIMESTAMP_FACTOR};
+
+/// The default amount of time between two messages from the same sender before a new group is started
+pub const DEFAULT_GROUP_GAP_SECONDS: i64 = 60;
+
+/// Tracks runs of consecutive messages from the same sender in each chat
+///
+/// Messages must be provided in the order they are rendered. Each message starts a new group if
+/// the previous message in the same chat came from someone else, or was sent more than the gap
+/// threshold apart.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::grouping::{SenderGroups, DEFAULT_GROUP_GAP_SECONDS};
+///
+/// let mut groups = SenderGroups::new(DEFAULT_GROUP_GAP_SECONDS);
+/// ```
+#[derive(Debug)]
+pub struct SenderGroups {
+    /// The maximum gap between grouped messages, in the same units as message dates
+    gap: i64,
+    /// Map of chat ID to the sender and date of the most recent message in that chat
+    last: HashMap<Option<i32>, (Option<i32>, bool, i64)>,
+}
+
+impl SenderGroups {
+    /// Create a new tracker where messages more than `gap_seconds` apart start new groups
+    #[must_use]
+    pub fn new(gap_seconds: i64) -> Self {
+        Self {
+            gap: gap_seconds.saturating_mul(TIMESTAMP_FACTOR),
+            last: HashMap::new(),
+        }
+    }
+
+    /// Determine if a message starts a new group, recording it as the latest message in its chat
+    pub fn starts_group(&mut self, message: &Message) -> bool {
+        let chat_id = message.chat_id.or(message.deleted_from);
+        let sender = (message.handle_id, message.is_from_me());
+
+        let starts_group = match self.last.get(&chat_id) {
+            Some((handle_id, is_from_me, date)) => {
+                (*handle_id, *is_from_me) != sender || (message.date - date).abs() > self.gap
+            }
+            None => true,
+        };
+
+        self.last
+            .insert(chat_id, (sender.0, sender.1, message.date));
+        starts_group
+    }
+}
+
+impl Default for SenderGroups {
+    fn default() -> Self {
+        Self::new(DEFAULT_GROUP_GAP_SECONDS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tables::messages::Message,
+        util::{dates::TIMESTAMP_FACTOR, grouping::SenderGroups},
+    };
+
+    fn message(chat_id: i32, handle_id: i32, is_from_me: bool, seconds: i64) -> Message {
+        let mut m = Message::blank();
+        m.chat_id = Some(chat_id);
+        m.handle_id = Some(handle_id);
+        m.is_from_me = is_from_me;
+        m.date = seconds * TIMESTAMP_FACTOR;
+        m
+    }
+
+    #[test]
+    fn can_group_messages_within_threshold() {
+        let mut groups = SenderGroups::new(60);
+
+        assert!(groups.starts_group(&message(1, 1, false, 0)));
+        assert!(!groups.starts_group(&message(1, 1, false, 30)));
+        assert!(!groups.starts_group(&message(1, 1, false, 90)));
+    }
+
+    #[test]
+    fn can_split_messages_beyond_threshold() {
+        let mut groups = SenderGroups::new(60);
+
+        assert!(groups.starts_group(&message(1, 1, false, 0)));
+        assert!(groups.starts_group(&message(1, 1, false, 61)));
+    }
+
+    #[test]
+    fn can_split_messages_from_different_senders() {
+        let mut groups = SenderGroups::new(60);
+
+        assert!(groups.starts_group(&message(1, 1, false, 0)));
+        assert!(groups.starts_group(&message(1, 0, true, 1)));
+        assert!(!groups.starts_group(&message(1, 0, true, 2)));
+        assert!(groups.starts_group(&message(1, 2, false, 3)));
+    }
+
+    #[test]
+    fn can_track_chats_separately() {
+        let mut groups = SenderGroups::new(60);
+
+        assert!(groups.starts_group(&message(1, 1, false, 0)));
+        assert!(groups.starts_group(&message(2, 2, false, 1)));
+        assert!(!groups.starts_group(&message(1, 1, false, 2)));
+    }
+}
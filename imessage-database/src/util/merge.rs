@@ -0,0 +1,297 @@
+/*!
+ Contains logic for combining the messages from several databases, like a series of backups, into one export.
+
+ Each database is read with the query that matches its own schema, so backups from different versions of
+ iOS or macOS can be merged. Messages are de-duplicated by GUID. Chats are matched across databases by their
+ [`canonical_identifier()`](crate::tables::chat::Chat::canonical_identifier), since `ROWID`s are only meaningful
+ inside the database they come from. For the same reason, filters that select chats, handles, or messages by
+ `ROWID` cannot be used when merging.
+
+ Every matching message is read into memory, with its text generated, before the merged timeline is sorted, so
+ merging needs memory in proportion to the number of messages across all of the databases. Narrow large merges
+ with a date range on the [`QueryContext`].
+*/
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rusqlite::{Connection, params_from_iter};
+
+use crate::{
+    error::table::TableError,
+    tables::{
+        chat::Chat,
+        handle::Handle,
+        messages::Message,
+        table::{Cacheable, Table},
+    },
+    util::query_context::QueryContext,
+};
+
+/// A message read from one of several merged databases
+#[derive(Debug)]
+pub struct MergedMessage {
+    /// The index of the database the message was read from, in the order the databases were provided
+    pub source: usize,
+    /// The message, with its text already generated
+    pub message: Message,
+    /// The canonical identifier of the chat the message belongs to, if it belongs to one
+    pub chat: Option<String>,
+    /// The phone number or email address of the sender, or [`None`] if the database owner sent the message
+    pub sender: Option<String>,
+}
+
+/// The union of the messages in several databases
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// use imessage_database::tables::table::get_connection;
+/// use imessage_database::util::{merge::MergedMessages, query_context::QueryContext};
+///
+/// let old = get_connection(Path::new("backup_2020/chat.db")).unwrap();
+/// let new = get_connection(Path::new("backup_2024/chat.db")).unwrap();
+/// let merged = MergedMessages::from_databases(&[&old, &new], &QueryContext::default()).unwrap();
+///
+/// for (chat, messages) in merged.by_chat() {
+///     println!("{chat:?}: {} messages", messages.len());
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct MergedMessages {
+    /// Every unique message, oldest first
+    messages: Vec<MergedMessage>,
+}
+
+impl MergedMessages {
+    /// Read the messages that match the provided [`QueryContext`] from each database and merge them
+    ///
+    /// If the same GUID appears in more than one database, the copy from the earliest database in `dbs` is kept.
+    /// Every matching message is held in memory until the merge finishes.
+    ///
+    /// Returns [`TableError::CannotMerge`] if `context` selects chats, handles, or messages by `ROWID`, since those
+    /// refer to different rows in each database.
+    pub fn from_databases(dbs: &[&Connection], context: &QueryContext) -> Result<Self, TableError> {
+        if context.selected_chat_ids.is_some()
+            || context.selected_handle_ids.is_some()
+            || context.since_rowid.is_some()
+        {
+            return Err(TableError::CannotMerge(String::from(
+                "Chat, handle, and ROWID filters cannot be used when merging databases",
+            )));
+        }
+
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut messages = vec![];
+
+        for (source, db) in dbs.iter().enumerate() {
+            let chats: HashMap<i32, Chat> = Chat::cache(db)?;
+            let handles: HashMap<i32, String> = Handle::cache(db)?;
+
+            let mut statement = Message::stream_rows(db, context)?;
+            let rows = statement
+                .query_map(params_from_iter(context.predicate_params()), |row| {
+                    Ok(Message::from_row(row))
+                })
+                .map_err(TableError::Messages)?;
+
+            for row in rows {
+                let mut message = Message::extract(row)?;
+                if !seen.insert(message.guid.clone()) {
+                    continue;
+                }
+                let _ = message.generate_text(db);
+                let chat = message
+                    .chat_id
+                    .or(message.deleted_from)
+                    .and_then(|chat_id| chats.get(&chat_id))
+                    .map(|chat| chat.canonical_identifier().to_string());
+                let sender = message
+                    .handle_id
+                    .filter(|_| !message.is_from_me)
+                    .and_then(|handle_id| handles.get(&handle_id))
+                    .cloned();
+                messages.push(MergedMessage {
+                    source,
+                    message,
+                    chat,
+                    sender,
+                });
+            }
+        }
+
+        messages.sort_by_key(|merged| (merged.message.date, merged.source, merged.message.rowid));
+        Ok(Self { messages })
+    }
+
+    /// Every unique message in a single timeline, oldest first
+    #[must_use]
+    pub fn timeline(&self) -> &[MergedMessage] {
+        &self.messages
+    }
+
+    /// Group the messages by chat, oldest first within each chat
+    ///
+    /// Messages that do not belong to a chat are grouped under [`None`].
+    #[must_use]
+    pub fn by_chat(&self) -> BTreeMap<Option<&str>, Vec<&MergedMessage>> {
+        let mut chats: BTreeMap<Option<&str>, Vec<&MergedMessage>> = BTreeMap::new();
+        for merged in &self.messages {
+            chats
+                .entry(merged.chat.as_deref())
+                .or_default()
+                .push(merged);
+        }
+        chats
+    }
+
+    /// The number of unique messages
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// `true` if none of the databases contained a message
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rusqlite::Connection;
+
+    use crate::{
+        error::table::TableError,
+        tables::table::get_empty_test_connection,
+        util::{merge::MergedMessages, query_context::QueryContext},
+    };
+
+    /// A database with the current schema
+    fn new_database() -> Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier, service_name, style) VALUES (7, 'iMessage;-;+15558675309', '+15558675309', 'iMessage', 45);
+            INSERT INTO handle (ROWID, id, service) VALUES (1, '+15558675309', 'iMessage');
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date) VALUES
+                (1, 'A', 'Hello', 1, 0, 100),
+                (2, 'B', 'Hi!', 0, 1, 200);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (7, 1), (7, 2);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    /// A database with an older schema that has no threaded replies, edits, or recently deleted messages
+    fn old_database() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "
+            CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, handle_id INTEGER, service TEXT, date INTEGER, date_read INTEGER, date_delivered INTEGER, is_from_me INTEGER, is_read INTEGER, item_type INTEGER, other_handle INTEGER, share_status INTEGER, share_direction INTEGER, group_title TEXT, group_action_type INTEGER, associated_message_guid TEXT, associated_message_type INTEGER, balloon_bundle_id TEXT, expressive_send_style_id TEXT, subject TEXT);
+            CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, guid TEXT, chat_identifier TEXT, service_name TEXT, display_name TEXT);
+            CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+            CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT, person_centric_id TEXT);
+            INSERT INTO handle (ROWID, id) VALUES (2, '+15558675309'), (3, '+15550000000');
+            CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+            INSERT INTO chat (ROWID, guid, chat_identifier, service_name) VALUES (3, 'iMessage;-;+15558675309', '+15558675309', 'iMessage');
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, item_type, group_action_type) VALUES
+                (10, 'B', 'Hi!', 0, 1, 200, 0, 0, 0, 0),
+                (11, 'C', 'Dinner?', 2, 0, 150, 0, 0, 0, 0),
+                (12, 'D', 'Wrong number', 3, 0, 50, 0, 0, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (3, 10), (3, 11);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_merge_overlapping_databases() {
+        let new = new_database();
+        let old = old_database();
+
+        let merged =
+            MergedMessages::from_databases(&[&new, &old], &QueryContext::default()).unwrap();
+
+        // `B` is in both databases, but only the first copy is kept
+        let timeline: Vec<(&str, usize)> = merged
+            .timeline()
+            .iter()
+            .map(|merged| (merged.message.guid.as_str(), merged.source))
+            .collect();
+        assert_eq!(timeline, vec![("D", 1), ("A", 0), ("C", 1), ("B", 0)]);
+        assert_eq!(
+            merged.timeline()[2].message.text.as_deref(),
+            Some("Dinner?")
+        );
+    }
+
+    #[test]
+    fn can_group_merged_messages_by_chat() {
+        let new = new_database();
+        let old = old_database();
+
+        let merged =
+            MergedMessages::from_databases(&[&old, &new], &QueryContext::default()).unwrap();
+        let chats = merged.by_chat();
+
+        // The chat has a different `ROWID` in each database
+        let guids: Vec<&str> = chats[&Some("+15558675309")]
+            .iter()
+            .map(|merged| merged.message.guid.as_str())
+            .collect();
+        assert_eq!(guids, vec!["A", "C", "B"]);
+        assert_eq!(chats[&None].len(), 1);
+        assert_eq!(merged.len(), 4);
+    }
+
+    #[test]
+    fn can_get_sender_from_source_database() {
+        let new = new_database();
+        let old = old_database();
+
+        let merged =
+            MergedMessages::from_databases(&[&new, &old], &QueryContext::default()).unwrap();
+
+        let senders: Vec<(&str, Option<&str>)> = merged
+            .timeline()
+            .iter()
+            .map(|merged| (merged.message.guid.as_str(), merged.sender.as_deref()))
+            .collect();
+        assert_eq!(
+            senders,
+            vec![
+                ("D", Some("+15550000000")),
+                ("A", Some("+15558675309")),
+                ("C", Some("+15558675309")),
+                ("B", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn cant_merge_with_rowid_filters() {
+        let new = new_database();
+        let old = old_database();
+
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([7]));
+        assert!(matches!(
+            MergedMessages::from_databases(&[&new, &old], &context),
+            Err(TableError::CannotMerge(_))
+        ));
+
+        let mut context = QueryContext::default();
+        context.set_since_rowid(1);
+        assert!(matches!(
+            MergedMessages::from_databases(&[&new, &old], &context),
+            Err(TableError::CannotMerge(_))
+        ));
+    }
+}
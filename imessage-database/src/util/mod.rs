@@ -2,13 +2,22 @@
  This module defines common utilities used across table queries.
 */
 
+pub mod backup_manifest;
 pub mod bundle_id;
 pub mod dates;
 pub mod dirs;
+pub mod graphemes;
+pub mod grouping;
+pub mod merge;
 pub mod output;
 pub mod platform;
 pub mod plist;
+pub mod predicate;
 pub mod query_context;
+pub mod response_time;
+#[cfg(feature = "scripts")]
+pub mod script;
+pub mod search_index;
 pub mod size;
 pub mod streamtyped;
 pub mod typedstream;
@@ -1,8 +1,87 @@
 /*!
- Contains functions that emit a loading message while we do other work.
+ Contains functions that emit a loading message and diagnostic data while we do other work.
+
+ By default, progress is written to `stderr` and diagnostic data to `stdout`. Library consumers that
+ want to capture or suppress this output can install their own [`OutputSink`] with [`set_output_sink()`].
 */
 
-use std::io::{Write, stdout};
+use std::{
+    io::{Write, stderr},
+    sync::RwLock,
+};
+
+/// The sink that receives progress and diagnostic messages, or [`StdioSink`] if unset
+static SINK: RwLock<Option<Box<dyn OutputSink>>> = RwLock::new(None);
+
+/// Receives the progress and diagnostic messages emitted by the library
+pub trait OutputSink: Send + Sync {
+    /// Called when a long-running operation starts
+    fn processing(&self);
+    /// Called when a long-running operation finishes
+    fn done_processing(&self);
+    /// Called with a single line of diagnostic data
+    fn diagnostic(&self, message: &str);
+}
+
+/// Writes progress to `stderr` and diagnostic data to `stdout`
+pub struct StdioSink;
+
+impl OutputSink for StdioSink {
+    fn processing(&self) {
+        eprint!("\rProcessing...");
+        stderr().flush().unwrap_or_default();
+    }
+
+    fn done_processing(&self) {
+        eprint!("\r");
+        stderr().flush().unwrap_or_default();
+    }
+
+    fn diagnostic(&self, message: &str) {
+        println!("{message}");
+    }
+}
+
+/// Discards every message
+pub struct SilentSink;
+
+impl OutputSink for SilentSink {
+    fn processing(&self) {}
+
+    fn done_processing(&self) {}
+
+    fn diagnostic(&self, _: &str) {}
+}
+
+/// Send all future progress and diagnostic messages to `sink`
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::output::{SilentSink, set_output_sink};
+///
+/// set_output_sink(Box::new(SilentSink));
+/// ```
+pub fn set_output_sink(sink: Box<dyn OutputSink>) {
+    if let Ok(mut current) = SINK.write() {
+        *current = Some(sink);
+    }
+}
+
+/// Restore the default [`StdioSink`]
+pub fn reset_output_sink() {
+    if let Ok(mut current) = SINK.write() {
+        *current = None;
+    }
+}
+
+/// Run `emit` with the installed sink
+fn with_sink(emit: impl FnOnce(&dyn OutputSink)) {
+    match SINK.read() {
+        Ok(current) => emit(current.as_deref().unwrap_or(&StdioSink)),
+        Err(_) => emit(&StdioSink),
+    }
+}
 
 /// Write to the CLI while something is working so that we can overwrite it later
 ///
@@ -15,8 +94,7 @@ use std::io::{Write, stdout};
 /// println!("Done working!");
 /// ```
 pub fn processing() {
-    print!("\rProcessing...");
-    stdout().flush().unwrap_or_default();
+    with_sink(|sink| sink.processing());
 }
 
 /// Overwrite the CLI when something is done working so that we can write cleanly later
@@ -30,6 +108,60 @@ pub fn processing() {
 /// done_processing();
 /// ```
 pub fn done_processing() {
-    print!("\r");
-    stdout().flush().unwrap_or_default();
+    with_sink(|sink| sink.done_processing());
+}
+
+/// Emit a line of diagnostic data
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::output::diagnostic;
+///
+/// diagnostic("Message diagnostic data:");
+/// ```
+pub fn diagnostic(message: &str) {
+    with_sink(|sink| sink.diagnostic(message));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::util::output::{
+        OutputSink, diagnostic, done_processing, processing, reset_output_sink, set_output_sink,
+    };
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl OutputSink for Recorder {
+        fn processing(&self) {
+            self.0.lock().unwrap().push(String::from("processing"));
+        }
+
+        fn done_processing(&self) {
+            self.0.lock().unwrap().push(String::from("done"));
+        }
+
+        fn diagnostic(&self, message: &str) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn can_capture_messages_with_custom_sink() {
+        let received = Arc::new(Mutex::new(vec![]));
+        set_output_sink(Box::new(Recorder(received.clone())));
+
+        processing();
+        diagnostic("Total messages: 1");
+        done_processing();
+        reset_output_sink();
+
+        // Other tests may emit progress at the same time, so only check for our messages
+        let received = received.lock().unwrap();
+        assert!(received.contains(&String::from("processing")));
+        assert!(received.contains(&String::from("Total messages: 1")));
+        assert!(received.contains(&String::from("done")));
+    }
 }
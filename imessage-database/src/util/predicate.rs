@@ -0,0 +1,304 @@
+/*!
+ Contains logic for building typed filters on arbitrary columns of the messages table.
+
+ A [`Predicate`] compares a single column of the `message` table to one or more values. Values are never written into
+ the SQL: each one becomes a numbered parameter like `?1` that is bound when the query runs, and column names are only
+ accepted if they are plain identifiers. Add predicates to a query with
+ [`QueryContext::add_predicate()`](crate::util::query_context::QueryContext::add_predicate).
+*/
+
+use std::fmt::Write;
+
+use rusqlite::types::{ToSql, ToSqlOutput};
+
+use crate::error::query_context::QueryContextError;
+
+/// A value a [`Predicate`] compares a column to, bound as a query parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PredicateValue {
+    /// An integer, also used for boolean columns like `is_from_me`
+    Int(i64),
+    /// A string
+    Text(String),
+}
+
+impl ToSql for PredicateValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            PredicateValue::Int(value) => value.to_sql(),
+            PredicateValue::Text(value) => value.to_sql(),
+        }
+    }
+}
+
+impl From<i64> for PredicateValue {
+    fn from(value: i64) -> Self {
+        PredicateValue::Int(value)
+    }
+}
+
+impl From<i32> for PredicateValue {
+    fn from(value: i32) -> Self {
+        PredicateValue::Int(i64::from(value))
+    }
+}
+
+impl From<bool> for PredicateValue {
+    fn from(value: bool) -> Self {
+        PredicateValue::Int(i64::from(value))
+    }
+}
+
+impl From<&str> for PredicateValue {
+    fn from(value: &str) -> Self {
+        PredicateValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for PredicateValue {
+    fn from(value: String) -> Self {
+        PredicateValue::Text(value)
+    }
+}
+
+/// How a [`Predicate`] compares its column to its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    Like,
+    In,
+    IsNull,
+    IsNotNull,
+}
+
+/// A comparison between a column of the `message` table and bound values
+///
+/// Predicates can only be built with their constructors, which reject column names that are not plain identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Predicate {
+    /// The column of the `message` table to compare
+    column: String,
+    /// The comparison to make
+    comparison: Comparison,
+    /// The values to compare against, in the order their parameters appear
+    values: Vec<PredicateValue>,
+}
+
+impl Predicate {
+    /// Build a predicate after checking that `column` is safe to write into a query
+    fn new(
+        column: &str,
+        comparison: Comparison,
+        values: Vec<PredicateValue>,
+    ) -> Result<Self, QueryContextError> {
+        let mut chars = column.chars();
+        let is_identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(QueryContextError::InvalidColumn(column.to_string()));
+        }
+
+        Ok(Self {
+            column: column.to_string(),
+            comparison,
+            values,
+        })
+    }
+
+    /// Select messages where `column` equals `value`
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::predicate::Predicate;
+    ///
+    /// let predicate = Predicate::eq("is_from_me", true).unwrap();
+    /// assert_eq!(predicate.to_sql(1), "m.is_from_me = ?1");
+    /// ```
+    pub fn eq(column: &str, value: impl Into<PredicateValue>) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::Eq, vec![value.into()])
+    }
+
+    /// Select messages where `column` does not equal `value`
+    pub fn not_eq(
+        column: &str,
+        value: impl Into<PredicateValue>,
+    ) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::NotEq, vec![value.into()])
+    }
+
+    /// Select messages where `column` is less than `value`
+    pub fn lt(column: &str, value: impl Into<PredicateValue>) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::Lt, vec![value.into()])
+    }
+
+    /// Select messages where `column` is greater than `value`
+    pub fn gt(column: &str, value: impl Into<PredicateValue>) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::Gt, vec![value.into()])
+    }
+
+    /// Select messages where `column` matches a SQL `LIKE` pattern, where `%` and `_` are wildcards
+    pub fn like(column: &str, pattern: &str) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::Like, vec![pattern.into()])
+    }
+
+    /// Select messages where `column` equals any of `values`
+    ///
+    /// An empty list of values selects no messages.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::predicate::Predicate;
+    ///
+    /// let predicate = Predicate::is_in("service", ["SMS", "RCS"]).unwrap();
+    /// assert_eq!(predicate.to_sql(3), "m.service IN (?3, ?4)");
+    /// ```
+    pub fn is_in<T: Into<PredicateValue>>(
+        column: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<Self, QueryContextError> {
+        Self::new(
+            column,
+            Comparison::In,
+            values.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    /// Select messages where `column` is `NULL`
+    pub fn is_null(column: &str) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::IsNull, vec![])
+    }
+
+    /// Select messages where `column` is not `NULL`
+    pub fn is_not_null(column: &str) -> Result<Self, QueryContextError> {
+        Self::new(column, Comparison::IsNotNull, vec![])
+    }
+
+    /// The values bound to this predicate's parameters, in order
+    #[must_use]
+    pub fn values(&self) -> &[PredicateValue] {
+        &self.values
+    }
+
+    /// Render the predicate as SQL, numbering its parameters from `first_param`
+    ///
+    /// The predicate uses one parameter for each of its [`values()`](Self::values).
+    #[must_use]
+    pub fn to_sql(&self, first_param: usize) -> String {
+        let column = format!("m.{}", self.column);
+        let operator = match self.comparison {
+            Comparison::Eq => "=",
+            Comparison::NotEq => "!=",
+            Comparison::Lt => "<",
+            Comparison::Gt => ">",
+            Comparison::Like => "LIKE",
+            Comparison::In => {
+                let mut params = String::new();
+                for idx in 0..self.values.len() {
+                    if idx > 0 {
+                        params.push_str(", ");
+                    }
+                    let _ = write!(params, "?{}", first_param + idx);
+                }
+                return format!("{column} IN ({params})");
+            }
+            Comparison::IsNull => return format!("{column} IS NULL"),
+            Comparison::IsNotNull => return format!("{column} IS NOT NULL"),
+        };
+        format!("{column} {operator} ?{first_param}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::query_context::QueryContextError,
+        util::predicate::{Predicate, PredicateValue},
+    };
+
+    #[test]
+    fn can_render_comparisons() {
+        assert_eq!(
+            Predicate::eq("is_from_me", 1).unwrap().to_sql(1),
+            "m.is_from_me = ?1"
+        );
+        assert_eq!(
+            Predicate::not_eq("service", "SMS").unwrap().to_sql(2),
+            "m.service != ?2"
+        );
+        assert_eq!(Predicate::lt("date", 10).unwrap().to_sql(3), "m.date < ?3");
+        assert_eq!(Predicate::gt("date", 10).unwrap().to_sql(4), "m.date > ?4");
+        assert_eq!(
+            Predicate::like("subject", "%party%").unwrap().to_sql(1),
+            "m.subject LIKE ?1"
+        );
+    }
+
+    #[test]
+    fn can_render_null_checks() {
+        let predicate = Predicate::is_null("balloon_bundle_id").unwrap();
+        assert_eq!(predicate.to_sql(1), "m.balloon_bundle_id IS NULL");
+        assert!(predicate.values().is_empty());
+
+        assert_eq!(
+            Predicate::is_not_null("thread_originator_guid")
+                .unwrap()
+                .to_sql(1),
+            "m.thread_originator_guid IS NOT NULL"
+        );
+    }
+
+    #[test]
+    fn can_render_in_list() {
+        let predicate = Predicate::is_in("handle_id", [1, 2, 3]).unwrap();
+
+        assert_eq!(predicate.to_sql(2), "m.handle_id IN (?2, ?3, ?4)");
+        assert_eq!(
+            predicate.values(),
+            &[
+                PredicateValue::Int(1),
+                PredicateValue::Int(2),
+                PredicateValue::Int(3)
+            ]
+        );
+        assert_eq!(
+            Predicate::is_in("handle_id", Vec::<i32>::new())
+                .unwrap()
+                .to_sql(1),
+            "m.handle_id IN ()"
+        );
+    }
+
+    #[test]
+    fn can_convert_values() {
+        assert_eq!(PredicateValue::from(true), PredicateValue::Int(1));
+        assert_eq!(PredicateValue::from(7_i64), PredicateValue::Int(7));
+        assert_eq!(
+            PredicateValue::from("SMS"),
+            PredicateValue::Text(String::from("SMS"))
+        );
+    }
+
+    #[test]
+    fn cant_build_predicate_with_unsafe_column() {
+        for column in [
+            "",
+            "1date",
+            "is_from_me = 1 OR 1",
+            "text; DROP TABLE message",
+            "m.text",
+            "\"text\"",
+        ] {
+            assert!(matches!(
+                Predicate::eq(column, 1),
+                Err(QueryContextError::InvalidColumn(_))
+            ));
+        }
+    }
+}
@@ -3,13 +3,45 @@
 */
 use std::collections::BTreeSet;
 
+use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::Connection;
+
+use crate::{
+    error::{query_context::QueryContextError, table::TableError},
+    tables::chat::Chat,
+    util::{
+        dates::{TIMESTAMP_FACTOR, get_offset},
+        predicate::{Predicate, PredicateValue},
+    },
+};
+
 #[derive(Debug, Default, PartialEq, Eq)]
 /// Represents filter configurations for a SQL query.
 pub struct QueryContext {
     pub limit: Option<i32>,
+    /// The number of matching messages to skip before the first one selected
+    pub offset: Option<u64>,
     pub selected_handle_ids: Option<BTreeSet<i32>>,
     /// Selected chat IDs
     pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// Selected calendar day as `(month, day)`, matched across every year
+    pub on_this_day: Option<(u32, u32)>,
+    /// Seconds east of UTC that the calendar day is matched in, or the local time zone if unset
+    pub utc_offset: Option<i32>,
+    /// Only select messages that received at least one tapback
+    pub only_with_tapbacks: bool,
+    /// Only select messages that received at least one reply
+    pub only_with_replies: bool,
+    /// Only select messages whose `text` column contains this term
+    pub search_term: Option<String>,
+    /// Only select messages sent at or after this date, in nanoseconds since the iMessage epoch
+    pub start: Option<i64>,
+    /// Only select messages sent at or before this date, in nanoseconds since the iMessage epoch
+    pub end: Option<i64>,
+    /// Only select messages with a `ROWID` greater than this one, to continue an earlier export
+    pub since_rowid: Option<i32>,
+    /// Typed filters on columns of the `message` table, compiled into bound parameters
+    pub predicates: Vec<Predicate>,
 }
 
 impl QueryContext {
@@ -26,6 +58,26 @@ impl QueryContext {
         self.limit = Some(limit);
     }
 
+    /// Populate a [`QueryContext`] to select one page of `page_size` messages, where the first page is `0`
+    ///
+    /// Messages are sorted by date, and messages sent at the same time are sorted by `ROWID`, so consecutive pages
+    /// never overlap or skip a message as long as the database does not change between them.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_page(2, 50);
+    /// assert_eq!(context.limit, Some(50));
+    /// assert_eq!(context.offset, Some(100));
+    /// ```
+    pub fn set_page(&mut self, page: u64, page_size: i32) {
+        self.limit = Some(page_size);
+        self.offset = Some(page.saturating_mul(u64::try_from(page_size).unwrap_or_default()));
+    }
+
     /// Populate a [`QueryContext`] with a list of handle IDs to select
     ///
     /// # Example:
@@ -56,6 +108,209 @@ impl QueryContext {
         self.selected_chat_ids = (!selected_chat_ids.is_empty()).then_some(selected_chat_ids);
     }
 
+    /// Populate a [`QueryContext`] to only select messages from the chats a person would know by `identifier`
+    ///
+    /// The chats are found with [`Chat::get_ids_for_identifier()`]. Unlike [`Self::set_selected_chat_ids()`], an
+    /// identifier that matches no chats selects no messages instead of clearing the filter.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::{dirs::default_db_path, query_context::QueryContext};
+    /// use imessage_database::tables::table::get_connection;
+    ///
+    /// let conn = get_connection(&default_db_path()).unwrap();
+    /// let mut context = QueryContext::default();
+    /// context.set_chat_identifier(&conn, "+15558675309").unwrap();
+    /// ```
+    pub fn set_chat_identifier(
+        &mut self,
+        db: &Connection,
+        identifier: &str,
+    ) -> Result<(), TableError> {
+        let chat_ids = Chat::get_ids_for_identifier(db, identifier)?;
+        self.selected_chat_ids = Some(chat_ids.into_iter().collect());
+        Ok(())
+    }
+
+    /// Populate a [`QueryContext`] with a calendar day to select messages from across every year
+    ///
+    /// The month and day are matched against the message date in the local time zone, unless
+    /// [`Self::set_utc_offset()`] selects a different one.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_on_this_day(5, 17).unwrap();
+    /// ```
+    pub fn set_on_this_day(&mut self, month: u32, day: u32) -> Result<(), QueryContextError> {
+        // Use a leap year so that February 29th is valid
+        if NaiveDate::from_ymd_opt(2000, month, day).is_none() {
+            return Err(QueryContextError::InvalidDate(format!(
+                "{month:02}-{day:02}"
+            )));
+        }
+        self.on_this_day = Some((month, day));
+        Ok(())
+    }
+
+    /// Populate a [`QueryContext`] with the offset from UTC, in seconds, that calendar days are matched in
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_on_this_day(5, 17).unwrap();
+    /// context.set_utc_offset(-4 * 3600);
+    /// ```
+    pub fn set_utc_offset(&mut self, seconds: i32) {
+        self.utc_offset = Some(seconds);
+    }
+
+    /// Populate a [`QueryContext`] to only select messages that received at least one tapback
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_only_with_tapbacks(true);
+    /// ```
+    pub fn set_only_with_tapbacks(&mut self, only_with_tapbacks: bool) {
+        self.only_with_tapbacks = only_with_tapbacks;
+    }
+
+    /// Populate a [`QueryContext`] to only select messages that received at least one reply
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_only_with_replies(true);
+    /// ```
+    pub fn set_only_with_replies(&mut self, only_with_replies: bool) {
+        self.only_with_replies = only_with_replies;
+    }
+
+    /// Populate a [`QueryContext`] to only select messages whose text contains a search term
+    ///
+    /// The term is matched case-insensitively for ASCII characters, and `%`, `_`, and quotes in it are matched
+    /// literally. Only the `text` column is searched: messages whose content is only stored in `attributedBody`,
+    /// which have a `NULL` `text` column, are never selected. An empty term clears the filter.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_search("dinner");
+    /// assert!(context.has_filters());
+    /// ```
+    pub fn set_search(&mut self, term: impl Into<String>) {
+        let term = term.into();
+        self.search_term = (!term.is_empty()).then_some(term);
+    }
+
+    /// Populate a [`QueryContext`] to only select messages sent at or after a date
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use chrono::{Local, TimeZone};
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_start(&Local.with_ymd_and_hms(2020, 5, 17, 0, 0, 0).unwrap());
+    /// assert!(context.has_filters());
+    /// ```
+    pub fn set_start(&mut self, start: &DateTime<Local>) {
+        self.start = Some(Self::apple_timestamp(start));
+    }
+
+    /// Populate a [`QueryContext`] to only select messages sent at or before a date
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use chrono::{Local, TimeZone};
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_end(&Local.with_ymd_and_hms(2020, 5, 17, 0, 0, 0).unwrap());
+    /// assert!(context.has_filters());
+    /// ```
+    pub fn set_end(&mut self, end: &DateTime<Local>) {
+        self.end = Some(Self::apple_timestamp(end));
+    }
+
+    /// Populate a [`QueryContext`] to only select messages added after the message with the given `ROWID`
+    ///
+    /// Rows are added to the messages table in increasing `ROWID` order, so passing the largest `ROWID` from an
+    /// earlier export selects only the messages that arrived since then.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_since_rowid(1024);
+    /// assert_eq!(context.since_rowid, Some(1024));
+    /// assert!(context.has_filters());
+    /// ```
+    pub fn set_since_rowid(&mut self, rowid: i32) {
+        self.since_rowid = Some(rowid);
+    }
+
+    /// Populate a [`QueryContext`] to only select messages that match a [`Predicate`]
+    ///
+    /// Every predicate must match. The values of each predicate are bound as query parameters, in the order returned
+    /// by [`Self::predicate_params()`].
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::{predicate::Predicate, query_context::QueryContext};
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.add_predicate(Predicate::eq("is_from_me", true).unwrap());
+    /// context.add_predicate(Predicate::is_in("service", ["SMS", "RCS"]).unwrap());
+    /// assert_eq!(context.predicate_params().count(), 3);
+    /// assert!(context.has_filters());
+    /// ```
+    pub fn add_predicate(&mut self, predicate: Predicate) {
+        self.predicates.push(predicate);
+    }
+
+    /// The values bound to the parameters of the search term, date range, and every [`Predicate`], in the order they
+    /// appear in the query
+    ///
+    /// The search term, if any, is bound first as a `LIKE` pattern that matches it literally, followed by the start and
+    /// end dates. Statements built from a context with any of these filters must be bound with these, for example with
+    /// [`rusqlite::params_from_iter()`].
+    pub fn predicate_params(&self) -> impl Iterator<Item = PredicateValue> + '_ {
+        self.search_term
+            .iter()
+            .map(|term| PredicateValue::Text(format!("%{}%", escape_like_pattern(term))))
+            .chain(self.start.map(PredicateValue::Int))
+            .chain(self.end.map(PredicateValue::Int))
+            .chain(self.predicates.iter().flat_map(Predicate::values).cloned())
+    }
+
+    /// Convert a date to nanoseconds since the iMessage epoch, the unit of the `date` column
+    fn apple_timestamp(date: &DateTime<Local>) -> i64 {
+        (date.timestamp() - get_offset()).saturating_mul(TIMESTAMP_FACTOR)
+    }
+
     /// Determine if the current `QueryContext` has any filters present
     ///
     /// # Example:
@@ -71,15 +326,37 @@ impl QueryContext {
     #[must_use]
     pub fn has_filters(&self) -> bool {
         self.limit.is_some()
+            || self.offset.is_some()
             || self.selected_chat_ids.is_some()
             || self.selected_handle_ids.is_some()
+            || self.on_this_day.is_some()
+            || self.only_with_tapbacks
+            || self.only_with_replies
+            || self.search_term.is_some()
+            || self.start.is_some()
+            || self.end.is_some()
+            || self.since_rowid.is_some()
+            || !self.predicates.is_empty()
+    }
+}
+
+/// Escape the wildcards in a `LIKE` pattern that uses `\` as its escape character
+pub(crate) fn escape_like_pattern(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
 #[cfg(test)]
 mod use_tests {
     use crate::util::{
-        query_context::QueryContext,
+        predicate::{Predicate, PredicateValue},
+        query_context::{QueryContext, escape_like_pattern},
     };
 
     #[test]
@@ -99,6 +376,67 @@ mod use_tests {
         assert!(context.has_filters());
     }
 
+    #[test]
+    fn can_create_page() {
+        let mut context = QueryContext::default();
+        context.set_page(0, 25);
+        assert_eq!(context.limit, Some(25));
+        assert_eq!(context.offset, Some(0));
+        assert!(context.has_filters());
+
+        context.set_page(3, 25);
+        assert_eq!(context.offset, Some(75));
+    }
+
+    #[test]
+    fn can_create_engagement_filters() {
+        let mut context = QueryContext::default();
+        context.set_only_with_tapbacks(true);
+        assert!(context.has_filters());
+
+        context.set_only_with_tapbacks(false);
+        context.set_only_with_replies(true);
+        assert!(context.has_filters());
+
+        context.set_only_with_replies(false);
+        assert!(!context.has_filters());
+    }
+
+    #[test]
+    fn can_create_search() {
+        let mut context = QueryContext::default();
+        context.set_search("dinner");
+        assert_eq!(context.search_term.as_deref(), Some("dinner"));
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![PredicateValue::from("%dinner%")]
+        );
+        assert!(context.has_filters());
+
+        context.set_search("");
+        assert!(context.search_term.is_none());
+        assert!(!context.has_filters());
+    }
+
+    #[test]
+    fn can_escape_like_pattern() {
+        assert_eq!(escape_like_pattern("50%_off\\"), "50\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn can_create_predicates() {
+        let mut context = QueryContext::default();
+        context.add_predicate(Predicate::eq("is_from_me", true).unwrap());
+        context.add_predicate(Predicate::is_not_null("subject").unwrap());
+        context.add_predicate(Predicate::lt("date", 100).unwrap());
+
+        assert_eq!(context.predicates.len(), 3);
+        assert_eq!(
+            context.predicate_params().collect::<Vec<_>>(),
+            vec![PredicateValue::Int(1), PredicateValue::Int(100)]
+        );
+        assert!(context.has_filters());
+    }
 }
 
 #[cfg(test)]
@@ -163,3 +501,70 @@ mod id_tests {
         assert!(!qc.has_filters());
     }
 }
+
+#[cfg(test)]
+mod date_range_tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::util::{
+        dates::{get_local_time, get_offset},
+        query_context::QueryContext,
+    };
+
+    #[test]
+    fn can_set_start() {
+        let date = Local.with_ymd_and_hms(2020, 5, 17, 12, 30, 0).unwrap();
+        let mut context = QueryContext::default();
+        context.set_start(&date);
+
+        // The bound converts back to the same local date
+        assert_eq!(
+            get_local_time(&context.start.unwrap(), &get_offset()).unwrap(),
+            date
+        );
+        assert!(context.end.is_none());
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_set_end() {
+        let date = Local.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+        let mut context = QueryContext::default();
+        context.set_end(&date);
+
+        assert_eq!(
+            get_local_time(&context.end.unwrap(), &get_offset()).unwrap(),
+            date
+        );
+        assert!(context.start.is_none());
+        assert!(context.has_filters());
+    }
+}
+
+#[cfg(test)]
+mod on_this_day_tests {
+    use crate::util::query_context::QueryContext;
+
+    #[test]
+    fn can_set_on_this_day() {
+        let mut qc = QueryContext::default();
+        qc.set_on_this_day(5, 17).unwrap();
+
+        assert_eq!(qc.on_this_day, Some((5, 17)));
+        assert!(qc.has_filters());
+    }
+
+    #[test]
+    fn can_set_on_this_day_leap_day() {
+        let mut qc = QueryContext::default();
+        assert!(qc.set_on_this_day(2, 29).is_ok());
+    }
+
+    #[test]
+    fn cant_set_on_this_day_invalid() {
+        let mut qc = QueryContext::default();
+        assert!(qc.set_on_this_day(2, 30).is_err());
+        assert!(qc.set_on_this_day(13, 1).is_err());
+        assert!(!qc.has_filters());
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+ Contains logic for measuring how long it takes the database owner and their contacts to reply to each other.
+*/
+
+use std::collections::HashMap;
+
+use crate::{tables::messages::Message, util::dates::TIMESTAMP_FACTOR};
+
+/// Summary of the response times in one direction of a conversation, in seconds
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ResponseStats {
+    /// The number of replies measured
+    pub count: usize,
+    /// The mean time to reply, or `None` if there were no replies
+    pub mean: Option<i64>,
+    /// The median time to reply, or `None` if there were no replies
+    pub median: Option<i64>,
+}
+
+impl ResponseStats {
+    /// Summarize a list of response times, in seconds
+    fn from_seconds(mut seconds: Vec<i64>) -> Self {
+        if seconds.is_empty() {
+            return Self::default();
+        }
+        seconds.sort_unstable();
+
+        let count = seconds.len();
+        let total: i64 = seconds.iter().sum();
+        let median = if count.is_multiple_of(2) {
+            (seconds[count / 2 - 1] + seconds[count / 2]) / 2
+        } else {
+            seconds[count / 2]
+        };
+
+        Self {
+            count,
+            mean: Some(total / count as i64),
+            median: Some(median),
+        }
+    }
+}
+
+/// Response times for both directions of a single conversation
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConversationResponseTimes {
+    /// Time for the database owner to reply to a received message
+    pub owner: ResponseStats,
+    /// Time for a contact to reply to a message the database owner sent
+    pub contacts: ResponseStats,
+}
+
+/// Collects messages to measure the time between a message and its reply in each chat
+///
+/// A reply is the first message after a message from the other side of the conversation: only adjacent
+/// messages where one was sent by the database owner and the other was received are paired. In a run
+/// of several messages from the same side, only the last one is answered by the reply. Messages between
+/// two contacts in a group chat do not count as replies. Tapbacks and announcements are ignored.
+///
+/// Messages can be recorded in any order.
+///
+/// # Example:
+///
+/// ```no_run
+/// use imessage_database::util::dirs::default_db_path;
+/// use imessage_database::tables::table::get_connection;
+/// use imessage_database::tables::messages::Message;
+/// use imessage_database::util::{query_context::QueryContext, response_time::ResponseTimes};
+///
+/// let db_path = default_db_path();
+/// let conn = get_connection(&db_path).unwrap();
+/// let context = QueryContext::default();
+///
+/// let mut response_times = ResponseTimes::default();
+/// for message in Message::iter(&conn, &context).unwrap() {
+///     response_times.record(&message.unwrap());
+/// }
+///
+/// for (chat_id, times) in response_times.per_chat() {
+///     println!("{chat_id}: {:?} {:?}", times.owner.median, times.contacts.median);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ResponseTimes {
+    /// Map of chat ID to the date and `is_from_me` flag of each message in that chat
+    timelines: HashMap<i32, Vec<(i64, bool)>>,
+}
+
+impl ResponseTimes {
+    /// Add a message to the conversation it belongs to
+    pub fn record(&mut self, message: &Message) {
+        if message.is_tapback() || message.is_announcement() {
+            return;
+        }
+        if let Some(chat_id) = message.chat_id.or(message.deleted_from) {
+            self.timelines
+                .entry(chat_id)
+                .or_default()
+                .push((message.date, message.is_from_me()));
+        }
+    }
+
+    /// Compute the response times for each chat, keyed by chat ID
+    #[must_use]
+    pub fn per_chat(&self) -> HashMap<i32, ConversationResponseTimes> {
+        self.timelines
+            .iter()
+            .map(|(chat_id, timeline)| {
+                let (owner, contacts) = Self::replies(timeline);
+                (
+                    *chat_id,
+                    ConversationResponseTimes {
+                        owner: ResponseStats::from_seconds(owner),
+                        contacts: ResponseStats::from_seconds(contacts),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Compute the response times across every chat
+    #[must_use]
+    pub fn overall(&self) -> ConversationResponseTimes {
+        let mut owner = vec![];
+        let mut contacts = vec![];
+        for timeline in self.timelines.values() {
+            let (chat_owner, chat_contacts) = Self::replies(timeline);
+            owner.extend(chat_owner);
+            contacts.extend(chat_contacts);
+        }
+        ConversationResponseTimes {
+            owner: ResponseStats::from_seconds(owner),
+            contacts: ResponseStats::from_seconds(contacts),
+        }
+    }
+
+    /// Find the seconds taken by the owner and by contacts to reply in a single chat
+    fn replies(timeline: &[(i64, bool)]) -> (Vec<i64>, Vec<i64>) {
+        let mut timeline = timeline.to_vec();
+        timeline.sort_by_key(|(date, _)| *date);
+
+        let mut owner = vec![];
+        let mut contacts = vec![];
+        for pair in timeline.windows(2) {
+            let ((previous_date, previous_from_me), (date, from_me)) = (pair[0], pair[1]);
+            if previous_from_me == from_me {
+                continue;
+            }
+            let seconds = (date - previous_date) / TIMESTAMP_FACTOR;
+            if from_me {
+                owner.push(seconds);
+            } else {
+                contacts.push(seconds);
+            }
+        }
+        (owner, contacts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tables::messages::Message,
+        util::{
+            dates::TIMESTAMP_FACTOR,
+            response_time::{ResponseStats, ResponseTimes},
+        },
+    };
+
+    fn message(chat_id: i32, is_from_me: bool, seconds: i64) -> Message {
+        let mut m = Message::blank();
+        m.chat_id = Some(chat_id);
+        m.handle_id = Some(if is_from_me { 0 } else { 1 });
+        m.is_from_me = is_from_me;
+        m.date = seconds * TIMESTAMP_FACTOR;
+        m
+    }
+
+    #[test]
+    fn can_measure_back_and_forth() {
+        let mut response_times = ResponseTimes::default();
+        // Recorded newest first, like messages streamed from the database
+        for m in [
+            message(1, true, 1000),
+            message(1, false, 700),
+            message(1, false, 600),
+            message(1, true, 400),
+            message(1, true, 300),
+            message(1, false, 100),
+            message(1, false, 0),
+        ] {
+            response_times.record(&m);
+        }
+
+        let stats = response_times.per_chat().remove(&1).unwrap();
+
+        // The owner replied after 200 and 300 seconds
+        assert_eq!(
+            stats.owner,
+            ResponseStats {
+                count: 2,
+                mean: Some(250),
+                median: Some(250)
+            }
+        );
+        // Contacts replied once, 200 seconds after the last message in the owner's run
+        assert_eq!(
+            stats.contacts,
+            ResponseStats {
+                count: 1,
+                mean: Some(200),
+                median: Some(200)
+            }
+        );
+    }
+
+    #[test]
+    fn can_track_chats_separately() {
+        let mut response_times = ResponseTimes::default();
+        for m in [
+            message(1, false, 0),
+            message(2, true, 10),
+            message(1, true, 20),
+        ] {
+            response_times.record(&m);
+        }
+
+        let per_chat = response_times.per_chat();
+
+        assert_eq!(per_chat[&1].owner.mean, Some(20));
+        assert_eq!(per_chat[&2].owner.count, 0);
+        assert_eq!(per_chat[&2].contacts.count, 0);
+    }
+
+    #[test]
+    fn can_combine_chats() {
+        let mut response_times = ResponseTimes::default();
+        for m in [
+            message(1, false, 0),
+            message(1, true, 10),
+            message(2, false, 100),
+            message(2, true, 130),
+            message(2, false, 190),
+        ] {
+            response_times.record(&m);
+        }
+
+        let overall = response_times.overall();
+
+        // Replies are only paired within a chat
+        assert_eq!(
+            overall.owner,
+            ResponseStats {
+                count: 2,
+                mean: Some(20),
+                median: Some(20)
+            }
+        );
+        assert_eq!(overall.contacts.mean, Some(60));
+    }
+
+    #[test]
+    fn can_ignore_tapbacks() {
+        let mut response_times = ResponseTimes::default();
+        let mut tapback = message(1, true, 5);
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some(String::from("p:0/A"));
+
+        for m in [message(1, false, 0), tapback, message(1, true, 60)] {
+            response_times.record(&m);
+        }
+
+        let stats = response_times.per_chat().remove(&1).unwrap();
+        assert_eq!(stats.owner.mean, Some(60));
+    }
+
+    #[test]
+    fn can_compute_odd_median() {
+        let stats = ResponseStats::from_seconds(vec![30, 10, 1000]);
+        assert_eq!(stats.median, Some(30));
+        assert_eq!(stats.mean, Some(346));
+    }
+}
@@ -0,0 +1,268 @@
+/*!
+ Contains logic for estimating which writing systems a conversation is written in.
+
+ The analysis is a heuristic: it classifies the letters in a sample of message text by Unicode script and
+ reports the scripts that make up a meaningful share of them. Exporters can use the result to pick fonts or
+ fallbacks. This module is only compiled with the `scripts` feature, which is off by default.
+*/
+
+use std::{collections::HashMap, fmt::Display};
+
+use rusqlite::{Connection, params_from_iter};
+
+use crate::{
+    error::table::TableError,
+    tables::{messages::Message, table::Table},
+    util::query_context::QueryContext,
+};
+
+/// The maximum number of messages sampled from each chat
+pub const MAX_SAMPLES: usize = 500;
+/// The minimum number of letters needed before a dominant script is reported
+pub const MIN_LETTERS: usize = 20;
+/// The share of sampled letters, in percent, a script needs to be considered dominant
+const DOMINANT_SHARE: usize = 20;
+
+/// A Unicode script, or writing system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    /// Japanese Hiragana and Katakana
+    Kana,
+    /// Chinese characters, also used in Japanese and Korean text
+    Han,
+    /// A letter from a script not listed here, or a sample with too little text to classify
+    Unknown,
+}
+
+impl Script {
+    /// Classify a character, returning `None` if it is not a letter
+    #[must_use]
+    pub fn from_char(c: char) -> Option<Self> {
+        if !c.is_alphabetic() {
+            return None;
+        }
+        Some(match u32::from(c) {
+            0x0041..=0x024F
+            | 0x1E00..=0x1EFF
+            | 0x2C60..=0x2C7F
+            | 0xA720..=0xA7FF
+            | 0xFF21..=0xFF5A => Self::Latin,
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Self::Greek,
+            0x0400..=0x052F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Self::Cyrillic,
+            0x0530..=0x058F => Self::Armenian,
+            0x0590..=0x05FF => Self::Hebrew,
+            0x0600..=0x06FF
+            | 0x0750..=0x077F
+            | 0x08A0..=0x08FF
+            | 0xFB50..=0xFDFF
+            | 0xFE70..=0xFEFF => Self::Arabic,
+            0x0900..=0x097F => Self::Devanagari,
+            0x0980..=0x09FF => Self::Bengali,
+            0x0E00..=0x0E7F => Self::Thai,
+            0x10A0..=0x10FF => Self::Georgian,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Self::Hangul,
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Self::Kana,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3134F => Self::Han,
+            _ => Self::Unknown,
+        })
+    }
+}
+
+impl Display for Script {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Script::Unknown => write!(fmt, "unknown"),
+            script => write!(fmt, "{script:?}"),
+        }
+    }
+}
+
+/// Counts the letters of each script in a sample of text
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::script::{Script, ScriptAnalyzer};
+///
+/// let mut analyzer = ScriptAnalyzer::default();
+/// analyzer.sample("Привет! Как дела? Давно не виделись.");
+/// assert_eq!(analyzer.dominant(), vec![Script::Cyrillic]);
+/// ```
+#[derive(Debug, Default)]
+pub struct ScriptAnalyzer {
+    /// Map of script to the number of letters seen in that script
+    counts: HashMap<Script, usize>,
+    /// The number of texts sampled so far
+    samples: usize,
+}
+
+impl ScriptAnalyzer {
+    /// Count the letters in a message's text
+    ///
+    /// Returns `false` once [`MAX_SAMPLES`] texts have been counted, after which further text is ignored.
+    pub fn sample(&mut self, text: &str) -> bool {
+        if self.samples >= MAX_SAMPLES {
+            return false;
+        }
+        self.samples += 1;
+        for script in text.chars().filter_map(Script::from_char) {
+            *self.counts.entry(script).or_default() += 1;
+        }
+        true
+    }
+
+    /// The scripts that make up a meaningful share of the sampled letters, most common first
+    ///
+    /// Returns [`Script::Unknown`] on its own if fewer than [`MIN_LETTERS`] letters were sampled.
+    #[must_use]
+    pub fn dominant(&self) -> Vec<Script> {
+        let total: usize = self.counts.values().sum();
+        if total < MIN_LETTERS {
+            return vec![Script::Unknown];
+        }
+        let mut scripts: Vec<(Script, usize)> = self
+            .counts
+            .iter()
+            .filter(|(_, count)| **count * 100 >= total * DOMINANT_SHARE)
+            .map(|(script, count)| (*script, *count))
+            .collect();
+        scripts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        scripts.into_iter().map(|(script, _)| script).collect()
+    }
+}
+
+/// Estimate the dominant scripts of every chat that matches the provided [`QueryContext`]
+///
+/// Up to [`MAX_SAMPLES`] of each chat's messages are sampled, oldest first. Messages without a chat are skipped.
+///
+/// # Example:
+///
+/// ```no_run
+/// use imessage_database::util::dirs::default_db_path;
+/// use imessage_database::tables::table::get_connection;
+/// use imessage_database::util::{query_context::QueryContext, script::scripts_by_chat};
+///
+/// let db_path = default_db_path();
+/// let conn = get_connection(&db_path).unwrap();
+/// let scripts = scripts_by_chat(&conn, &QueryContext::default()).unwrap();
+/// ```
+pub fn scripts_by_chat(
+    db: &Connection,
+    context: &QueryContext,
+) -> Result<HashMap<i32, Vec<Script>>, TableError> {
+    let mut analyzers: HashMap<i32, ScriptAnalyzer> = HashMap::new();
+
+    let mut statement = Message::stream_rows(db, context)?;
+    let messages = statement
+        .query_map(params_from_iter(context.predicate_params()), |row| {
+            Ok(Message::from_row(row))
+        })
+        .map_err(TableError::Messages)?;
+
+    for message in messages {
+        let mut message = Message::extract(message)?;
+        let Some(chat_id) = message.chat_id.or(message.deleted_from) else {
+            continue;
+        };
+        let analyzer = analyzers.entry(chat_id).or_default();
+        if analyzer.samples >= MAX_SAMPLES || message.is_tapback() {
+            continue;
+        }
+        if let Ok(text) = message.generate_text(db) {
+            analyzer.sample(text);
+        }
+    }
+
+    Ok(analyzers
+        .into_iter()
+        .map(|(chat_id, analyzer)| (chat_id, analyzer.dominant()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read_to_string};
+
+    use crate::{
+        tables::table::get_connection,
+        util::{
+            query_context::QueryContext,
+            script::{Script, ScriptAnalyzer, scripts_by_chat},
+        },
+    };
+
+    fn fixture(name: &str) -> String {
+        read_to_string(current_dir().unwrap().join("test_data/scripts").join(name)).unwrap()
+    }
+
+    fn dominant(text: &str) -> Vec<Script> {
+        let mut analyzer = ScriptAnalyzer::default();
+        for line in text.lines() {
+            analyzer.sample(line);
+        }
+        analyzer.dominant()
+    }
+
+    #[test]
+    fn can_classify_letters() {
+        assert_eq!(Script::from_char('a'), Some(Script::Latin));
+        assert_eq!(Script::from_char('é'), Some(Script::Latin));
+        assert_eq!(Script::from_char('ж'), Some(Script::Cyrillic));
+        assert_eq!(Script::from_char('ש'), Some(Script::Hebrew));
+        assert_eq!(Script::from_char('ね'), Some(Script::Kana));
+        assert_eq!(Script::from_char('한'), Some(Script::Hangul));
+        assert_eq!(Script::from_char('字'), Some(Script::Han));
+        assert_eq!(Script::from_char('1'), None);
+        assert_eq!(Script::from_char('!'), None);
+        assert_eq!(Script::from_char('❤'), None);
+    }
+
+    #[test]
+    fn can_find_dominant_script() {
+        assert_eq!(dominant(&fixture("greek.txt")), vec![Script::Greek]);
+    }
+
+    #[test]
+    fn can_find_mixed_scripts() {
+        assert_eq!(
+            dominant(&fixture("japanese_english.txt")),
+            vec![Script::Latin, Script::Kana]
+        );
+    }
+
+    #[test]
+    fn cant_classify_short_text() {
+        assert_eq!(dominant("ok 👍"), vec![Script::Unknown]);
+        assert_eq!(dominant(""), vec![Script::Unknown]);
+        assert_eq!(Script::Unknown.to_string(), "unknown");
+        assert_eq!(Script::Cyrillic.to_string(), "Cyrillic");
+    }
+
+    #[test]
+    fn can_stop_sampling() {
+        let mut analyzer = ScriptAnalyzer::default();
+        assert!((0..super::MAX_SAMPLES).all(|_| analyzer.sample("a")));
+        assert!(!analyzer.sample("a"));
+    }
+
+    #[test]
+    fn can_skip_messages_without_chat() {
+        let db_path = current_dir().unwrap().join("test_data/db/test.db");
+        let conn = get_connection(&db_path).unwrap();
+
+        // The only message in the test database is not joined to a chat
+        let scripts = scripts_by_chat(&conn, &QueryContext::default()).unwrap();
+        assert!(scripts.is_empty());
+    }
+}
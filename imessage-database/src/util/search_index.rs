@@ -0,0 +1,189 @@
+/*!
+ Contains logic for building an opt-in full-text index over message text, for sessions that search many times.
+
+ Filtering with [`QueryContext::set_search()`](crate::util::query_context::QueryContext::set_search) scans the
+ `text` column with `LIKE` on every query, which is slow on large databases. A [`SearchIndex`] reads every message
+ once, including text that is only stored in the `attributedBody` typedstream, and copies it into an in-memory
+ [FTS5](https://www.sqlite.org/fts5.html) table. Building the index blocks until every message is read, so it is
+ only worth it when the same database is searched repeatedly.
+
+ If the linked SQLite was built without FTS5, the index falls back to a plain in-memory table searched with `LIKE`,
+ which is still faster than querying the messages table but matches substrings instead of whole words.
+*/
+
+use rusqlite::{Connection, params};
+
+use crate::{
+    error::table::TableError,
+    tables::messages::Message,
+    util::query_context::{QueryContext, escape_like_pattern},
+};
+
+/// The name of the table in the index database that holds the text of each message
+const INDEX_TABLE: &str = "message_text";
+
+/// An in-memory index of message text, built once with [`SearchIndex::build()`]
+pub struct SearchIndex {
+    /// The in-memory database that holds the index
+    index: Connection,
+    /// `true` if the index is an FTS5 table, `false` if it is a plain table searched with `LIKE`
+    fts5: bool,
+}
+
+impl SearchIndex {
+    /// Read the text of every message in `db` into a new in-memory index
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::util::search_index::SearchIndex;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let index = SearchIndex::build(&conn).unwrap();
+    /// for rowid in index.search_fts("dinner").unwrap() {
+    ///     println!("{rowid}");
+    /// }
+    /// ```
+    pub fn build(db: &Connection) -> Result<Self, TableError> {
+        let index = Connection::open_in_memory().map_err(TableError::Messages)?;
+        let fts5 = index
+            .execute_batch(&format!(
+                "CREATE VIRTUAL TABLE {INDEX_TABLE} USING fts5(text)"
+            ))
+            .is_ok();
+        Self::build_with(db, index, fts5)
+    }
+
+    /// Fill the index, creating a plain table first if FTS5 is not available
+    fn build_with(db: &Connection, mut index: Connection, fts5: bool) -> Result<Self, TableError> {
+        if !fts5 {
+            index
+                .execute_batch(&format!(
+                    "CREATE TABLE {INDEX_TABLE} (rowid INTEGER PRIMARY KEY, text TEXT)"
+                ))
+                .map_err(TableError::Messages)?;
+        }
+
+        let transaction = index.transaction().map_err(TableError::Messages)?;
+        {
+            let mut insert = transaction
+                .prepare(&format!(
+                    "INSERT INTO {INDEX_TABLE} (rowid, text) VALUES (?1, ?2)"
+                ))
+                .map_err(TableError::Messages)?;
+            for message in Message::iter(db, &QueryContext::default())?.with_text(true) {
+                let message = message?;
+                if let Some(text) = message.text.as_deref().filter(|text| !text.is_empty()) {
+                    insert
+                        .execute(params![message.rowid, text])
+                        .map_err(TableError::Messages)?;
+                }
+            }
+        }
+        transaction.commit().map_err(TableError::Messages)?;
+
+        Ok(Self { index, fts5 })
+    }
+
+    /// `true` if the index uses FTS5, `false` if the linked SQLite does not support it
+    #[must_use]
+    pub fn uses_fts5(&self) -> bool {
+        self.fts5
+    }
+
+    /// Get the `ROWID`s of the messages whose text contains `term`, in ascending order
+    ///
+    /// With FTS5, `term` is matched as a phrase of whole words, ignoring case, so `din` does not match `dinner`.
+    /// Without it, `term` is matched as a substring. Operators like `OR` are not interpreted, and an empty term
+    /// matches nothing.
+    pub fn search_fts(&self, term: &str) -> Result<Vec<i32>, TableError> {
+        let term = term.trim();
+        if term.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let (query, pattern) = if self.fts5 {
+            (
+                format!(
+                    "SELECT rowid FROM {INDEX_TABLE} WHERE {INDEX_TABLE} MATCH ?1 ORDER BY rowid"
+                ),
+                format!("\"{}\"", term.replace('"', "\"\"")),
+            )
+        } else {
+            (
+                format!(
+                    "SELECT rowid FROM {INDEX_TABLE} WHERE text LIKE ?1 ESCAPE '\\' ORDER BY rowid"
+                ),
+                format!("%{}%", escape_like_pattern(term)),
+            )
+        };
+
+        let mut statement = self.index.prepare(&query).map_err(TableError::Messages)?;
+        let rows = statement
+            .query_map([pattern], |row| row.get(0))
+            .map_err(TableError::Messages)?;
+        rows.collect::<Result<Vec<i32>, _>>()
+            .map_err(TableError::Messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::{tables::table::get_empty_test_connection, util::search_index::SearchIndex};
+
+    fn database() -> Connection {
+        let db = get_empty_test_connection();
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'a', 'Dinner at 8?', 10, 0, 0),
+                (2, 'b', 'Sounds good', 20, 0, 0),
+                (3, 'c', 'What about DINNER tomorrow', 30, 0, 0),
+                (4, 'd', NULL, 40, 0, 0),
+                (5, 'e', '100% \"sure\"', 50, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4), (1, 5);
+            ",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_search_fts5_index() {
+        let index = SearchIndex::build(&database()).unwrap();
+
+        assert!(index.uses_fts5());
+        assert_eq!(index.search_fts("dinner").unwrap(), vec![1, 3]);
+        assert_eq!(index.search_fts("sounds good").unwrap(), vec![2]);
+        // Whole words only
+        assert!(index.search_fts("din").unwrap().is_empty());
+        // Quotes and operators are matched as text
+        assert_eq!(index.search_fts("\"sure\"").unwrap(), vec![5]);
+        assert!(index.search_fts("dinner OR good").unwrap().is_empty());
+    }
+
+    #[test]
+    fn can_search_without_fts5() {
+        let index =
+            SearchIndex::build_with(&database(), Connection::open_in_memory().unwrap(), false)
+                .unwrap();
+
+        assert!(!index.uses_fts5());
+        assert_eq!(index.search_fts("dinner").unwrap(), vec![1, 3]);
+        assert_eq!(index.search_fts("din").unwrap(), vec![1, 3]);
+        assert_eq!(index.search_fts("100%").unwrap(), vec![5]);
+        assert_eq!(index.search_fts("%").unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn cant_search_empty_term() {
+        let index = SearchIndex::build(&database()).unwrap();
+
+        assert!(index.search_fts("  ").unwrap().is_empty());
+    }
+}
@@ -2,6 +2,21 @@
  Data structures and models used by the `typedstream` parser.
 */
 
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::error::typedstream::TypedStreamError;
+
+/// Names of the classes whose first embedded [`OutputData::String`] is their text
+///
+/// Message text is usually archived as an `NSString` or `NSMutableString`, but some `attributedBody` streams wrap
+/// it directly in an `NSAttributedString` or `NSMutableAttributedString` instead.
+const STRING_CLASSES: [&str; 4] = [
+    "NSString",
+    "NSMutableString",
+    "NSAttributedString",
+    "NSMutableAttributedString",
+];
+
 /// Represents a class stored in the `typedstream`
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Class {
@@ -58,8 +73,9 @@ pub enum Archivable {
 }
 
 impl Archivable {
-    /// If `self` is an [`Object`](Archivable::Object) that contains a [`Class`] named `NSString` or `NSMutableString`,
-    /// extract a [`&str`](str) from the associated [`Data`](Archivable::Data).
+    /// If `self` is an [`Object`](Archivable::Object) that contains a [`Class`] named `NSString`, `NSMutableString`,
+    /// `NSAttributedString`, or `NSMutableAttributedString`, extract a [`&str`](str) from the associated
+    /// [`Data`](Archivable::Data).
     ///
     /// # Example
     ///
@@ -87,7 +103,7 @@ impl Archivable {
     #[must_use]
     pub fn as_nsstring(&self) -> Option<&str> {
         if let Archivable::Object(Class { name, .. }, value) = self {
-            if name == "NSString" || name == "NSMutableString" {
+            if STRING_CLASSES.contains(&name.as_str()) {
                 if let Some(OutputData::String(text)) = value.first() {
                     return Some(text);
                 }
@@ -282,3 +298,73 @@ pub(crate) enum ClassResult {
     /// A new class heirarchy to be inserted into the [`TypedStreamReader::object_table`](crate::util::typedstream::parser::TypedStreamReader::object_table)
     ClassHierarchy(Vec<Archivable>),
 }
+
+/// The reason a `typedstream` could not be fully parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageReason {
+    /// The stream ended in the middle of a value, usually because the data was only partially written
+    UnexpectedEof,
+    /// The stream does not start with the header used by macOS and iOS
+    InvalidHeader,
+    /// The stream referenced a class or type at this index, but nothing was stored there
+    UnknownReference(u64),
+    /// The stream contained data that could not be decoded, like invalid UTF-8 or a malformed array
+    InvalidData,
+}
+
+impl From<&TypedStreamError> for DamageReason {
+    fn from(error: &TypedStreamError) -> Self {
+        match error {
+            TypedStreamError::OutOfBounds(_, _) => DamageReason::UnexpectedEof,
+            TypedStreamError::InvalidHeader => DamageReason::InvalidHeader,
+            TypedStreamError::SliceError(_)
+            | TypedStreamError::StringParseError(_)
+            | TypedStreamError::InvalidArray
+            | TypedStreamError::InvalidPointer(_) => DamageReason::InvalidData,
+        }
+    }
+}
+
+impl Display for DamageReason {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            DamageReason::UnexpectedEof => write!(fmt, "unexpected end of data"),
+            DamageReason::InvalidHeader => write!(fmt, "invalid header"),
+            DamageReason::UnknownReference(index) => {
+                write!(fmt, "reference to unknown class or type {index}")
+            }
+            DamageReason::InvalidData => write!(fmt, "invalid data"),
+        }
+    }
+}
+
+/// Describes where and why parsing a damaged `typedstream` failed
+///
+/// Created by [`TypedStreamReader::diagnose()`](crate::util::typedstream::parser::TypedStreamReader::diagnose).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedStreamDiagnostic {
+    /// The number of bytes read before the first problem was found
+    pub consumed: usize,
+    /// The total number of bytes in the stream
+    pub length: usize,
+    /// Why the stream could not be parsed
+    pub reason: DamageReason,
+}
+
+impl TypedStreamDiagnostic {
+    /// `true` if the stream ended early, as when a blob is only partially written, else `false`
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.reason, DamageReason::UnexpectedEof)
+    }
+}
+
+impl Display for TypedStreamDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            fmt,
+            "{} after {} of {} bytes",
+            self.reason, self.consumed, self.length
+        )
+    }
+}
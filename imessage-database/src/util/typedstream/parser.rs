@@ -12,7 +12,9 @@ use std::collections::HashSet;
 
 use crate::{
     error::typedstream::TypedStreamError,
-    util::typedstream::models::{Archivable, Class, ClassResult, OutputData, Type},
+    util::typedstream::models::{
+        Archivable, Class, ClassResult, DamageReason, OutputData, Type, TypedStreamDiagnostic,
+    },
 };
 
 /// Indicates an [`i16`] in the byte stream
@@ -55,6 +57,8 @@ pub struct TypedStreamReader<'a> {
     seen_embedded_types: HashSet<u32>,
     /// Stores the position of the current [`Archivable::Placeholder`]
     placeholder: Option<usize>,
+    /// The first problem [`TypedStreamReader::parse()`] recovered from, reported by [`TypedStreamReader::diagnose()`]
+    damage: Option<TypedStreamDiagnostic>,
 }
 
 impl<'a> TypedStreamReader<'a> {
@@ -77,6 +81,18 @@ impl<'a> TypedStreamReader<'a> {
             object_table: vec![],
             seen_embedded_types: HashSet::new(),
             placeholder: None,
+            damage: None,
+        }
+    }
+
+    /// Remember the first problem found while parsing, if one has not been found already
+    fn record_damage(&mut self, consumed: usize, reason: DamageReason) {
+        if self.damage.is_none() {
+            self.damage = Some(TypedStreamDiagnostic {
+                consumed: consumed.min(self.stream.len()),
+                length: self.stream.len(),
+                reason,
+            });
         }
     }
 
@@ -269,6 +285,9 @@ impl<'a> TypedStreamReader<'a> {
 
                 if length >= REFERENCE_TAG {
                     let index = length - REFERENCE_TAG;
+                    if index as usize >= self.object_table.len() {
+                        self.record_damage(self.idx, DamageReason::UnknownReference(index));
+                    }
                     return Ok(ClassResult::Index(index as usize));
                 }
 
@@ -318,7 +337,11 @@ impl<'a> TypedStreamReader<'a> {
                 Ok(None)
             }
             _ => {
+                let start = self.idx;
                 let index = self.read_pointer()?;
+                if index as usize >= self.object_table.len() {
+                    self.record_damage(start, DamageReason::UnknownReference(u64::from(index)));
+                }
                 Ok(self.object_table.get(index as usize))
             }
         }
@@ -376,7 +399,11 @@ impl<'a> TypedStreamReader<'a> {
                     self.idx += 1;
                 }
 
+                let start = self.idx;
                 let ref_tag = self.read_pointer()?;
+                if ref_tag as usize >= self.types_table.len() {
+                    self.record_damage(start, DamageReason::UnknownReference(u64::from(ref_tag)));
+                }
                 let result = self.types_table.get(ref_tag as usize);
 
                 if embedded {
@@ -537,13 +564,45 @@ impl<'a> TypedStreamReader<'a> {
 
             // First, get the current type
             if let Some(found_types) = self.get_type(false)? {
-                let result = self.read_types(found_types);
-                if let Ok(Some(res)) = result {
-                    out_v.push(res);
+                let start = self.idx;
+                match self.read_types(found_types) {
+                    Ok(Some(res)) => out_v.push(res),
+                    Ok(None) => {}
+                    Err(why) => self.record_damage(start, DamageReason::from(&why)),
                 }
             }
         }
 
         Ok(out_v)
     }
+
+    /// Parse the stream like [`TypedStreamReader::parse()`], but report the first problem found instead of
+    /// skipping over it.
+    ///
+    /// [`TypedStreamReader::parse()`] recovers from damaged objects by skipping them, so a partially written
+    /// blob can still yield some of its text. This method returns a [`TypedStreamDiagnostic`] describing how many
+    /// bytes were read before the first problem and why parsing failed, which helps identify damaged messages.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::typedstream::parser::TypedStreamReader;
+    ///
+    /// let bytes: Vec<u8> = vec![0x04, 0x0b]; // A stream that ends in the middle of the header
+    /// let mut reader = TypedStreamReader::from(&bytes);
+    /// if let Err(diagnostic) = reader.diagnose() {
+    ///     println!("{diagnostic}"); // unexpected end of data after 2 of 2 bytes
+    /// }
+    /// ```
+    pub fn diagnose(&mut self) -> Result<Vec<Archivable>, TypedStreamDiagnostic> {
+        match (self.parse(), self.damage.take()) {
+            (_, Some(damage)) => Err(damage),
+            (Ok(items), None) => Ok(items),
+            (Err(why), None) => Err(TypedStreamDiagnostic {
+                consumed: self.idx.min(self.stream.len()),
+                length: self.stream.len(),
+                reason: DamageReason::from(&why),
+            }),
+        }
+    }
 }
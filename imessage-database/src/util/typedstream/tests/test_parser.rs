@@ -6,7 +6,7 @@ mod parser_tests {
     use std::vec;
 
     use crate::util::typedstream::{
-        models::{Archivable, Class, OutputData},
+        models::{Archivable, Class, DamageReason, OutputData, TypedStreamDiagnostic},
         parser::TypedStreamReader,
     };
 
@@ -185,6 +185,34 @@ mod parser_tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_parse_attributed_string_wrapper() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/AttributedStringWrapper");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let result = parser.parse().unwrap();
+
+        println!("\n\nGot data!");
+        result.iter().for_each(|item| println!("{item:?}"));
+
+        let expected = Archivable::Object(
+            Class {
+                name: "NSMutableAttributedString".to_string(),
+                version: 1,
+            },
+            vec![OutputData::String("Noter test".to_string())],
+        );
+
+        assert_eq!(result.first(), Some(&expected));
+        assert_eq!(expected.as_nsstring(), Some("Noter test"));
+    }
+
     #[test]
     fn test_parse_text_basic_2() {
         let typedstream_path = current_dir()
@@ -3816,4 +3844,66 @@ mod parser_tests {
             assert_eq!(result[idx], expected[idx]);
         }
     }
+
+    #[test]
+    fn test_diagnose_truncated() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Truncated");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let diagnostic = parser.diagnose().unwrap_err();
+
+        assert!(diagnostic.is_truncated());
+        assert_eq!(diagnostic.reason, DamageReason::UnexpectedEof);
+        assert_eq!(diagnostic.length, bytes.len());
+        // The header and class chain are intact, so the damage is in the message text
+        assert!(diagnostic.consumed > 0x70);
+        assert!(diagnostic.consumed <= bytes.len());
+    }
+
+    #[test]
+    fn test_diagnose_intact() {
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/AttributedBodyTextOnly");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let expected = TypedStreamReader::from(&bytes).parse().unwrap();
+        let result = TypedStreamReader::from(&bytes).diagnose().unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_diagnose_unknown_reference() {
+        // A valid header followed by a reference to a type that was never defined
+        let mut bytes = vec![0x04, 0x0b];
+        bytes.extend_from_slice(b"streamtyped");
+        bytes.extend_from_slice(&[0x81, 0xe8, 0x03, 0x97, 0x86]);
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        let diagnostic = parser.diagnose().unwrap_err();
+
+        assert_eq!(
+            diagnostic,
+            TypedStreamDiagnostic {
+                consumed: 16,
+                length: 18,
+                reason: DamageReason::UnknownReference(5),
+            }
+        );
+        assert!(!diagnostic.is_truncated());
+        assert_eq!(
+            diagnostic.to_string(),
+            "reference to unknown class or type 5 after 16 of 18 bytes"
+        );
+    }
 }
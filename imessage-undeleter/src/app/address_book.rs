@@ -0,0 +1,333 @@
+/*!
+ Contains logic for reading contact names from the macOS AddressBook.
+
+ Contacts are stored in a SQLite database for each account that syncs them, like iCloud or Google, at
+ `~/Library/Application Support/AddressBook/Sources/<source>/AddressBook-v22.abcddb`. Contacts that were created on
+ the Mac itself are stored in `~/Library/Application Support/AddressBook/AddressBook-v22.abcddb`.
+
+ Phone numbers are stored the way they were typed, like `(555) 867-5309`, while the Messages database usually stores
+ them in E.164 format, like `+15558675309`. Both are normalized with [`normalize_handle()`] before they are compared.
+ Numbers typed without a country code are given the one passed with `--country-code`, or [`DEFAULT_COUNTRY_CODE`].
+*/
+
+use std::{
+    collections::HashMap,
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use imessage_database::util::dirs::home;
+use rusqlite::{Connection, OpenFlags};
+
+/// The directory that contains the AddressBook, relative to the home directory
+const ADDRESS_BOOK_DIR: &str = "Library/Application Support/AddressBook";
+/// The name of the AddressBook database file in each source
+const ADDRESS_BOOK_FILE: &str = "AddressBook-v22.abcddb";
+/// The country calling code assumed for phone numbers stored without one, unless another one is selected
+pub const DEFAULT_COUNTRY_CODE: &str = "1";
+/// The fewest digits a phone number can have and still be given a country code; shorter numbers are short codes
+const MIN_NATIONAL_DIGITS: usize = 7;
+/// The most digits a national phone number has, so a longer number that starts with the country code already has it
+const MAX_NATIONAL_DIGITS: usize = 10;
+
+/// Get the AddressBook databases for the current user, in a deterministic order
+///
+/// Databases that do not exist are skipped, so this is empty on systems without an AddressBook.
+pub fn address_book_sources() -> Vec<PathBuf> {
+    let root = PathBuf::from(home()).join(ADDRESS_BOOK_DIR);
+
+    let mut sources: Vec<PathBuf> = read_dir(root.join("Sources"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|source| source.path().join(ADDRESS_BOOK_FILE))
+        .collect();
+    sources.sort();
+    sources.push(root.join(ADDRESS_BOOK_FILE));
+
+    sources.retain(|path| path.exists());
+    sources
+}
+
+/// Build a map of normalized phone numbers and email addresses to contact names from several AddressBook databases
+///
+/// If a handle belongs to contacts in more than one source, the name from the first source is used. Sources that
+/// cannot be read are skipped with a warning. Phone numbers without a country code are given `country_code`.
+pub fn load_address_book(sources: &[PathBuf], country_code: &str) -> HashMap<String, String> {
+    let mut names: HashMap<String, String> = HashMap::new();
+    for source in sources {
+        match read_source(source, country_code) {
+            Ok(contacts) => {
+                for (handle, name) in contacts {
+                    names.entry(handle).or_insert(name);
+                }
+            }
+            Err(why) => eprintln!("Unable to read contacts from {}: {why}", source.display()),
+        }
+    }
+    names
+}
+
+/// Read the names of the contacts in a single AddressBook database, keyed by normalized handle
+fn read_source(path: &Path, country_code: &str) -> Result<Vec<(String, String)>, rusqlite::Error> {
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut contacts = vec![];
+    for query in [
+        "SELECT r.ZFIRSTNAME, r.ZLASTNAME, r.ZORGANIZATION, r.ZNICKNAME, p.ZFULLNUMBER FROM ZABCDPHONENUMBER as p JOIN ZABCDRECORD as r ON p.ZOWNER = r.Z_PK ORDER BY p.Z_PK",
+        "SELECT r.ZFIRSTNAME, r.ZLASTNAME, r.ZORGANIZATION, r.ZNICKNAME, e.ZADDRESS FROM ZABCDEMAILADDRESS as e JOIN ZABCDRECORD as r ON e.ZOWNER = r.Z_PK ORDER BY e.Z_PK",
+    ] {
+        let mut statement = db.prepare(query)?;
+        let rows = statement.query_map([], |row| {
+            let name = display_name(
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            );
+            Ok((row.get::<_, Option<String>>(4)?, name))
+        })?;
+
+        for row in rows {
+            if let (Some(handle), Some(name)) = row? {
+                contacts.push((normalize_handle(&handle, country_code), name));
+            }
+        }
+    }
+    Ok(contacts)
+}
+
+/// The name shown for a contact: their full name, then their organization, then their nickname
+fn display_name(
+    first: Option<String>,
+    last: Option<String>,
+    organization: Option<String>,
+    nickname: Option<String>,
+) -> Option<String> {
+    let full_name = [first, last]
+        .into_iter()
+        .flatten()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    [Some(full_name), organization, nickname]
+        .into_iter()
+        .flatten()
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
+
+/// Normalize a phone number or email address so the same handle is written the same way everywhere
+///
+/// Email addresses are lowercased. Phone numbers are converted to E.164 format, like `+15558675309`: formatting is
+/// removed, an international `00` prefix is replaced with `+`, and numbers stored without a country code are given
+/// `country_code` after dropping a leading trunk `0`. Short codes and other handles are left as they are.
+pub fn normalize_handle(handle: &str, country_code: &str) -> String {
+    let handle = handle.trim();
+    if handle.contains('@') {
+        return handle.to_lowercase();
+    }
+
+    let digits: String = handle.chars().filter(char::is_ascii_digit).collect();
+    if digits.is_empty() || handle.chars().any(char::is_alphabetic) {
+        return handle.to_lowercase();
+    }
+
+    if handle.starts_with('+') {
+        format!("+{digits}")
+    } else if let Some(international) = digits.strip_prefix("00") {
+        format!("+{international}")
+    } else if digits.len() > MAX_NATIONAL_DIGITS && digits.starts_with(country_code) {
+        format!("+{digits}")
+    } else if digits.len() >= MIN_NATIONAL_DIGITS {
+        let national = digits.strip_prefix('0').unwrap_or(&digits);
+        format!("+{country_code}{national}")
+    } else {
+        digits
+    }
+}
+
+/// Map each participant ID to the name of the contact it belongs to, if the AddressBook has one
+///
+/// `participants` maps participant IDs to the phone number or email address stored in the Messages database.
+pub fn resolve_contact_names(
+    participants: &HashMap<i32, String>,
+    address_book: &HashMap<String, String>,
+    country_code: &str,
+) -> HashMap<i32, String> {
+    participants
+        .iter()
+        .filter_map(|(handle_id, handle)| {
+            address_book
+                .get(&normalize_handle(handle, country_code))
+                .map(|name| (*handle_id, name.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+    };
+
+    use rusqlite::Connection;
+
+    use crate::app::{
+        address_book::{
+            DEFAULT_COUNTRY_CODE, load_address_book, normalize_handle, resolve_contact_names,
+        },
+        options::Options,
+        runtime::Config,
+    };
+
+    fn fake_source(path: &std::path::Path, contacts: &str) {
+        let db = Connection::open(path).unwrap();
+        db.execute_batch(&format!(
+            "
+            CREATE TABLE ZABCDRECORD (Z_PK INTEGER PRIMARY KEY, ZFIRSTNAME VARCHAR, ZLASTNAME VARCHAR, ZORGANIZATION VARCHAR, ZNICKNAME VARCHAR);
+            CREATE TABLE ZABCDPHONENUMBER (Z_PK INTEGER PRIMARY KEY, ZOWNER INTEGER, ZFULLNUMBER VARCHAR);
+            CREATE TABLE ZABCDEMAILADDRESS (Z_PK INTEGER PRIMARY KEY, ZOWNER INTEGER, ZADDRESS VARCHAR);
+            {contacts}
+            "
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn can_normalize_phone_numbers() {
+        assert_eq!(
+            normalize_handle("+1 (555) 867-5309", DEFAULT_COUNTRY_CODE),
+            "+15558675309"
+        );
+        assert_eq!(
+            normalize_handle("(555) 867-5309", DEFAULT_COUNTRY_CODE),
+            "+15558675309"
+        );
+        assert_eq!(
+            normalize_handle("1-555-867-5309", DEFAULT_COUNTRY_CODE),
+            "+15558675309"
+        );
+        assert_eq!(
+            normalize_handle("0044 20 7946 0958", DEFAULT_COUNTRY_CODE),
+            "+442079460958"
+        );
+        assert_eq!(
+            normalize_handle("+44 20 7946 0958", DEFAULT_COUNTRY_CODE),
+            "+442079460958"
+        );
+        assert_eq!(
+            normalize_handle("555 8675", DEFAULT_COUNTRY_CODE),
+            "+15558675"
+        );
+        assert_eq!(normalize_handle("262966", DEFAULT_COUNTRY_CODE), "262966");
+    }
+
+    #[test]
+    fn can_normalize_phone_numbers_in_other_countries() {
+        // A UK mobile number typed with its trunk prefix
+        assert_eq!(normalize_handle("07911 123456", "44"), "+447911123456");
+        assert_eq!(normalize_handle("447911 123456", "44"), "+447911123456");
+        assert_eq!(normalize_handle("+44 7911 123456", "44"), "+447911123456");
+        // Numbers with a different country code are not changed
+        assert_eq!(normalize_handle("+1 (555) 867-5309", "44"), "+15558675309");
+    }
+
+    #[test]
+    fn can_normalize_email_addresses() {
+        assert_eq!(
+            normalize_handle(" Alice@Example.com ", DEFAULT_COUNTRY_CODE),
+            "alice@example.com"
+        );
+        assert_eq!(
+            normalize_handle("urn:biz:ABC123", DEFAULT_COUNTRY_CODE),
+            "urn:biz:abc123"
+        );
+    }
+
+    #[test]
+    fn can_load_multiple_sources() {
+        let dir = temp_dir().join("imessage-undeleter-address-book-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let icloud = dir.join("icloud.abcddb");
+        let google = dir.join("google.abcddb");
+        fake_source(
+            &icloud,
+            "
+            INSERT INTO ZABCDRECORD VALUES (1, 'Alice', 'Smith', NULL, NULL), (2, NULL, NULL, 'Pizza Place', NULL);
+            INSERT INTO ZABCDPHONENUMBER VALUES (1, 1, '(555) 867-5309'), (2, 2, '+1 555 123 4567');
+            INSERT INTO ZABCDEMAILADDRESS VALUES (1, 1, 'Alice@Example.com');
+            ",
+        );
+        fake_source(
+            &google,
+            "
+            INSERT INTO ZABCDRECORD VALUES (1, 'Ally', NULL, NULL, NULL), (2, 'Bob', NULL, NULL, NULL);
+            INSERT INTO ZABCDPHONENUMBER VALUES (1, 1, '+15558675309'), (2, 2, '0044 20 7946 0958');
+            ",
+        );
+
+        let names = load_address_book(
+            &[icloud, google, dir.join("missing.abcddb")],
+            DEFAULT_COUNTRY_CODE,
+        );
+
+        // The first source wins when a handle is in both
+        assert_eq!(names.len(), 4);
+        assert_eq!(names["+15558675309"], "Alice Smith");
+        assert_eq!(names["+15551234567"], "Pizza Place");
+        assert_eq!(names["alice@example.com"], "Alice Smith");
+        assert_eq!(names["+442079460958"], "Bob");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_load_source_in_other_country() {
+        let dir = temp_dir().join("imessage-undeleter-address-book-uk-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let source = dir.join("icloud.abcddb");
+        fake_source(
+            &source,
+            "
+            INSERT INTO ZABCDRECORD VALUES (1, 'Alice', NULL, NULL, NULL);
+            INSERT INTO ZABCDPHONENUMBER VALUES (1, 1, '07911 123456');
+            ",
+        );
+
+        let names = load_address_book(&[source], "44");
+        let participants = HashMap::from([(1, String::from("+447911123456"))]);
+
+        assert_eq!(names["+447911123456"], "Alice");
+        assert_eq!(
+            resolve_contact_names(&participants, &names, "44")[&1],
+            "Alice"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_resolve_participant_names() {
+        let address_book =
+            HashMap::from([(String::from("+15558675309"), String::from("Alice Smith"))]);
+        let participants = HashMap::from([
+            (1, String::from("+15558675309")),
+            (2, String::from("bob@example.com")),
+        ]);
+
+        let mut config = Config::fake_app(Options::fake_options());
+        config.contact_names =
+            resolve_contact_names(&participants, &address_book, DEFAULT_COUNTRY_CODE);
+        config.participants = participants;
+
+        assert_eq!(config.who(Some(1), false, &None), "Alice Smith");
+        assert_eq!(config.who(Some(2), false, &None), "bob@example.com");
+    }
+}
@@ -0,0 +1,121 @@
+/*!
+ Contains data structures used to describe how attachment placeholders are written in plain text exports.
+*/
+
+use std::fmt::Display;
+
+/// The character Messages uses to mark where an attachment sits in a message body
+pub const ATTACHMENT_CHAR: char = '\u{FFFC}';
+/// The text written in place of an attachment when placeholders are labeled
+pub const ATTACHMENT_LABEL: &str = "[attachment]";
+
+/// How plain text exports write the `U+FFFC` object replacement character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttachmentPlaceholder {
+    /// Replace each placeholder with [`ATTACHMENT_LABEL`]
+    #[default]
+    Label,
+    /// Remove placeholders from the text
+    Strip,
+    /// Leave placeholders in the text
+    Keep,
+}
+
+impl AttachmentPlaceholder {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(placeholder: &str) -> Option<Self> {
+        match placeholder.to_lowercase().as_str() {
+            "label" => Some(Self::Label),
+            "strip" => Some(Self::Strip),
+            "keep" => Some(Self::Keep),
+            _ => None,
+        }
+    }
+
+    /// Rewrite the attachment placeholders in message text
+    ///
+    /// When stripping, whitespace left at either end of the text is removed as well.
+    pub fn apply(&self, text: &str) -> String {
+        if !text.contains(ATTACHMENT_CHAR) {
+            return text.to_string();
+        }
+        match self {
+            AttachmentPlaceholder::Label => text.replace(ATTACHMENT_CHAR, ATTACHMENT_LABEL),
+            AttachmentPlaceholder::Strip => text.replace(ATTACHMENT_CHAR, "").trim().to_string(),
+            AttachmentPlaceholder::Keep => text.to_string(),
+        }
+    }
+}
+
+impl Display for AttachmentPlaceholder {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentPlaceholder::Label => write!(fmt, "label"),
+            AttachmentPlaceholder::Strip => write!(fmt, "strip"),
+            AttachmentPlaceholder::Keep => write!(fmt, "keep"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use imessage_database::util::streamtyped::parse;
+
+    use crate::app::attachment_placeholder::AttachmentPlaceholder;
+
+    fn attachment_i16() -> String {
+        let bytes = read(
+            current_dir()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .join("imessage-database/test_data/typedstream/AttachmentI16"),
+        )
+        .unwrap();
+        parse(bytes).unwrap()
+    }
+
+    #[test]
+    fn can_parse_attachment_placeholder() {
+        assert_eq!(
+            AttachmentPlaceholder::from_cli("strip"),
+            Some(AttachmentPlaceholder::Strip)
+        );
+        assert_eq!(
+            AttachmentPlaceholder::from_cli("KEEP"),
+            Some(AttachmentPlaceholder::Keep)
+        );
+        assert_eq!(AttachmentPlaceholder::from_cli("remove"), None);
+        assert_eq!(
+            AttachmentPlaceholder::default(),
+            AttachmentPlaceholder::Label
+        );
+    }
+
+    #[test]
+    fn can_replace_attachment_placeholder() {
+        let text = attachment_i16();
+        assert_eq!(text, "\u{FFFC}");
+
+        assert_eq!(AttachmentPlaceholder::Label.apply(&text), "[attachment]");
+        assert_eq!(AttachmentPlaceholder::Strip.apply(&text), "");
+        assert_eq!(AttachmentPlaceholder::Keep.apply(&text), "\u{FFFC}");
+    }
+
+    #[test]
+    fn can_replace_placeholders_around_text() {
+        let text = "Look! \u{FFFC}\u{FFFC}";
+
+        assert_eq!(
+            AttachmentPlaceholder::Label.apply(text),
+            "Look! [attachment][attachment]"
+        );
+        assert_eq!(AttachmentPlaceholder::Strip.apply(text), "Look!");
+        assert_eq!(
+            AttachmentPlaceholder::Strip.apply("No attachments "),
+            "No attachments "
+        );
+    }
+}
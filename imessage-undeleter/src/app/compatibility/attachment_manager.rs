@@ -10,22 +10,13 @@ use std::{
 
 use imessage_database::{
     message_types::handwriting::HandwrittenMessage,
-    tables::{
-        attachment::{Attachment, MediaType},
-        messages::Message,
-    },
+    tables::{attachment::Attachment, messages::Message},
 };
 
 use crate::app::{
     compatibility::{
         backup::decrypt_file,
-        converters::{
-            audio::audio_copy_convert,
-            common::{copy_raw, update_file_metadata},
-            image::image_copy_convert,
-            sticker::sticker_copy_convert,
-            video::video_copy_convert,
-        },
+        converters::{common::update_file_metadata, registry::ConverterRegistry},
         models::{AudioConverter, Converter, HardwareEncoder, ImageConverter, VideoConverter},
     },
     runtime::Config,
@@ -34,20 +25,20 @@ use crate::app::{
 #[derive(Debug, PartialEq, Eq, Default)]
 pub struct AttachmentManager {
     pub mode: AttachmentManagerMode,
-    pub image_converter: Option<ImageConverter>,
-    pub audio_converter: Option<AudioConverter>,
-    pub video_converter: Option<VideoConverter>,
-    hardware_encoder: Option<HardwareEncoder>,
+    /// Converters used when copying attachments; register custom ones to handle other formats
+    pub converters: ConverterRegistry,
 }
 
 impl AttachmentManager {
     pub fn from(mode: AttachmentManagerMode) -> Self {
         AttachmentManager {
             mode,
-            image_converter: ImageConverter::determine(),
-            audio_converter: AudioConverter::determine(),
-            video_converter: VideoConverter::determine(),
-            hardware_encoder: HardwareEncoder::detect(),
+            converters: ConverterRegistry::builtin(
+                ImageConverter::determine(),
+                AudioConverter::determine(),
+                VideoConverter::determine(),
+                HardwareEncoder::detect(),
+            ),
         }
     }
 }
@@ -60,7 +51,7 @@ impl AttachmentManager {
         handwriting: &HandwrittenMessage,
         config: &Config,
     ) -> Option<PathBuf> {
-        if !matches!(self.mode, AttachmentManagerMode::Disabled) {
+        if self.mode.copies_files() {
             // Create a path to copy the file to
             let mut to = config.attachment_path();
 
@@ -110,13 +101,9 @@ impl AttachmentManager {
         new_name: &str,
         config: &Config,
     ) -> Option<()> {
-        if !matches!(self.mode, AttachmentManagerMode::Disabled) {
+        if self.mode.copies_files() {
             // Resolve the path to the attachment
-            let attachment_path = attachment.resolved_attachment_path(
-                &config.options.platform,
-                &config.options.db_path,
-                config.options.attachment_root.as_deref(),
-            )?;
+            let attachment_path = config.attachment_source_path(attachment)?;
 
             let mut is_temp = false;
             let mut from = PathBuf::from(&attachment_path);
@@ -148,6 +135,9 @@ impl AttachmentManager {
 
             // Create a path to copy the file to
             let mut to = config.tmp_attachment_path();
+            if let Some(folder) = config.chat_attachment_folder(message.chat_id.or(message.deleted_from)) {
+                to.push(folder);
+            }
             to.push(new_name);
 
             // Set the new file's extension to the original one, if provided
@@ -162,55 +152,7 @@ impl AttachmentManager {
             }
 
             // If we convert the attachment, we need to update the media type
-            let mut new_media_type: Option<MediaType> = None;
-
-            match attachment.mime_type() {
-                MediaType::Image(_) => match &self.image_converter {
-                    Some(converter) => {
-                        if attachment.is_sticker {
-                            new_media_type = sticker_copy_convert(
-                                &from,
-                                &mut to,
-                                converter,
-                                &self.video_converter,
-                                attachment.mime_type(),
-                            );
-                        } else {
-                            new_media_type = image_copy_convert(
-                                &from,
-                                &mut to,
-                                converter,
-                                attachment.mime_type(),
-                            );
-                        }
-                    }
-                    None => copy_raw(&from, &to),
-                },
-                MediaType::Video(_) => match &self.video_converter {
-                    Some(converter) => {
-                        new_media_type = video_copy_convert(
-                            &from,
-                            &mut to,
-                            converter,
-                            &self.hardware_encoder,
-                            attachment.mime_type(),
-                        );
-                    }
-                    None => copy_raw(&from, &to),
-                },
-                MediaType::Audio(_) => match &self.audio_converter {
-                        Some(converter) => {
-                            new_media_type = audio_copy_convert(
-                                &from,
-                                &mut to,
-                                converter,
-                                attachment.mime_type(),
-                            );
-                        }
-                        None => copy_raw(&from, &to),
-                },
-                _ => copy_raw(&from, &to),
-            }
+            let new_media_type = self.converters.copy_convert(&from, &mut to, attachment);
 
             // Update file metadata
             if is_temp {
@@ -248,6 +190,8 @@ pub enum AttachmentManagerMode {
     Clone,
     /// Copy and convert all attachments to more compatible formats using a [`Converter`]
     Full,
+    /// Do not copy attachments; reference the original files with absolute `file://` URLs
+    Link,
 }
 
 impl Default for AttachmentManagerMode {
@@ -264,9 +208,15 @@ impl AttachmentManagerMode {
             "basic" => Some(Self::Basic),
             "clone" => Some(Self::Clone),
             "full" => Some(Self::Full),
+            "link" => Some(Self::Link),
             _ => None,
         }
     }
+
+    /// `true` if attachments are copied into the export directory, else `false`
+    pub fn copies_files(&self) -> bool {
+        !matches!(self, Self::Disabled | Self::Link)
+    }
 }
 
 impl Display for AttachmentManagerMode {
@@ -276,13 +226,99 @@ impl Display for AttachmentManagerMode {
             AttachmentManagerMode::Basic => write!(fmt, "basic"),
             AttachmentManagerMode::Clone => write!(fmt, "clone"),
             AttachmentManagerMode::Full => write!(fmt, "full"),
+            AttachmentManagerMode::Link => write!(fmt, "link"),
+        }
+    }
+}
+
+/// Represents how copied attachments are arranged in the attachment directory
+#[derive(Debug, PartialEq, Eq, Default)]
+pub enum AttachmentLayout {
+    /// Place all attachments in the same directory
+    #[default]
+    Flat,
+    /// Place each chat's attachments in a subdirectory named after the chat
+    PerChat,
+}
+
+impl AttachmentLayout {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(layout: &str) -> Option<Self> {
+        match layout.to_lowercase().as_str() {
+            "flat" => Some(Self::Flat),
+            "chat" => Some(Self::PerChat),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AttachmentLayout {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentLayout::Flat => write!(fmt, "flat"),
+            AttachmentLayout::PerChat => write!(fmt, "chat"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::app::compatibility::attachment_manager::AttachmentManagerMode;
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    use imessage_database::tables::{
+        attachment::{Attachment, MediaType},
+        chat::Chat,
+    };
+
+    use crate::app::{
+        compatibility::{
+            attachment_manager::{AttachmentLayout, AttachmentManagerMode},
+            converters::{common::copy_raw, registry::AttachmentConverter},
+        },
+        options::Options,
+        runtime::Config,
+    };
+
+    /// Stands in for `sips` or `magick`, which are not available everywhere the tests run
+    #[derive(Debug)]
+    struct FakeHeicConverter;
+
+    impl AttachmentConverter for FakeHeicConverter {
+        fn name(&self) -> &str {
+            "fake heic"
+        }
+
+        fn handles(&self, attachment: &Attachment) -> bool {
+            matches!(attachment.mime_type(), MediaType::Image("heic"))
+        }
+
+        fn copy_convert(
+            &self,
+            from: &Path,
+            to: &mut PathBuf,
+            _: &Attachment,
+        ) -> Option<MediaType<'static>> {
+            to.set_extension("jpeg");
+            copy_raw(from, to);
+            Some(MediaType::Image("jpeg"))
+        }
+    }
+
+    fn heic_attachment(dir: &Path) -> Attachment {
+        let from = dir.join("IMG_0001.HEIC");
+        write(&from, b"heic").unwrap();
+
+        let mut attachment = Config::fake_attachment();
+        attachment.filename = Some(from.display().to_string());
+        attachment.transfer_name = Some(String::from("IMG_0001.HEIC"));
+        attachment.uti = Some(String::from("public.heic"));
+        attachment.mime_type = None;
+        attachment
+    }
 
     #[test]
     fn test_attachment_manager_mode() {
@@ -302,6 +338,131 @@ mod tests {
             AttachmentManagerMode::from_cli("full"),
             Some(AttachmentManagerMode::Full)
         );
+        assert_eq!(
+            AttachmentManagerMode::from_cli("link"),
+            Some(AttachmentManagerMode::Link)
+        );
         assert_eq!(AttachmentManagerMode::from_cli("invalid"), None);
     }
+
+    #[test]
+    fn can_skip_copying_linked_attachments() {
+        assert!(AttachmentManagerMode::Full.copies_files());
+        assert!(AttachmentManagerMode::Clone.copies_files());
+        assert!(!AttachmentManagerMode::Disabled.copies_files());
+        assert!(!AttachmentManagerMode::Link.copies_files());
+    }
+
+    #[test]
+    fn test_attachment_layout() {
+        assert_eq!(
+            AttachmentLayout::from_cli("flat"),
+            Some(AttachmentLayout::Flat)
+        );
+        assert_eq!(
+            AttachmentLayout::from_cli("chat"),
+            Some(AttachmentLayout::PerChat)
+        );
+        assert_eq!(AttachmentLayout::from_cli("invalid"), None);
+    }
+
+    #[test]
+    fn can_copy_attachments_into_chat_folders() {
+        let dir = temp_dir().join("imessage-undeleter-chat-layout");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("source.png");
+        write(&from, b"attachment").unwrap();
+
+        let mut options = Options::fake_options();
+        options.export_path = dir.join("export");
+        options.attachment_layout = AttachmentLayout::PerChat;
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: String::from("+15558675309"),
+                service_name: Some(String::from("iMessage")),
+                display_name: Some(String::from("Family")),
+                style: None,
+                group_id: None,
+            },
+        );
+
+        let mut message = Config::fake_message();
+        message.chat_id = Some(1);
+        let mut attachment = Config::fake_attachment();
+        attachment.filename = Some(from.display().to_string());
+
+        config
+            .options
+            .attachment_manager
+            .handle_attachment(&message, &mut attachment, "0", &config)
+            .unwrap();
+
+        let expected = config
+            .tmp_attachment_path()
+            .join("Family - 1")
+            .join("0.png");
+        assert!(expected.exists());
+        assert_eq!(attachment.copied_path, Some(expected));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_report_converted_heic_as_jpeg() {
+        let dir = temp_dir().join("imessage-undeleter-heic-convert");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut options = Options::fake_options();
+        options.export_path = dir.join("export");
+        options
+            .attachment_manager
+            .converters
+            .register(FakeHeicConverter);
+        let config = Config::fake_app(options);
+
+        let mut attachment = heic_attachment(&dir);
+        config
+            .options
+            .attachment_manager
+            .handle_attachment(&Config::fake_message(), &mut attachment, "0", &config)
+            .unwrap();
+
+        let expected = config.tmp_attachment_path().join("0.jpeg");
+        assert!(expected.exists());
+        assert_eq!(attachment.copied_path, Some(expected));
+        assert_eq!(attachment.mime_type.as_deref(), Some("image/jpeg"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_copy_heic_without_converter() {
+        let dir = temp_dir().join("imessage-undeleter-heic-no-converter");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut options = Options::fake_options();
+        options.export_path = dir.join("export");
+        let config = Config::fake_app(options);
+
+        let mut attachment = heic_attachment(&dir);
+        config
+            .options
+            .attachment_manager
+            .handle_attachment(&Config::fake_message(), &mut attachment, "0", &config)
+            .unwrap();
+
+        // The original file is kept as it is
+        let expected = config.tmp_attachment_path().join("0.HEIC");
+        assert!(expected.exists());
+        assert_eq!(attachment.copied_path, Some(expected));
+        assert_eq!(attachment.mime_type(), MediaType::Image("heic"));
+
+        remove_dir_all(&dir).unwrap();
+    }
 }
@@ -6,9 +6,15 @@ use std::{
 };
 
 use crabapple::{Authentication, Backup};
-use imessage_database::{tables::table::DEFAULT_PATH_IOS, util::platform::Platform};
+use imessage_database::{
+    tables::table::DEFAULT_PATH_IOS,
+    util::{backup_manifest::BackupManifest, platform::Platform},
+};
 
-use crate::app::{error::RuntimeError, options::Options};
+use crate::app::{
+    error::{RuntimeError, error_chain},
+    options::Options,
+};
 
 const MAX_IN_MEMORY_DECRYPT: u64 = 25 * 1024 * 1024;
 
@@ -26,6 +32,26 @@ pub fn decrypt_backup(options: &Options) -> Result<Option<Backup>, RuntimeError>
     Ok(Some(backup))
 }
 
+/// Open the file index of an unencrypted iOS backup, so attachments can be found by their `Manifest.db` entry
+///
+/// Returns `None` for other platforms, for encrypted backups, or if the index cannot be read, in which case attachment
+/// paths are derived from their names instead.
+pub fn open_backup_manifest(options: &Options) -> Option<BackupManifest> {
+    if !matches!(options.platform, Platform::iOS) || options.cleartext_password.is_some() {
+        return None;
+    }
+    match BackupManifest::open(&options.db_path) {
+        Ok(manifest) => Some(manifest),
+        Err(why) => {
+            eprintln!(
+                "Unable to read the backup manifest, attachment paths will be derived from their names: {}",
+                error_chain(&why)
+            );
+            None
+        }
+    }
+}
+
 pub fn get_decrypted_message_database(backup: &Backup) -> Result<PathBuf, RuntimeError> {
     let (_, file_id) = DEFAULT_PATH_IOS.split_at(3);
     eprintln!("  [2/3] Resolving messages database...");
@@ -103,13 +103,25 @@ pub(crate) fn copy_raw(from: &Path, to: &Path) {
 }
 
 /// Update the metadata of a copied file, falling back to the original file's metadata if necessary
+///
+/// If attachment times are preserved, the copy keeps the original file's modification time unless it
+/// is unreliable, in which case the message's date is used instead.
 pub(crate) fn update_file_metadata(from: &Path, to: &Path, message: &Message, config: &Config) {
     // Update file metadata
     if let Ok(metadata) = metadata(from) {
-        // The modification time is the message's date, otherwise the the original file's creation time
-        let mtime = match message.date(&config.offset) {
-            Ok(date) => FileTime::from_unix_time(date.timestamp(), date.timestamp_subsec_nanos()),
-            Err(_) => FileTime::from_last_modification_time(&metadata),
+        let source_mtime = FileTime::from_last_modification_time(&metadata);
+        let message_mtime = message
+            .date(&config.offset)
+            .ok()
+            .map(|date| FileTime::from_unix_time(date.timestamp(), date.timestamp_subsec_nanos()));
+
+        let mtime = if config.options.preserve_attachment_times
+            && is_reliable_time(source_mtime, config.offset)
+        {
+            source_mtime
+        } else {
+            // The modification time is the message's date, otherwise the the original file's creation time
+            message_mtime.unwrap_or(source_mtime)
         };
 
         // The new last access time comes from the metadata of the original file
@@ -120,3 +132,72 @@ pub(crate) fn update_file_metadata(from: &Path, to: &Path, message: &Message, co
         }
     }
 }
+
+/// Determine if a file time could have come from a real attachment
+///
+/// Times before iMessage's epoch or in the future are usually left by restores or broken copies.
+fn is_reliable_time(time: FileTime, offset: i64) -> bool {
+    time.unix_seconds() >= offset && time <= FileTime::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, metadata, remove_dir_all, write},
+    };
+
+    use filetime::{FileTime, set_file_mtime};
+
+    use crate::app::{
+        compatibility::converters::common::{copy_raw, update_file_metadata},
+        options::Options,
+        runtime::Config,
+    };
+
+    /// 2021-05-17 12:00:00 UTC
+    const SOURCE_MTIME: i64 = 1_621_252_800;
+
+    fn copy_with_times(name: &str, preserve: bool, source_mtime: i64) -> FileTime {
+        let dir = temp_dir().join(format!("imessage-undeleter-{name}"));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let from = dir.join("source.jpg");
+        let to = dir.join("copied").join("source.jpg");
+        write(&from, b"attachment").unwrap();
+        set_file_mtime(&from, FileTime::from_unix_time(source_mtime, 0)).unwrap();
+
+        let mut options = Options::fake_options();
+        options.preserve_attachment_times = preserve;
+        let config = Config::fake_app(options);
+        let mut message = Config::fake_message();
+        // 2022-05-17 12:00:00 UTC
+        message.date = 674_481_600_000_000_000;
+
+        copy_raw(&from, &to);
+        update_file_metadata(&from, &to, &message, &config);
+
+        let mtime = FileTime::from_last_modification_time(&metadata(&to).unwrap());
+        remove_dir_all(&dir).unwrap();
+        mtime
+    }
+
+    #[test]
+    fn can_preserve_source_mtime() {
+        let mtime = copy_with_times("preserve-mtime", true, SOURCE_MTIME);
+        assert_eq!(mtime, FileTime::from_unix_time(SOURCE_MTIME, 0));
+    }
+
+    #[test]
+    fn can_use_message_date_by_default() {
+        let mtime = copy_with_times("message-mtime", false, SOURCE_MTIME);
+        assert_eq!(mtime.unix_seconds(), 1_652_788_800);
+    }
+
+    #[test]
+    fn can_use_message_date_for_unreliable_mtime() {
+        let mtime = copy_with_times("unreliable-mtime", true, 0);
+        assert_eq!(mtime.unix_seconds(), 1_652_788_800);
+    }
+}
@@ -13,7 +13,7 @@ use crate::app::compatibility::{
 
 /// Copy an image file, converting if possible
 ///
-/// - Attachment `HEIC` files convert to `JPEG`
+/// - Attachment `HEIC` and `HEIF` files convert to `JPEG`
 /// - Fallback to the original format
 pub(crate) fn image_copy_convert(
     from: &Path,
@@ -21,7 +21,10 @@ pub(crate) fn image_copy_convert(
     converter: &ImageConverter,
     mime_type: MediaType,
 ) -> Option<MediaType<'static>> {
-    if matches!(mime_type, MediaType::Image("heic" | "HEIC")) {
+    if matches!(
+        mime_type,
+        MediaType::Image("heic" | "HEIC" | "heif" | "HEIF")
+    ) {
         let output_type = ImageType::Jpeg;
 
         // Update extension for conversion
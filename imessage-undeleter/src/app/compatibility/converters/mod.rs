@@ -5,5 +5,6 @@
 pub mod audio;
 pub mod common;
 pub mod image;
+pub mod registry;
 pub mod sticker;
 pub mod video;
@@ -0,0 +1,369 @@
+/*!
+ Defines the extension point used to convert attachments while they are copied.
+
+ Each [`AttachmentConverter`] decides which attachments it handles. The [`ConverterRegistry`] selects
+ the converter for an attachment, falling back to copying the file unaltered when none apply.
+*/
+
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use imessage_database::tables::attachment::{Attachment, MediaType};
+
+use crate::app::compatibility::{
+    converters::{
+        audio::audio_copy_convert, common::copy_raw, image::image_copy_convert,
+        sticker::sticker_copy_convert, video::video_copy_convert,
+    },
+    models::{AudioConverter, HardwareEncoder, ImageConverter, VideoConverter},
+};
+
+/// Copies an attachment to the export directory, converting it to a different format if desired
+pub trait AttachmentConverter: Debug {
+    /// A short name used to identify the converter
+    fn name(&self) -> &str;
+
+    /// `true` if this converter should be used for the attachment, else `false`
+    fn handles(&self, attachment: &Attachment) -> bool;
+
+    /// Copy the file at `from` to `to`, converting it if needed
+    ///
+    /// If the file is converted, `to` should be updated with the new extension and the new
+    /// [`MediaType`] returned.
+    fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>>;
+}
+
+/// Converts `HEIC` images to `JPEG`
+#[derive(Debug)]
+pub struct ImageAttachmentConverter(pub ImageConverter);
+
+impl AttachmentConverter for ImageAttachmentConverter {
+    fn name(&self) -> &str {
+        "image"
+    }
+
+    fn handles(&self, attachment: &Attachment) -> bool {
+        !attachment.is_sticker && matches!(attachment.mime_type(), MediaType::Image(_))
+    }
+
+    fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>> {
+        image_copy_convert(from, to, &self.0, attachment.mime_type())
+    }
+}
+
+/// Converts `HEIC` and `HEICS` stickers to `PNG` and `GIF`
+#[derive(Debug)]
+pub struct StickerAttachmentConverter {
+    pub image_converter: ImageConverter,
+    pub video_converter: Option<VideoConverter>,
+}
+
+impl AttachmentConverter for StickerAttachmentConverter {
+    fn name(&self) -> &str {
+        "sticker"
+    }
+
+    fn handles(&self, attachment: &Attachment) -> bool {
+        attachment.is_sticker && matches!(attachment.mime_type(), MediaType::Image(_))
+    }
+
+    fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>> {
+        sticker_copy_convert(
+            from,
+            to,
+            &self.image_converter,
+            &self.video_converter,
+            attachment.mime_type(),
+        )
+    }
+}
+
+/// Converts `MOV` videos to `MP4`
+#[derive(Debug)]
+pub struct VideoAttachmentConverter {
+    pub converter: VideoConverter,
+    pub hardware_encoder: Option<HardwareEncoder>,
+}
+
+impl AttachmentConverter for VideoAttachmentConverter {
+    fn name(&self) -> &str {
+        "video"
+    }
+
+    fn handles(&self, attachment: &Attachment) -> bool {
+        matches!(attachment.mime_type(), MediaType::Video(_))
+    }
+
+    fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>> {
+        video_copy_convert(
+            from,
+            to,
+            &self.converter,
+            &self.hardware_encoder,
+            attachment.mime_type(),
+        )
+    }
+}
+
+/// Converts `CAF` and `AMR` audio to `MP4`
+#[derive(Debug)]
+pub struct AudioAttachmentConverter(pub AudioConverter);
+
+impl AttachmentConverter for AudioAttachmentConverter {
+    fn name(&self) -> &str {
+        "audio"
+    }
+
+    fn handles(&self, attachment: &Attachment) -> bool {
+        matches!(attachment.mime_type(), MediaType::Audio(_))
+    }
+
+    fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>> {
+        audio_copy_convert(from, to, &self.0, attachment.mime_type())
+    }
+}
+
+/// The set of converters available when copying attachments
+///
+/// Converters registered later take priority over ones registered earlier, so a custom converter
+/// can replace a builtin one for the attachments it handles.
+#[derive(Debug, Default)]
+pub struct ConverterRegistry {
+    converters: Vec<Box<dyn AttachmentConverter>>,
+}
+
+impl ConverterRegistry {
+    /// Create a registry containing the builtin converters for the programs found on the system
+    pub fn builtin(
+        image_converter: Option<ImageConverter>,
+        audio_converter: Option<AudioConverter>,
+        video_converter: Option<VideoConverter>,
+        hardware_encoder: Option<HardwareEncoder>,
+    ) -> Self {
+        let mut registry = Self::default();
+        if let Some(converter) = image_converter {
+            registry.register(ImageAttachmentConverter(converter));
+            registry.register(StickerAttachmentConverter {
+                image_converter: converter,
+                video_converter,
+            });
+        }
+        if let Some(converter) = video_converter {
+            registry.register(VideoAttachmentConverter {
+                converter,
+                hardware_encoder,
+            });
+        }
+        if let Some(converter) = audio_converter {
+            registry.register(AudioAttachmentConverter(converter));
+        }
+        registry
+    }
+
+    /// Add a converter to the registry
+    pub fn register(&mut self, converter: impl AttachmentConverter + 'static) {
+        self.converters.push(Box::new(converter));
+    }
+
+    /// Find the converter to use for an attachment, if any
+    pub fn find(&self, attachment: &Attachment) -> Option<&dyn AttachmentConverter> {
+        self.converters
+            .iter()
+            .rev()
+            .find(|converter| converter.handles(attachment))
+            .map(AsRef::as_ref)
+    }
+
+    /// Copy an attachment using the converter registered for it, or copy it unaltered if there is none
+    pub fn copy_convert(
+        &self,
+        from: &Path,
+        to: &mut PathBuf,
+        attachment: &Attachment,
+    ) -> Option<MediaType<'static>> {
+        match self.find(attachment) {
+            Some(converter) => converter.copy_convert(from, to, attachment),
+            None => {
+                copy_raw(from, to);
+                None
+            }
+        }
+    }
+
+    /// The names of the registered converters, in the order they were registered
+    pub fn names(&self) -> Vec<&str> {
+        self.converters.iter().map(|c| c.name()).collect()
+    }
+}
+
+impl PartialEq for ConverterRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.names() == other.names()
+    }
+}
+
+impl Eq for ConverterRegistry {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    use imessage_database::tables::attachment::{Attachment, MediaType};
+
+    use crate::app::{
+        compatibility::{
+            converters::registry::{AttachmentConverter, ConverterRegistry},
+            models::{AudioConverter, ImageConverter},
+        },
+        runtime::Config,
+    };
+
+    /// Writes a marker file instead of converting, so tests can tell it ran
+    #[derive(Debug)]
+    struct StubConverter;
+
+    impl AttachmentConverter for StubConverter {
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn handles(&self, attachment: &Attachment) -> bool {
+            matches!(attachment.mime_type(), MediaType::Application("pdf"))
+        }
+
+        fn copy_convert(
+            &self,
+            _: &Path,
+            to: &mut PathBuf,
+            _: &Attachment,
+        ) -> Option<MediaType<'static>> {
+            to.set_extension("txt");
+            write(&to, "converted").ok()?;
+            Some(MediaType::Text("plain"))
+        }
+    }
+
+    fn attachment(mime_type: &str) -> Attachment {
+        let mut attachment = Config::fake_attachment();
+        attachment.mime_type = Some(mime_type.to_string());
+        attachment
+    }
+
+    #[test]
+    fn can_register_custom_converter() {
+        let dir = temp_dir().join("imessage-undeleter-converter-registry");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("source.pdf");
+        write(&from, "original").unwrap();
+
+        let mut registry = ConverterRegistry::default();
+        registry.register(StubConverter);
+
+        let pdf = attachment("application/pdf");
+        assert_eq!(registry.find(&pdf).map(|c| c.name()), Some("stub"));
+
+        let mut to = dir.join("copy.pdf");
+        let media_type = registry.copy_convert(&from, &mut to, &pdf);
+
+        assert_eq!(media_type, Some(MediaType::Text("plain")));
+        assert_eq!(to, dir.join("copy.txt"));
+        assert_eq!(read_to_string(&to).unwrap(), "converted");
+
+        let _ = remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn can_copy_unhandled_attachment_raw() {
+        let dir = temp_dir().join("imessage-undeleter-converter-registry-raw");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("source.zip");
+        write(&from, "original").unwrap();
+
+        let mut registry = ConverterRegistry::default();
+        registry.register(StubConverter);
+
+        let zip = attachment("application/zip");
+        assert!(registry.find(&zip).is_none());
+
+        let mut to = dir.join("copy.zip");
+        assert_eq!(registry.copy_convert(&from, &mut to, &zip), None);
+        assert_eq!(read_to_string(&to).unwrap(), "original");
+
+        let _ = remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn can_prefer_later_registrations() {
+        let mut registry = ConverterRegistry::builtin(
+            Some(ImageConverter::Sips),
+            Some(AudioConverter::Ffmpeg),
+            None,
+            None,
+        );
+        assert_eq!(registry.names(), vec!["image", "sticker", "audio"]);
+
+        #[derive(Debug)]
+        struct AllImages;
+        impl AttachmentConverter for AllImages {
+            fn name(&self) -> &str {
+                "all images"
+            }
+
+            fn handles(&self, attachment: &Attachment) -> bool {
+                matches!(attachment.mime_type(), MediaType::Image(_))
+            }
+
+            fn copy_convert(
+                &self,
+                _: &Path,
+                _: &mut PathBuf,
+                _: &Attachment,
+            ) -> Option<MediaType<'static>> {
+                None
+            }
+        }
+
+        let image = attachment("image/heic");
+        assert_eq!(registry.find(&image).map(|c| c.name()), Some("image"));
+
+        registry.register(AllImages);
+        assert_eq!(registry.find(&image).map(|c| c.name()), Some("all images"));
+        assert_eq!(
+            registry.find(&attachment("audio/x-caf")).map(|c| c.name()),
+            Some("audio")
+        );
+    }
+}
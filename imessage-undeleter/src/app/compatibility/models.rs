@@ -62,7 +62,7 @@ impl AudioType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Program used to convert/encode images
 pub enum ImageConverter {
     /// macOS Builtin
@@ -130,7 +130,7 @@ impl Display for AudioConverter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Program used to convert/encode videos
 pub enum VideoConverter {
     Ffmpeg,
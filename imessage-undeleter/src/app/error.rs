@@ -3,8 +3,10 @@
 */
 
 use std::{
+    error::Error,
     fmt::{Display, Formatter, Result},
     io::Error as IoError,
+    iter::successors,
 };
 
 use crabapple::error::BackupError;
@@ -18,6 +20,7 @@ pub enum RuntimeError {
     DatabaseError(TableError),
     BackupError(BackupError),
     FileNameError,
+    ExportError(rusqlite::Error),
 }
 
 impl Display for RuntimeError {
@@ -28,6 +31,7 @@ impl Display for RuntimeError {
             RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
             RuntimeError::BackupError(why) => write!(fmt, "{why}"),
             RuntimeError::FileNameError => write!(fmt, "Invalid file name!"),
+            RuntimeError::ExportError(_) => write!(fmt, "Unable to write export"),
         }
     }
 }
@@ -49,3 +53,53 @@ impl From<IoError> for RuntimeError {
         RuntimeError::DiskError(err)
     }
 }
+
+impl From<rusqlite::Error> for RuntimeError {
+    fn from(err: rusqlite::Error) -> Self {
+        RuntimeError::ExportError(err)
+    }
+}
+
+impl Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        // Variants that display the wrapped error report its source instead of the error itself
+        match self {
+            RuntimeError::DiskError(why) => why.source(),
+            RuntimeError::DatabaseError(why) => why.source(),
+            RuntimeError::BackupError(why) => why.source(),
+            RuntimeError::ExportError(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+/// Format an error followed by each error that caused it, i.e. `Failed to parse messages row: no such table: message`
+pub fn error_chain(err: &dyn Error) -> String {
+    successors(Some(err), |&err| err.source())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use imessage_database::tables::{messages::Message, table::Table};
+    use rusqlite::Connection;
+
+    use crate::app::error::{RuntimeError, error_chain};
+
+    #[test]
+    fn can_chain_to_database_error() {
+        let db = Connection::open_in_memory().unwrap();
+        let err = RuntimeError::from(Message::get(&db).err().unwrap());
+
+        // RuntimeError displays the TableError, so the next error in the chain is the rusqlite::Error
+        let cause = err.source().unwrap();
+        assert!(cause.downcast_ref::<rusqlite::Error>().is_some());
+        assert!(
+            error_chain(&err).starts_with("Failed to parse messages row: no such table: message")
+        );
+    }
+}
@@ -0,0 +1,276 @@
+/*!
+ Contains logic for reading when and at what size an image attachment was captured.
+
+ Only JPEG files are parsed. The capture date comes from the EXIF `DateTimeOriginal` tag and the dimensions
+ come from the frame header, falling back to the EXIF `PixelXDimension` and `PixelYDimension` tags. When a file
+ carries no readable metadata, no metadata is returned.
+
+ HEIC and other HEIF images, which iPhones save by default, keep their EXIF data in an item of the ISOBMFF
+ container instead of a JPEG segment. Those files are not read, so they have no metadata.
+
+ This module is only compiled with the `exif` feature. Without it, exports leave the capture date and dimensions of
+ images empty.
+*/
+
+use std::path::Path;
+
+use imessage_database::tables::attachment::{Attachment, MediaType};
+
+/// Metadata describing how an image was captured
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImageMetadata {
+    /// When the photo was taken, in the camera's local time, like `2021-05-17T12:00:00`
+    pub date_taken: Option<String>,
+    /// Width of the image in pixels
+    pub width: Option<u32>,
+    /// Height of the image in pixels
+    pub height: Option<u32>,
+}
+
+impl ImageMetadata {
+    /// Read the metadata of an image attachment stored at `path`
+    ///
+    /// Returns `None` if the attachment is not a JPEG image or its metadata cannot be read.
+    pub fn from_attachment(attachment: &Attachment, path: &Path) -> Option<Self> {
+        match attachment.mime_type() {
+            MediaType::Image(_) => Self::from_path(path),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        use std::{fs::File, io::Read};
+
+        use crate::app::exif::jpeg::{MAX_HEADER_BYTES, parse_jpeg};
+
+        // EXIF data must fit in a single 64 KiB segment near the start of the file
+        let mut bytes = vec![];
+        File::open(path)
+            .ok()?
+            .take(MAX_HEADER_BYTES)
+            .read_to_end(&mut bytes)
+            .ok()?;
+        parse_jpeg(&bytes)
+    }
+}
+
+/// A minimal reader for the JPEG and TIFF structures that carry EXIF data
+mod jpeg {
+    use crate::app::exif::ImageMetadata;
+
+    /// The number of bytes read from the start of an image while looking for metadata
+    pub(super) const MAX_HEADER_BYTES: u64 = 128 * 1024;
+
+    /// EXIF tag that points to the EXIF sub-IFD
+    const TAG_EXIF_IFD: u16 = 0x8769;
+    /// EXIF tag for when the photo was taken
+    const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+    /// EXIF tag for the width of the image
+    const TAG_PIXEL_X_DIMENSION: u16 = 0xA002;
+    /// EXIF tag for the height of the image
+    const TAG_PIXEL_Y_DIMENSION: u16 = 0xA003;
+
+    /// Walk the segments of a JPEG file until the image data starts
+    pub(super) fn parse_jpeg(bytes: &[u8]) -> Option<ImageMetadata> {
+        if !bytes.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+
+        let mut metadata = ImageMetadata::default();
+        let mut pos = 2;
+        while let Some(&[0xFF, marker, high, low]) = bytes.get(pos..pos + 4) {
+            // Start of scan and end of image: there are no more headers
+            if matches!(marker, 0xDA | 0xD9) {
+                break;
+            }
+            let length = usize::from(u16::from_be_bytes([high, low]));
+            let Some(segment) = bytes.get(pos + 4..pos + 2 + length) else {
+                break;
+            };
+
+            match marker {
+                0xE1 => {
+                    if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                        parse_exif(tiff, &mut metadata);
+                    }
+                }
+                // Start of frame headers, excluding the DHT, JPG, and DAC markers that share the range
+                0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                    if let Some(&[_, h1, h2, w1, w2]) = segment.get(..5) {
+                        metadata.height = Some(u32::from(u16::from_be_bytes([h1, h2])));
+                        metadata.width = Some(u32::from(u16::from_be_bytes([w1, w2])));
+                    }
+                }
+                _ => {}
+            }
+            pos += 2 + length;
+        }
+
+        (metadata != ImageMetadata::default()).then_some(metadata)
+    }
+
+    /// Read the tags we care about from an EXIF TIFF structure
+    fn parse_exif(data: &[u8], metadata: &mut ImageMetadata) -> Option<()> {
+        let tiff = Tiff::new(data)?;
+        let exif_ifd = tiff
+            .entries(tiff.u32(4)?)
+            .find(|entry| entry.tag == TAG_EXIF_IFD)
+            .and_then(|entry| tiff.u32(entry.value))?;
+
+        for entry in tiff.entries(exif_ifd) {
+            match entry.tag {
+                TAG_DATE_TIME_ORIGINAL => metadata.date_taken = tiff.date(&entry),
+                TAG_PIXEL_X_DIMENSION => metadata.width = metadata.width.or(tiff.number(&entry)),
+                TAG_PIXEL_Y_DIMENSION => metadata.height = metadata.height.or(tiff.number(&entry)),
+                _ => {}
+            }
+        }
+        Some(())
+    }
+
+    /// A single 12-byte entry in a TIFF image file directory
+    struct IfdEntry {
+        tag: u16,
+        kind: u16,
+        count: u32,
+        /// Offset of the entry's value field, which holds the value itself or an offset to it
+        value: usize,
+    }
+
+    /// A TIFF structure with its byte order
+    struct Tiff<'a> {
+        data: &'a [u8],
+        little_endian: bool,
+    }
+
+    impl<'a> Tiff<'a> {
+        fn new(data: &'a [u8]) -> Option<Self> {
+            let little_endian = match data.get(..2)? {
+                b"II" => true,
+                b"MM" => false,
+                _ => return None,
+            };
+            Some(Self {
+                data,
+                little_endian,
+            })
+        }
+
+        fn u16(&self, at: usize) -> Option<u16> {
+            let bytes = [*self.data.get(at)?, *self.data.get(at + 1)?];
+            Some(if self.little_endian {
+                u16::from_le_bytes(bytes)
+            } else {
+                u16::from_be_bytes(bytes)
+            })
+        }
+
+        fn u32(&self, at: usize) -> Option<usize> {
+            let bytes: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+            let value = if self.little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            };
+            usize::try_from(value).ok()
+        }
+
+        /// Iterate over the entries of the directory at `offset`
+        fn entries(&self, offset: usize) -> impl Iterator<Item = IfdEntry> + '_ {
+            let count = self.u16(offset).unwrap_or(0);
+            (0..usize::from(count)).filter_map(move |idx| {
+                let start = offset + 2 + idx * 12;
+                Some(IfdEntry {
+                    tag: self.u16(start)?,
+                    kind: self.u16(start + 2)?,
+                    count: u32::try_from(self.u32(start + 4)?).ok()?,
+                    value: start + 8,
+                })
+            })
+        }
+
+        /// Read a SHORT or LONG value
+        fn number(&self, entry: &IfdEntry) -> Option<u32> {
+            match entry.kind {
+                3 => self.u16(entry.value).map(u32::from),
+                4 => self
+                    .u32(entry.value)
+                    .and_then(|value| u32::try_from(value).ok()),
+                _ => None,
+            }
+        }
+
+        /// Read an EXIF date like `2021:05:17 12:00:00` as `2021-05-17T12:00:00`
+        fn date(&self, entry: &IfdEntry) -> Option<String> {
+            // ASCII values longer than 4 bytes are stored at an offset
+            if entry.kind != 2 || entry.count <= 4 {
+                return None;
+            }
+            let start = self.u32(entry.value)?;
+            let text = self
+                .data
+                .get(start..start + usize::try_from(entry.count).ok()?)?;
+            let text = std::str::from_utf8(text).ok()?.trim_end_matches('\0');
+            chrono::NaiveDateTime::parse_from_str(text, "%Y:%m:%d %H:%M:%S")
+                .ok()
+                .map(|date| date.format("%Y-%m-%dT%H:%M:%S").to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, path::PathBuf};
+
+    use crate::app::exif::{ImageMetadata, jpeg::parse_jpeg};
+
+    fn fixture() -> PathBuf {
+        current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/exif/DateTimeOriginal.jpg")
+    }
+
+    #[test]
+    fn can_read_date_taken() {
+        assert_eq!(
+            ImageMetadata::from_path(&fixture()),
+            Some(ImageMetadata {
+                date_taken: Some(String::from("2021-05-17T12:00:00")),
+                width: Some(4032),
+                height: Some(3024),
+            })
+        );
+    }
+
+    #[test]
+    fn cant_read_missing_exif() {
+        assert_eq!(parse_jpeg(b"not an image"), None);
+        assert_eq!(parse_jpeg(&[0xFF, 0xD8, 0xFF, 0xD9]), None);
+        assert_eq!(
+            ImageMetadata::from_path(&fixture().with_extension("png")),
+            None
+        );
+    }
+
+    #[test]
+    fn cant_read_heic_exif() {
+        // The start of a HEIC file, with EXIF data copied from the JPEG fixture
+        let jpeg = std::fs::read(fixture()).unwrap();
+        let exif = jpeg
+            .windows(6)
+            .position(|window| window == b"Exif\0\0")
+            .unwrap();
+        let mut heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic".to_vec();
+        heic.extend_from_slice(&jpeg[exif..]);
+
+        assert_eq!(parse_jpeg(&heic), None);
+    }
+
+    #[test]
+    fn cant_read_truncated_exif() {
+        let bytes = std::fs::read(fixture()).unwrap();
+        assert_eq!(parse_jpeg(&bytes[..40]), None);
+    }
+}
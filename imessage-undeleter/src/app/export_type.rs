@@ -0,0 +1,105 @@
+/*!
+ Contains data structures used to describe export types.
+*/
+
+use std::fmt::Display;
+
+/// Possible one-shot export types
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExportType {
+    /// Simplified SQLite database export
+    Sqlite,
+    /// Summary statistics as a JSON report
+    Stats,
+    /// A CSV catalog of every attachment
+    Manifest,
+    /// A CSV list of every contact
+    Contacts,
+    /// Telegram's chat export JSON, for importing into Telegram
+    Telegram,
+    /// Newline-delimited JSON, one record per message
+    Json,
+    /// A web page for each conversation, laid out like the Messages app
+    Html,
+    /// A Markdown file for each conversation, for pasting into notes apps
+    Markdown,
+    /// A CSV file with one row per message, for spreadsheets and data analysis
+    Csv,
+}
+
+impl ExportType {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(export_type: &str) -> Option<Self> {
+        match export_type.to_lowercase().as_str() {
+            "sqlite" | "db" => Some(Self::Sqlite),
+            "stats" => Some(Self::Stats),
+            "manifest" => Some(Self::Manifest),
+            "contacts" => Some(Self::Contacts),
+            "telegram" => Some(Self::Telegram),
+            "json" => Some(Self::Json),
+            "html" => Some(Self::Html),
+            "markdown" | "md" => Some(Self::Markdown),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /// Whether the format can continue the files an earlier export wrote, for `--since-rowid`
+    pub fn can_append(&self) -> bool {
+        matches!(
+            self,
+            Self::Manifest | Self::Json | Self::Html | Self::Markdown | Self::Csv
+        )
+    }
+}
+
+impl Display for ExportType {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportType::Sqlite => write!(fmt, "sqlite"),
+            ExportType::Stats => write!(fmt, "stats"),
+            ExportType::Manifest => write!(fmt, "manifest"),
+            ExportType::Contacts => write!(fmt, "contacts"),
+            ExportType::Telegram => write!(fmt, "telegram"),
+            ExportType::Json => write!(fmt, "json"),
+            ExportType::Html => write!(fmt, "html"),
+            ExportType::Markdown => write!(fmt, "markdown"),
+            ExportType::Csv => write!(fmt, "csv"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::export_type::ExportType;
+
+    #[test]
+    fn can_parse_export_type() {
+        assert_eq!(ExportType::from_cli("sqlite"), Some(ExportType::Sqlite));
+        assert_eq!(ExportType::from_cli("SQLite"), Some(ExportType::Sqlite));
+        assert_eq!(ExportType::from_cli("db"), Some(ExportType::Sqlite));
+        assert_eq!(ExportType::from_cli("stats"), Some(ExportType::Stats));
+        assert_eq!(ExportType::from_cli("manifest"), Some(ExportType::Manifest));
+        assert_eq!(ExportType::from_cli("contacts"), Some(ExportType::Contacts));
+        assert_eq!(ExportType::from_cli("Telegram"), Some(ExportType::Telegram));
+        assert_eq!(ExportType::from_cli("JSON"), Some(ExportType::Json));
+        assert_eq!(ExportType::from_cli("html"), Some(ExportType::Html));
+        assert_eq!(ExportType::from_cli("markdown"), Some(ExportType::Markdown));
+        assert_eq!(ExportType::from_cli("md"), Some(ExportType::Markdown));
+        assert_eq!(ExportType::from_cli("CSV"), Some(ExportType::Csv));
+        assert_eq!(ExportType::from_cli("pdf"), None);
+    }
+
+    #[test]
+    fn can_only_append_to_streamed_formats() {
+        assert!(ExportType::Csv.can_append());
+        assert!(ExportType::Json.can_append());
+        assert!(ExportType::Manifest.can_append());
+        assert!(ExportType::Html.can_append());
+        assert!(ExportType::Markdown.can_append());
+        assert!(!ExportType::Sqlite.can_append());
+        assert!(!ExportType::Stats.can_append());
+        assert!(!ExportType::Contacts.can_append());
+        assert!(!ExportType::Telegram.can_append());
+    }
+}
@@ -1,5 +1,14 @@
+pub mod address_book;
+pub mod attachment_placeholder;
 pub mod compatibility;
 pub mod error;
+#[cfg(feature = "exif")]
+pub mod exif;
+pub mod export_type;
 pub mod options;
+pub mod quick_export;
+pub mod reaction_placement;
 pub mod runtime;
-pub mod sanitizers;
\ No newline at end of file
+pub mod sanitizers;
+pub mod text_filter;
+pub mod timestamp_granularity;
@@ -4,11 +4,13 @@
 
 use std::path::PathBuf;
 
+use chrono_tz::Tz;
 use clap::{Arg, ArgAction, ArgMatches, Command, crate_version};
 
 use imessage_database::{
     tables::{attachment::DEFAULT_ATTACHMENT_ROOT, table::DEFAULT_PATH_IOS},
     util::{
+        dates::utc_offset_on_day,
         dirs::{default_db_path, home},
         platform::Platform,
         query_context::QueryContext,
@@ -16,8 +18,16 @@ use imessage_database::{
 };
 
 use crate::app::{
-    compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
+    address_book::DEFAULT_COUNTRY_CODE,
+    attachment_placeholder::AttachmentPlaceholder,
+    compatibility::attachment_manager::{
+        AttachmentLayout, AttachmentManager, AttachmentManagerMode,
+    },
     error::RuntimeError,
+    export_type::ExportType,
+    reaction_placement::ReactionPlacement,
+    text_filter::TextFilter,
+    timestamp_granularity::TimestampGranularity,
 };
 
 /// Default export directory name
@@ -33,10 +43,42 @@ pub const OPTION_PLATFORM: &str = "platform";
 pub const OPTION_USE_CALLER_ID: &str = "use-caller-id";
 pub const OPTION_CONVERSATION_FILTER: &str = "conversation-filter";
 pub const OPTION_CLEARTEXT_PASSWORD: &str = "cleartext-password";
+pub const OPTION_EXPORT_TYPE: &str = "format";
+pub const OPTION_SKIP_EMPTY_CHATS: &str = "skip-empty-chats";
+pub const OPTION_REDACT_ONE_TIME_CODES: &str = "redact-one-time-codes";
+pub const OPTION_ON_THIS_DAY: &str = "on-this-day";
+pub const OPTION_PRESERVE_ATTACHMENT_TIMES: &str = "preserve-attachment-times";
+pub const OPTION_COPY_METHOD: &str = "copy-method";
+pub const OPTION_SEPARATE_RECOVERED: &str = "separate-recovered";
+pub const OPTION_ATTACHMENT_LAYOUT: &str = "attachment-layout";
+pub const OPTION_EXPORT_ALL: &str = "export-all";
+pub const OPTION_REDACT_IDENTITIES: &str = "redact-identities";
+pub const OPTION_MESSAGE_JSON: &str = "message-json";
+pub const OPTION_REACTION_PLACEMENT: &str = "reaction-placement";
+pub const OPTION_ACTIVE_WITHIN_DAYS: &str = "active-within-days";
+pub const OPTION_SHOW_RECEIPTS: &str = "show-receipts";
+pub const OPTION_ATTACHMENT_PLACEHOLDER: &str = "attachment-placeholder";
+pub const OPTION_ATTACHMENT_BASE_URL: &str = "attachment-base-url";
+pub const OPTION_MATCH_REGEX: &str = "match-regex";
+pub const OPTION_MAX_FILE_SIZE: &str = "max-file-size";
+pub const OPTION_TIMESTAMP_GRANULARITY: &str = "timestamp-granularity";
+pub const OPTION_TIMEZONE: &str = "timezone";
+pub const OPTION_SINCE_ROWID: &str = "since-rowid";
+pub const OPTION_WRAP_WIDTH: &str = "wrap-width";
+pub const OPTION_COUNTRY_CODE: &str = "country-code";
+pub const OPTION_MERGE_DB_PATH: &str = "merge-db-path";
 
 // Other CLI Text
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
-pub const ABOUT: &str = "The `imessage-undeleter` binary watches iMessage conversations for deleted messages.\n";
+pub const SUPPORTED_EXPORT_TYPES: &str =
+    "sqlite, stats, manifest, contacts, telegram, json, html, markdown, csv";
+pub const SUPPORTED_COPY_METHODS: &str = "clone, basic, full, link, disabled";
+pub const SUPPORTED_ATTACHMENT_LAYOUTS: &str = "flat, chat";
+pub const SUPPORTED_REACTION_PLACEMENTS: &str = "before, after";
+pub const SUPPORTED_ATTACHMENT_PLACEHOLDERS: &str = "label, strip, keep";
+pub const SUPPORTED_TIMESTAMP_GRANULARITIES: &str = "full, time, date";
+pub const ABOUT: &str =
+    "The `imessage-undeleter` binary watches iMessage conversations for deleted messages.\n";
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Options {
@@ -46,6 +88,8 @@ pub struct Options {
     pub attachment_root: Option<String>,
     /// The attachment manager type used to copy files
     pub attachment_manager: AttachmentManager,
+    /// How copied attachments are arranged in the attachment directory
+    pub attachment_layout: AttachmentLayout,
     /// Where the app will save exported data
     pub export_path: PathBuf,
     /// Query context describing SQL query filters
@@ -60,6 +104,46 @@ pub struct Options {
     pub conversation_filter: Option<String>,
     /// An optional password for encrypted backups
     pub cleartext_password: Option<String>,
+    /// If not empty, export the database once in these formats instead of monitoring it
+    pub export_types: Vec<ExportType>,
+    /// If true, do not export chats that only contain system messages
+    pub skip_empty_chats: bool,
+    /// If true, replace one-time codes in message text with a label
+    pub redact_one_time_codes: bool,
+    /// If true, replace contact names with pseudonyms and remove phone numbers and emails from message text
+    pub redact_identities: bool,
+    /// If true, copied attachments keep the original file's modification time
+    pub preserve_attachment_times: bool,
+    /// If true, reports list messages recovered from Recently Deleted in their own section
+    pub separate_recovered: bool,
+    /// If set, print the message with this GUID as JSON instead of exporting
+    pub message_json: Option<String>,
+    /// Where human-readable exports write the reactions to a message
+    pub reaction_placement: ReactionPlacement,
+    /// If set, only export chats that have a message from the last this many days
+    pub active_within_days: Option<u32>,
+    /// If true, human-readable exports note when outgoing messages were delivered or read
+    pub show_receipts: bool,
+    /// How plain text exports write the placeholder character that marks an attachment
+    pub attachment_placeholder: AttachmentPlaceholder,
+    /// If set, exports link to copied attachments under this URL instead of by relative path
+    pub attachment_base_url: Option<String>,
+    /// If set, only export messages whose text matches this expression
+    pub match_regex: Option<TextFilter>,
+    /// If set, exported files are split into numbered volumes of about this many bytes
+    pub max_file_size: Option<u64>,
+    /// How much of each message's timestamp human-readable exports write
+    pub timestamp_granularity: TimestampGranularity,
+    /// If set, exported dates are written in this time zone instead of the time zone of the current machine
+    pub timezone: Option<Tz>,
+    /// If set, only export messages added after this `ROWID`, appending them to the files of an earlier export
+    pub since_rowid: Option<i32>,
+    /// If set, the text of deleted messages is hard-wrapped at this many characters
+    pub wrap_width: Option<usize>,
+    /// The country calling code given to AddressBook phone numbers stored without one
+    pub country_code: String,
+    /// Other `chat.db` files whose messages are merged into the JSON export
+    pub merge_db_paths: Vec<PathBuf>,
 }
 
 impl Options {
@@ -67,14 +151,46 @@ impl Options {
         let user_path: Option<&String> = args.get_one(OPTION_DB_PATH);
         let attachment_root: Option<&String> = args.get_one(OPTION_ATTACHMENT_ROOT);
         let user_export_path: Option<&String> = args.get_one(OPTION_EXPORT_PATH);
-        let check_last_n_messages_string: Option<&String> = args.get_one(OPTION_CHECK_LAST_N_MESSAGES);
+        let check_last_n_messages_string: Option<&String> =
+            args.get_one(OPTION_CHECK_LAST_N_MESSAGES);
         let custom_name: Option<&String> = args.get_one(OPTION_CUSTOM_NAME);
         let use_caller_id = args.get_flag(OPTION_USE_CALLER_ID);
         let platform_type: Option<&String> = args.get_one(OPTION_PLATFORM);
         let conversation_filter: Option<&String> = args.get_one(OPTION_CONVERSATION_FILTER);
         let cleartext_password: Option<&String> = args.get_one(OPTION_CLEARTEXT_PASSWORD);
+        let export_file_types: Vec<&String> = args
+            .get_many(OPTION_EXPORT_TYPE)
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let skip_empty_chats = args.get_flag(OPTION_SKIP_EMPTY_CHATS);
+        let redact_one_time_codes = args.get_flag(OPTION_REDACT_ONE_TIME_CODES);
+        let redact_identities = args.get_flag(OPTION_REDACT_IDENTITIES);
+        let on_this_day: Option<&String> = args.get_one(OPTION_ON_THIS_DAY);
+        let preserve_attachment_times = args.get_flag(OPTION_PRESERVE_ATTACHMENT_TIMES);
+        let copy_method: Option<&String> = args.get_one(OPTION_COPY_METHOD);
+        let separate_recovered = args.get_flag(OPTION_SEPARATE_RECOVERED);
+        let layout: Option<&String> = args.get_one(OPTION_ATTACHMENT_LAYOUT);
+        let message_json: Option<&String> = args.get_one(OPTION_MESSAGE_JSON);
+        let placement: Option<&String> = args.get_one(OPTION_REACTION_PLACEMENT);
+        let active_within: Option<&String> = args.get_one(OPTION_ACTIVE_WITHIN_DAYS);
+        let show_receipts = args.get_flag(OPTION_SHOW_RECEIPTS);
+        let placeholder: Option<&String> = args.get_one(OPTION_ATTACHMENT_PLACEHOLDER);
+        let attachment_base_url: Option<&String> = args.get_one(OPTION_ATTACHMENT_BASE_URL);
+        let match_regex: Option<&String> = args.get_one(OPTION_MATCH_REGEX);
+        let max_file_size: Option<&String> = args.get_one(OPTION_MAX_FILE_SIZE);
+        let granularity: Option<&String> = args.get_one(OPTION_TIMESTAMP_GRANULARITY);
+        let timezone: Option<&String> = args.get_one(OPTION_TIMEZONE);
+        let since: Option<&String> = args.get_one(OPTION_SINCE_ROWID);
+        let wrap: Option<&String> = args.get_one(OPTION_WRAP_WIDTH);
+        let country_code: Option<&String> = args.get_one(OPTION_COUNTRY_CODE);
+        let merge_db_paths: Vec<PathBuf> = args
+            .get_many::<String>(OPTION_MERGE_DB_PATH)
+            .map(|paths| paths.map(PathBuf::from).collect())
+            .unwrap_or_default();
 
-        let check_last_n_messages: Option<i32> = check_last_n_messages_string.map(|s| s.parse::<i32>().ok()).flatten();
+        let check_last_n_messages: Option<i32> = check_last_n_messages_string
+            .map(|s| s.parse::<i32>().ok())
+            .flatten();
 
         // Prevent custom_name vs. use_caller_id collision
         if custom_name.is_some() && use_caller_id {
@@ -83,11 +199,60 @@ impl Options {
             )));
         }
 
+        // Build the export types, writing each format once
+        let mut export_types = vec![];
+        for export_type_str in export_file_types {
+            let export_type = ExportType::from_cli(export_type_str).ok_or(RuntimeError::InvalidOptions(format!(
+                "{export_type_str} is not a valid export type! Must be one of <{SUPPORTED_EXPORT_TYPES}>"
+            )))?;
+            if !export_types.contains(&export_type) {
+                export_types.push(export_type);
+            }
+        }
+
         // Build query context
         let mut query_context = QueryContext::default();
         if let Some(limit) = check_last_n_messages {
             query_context.set_limit(limit.clone());
         }
+        if let Some(calendar_day) = on_this_day {
+            let (month, day) = calendar_day
+                .split_once('-')
+                .and_then(|(month, day)| Some((month.parse().ok()?, day.parse().ok()?)))
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "{calendar_day} is not a valid calendar day! Must be in the format MM-DD"
+                )))?;
+            query_context
+                .set_on_this_day(month, day)
+                .map_err(|why| RuntimeError::InvalidOptions(why.to_string()))?;
+        }
+
+        // Continue an earlier export from the last message it wrote
+        let since_rowid = match since {
+            Some(since) => Some(since.parse::<i32>().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{since} is not a valid message ROWID for --{OPTION_SINCE_ROWID}!"
+                ))
+            })?),
+            None => None,
+        };
+        if let Some(rowid) = since_rowid {
+            // Reports that are rewritten from scratch would lose what earlier exports wrote
+            if let Some(export_type) = export_types
+                .iter()
+                .find(|export_type| !export_type.can_append())
+            {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_SINCE_ROWID} is enabled; {export_type} exports cannot be continued"
+                )));
+            }
+            if on_this_day.is_some() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_SINCE_ROWID} is enabled; --{OPTION_ON_THIS_DAY} reports cannot be continued"
+                )));
+            }
+            query_context.set_since_rowid(rowid);
+        }
 
         // We have to allocate a PathBuf here because it can be created from data owned by this function in the default state
         let db_path = match user_path {
@@ -131,15 +296,144 @@ impl Options {
         }
 
         // Determine the attachment manager mode
-        let attachment_manager_mode = AttachmentManagerMode::default();
+        let attachment_manager_mode = match copy_method {
+            Some(method) => AttachmentManagerMode::from_cli(method).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{method} is not a valid copy method! Must be one of <{SUPPORTED_COPY_METHODS}>"
+                )),
+            )?,
+            None => AttachmentManagerMode::default(),
+        };
+
+        // Determine where copied attachments are placed
+        let attachment_layout = match layout {
+            Some(layout) => AttachmentLayout::from_cli(layout).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{layout} is not a valid attachment layout! Must be one of <{SUPPORTED_ATTACHMENT_LAYOUTS}>"
+                )),
+            )?,
+            None => AttachmentLayout::default(),
+        };
+
+        // Determine where reactions are written relative to their message
+        let reaction_placement = match placement {
+            Some(placement) => ReactionPlacement::from_cli(placement).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{placement} is not a valid reaction placement! Must be one of <{SUPPORTED_REACTION_PLACEMENTS}>"
+                )),
+            )?,
+            None => ReactionPlacement::default(),
+        };
+
+        // Determine how attachment placeholders are written in plain text
+        let attachment_placeholder = match placeholder {
+            Some(placeholder) => AttachmentPlaceholder::from_cli(placeholder).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{placeholder} is not a valid attachment placeholder! Must be one of <{SUPPORTED_ATTACHMENT_PLACEHOLDERS}>"
+                )),
+            )?,
+            None => AttachmentPlaceholder::default(),
+        };
+
+        // Determine how much of each timestamp human-readable exports write
+        let timestamp_granularity = match granularity {
+            Some(granularity) => TimestampGranularity::from_cli(granularity).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{granularity} is not a valid timestamp granularity! Must be one of <{SUPPORTED_TIMESTAMP_GRANULARITIES}>"
+                )),
+            )?,
+            None => TimestampGranularity::default(),
+        };
+
+        // Determine the time zone exported dates are written in
+        let timezone = match timezone {
+            Some(timezone) => Some(timezone.parse::<Tz>().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{timezone} is not a valid time zone! Must be an IANA time zone name, like America/New_York"
+                ))
+            })?),
+            None => None,
+        };
+        // Match calendar days in the same time zone that dates are written in
+        if let (Some(tz), Some((month, day))) = (timezone, query_context.on_this_day) {
+            query_context.set_utc_offset(utc_offset_on_day(tz, month, day));
+        }
+
+        // Compile the text filter once so an invalid expression is reported before exporting
+        let match_regex = match match_regex {
+            Some(pattern) => Some(TextFilter::new(pattern).map_err(|why| {
+                RuntimeError::InvalidOptions(format!(
+                    "{pattern} is not a valid regular expression for --{OPTION_MATCH_REGEX}: {why}"
+                ))
+            })?),
+            None => None,
+        };
+
+        // Validate the window used to select recently active chats
+        let active_within_days = match active_within {
+            Some(days) => Some(days.parse::<u32>().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{days} is not a valid number of days for --{OPTION_ACTIVE_WITHIN_DAYS}!"
+                ))
+            })?),
+            None => None,
+        };
+
+        // Validate the width deleted message text is wrapped at
+        let wrap_width = match wrap {
+            Some(width) => Some(
+                width
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or(RuntimeError::InvalidOptions(format!(
+                        "{width} is not a valid number of characters for --{OPTION_WRAP_WIDTH}!"
+                    )))?,
+            ),
+            None => None,
+        };
+
+        // Validate the country calling code used to match contacts, like `44` or `+44`
+        let country_code = match country_code {
+            Some(code) => {
+                let digits = code.strip_prefix('+').unwrap_or(code);
+                if digits.is_empty()
+                    || digits.len() > 3
+                    || !digits.chars().all(|c| c.is_ascii_digit())
+                {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "{code} is not a valid country calling code for --{OPTION_COUNTRY_CODE}!"
+                    )));
+                }
+                digits.to_string()
+            }
+            None => DEFAULT_COUNTRY_CODE.to_string(),
+        };
+
+        // Merged databases are only written to the JSON export
+        if !merge_db_paths.is_empty() && export_types != [ExportType::Json] {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "--{OPTION_MERGE_DB_PATH} can only be used with --{OPTION_EXPORT_TYPE} json"
+            )));
+        }
+
+        // Validate the size at which exported files are split
+        let max_file_size = match max_file_size {
+            Some(size) => Some(parse_file_size(size).ok_or(RuntimeError::InvalidOptions(format!(
+                "{size} is not a valid size for --{OPTION_MAX_FILE_SIZE}! Must be a number of bytes, optionally followed by KB, MB, or GB"
+            )))?),
+            None => None,
+        };
 
         // Validate the provided export path
-        let export_path = PathBuf::from(user_export_path.unwrap_or(&format!("./{DEFAULT_OUTPUT_DIR}")));
+        let export_path =
+            PathBuf::from(user_export_path.unwrap_or(&format!("./{DEFAULT_OUTPUT_DIR}")));
 
         Ok(Options {
             db_path,
             attachment_root: attachment_root.cloned(),
             attachment_manager: AttachmentManager::from(attachment_manager_mode),
+            attachment_layout,
             export_path,
             query_context,
             custom_name: custom_name.cloned(),
@@ -147,6 +441,26 @@ impl Options {
             platform,
             conversation_filter: conversation_filter.cloned(),
             cleartext_password: cleartext_password.cloned(),
+            export_types,
+            skip_empty_chats,
+            redact_one_time_codes,
+            redact_identities,
+            preserve_attachment_times,
+            separate_recovered,
+            message_json: message_json.cloned(),
+            reaction_placement,
+            active_within_days,
+            show_receipts,
+            attachment_placeholder,
+            attachment_base_url: attachment_base_url.cloned(),
+            match_regex,
+            max_file_size,
+            timestamp_granularity,
+            timezone,
+            since_rowid,
+            wrap_width,
+            country_code,
+            merge_db_paths,
         })
     }
 
@@ -159,12 +473,29 @@ impl Options {
     }
 }
 
+/// Parse a file size like `500000`, `750KB`, or `10MB` into a number of bytes
+///
+/// Suffixes are case insensitive and use powers of 1024. Zero is not a valid size.
+fn parse_file_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_uppercase();
+    let (number, multiplier) = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10), ("B", 1)]
+        .into_iter()
+        .find_map(|(suffix, multiplier)| Some((size.strip_suffix(suffix)?, multiplier)))
+        .unwrap_or((size.as_str(), 1));
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|number| *number > 0)
+        .and_then(|number| number.checked_mul(multiplier))
+}
+
 /// Ensure export path is empty or does not contain files of the existing export type
 ///
 /// We have to allocate a `PathBuf` here because it can be created from data owned by this function in the default state
 
 /// Build the command line argument parser
-fn get_command() -> Command {
+pub(crate) fn get_command() -> Command {
     Command::new("iMessage Exporter")
         .version(crate_version!())
         .about(ABOUT)
@@ -213,7 +544,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_CUSTOM_NAME)
                 .short('m')
                 .long(OPTION_CUSTOM_NAME)
-                .help(format!("Specify an optional custom name for the database owner's messages in exports\nConflicts with --{OPTION_USE_CALLER_ID}\n"))
+                .help(format!("Specify an optional custom name for the database owner in exports, used instead of \"Me\"\nConflicts with --{OPTION_USE_CALLER_ID}\n"))
                 .display_order(10)
         )
         .arg(
@@ -240,9 +571,257 @@ fn get_command() -> Command {
                 .display_order(14)
                 .value_name("password"),
         )
+        .arg(
+            Arg::new(OPTION_EXPORT_TYPE)
+                .short('f')
+                .long(OPTION_EXPORT_TYPE)
+                .help("Export the database once in the specified formats instead of monitoring it for deleted messages\nSeparate multiple formats with commas to write all of them in a single pass\n")
+                .display_order(1)
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name(SUPPORTED_EXPORT_TYPES),
+        )
+        .arg(
+            Arg::new(OPTION_SKIP_EMPTY_CHATS)
+                .long(OPTION_SKIP_EMPTY_CHATS)
+                .help("Do not export chats that only contain system messages, like group name changes\nIf omitted, all chats are exported\n")
+                .action(ArgAction::SetTrue)
+                .display_order(15)
+        )
+        .arg(
+            Arg::new(OPTION_REDACT_ONE_TIME_CODES)
+                .long(OPTION_REDACT_ONE_TIME_CODES)
+                .help("Replace one-time codes, i.e. from 2FA messages, with a label in exports\nIf omitted, the codes are preserved\n")
+                .action(ArgAction::SetTrue)
+                .display_order(16)
+        )
+        .arg(
+            Arg::new(OPTION_ON_THIS_DAY)
+                .long(OPTION_ON_THIS_DAY)
+                .help(format!("Write a report of the messages sent on a calendar day across every year\nCombine with --{OPTION_EXPORT_TYPE} to export only those messages instead\n"))
+                .display_order(17)
+                .value_name("MM-DD"),
+        )
+        .arg(
+            Arg::new(OPTION_PRESERVE_ATTACHMENT_TIMES)
+                .long(OPTION_PRESERVE_ATTACHMENT_TIMES)
+                .help("Keep the original modification time on copied attachments\nIf the original time is unreliable, the message's date is used\nIf omitted, copied attachments use the message's date\n")
+                .action(ArgAction::SetTrue)
+                .display_order(18)
+        )
+        .arg(
+            Arg::new(OPTION_COPY_METHOD)
+                .short('c')
+                .long(OPTION_COPY_METHOD)
+                .help("Specify how attachments are handled\n`link` does not copy attachments; exports reference the original files with `file://` URLs\n`disabled` does not copy attachments\nIf omitted, the default is `full`\n")
+                .display_order(7)
+                .value_name(SUPPORTED_COPY_METHODS),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_LAYOUT)
+                .long(OPTION_ATTACHMENT_LAYOUT)
+                .help("Specify how copied attachments are arranged\n`chat` places each chat's attachments in a subdirectory named after the chat\nIf omitted, the default is `flat`\n")
+                .display_order(8)
+                .value_name(SUPPORTED_ATTACHMENT_LAYOUTS),
+        )
+        .arg(
+            Arg::new(OPTION_SEPARATE_RECOVERED)
+                .long(OPTION_SEPARATE_RECOVERED)
+                .help(format!("List messages recovered from Recently Deleted in their own section of the --{OPTION_ON_THIS_DAY} report\nIf omitted, recovered messages are labeled where they were sent\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(19)
+        )
+        .arg(
+            Arg::new(OPTION_EXPORT_ALL)
+                .long(OPTION_EXPORT_ALL)
+                .help(format!("Export every chat with the default settings, then exit\nOnly --{OPTION_DB_PATH} and --{OPTION_EXPORT_PATH} are used\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(20)
+        )
+        .arg(
+            Arg::new(OPTION_REDACT_IDENTITIES)
+                .long(OPTION_REDACT_IDENTITIES)
+                .help("Replace contact names with pseudonyms like `Contact 1` and remove phone numbers and emails from message text\nAttachments are still copied or linked, and file names are not changed\nIf omitted, names and message text are preserved\n")
+                .action(ArgAction::SetTrue)
+                .display_order(21)
+        )
+        .arg(
+            Arg::new(OPTION_MESSAGE_JSON)
+                .long(OPTION_MESSAGE_JSON)
+                .help("Print the message with this GUID as JSON, including its edits, attachments, and tapbacks, then exit\nUseful for sharing a single message in a bug report\n")
+                .display_order(22)
+                .value_name("GUID"),
+        )
+        .arg(
+            Arg::new(OPTION_REACTION_PLACEMENT)
+                .long(OPTION_REACTION_PLACEMENT)
+                .help("Specify whether reactions are written before or after the message they target in human-readable exports\nIf omitted, the default is `after`\n")
+                .display_order(9)
+                .value_name(SUPPORTED_REACTION_PLACEMENTS),
+        )
+        .arg(
+            Arg::new(OPTION_ACTIVE_WITHIN_DAYS)
+                .long(OPTION_ACTIVE_WITHIN_DAYS)
+                .help("Only export chats that have a message from the last n days\nSelected chats are exported in full, including their older messages\nIf omitted, all chats are exported\n")
+                .display_order(12)
+                .value_name("30"),
+        )
+        .arg(
+            Arg::new(OPTION_SHOW_RECEIPTS)
+                .long(OPTION_SHOW_RECEIPTS)
+                .help("Note when outgoing messages were delivered or read in human-readable exports\nLike the Messages app, only the latest message in a run of outgoing messages is annotated\n")
+                .action(ArgAction::SetTrue)
+                .display_order(23)
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_PLACEHOLDER)
+                .long(OPTION_ATTACHMENT_PLACEHOLDER)
+                .help("Specify how plain text exports write the character that marks where an attachment was sent
+`label` writes `[attachment]`, `strip` removes it, and `keep` leaves it as-is
+If omitted, the default is `label`
+")
+                .display_order(24)
+                .value_name(SUPPORTED_ATTACHMENT_PLACEHOLDERS),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_BASE_URL)
+                .long(OPTION_ATTACHMENT_BASE_URL)
+                .help("Link to copied attachments under this URL instead of by their path relative to the export directory
+Useful when an export is served from a web server, like `/media` or `https://example.com/media`
+Attachments that are not copied keep their original location
+")
+                .display_order(25)
+                .value_name("/media"),
+        )
+        .arg(
+            Arg::new(OPTION_MATCH_REGEX)
+                .long(OPTION_MATCH_REGEX)
+                .help("Only export messages whose text matches this regular expression
+The expression is matched after the message body is generated, so tapbacks and system messages without text are skipped
+If omitted, all messages are exported
+")
+                .display_order(26)
+                .value_name("PATTERN"),
+        )
+        .arg(
+            Arg::new(OPTION_MAX_FILE_SIZE)
+                .long(OPTION_MAX_FILE_SIZE)
+                .help("Split large export files into numbered volumes of about this size, like `50MB`
+A new volume is started between messages, so a single message is never split across files
+If omitted, each export is written to a single file
+")
+                .display_order(27)
+                .value_name("50MB"),
+        )
+        .arg(
+            Arg::new(OPTION_TIMESTAMP_GRANULARITY)
+                .long(OPTION_TIMESTAMP_GRANULARITY)
+                .help("Specify how much of each message's timestamp human-readable exports write
+`full` writes the date and time, `time` writes only the time, and `date` writes only the date
+If omitted, the default is `full`
+")
+                .display_order(28)
+                .value_name(SUPPORTED_TIMESTAMP_GRANULARITIES),
+        )
+        .arg(
+            Arg::new(OPTION_TIMEZONE)
+                .long(OPTION_TIMEZONE)
+                .help("Write exported dates in this time zone, like `America/New_York`
+Useful when exporting a database on a machine in a different time zone than the device it came from
+If omitted, dates are written in the time zone of the current machine
+")
+                .display_order(29)
+                .value_name("America/New_York"),
+        )
+        .arg(
+            Arg::new(OPTION_SINCE_ROWID)
+                .long(OPTION_SINCE_ROWID)
+                .help("Only export messages added after the message with this ROWID, for incremental backups
+New messages are appended to the files an earlier export wrote to the same directory
+CSV, JSON, and manifest exports append them in ascending ROWID order
+Only CSV, JSON, manifest, HTML, and Markdown exports can be continued
+Each export prints the last ROWID it wrote, which can be passed here the next time
+")
+                .display_order(30)
+                .value_name("rowid"),
+        )
+        .arg(
+            Arg::new(OPTION_WRAP_WIDTH)
+                .long(OPTION_WRAP_WIDTH)
+                .help("Hard-wrap the text of deleted messages written to LOGFILE.html at this many characters
+Lines are broken at word boundaries, and words longer than the width are split
+If omitted, text is not wrapped
+")
+                .display_order(31)
+                .value_name("80"),
+        )
+        .arg(
+            Arg::new(OPTION_COUNTRY_CODE)
+                .long(OPTION_COUNTRY_CODE)
+                .help(format!("The country calling code of phone numbers saved in Contacts without one, like `44` for the UK
+Used to match contact names to conversations on macOS
+If omitted, the default is `{DEFAULT_COUNTRY_CODE}`
+"))
+                .display_order(32)
+                .value_name("1"),
+        )
+        .arg(
+            Arg::new(OPTION_MERGE_DB_PATH)
+                .long(OPTION_MERGE_DB_PATH)
+                .help(format!("Merge the messages from another `chat.db` file, like an older backup, into the JSON export
+Messages are de-duplicated and written oldest first in a single timeline
+Repeat to merge several databases; requires --{OPTION_EXPORT_TYPE} json
+"))
+                .display_order(33)
+                .action(ArgAction::Append)
+                .value_name("path/to/chat.db"),
+        )
 }
 
 /// Parse arguments from the command line
 pub fn from_command_line() -> ArgMatches {
     get_command().get_matches()
 }
+
+#[cfg(test)]
+impl Options {
+    pub fn fake_options() -> Options {
+        Options {
+            db_path: std::env::current_dir()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .join("imessage-database/test_data/db/test.db"),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            attachment_layout: AttachmentLayout::default(),
+            export_path: PathBuf::from(format!("/tmp/{DEFAULT_OUTPUT_DIR}")),
+            query_context: QueryContext::default(),
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::macOS,
+            conversation_filter: None,
+            cleartext_password: None,
+            export_types: vec![],
+            skip_empty_chats: false,
+            redact_one_time_codes: false,
+            redact_identities: false,
+            preserve_attachment_times: false,
+            separate_recovered: false,
+            message_json: None,
+            reaction_placement: ReactionPlacement::default(),
+            active_within_days: None,
+            show_receipts: false,
+            attachment_placeholder: AttachmentPlaceholder::default(),
+            attachment_base_url: None,
+            match_regex: None,
+            max_file_size: None,
+            timestamp_granularity: TimestampGranularity::default(),
+            timezone: None,
+            since_rowid: None,
+            wrap_width: None,
+            country_code: DEFAULT_COUNTRY_CODE.to_string(),
+            merge_db_paths: vec![],
+        }
+    }
+}
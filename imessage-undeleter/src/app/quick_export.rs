@@ -0,0 +1,165 @@
+/*!
+ A single entry point that exports an entire database with sensible defaults.
+*/
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::{Path, PathBuf},
+};
+
+use imessage_database::util::{platform::Platform, query_context::QueryContext};
+
+use crate::app::{
+    address_book::DEFAULT_COUNTRY_CODE,
+    attachment_placeholder::AttachmentPlaceholder,
+    compatibility::attachment_manager::{
+        AttachmentLayout, AttachmentManager, AttachmentManagerMode,
+    },
+    error::RuntimeError,
+    export_type::ExportType,
+    options::Options,
+    reaction_placement::ReactionPlacement,
+    runtime::Config,
+    timestamp_granularity::TimestampGranularity,
+};
+
+/// The format used by [`export_everything()`]
+pub const DEFAULT_EXPORT_TYPE: ExportType = ExportType::Html;
+
+/// Describes what [`export_everything()`] wrote
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// The format the database was exported to
+    pub export_type: ExportType,
+    /// The directory the export was written to
+    pub export_path: PathBuf,
+    /// The number of chats with at least one exported message
+    pub chats: usize,
+    /// The number of messages written to the export
+    pub messages: u64,
+    /// The number of attachments sent with the exported messages
+    pub attachments: u64,
+}
+
+impl Display for ExportSummary {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            fmt,
+            "Exported {} messages with {} attachments from {} chats as {} to {}",
+            self.messages,
+            self.attachments,
+            self.chats,
+            self.export_type,
+            self.export_path.display()
+        )
+    }
+}
+
+/// Export every chat in a database to a directory without building [`Options`] by hand
+///
+/// Uses the same defaults as the command line: attachments are copied and converted, and no chats are
+/// filtered out. The platform is detected from `db_path`.
+pub fn export_everything(
+    db_path: &Path,
+    export_path: &Path,
+) -> Result<ExportSummary, RuntimeError> {
+    let options = Options {
+        db_path: db_path.to_path_buf(),
+        attachment_root: None,
+        attachment_manager: AttachmentManager::from(AttachmentManagerMode::Full),
+        attachment_layout: AttachmentLayout::default(),
+        export_path: export_path.to_path_buf(),
+        query_context: QueryContext::default(),
+        custom_name: None,
+        use_caller_id: false,
+        platform: Platform::determine(db_path)?,
+        conversation_filter: None,
+        cleartext_password: None,
+        export_types: vec![DEFAULT_EXPORT_TYPE],
+        skip_empty_chats: false,
+        redact_one_time_codes: false,
+        redact_identities: false,
+        preserve_attachment_times: false,
+        separate_recovered: false,
+        message_json: None,
+        reaction_placement: ReactionPlacement::default(),
+        active_within_days: None,
+        show_receipts: false,
+        attachment_placeholder: AttachmentPlaceholder::default(),
+        attachment_base_url: None,
+        match_regex: None,
+        max_file_size: None,
+        timestamp_granularity: TimestampGranularity::default(),
+        timezone: None,
+        since_rowid: None,
+        wrap_width: None,
+        country_code: DEFAULT_COUNTRY_CODE.to_string(),
+        merge_db_paths: vec![],
+    };
+
+    eprintln!("[1/3] Reading {}...", db_path.display());
+    let config = Config::new(options)?;
+
+    eprintln!("[2/3] Exporting to {}...", export_path.display());
+    config.start()?;
+
+    eprintln!("[3/3] Summarizing export...");
+    let exported = config.exported.borrow();
+    let summary = ExportSummary {
+        export_type: DEFAULT_EXPORT_TYPE,
+        export_path: export_path.to_path_buf(),
+        chats: exported.chats.len(),
+        messages: exported.messages,
+        attachments: exported.attachments,
+    };
+    eprintln!("{summary}");
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::{current_dir, temp_dir},
+        fs::{read_dir, read_to_string, remove_dir_all},
+    };
+
+    use crate::app::quick_export::{DEFAULT_EXPORT_TYPE, export_everything};
+
+    #[test]
+    fn can_export_everything() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let export_path = temp_dir().join("imessage-undeleter-export-everything");
+        let _ = remove_dir_all(&export_path);
+
+        let summary = export_everything(&db_path, &export_path).unwrap();
+
+        assert_eq!(summary.export_type, DEFAULT_EXPORT_TYPE);
+        assert_eq!(summary.export_path, export_path);
+        // Only rows that were exported are counted: the database has three attachments, but only one belongs to
+        // its message, and that message is not joined to a chat
+        assert_eq!(summary.messages, 1);
+        assert_eq!(summary.attachments, 1);
+        assert_eq!(summary.chats, 0);
+
+        // The export contains every message in the source database
+        let exported: u64 = read_dir(&export_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+            .map(|path| {
+                read_to_string(path)
+                    .unwrap()
+                    .matches("<div class=\"message ")
+                    .count() as u64
+            })
+            .sum();
+        assert_eq!(exported, summary.messages);
+
+        remove_dir_all(&export_path).unwrap();
+    }
+}
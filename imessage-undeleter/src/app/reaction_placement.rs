@@ -0,0 +1,84 @@
+/*!
+ Contains data structures used to describe where reactions are written relative to the message they target.
+*/
+
+use std::fmt::Display;
+
+/// Where human-readable exports write the reactions to a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReactionPlacement {
+    /// Write reactions above the message they target
+    Before,
+    /// Write reactions below the message they target
+    #[default]
+    After,
+}
+
+impl ReactionPlacement {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(placement: &str) -> Option<Self> {
+        match placement.to_lowercase().as_str() {
+            "before" => Some(Self::Before),
+            "after" => Some(Self::After),
+            _ => None,
+        }
+    }
+
+    /// Combine a rendered message with its rendered reactions, in display order
+    ///
+    /// The message and its reactions stay together, so the group can be reordered as a unit.
+    pub fn arrange<T>(&self, message: T, reactions: Vec<T>) -> Vec<T> {
+        let mut group = Vec::with_capacity(reactions.len() + 1);
+        match self {
+            ReactionPlacement::Before => {
+                group.extend(reactions);
+                group.push(message);
+            }
+            ReactionPlacement::After => {
+                group.push(message);
+                group.extend(reactions);
+            }
+        }
+        group
+    }
+}
+
+impl Display for ReactionPlacement {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReactionPlacement::Before => write!(fmt, "before"),
+            ReactionPlacement::After => write!(fmt, "after"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::reaction_placement::ReactionPlacement;
+
+    #[test]
+    fn can_parse_reaction_placement() {
+        assert_eq!(
+            ReactionPlacement::from_cli("before"),
+            Some(ReactionPlacement::Before)
+        );
+        assert_eq!(
+            ReactionPlacement::from_cli("AFTER"),
+            Some(ReactionPlacement::After)
+        );
+        assert_eq!(ReactionPlacement::from_cli("above"), None);
+        assert_eq!(ReactionPlacement::default(), ReactionPlacement::After);
+    }
+
+    #[test]
+    fn can_arrange_reactions() {
+        assert_eq!(
+            ReactionPlacement::Before.arrange("message", vec!["a", "b"]),
+            vec!["a", "b", "message"]
+        );
+        assert_eq!(
+            ReactionPlacement::After.arrange("message", vec!["a", "b"]),
+            vec!["message", "a", "b"]
+        );
+    }
+}
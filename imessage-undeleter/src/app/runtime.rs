@@ -3,42 +3,71 @@
 */
 
 use std::{
+    cell::{Cell, RefCell},
     cmp::min,
     collections::{BTreeSet, HashMap, HashSet},
     fs::{self, File, OpenOptions, create_dir_all, remove_dir_all, remove_file, rename},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     thread,
     time::Duration,
 };
 
+use chrono::{DateTime, FixedOffset, Utc};
 use crabapple::Backup;
 use rusqlite::Connection;
 
 use crate::{
     TXT,
     app::{
-        compatibility::backup::{decrypt_backup, get_decrypted_message_database},
+        address_book::{address_book_sources, load_address_book, resolve_contact_names},
+        compatibility::{
+            attachment_manager::{AttachmentLayout, AttachmentManagerMode},
+            backup::{decrypt_backup, get_decrypted_message_database, open_backup_manifest},
+        },
         error::RuntimeError,
+        export_type::ExportType,
         options::{OPTION_CLEARTEXT_PASSWORD, Options},
-        sanitizers::sanitize_filename,
+        sanitizers::{file_url, join_url, redact_contact_details, sanitize_filename},
+    },
+    exporters::{
+        contacts::Contacts,
+        csv::CSV,
+        exporter::{
+            ATTACHMENT_NO_FILENAME, CONTACT_REDACTED, Exporter, export_all, format_pin_event,
+        },
+        html::HTML,
+        json::JSON,
+        manifest::Manifest,
+        markdown::MD,
+        message_json::message_json,
+        on_this_day::OnThisDay,
+        sqlite::SQLite,
+        stats::Stats,
+        telegram::Telegram,
+        txt::TXTFormatting,
+        volume::VolumeWriter,
     },
-    exporters::exporter::ATTACHMENT_NO_FILENAME,
 };
 
 use imessage_database::{
+    error::message::MessageError,
     tables::{
         attachment::Attachment,
         chat::Chat,
         chat_handle::ChatToHandle,
         handle::Handle,
-        messages::Message,
+        messages::{Message, tapbacks::applied_tapbacks},
         table::{
-            ATTACHMENTS_DIR, Cacheable, Deduplicate, ME, ORPHANED, UNKNOWN,
+            ATTACHMENTS_DIR, Cacheable, Deduplicate, ME, ORPHANED, SchemaReport, UNKNOWN,
             get_connection,
         },
     },
-    util::{dates::get_offset, platform::Platform},
+    util::{
+        backup_manifest::BackupManifest,
+        dates::{TIMESTAMP_FACTOR, get_local_time, get_offset, get_time_in_zone},
+        platform::Platform,
+    },
 };
 
 const MAX_LENGTH: usize = 235;
@@ -53,10 +82,16 @@ pub struct Config {
     pub chatroom_participants: HashMap<i32, BTreeSet<i32>>,
     /// Map of participant ID to contact info
     pub participants: HashMap<i32, String>,
+    /// Map of participant ID to the contact's name from the AddressBook, if one was found
+    pub contact_names: HashMap<i32, String>,
     /// Map of participant ID to an internal unique participant ID
     pub real_participants: HashMap<i32, i32>,
+    /// Map of participant ID to the name shown in exports, populated if identities are redacted
+    pub pseudonyms: HashMap<i32, String>,
     /// Messages that are tapbacks (reactions) to other messages
     pub tapbacks: HashMap<String, HashMap<usize, Vec<Message>>>,
+    /// Chat IDs that only contain system messages, populated if empty chats are skipped
+    pub empty_chats: HashSet<i32>,
     /// App configuration options
     pub options: Options,
     /// Global date offset used by the iMessage database:
@@ -65,6 +100,23 @@ pub struct Config {
     pub db: Option<Connection>,
     /// An optional encrypted iOS backup
     pub backup: Option<Backup>,
+    /// The file index of an unencrypted iOS backup, used to find attachments
+    pub backup_manifest: Option<BackupManifest>,
+    /// The largest message `ROWID` exported so far, which can be passed to `--since-rowid` to continue the export
+    pub last_exported_rowid: Cell<Option<i32>>,
+    /// What one-shot exports have written so far
+    pub exported: RefCell<ExportCounts>,
+}
+
+/// Tallies of the rows written by one-shot exports
+#[derive(Debug, Default)]
+pub struct ExportCounts {
+    /// The number of messages written
+    pub messages: u64,
+    /// The number of attachments sent with the written messages
+    pub attachments: u64,
+    /// The chat IDs the written messages belong to
+    pub chats: HashSet<i32>,
 }
 
 impl Config {
@@ -84,6 +136,67 @@ impl Config {
         }
     }
 
+    /// Get the tapbacks that are still applied to a message, ordered by message part and then by date
+    ///
+    /// A tapback is dropped if the same person later replaced or removed their tapback on that part of the message.
+    pub fn reactions_to(&self, message: &Message) -> Vec<&Message> {
+        self.tapbacks
+            .get(&message.guid)
+            .map(|parts| {
+                applied_tapbacks(parts)
+                    .into_iter()
+                    .map(|(_, tapback)| tapback)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Determine if messages from a chat should be included in an export
+    pub fn include_chat(&self, chat_id: Option<i32>) -> bool {
+        match chat_id {
+            Some(chat_id) => !self.empty_chats.contains(&chat_id),
+            None => true,
+        }
+    }
+
+    /// Determine if a message's text matches the `--match-regex` filter
+    ///
+    /// This must be called after [`Message::generate_text()`], since the filter runs over the generated body.
+    /// Every message matches if no filter is set; otherwise, messages without text never match.
+    pub fn matches_text(&self, message: &Message) -> bool {
+        match &self.options.match_regex {
+            Some(filter) => message
+                .text
+                .as_deref()
+                .is_some_and(|text| filter.is_match(text)),
+            None => true,
+        }
+    }
+
+    /// Convert a date from the iMessage table into the time zone exports are written in
+    ///
+    /// Dates are rendered in [`Options::timezone`] if it is set, otherwise in the time zone of the current machine.
+    pub fn time(&self, date_stamp: &i64) -> Result<DateTime<FixedOffset>, MessageError> {
+        match self.options.timezone {
+            Some(tz) => {
+                get_time_in_zone(date_stamp, &self.offset, tz).map(|date| date.fixed_offset())
+            }
+            None => get_local_time(date_stamp, &self.offset).map(|date| date.fixed_offset()),
+        }
+    }
+
+    /// Create an export file at `path`, or continue the one an earlier export wrote if [`Options::since_rowid`] is set
+    ///
+    /// When continuing an export, messages written with [`VolumeWriter::write_row()`] are written in ascending `ROWID`
+    /// order after the ones already in the file.
+    pub fn open_volumes(&self, path: &Path, header: &str) -> Result<VolumeWriter, RuntimeError> {
+        let max_file_size = self.options.max_file_size;
+        match self.options.since_rowid {
+            Some(_) => Ok(VolumeWriter::append(path, max_file_size, header)?.sorted_by_rowid()),
+            None => VolumeWriter::create(path, max_file_size, header),
+        }
+    }
+
     /// Get the attachment path for the current session
     pub fn attachment_path(&self) -> PathBuf {
         let mut path = self.options.export_path.clone();
@@ -107,6 +220,39 @@ impl Config {
         String::from(ORPHANED)
     }
 
+    /// Get the subdirectory a chat's attachments are copied to, relative to the attachment path
+    ///
+    /// Returns [`None`] if attachments are not grouped by chat
+    pub fn chat_attachment_folder(&self, chat_id: Option<i32>) -> Option<String> {
+        match self.options.attachment_layout {
+            AttachmentLayout::Flat => None,
+            AttachmentLayout::PerChat => Some(
+                chat_id
+                    .and_then(|chat_id| self.chatrooms.get(&chat_id))
+                    .map_or_else(|| String::from(ORPHANED), |chat| self.filename(chat)),
+            ),
+        }
+    }
+
+    /// Resolve the path to an attachment's file in the source data
+    ///
+    /// Attachments in an unencrypted iOS backup are looked up in the backup's `Manifest.db` first, falling back to
+    /// the path derived from the attachment's name.
+    pub fn attachment_source_path(&self, attachment: &Attachment) -> Option<String> {
+        self.backup_manifest
+            .as_ref()
+            .zip(attachment.filename.as_deref())
+            .and_then(|(manifest, filename)| manifest.attachment_path(filename))
+            .map(|path| path.display().to_string())
+            .or_else(|| {
+                attachment.resolved_attachment_path(
+                    &self.options.platform,
+                    &self.options.db_path,
+                    self.options.attachment_root.as_deref(),
+                )
+            })
+    }
+
     /// Generate a file path for an attachment
     ///
     /// If the attachment was copied, use that path
@@ -120,18 +266,39 @@ impl Config {
                 }
                 path.display().to_string()
             }
-            None => attachment
-                .resolved_attachment_path(
-                    &self.options.platform,
-                    &self.options.db_path,
-                    self.options.attachment_root.as_deref(),
-                )
-                .unwrap_or_else(|| {
-                    attachment
-                        .filename()
-                        .unwrap_or(ATTACHMENT_NO_FILENAME)
-                        .to_string()
-                }),
+            None => self.attachment_source_path(attachment).unwrap_or_else(|| {
+                attachment
+                    .filename()
+                    .unwrap_or(ATTACHMENT_NO_FILENAME)
+                    .to_string()
+            }),
+        }
+    }
+
+    /// Generate a link to an attachment for use in exports
+    ///
+    /// If a base URL is set, attachments copied into the export directory are linked relative to it.
+    /// If attachments are linked instead of copied, this is an absolute `file://` URL to the
+    /// original file, otherwise it is the same as [`Self::message_attachment_path()`]
+    pub fn message_attachment_link(&self, attachment: &Attachment) -> String {
+        let exported = attachment
+            .copied_path
+            .as_ref()
+            .and_then(|path| path.strip_prefix(&self.options.export_path).ok());
+        if let (Some(base), Some(exported)) = (&self.options.attachment_base_url, exported) {
+            return join_url(base, exported);
+        }
+
+        if !matches!(
+            self.options.attachment_manager.mode,
+            AttachmentManagerMode::Link
+        ) {
+            return self.message_attachment_path(attachment);
+        }
+
+        match self.attachment_source_path(attachment) {
+            Some(path) => file_url(Path::new(&path)),
+            None => self.message_attachment_path(attachment),
         }
     }
 
@@ -185,7 +352,12 @@ impl Config {
         let mut added = 0;
         let mut out_s = String::with_capacity(MAX_LENGTH);
         for participant_id in participants {
-            let participant = self.who(Some(*participant_id), false, &None);
+            // File names keep real names, even if identities are redacted in exported content
+            let participant = self
+                .contact_names
+                .get(participant_id)
+                .or_else(|| self.participants.get(participant_id))
+                .map_or(UNKNOWN, String::as_str);
             if participant.len() + out_s.len() < MAX_LENGTH {
                 if !out_s.is_empty() {
                     out_s.push_str(", ");
@@ -239,31 +411,86 @@ impl Config {
             )));
         }
 
+        // Warn up front about data this database cannot provide
+        let schema = SchemaReport::check(&conn);
+        if !schema.supports_export() {
+            return Err(RuntimeError::InvalidOptions(String::from(
+                "The provided database is missing the message tables required for export!",
+            )));
+        }
+        eprintln!("Detected database schema: {}", schema.version);
+        for feature in schema.unsupported_features() {
+            eprintln!("Warning: this database does not support {feature}");
+        }
+
         eprintln!("Building cache...");
         eprintln!("  [1/4] Caching chats...");
         let chatrooms = Chat::cache(&conn)?;
         eprintln!("  [2/4] Caching chatrooms...");
         let chatroom_participants = ChatToHandle::cache(&conn)?;
         eprintln!("  [3/4] Caching participants...");
-        let participants = Handle::cache(&conn)?;
+        let mut participants = Handle::cache(&conn)?;
+        // Handle ID 0 is the database owner, so use the same label as outgoing messages
+        if let Some(name) = &options.custom_name {
+            participants.insert(0, name.clone());
+        }
+        // Only a Mac has an AddressBook for the user who owns the database
+        let contact_names = if matches!(options.platform, Platform::macOS) {
+            resolve_contact_names(
+                &participants,
+                &load_address_book(&address_book_sources(), &options.country_code),
+                &options.country_code,
+            )
+        } else {
+            HashMap::new()
+        };
+        let backup_manifest = open_backup_manifest(&options);
         eprintln!("  [4/4] Caching tapbacks...");
         let tapbacks = Message::cache(&conn)?;
+        let empty_chats = if options.skip_empty_chats {
+            eprintln!("  [+] Finding empty chats...");
+            Self::find_empty_chats(&conn)?
+        } else {
+            HashSet::new()
+        };
         eprintln!("Cache built!");
 
+        let real_participants = Handle::dedupe(&participants);
+        let pseudonyms = if options.redact_identities {
+            Self::pseudonyms(&real_participants)
+        } else {
+            HashMap::new()
+        };
+
         Ok(Config {
             chatrooms,
             real_chatrooms: ChatToHandle::dedupe(&chatroom_participants),
             chatroom_participants,
-            real_participants: Handle::dedupe(&participants),
+            real_participants,
+            pseudonyms,
             participants,
+            contact_names,
             tapbacks,
+            empty_chats,
             options,
             offset: get_offset(),
             db: Some(conn),
             backup,
+            backup_manifest,
+            last_exported_rowid: Cell::new(None),
+            exported: RefCell::new(ExportCounts::default()),
         })
     }
 
+    /// Find the chats that do not contain any messages written by a person
+    pub(crate) fn find_empty_chats(db: &Connection) -> Result<HashSet<i32>, RuntimeError> {
+        Ok(Chat::user_message_counts(db)?
+            .into_iter()
+            .filter(|(_, num_messages)| *num_messages == 0)
+            .map(|(chat_id, _)| chat_id)
+            .collect())
+    }
+
     /// Get the current database connection, if it is alive
     ///
     /// # Panics
@@ -279,7 +506,7 @@ impl Config {
     }
 
     /// Convert comma separated list of participant strings into table chat IDs using
-    ///   1) filter `self.participant` keys based on the values (by comparing to user values)
+    ///   1) filter `self.participant` keys based on the values and contact names (by comparing to user values)
     ///   2) get the chat IDs keys from `self.chatroom_participants` for values that contain the selected `handle_ids`
     ///   3) send those chat and handle IDs to the query context so they are included in the message table filters
     pub(crate) fn resolve_filtered_handles(&mut self) {
@@ -289,9 +516,10 @@ impl Config {
             let mut included_chatrooms: BTreeSet<i32> = BTreeSet::new();
             let mut included_handles: BTreeSet<i32> = BTreeSet::new();
 
-            // First: Scan the list of participants for included handle IDs
+            // First: Scan the list of participants and their contact names for included handle IDs
             self.participants
                 .iter()
+                .chain(&self.contact_names)
                 .for_each(|(handle_id, handle_name)| {
                     for included_name in &parsed_handle_filter {
                         if handle_name.contains(included_name) {
@@ -321,6 +549,40 @@ impl Config {
         }
     }
 
+    /// Restrict the export to chats whose most recent message is within the window set by
+    /// [`Options::active_within_days`]
+    ///
+    /// Chats are selected by their latest message, so every message in a selected chat is exported. If a
+    /// conversation filter already selected some chats, only the ones that are also active are kept.
+    pub(crate) fn resolve_active_chats(&mut self) -> Result<(), RuntimeError> {
+        let Some(days) = self.options.active_within_days else {
+            return Ok(());
+        };
+
+        let now = (Utc::now().timestamp() - self.offset) * TIMESTAMP_FACTOR;
+        let since = now.saturating_sub(i64::from(days) * 86400 * TIMESTAMP_FACTOR);
+        let active = Chat::active_since(self.db(), since)?;
+
+        let selected: BTreeSet<i32> = match &self.options.query_context.selected_chat_ids {
+            Some(selected) => selected.intersection(&active).copied().collect(),
+            None => active,
+        };
+        // An empty selection would disable the chat filter, so stop instead of exporting everything
+        if selected.is_empty() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "No chats have messages from the last {days} days!"
+            )));
+        }
+
+        eprintln!(
+            "Exporting {} chat{} active in the last {days} days...",
+            selected.len(),
+            if selected.len() == 1 { "" } else { "s" }
+        );
+        self.options.query_context.set_selected_chat_ids(selected);
+        Ok(())
+    }
+
     /// If we set some filtered chatrooms, emit how many will be included in the export
     fn log_filtered_handles_and_chats(&self) {
         if let (Some(selected_handle_ids), Some(selected_chat_ids)) = (
@@ -378,8 +640,58 @@ impl Config {
             }
         }
 
+        // Debugging a single message does not write any files
+        if let Some(guid) = &self.options.message_json {
+            println!("{}", message_json(self, guid)?);
+            return Ok(());
+        }
+
         // Ensure the path we want to export to exists
         create_dir_all(&self.options.export_path)?;
+
+        // Merged databases are written to a single JSON timeline
+        if !self.options.merge_db_paths.is_empty() {
+            return JSON::new(self)?.export_merged();
+        }
+
+        // One-shot exports do not need to monitor the database
+        if !self.options.export_types.is_empty() {
+            let mut exporters = self
+                .options
+                .export_types
+                .iter()
+                .map(|export_type| {
+                    Ok(match export_type {
+                        ExportType::Sqlite => Box::new(SQLite::new(self)?) as Box<dyn Exporter>,
+                        ExportType::Stats => Box::new(Stats::new(self)?),
+                        ExportType::Manifest => Box::new(Manifest::new(self)?),
+                        ExportType::Contacts => Box::new(Contacts::new(self)?),
+                        ExportType::Telegram => Box::new(Telegram::new(self)?),
+                        ExportType::Json => Box::new(JSON::new(self)?),
+                        ExportType::Html => Box::new(HTML::new(self)?),
+                        ExportType::Markdown => Box::new(MD::new(self)?),
+                        ExportType::Csv => Box::new(CSV::new(self)?),
+                    })
+                })
+                .collect::<Result<Vec<_>, RuntimeError>>()?;
+            let mut exporters: Vec<&mut dyn Exporter> = exporters
+                .iter_mut()
+                .map(|exporter| exporter.as_mut() as &mut dyn Exporter)
+                .collect();
+            export_all(self, &mut exporters)?;
+            if let Some(rowid) = self.last_exported_rowid.get() {
+                eprintln!(
+                    "Exported messages up to ROWID {rowid}; pass --since-rowid {rowid} to continue from here"
+                );
+            }
+            return Ok(());
+        }
+
+        // Reports for a calendar day also run once
+        if self.options.query_context.on_this_day.is_some() {
+            return OnThisDay::new(self)?.iter_messages();
+        }
+
         if self.tmp_attachment_path().is_dir() {
             remove_dir_all(&self.tmp_attachment_path())?;
         } else if self.tmp_attachment_path().exists() {
@@ -399,14 +711,25 @@ impl Config {
             .append(true)
             .create(true)
             .open(&logfile_path)?;
-            
+
         println!("🔍 Starting iMessage deletion monitor...");
         println!("📁 Deleted messages will be saved to: {:?}", logfile_path);
-        println!("📂 Attachments will be saved to: {:?}", self.attachment_path());
+        println!(
+            "📂 Attachments will be saved to: {:?}",
+            self.attachment_path()
+        );
         println!("👀 Monitoring messages for phone number filter...");
         println!("⏱️  Scanning every few seconds for changes...\n");
-        
-        let mut txt_instance = TXT::new(self)?;
+
+        // Deleted messages are written into HTML, so wrapped lines need visible breaks
+        let formatting = TXTFormatting::default()
+            .with_timestamp_granularity(self.options.timestamp_granularity)
+            .with_line_break("<br>");
+        let formatting = match self.options.wrap_width {
+            Some(width) => formatting.with_wrap_width(width),
+            None => formatting,
+        };
+        let mut txt_instance = TXT::with_formatting(self, formatting)?;
         // let mut scan_count = 0;
         loop {
             // scan_count += 1;
@@ -431,7 +754,7 @@ impl Config {
                             &last_message,
                             &last_message_attachments,
                             &mut outfile,
-                            &txt_instance,
+                            &mut txt_instance,
                         )?;
                     }
                     attachment_destinations = last_message_attachments;
@@ -498,9 +821,11 @@ impl Config {
         last_message: &Message,
         last_message_attachments: &Vec<PathBuf>,
         outfile: &mut File,
-        txt_instance: &TXT,
+        txt_instance: &mut TXT,
     ) -> Result<(), RuntimeError> {
-        let message_preview = last_message.text.clone()
+        let message_preview = last_message
+            .text
+            .clone()
             .unwrap_or_default()
             .chars()
             .take(50)
@@ -510,33 +835,85 @@ impl Config {
         } else {
             message_preview
         };
-        
+
         println!(
             "🚨 DELETED MESSAGE DETECTED! \"{}\" with {} attachment(s)",
-            if message_preview.is_empty() { "[No text content]" } else { &message_preview },
+            if message_preview.is_empty() {
+                "[No text content]"
+            } else {
+                &message_preview
+            },
             last_message.num_attachments,
         );
-        
+
         let sender = self.who(
             last_message.handle_id,
             last_message.is_from_me(),
             &last_message.destination_caller_id,
         );
         println!("   👤 From: {}", sender);
+        if let Some(day) = txt_instance.format_day_heading(last_message) {
+            writeln!(outfile, "<h1>{day}</h1>")?;
+        }
         writeln!(
             outfile,
-            "<h2>==={}:{}</h2>",
-            sender,
-            txt_instance.get_time(last_message)
+            "<h2>{}</h2>",
+            txt_instance.format_header(sender, last_message)
         )?;
-        if let Some(text) = &last_message.text {
-            if text != " " {
-                writeln!(outfile, "<p>Text: {}</p><br>", text)?;
-            }
+        let body = if let Some(reaction) = txt_instance.format_tapback_label(last_message) {
+            Some(format!("<p>Reaction: {}</p><br>", reaction))
+        } else if let Some(action) = last_message.pin_action() {
+            let pinned = last_message.pinned_message(self.db());
+            Some(format!(
+                "<p>{}</p><br>",
+                self.redact_text(format_pin_event(sender, action, pinned.as_ref()))
+            ))
+        } else {
+            last_message
+                .text
+                .as_ref()
+                .filter(|text| *text != " ")
+                .map(|text| {
+                    format!(
+                        "<p>Text: {}</p><br>",
+                        txt_instance.format_text(&self.redact_text(text.clone()))
+                    )
+                })
+        };
+        let reactions: Vec<String> = self
+            .reactions_to(last_message)
+            .into_iter()
+            .filter_map(|tapback| {
+                let reaction = txt_instance.format_tapback(tapback)?;
+                let who = self.who(
+                    tapback.handle_id,
+                    tapback.is_from_me(),
+                    &tapback.destination_caller_id,
+                );
+                Some(format!("{reaction} by {who}"))
+            })
+            .collect();
+        let reactions = (!reactions.is_empty())
+            .then(|| format!("<p>Reactions: {}</p><br>", reactions.join(", ")));
+        for line in self
+            .options
+            .reaction_placement
+            .arrange(body, vec![reactions])
+            .into_iter()
+            .flatten()
+        {
+            writeln!(outfile, "{line}")?;
         }
         writeln!(outfile, "<p>Attachments:</p><br>")?;
+        let mut attachment_dir = self.attachment_path();
+        if let Some(folder) =
+            self.chat_attachment_folder(last_message.chat_id.or(last_message.deleted_from))
+        {
+            attachment_dir.push(folder);
+            create_dir_all(&attachment_dir)?;
+        }
         for attachment in last_message_attachments {
-            let mut attachment_path = self.attachment_path().canonicalize().unwrap();
+            let mut attachment_path = attachment_dir.canonicalize().unwrap();
             attachment_path.push(attachment.file_name().unwrap());
             println!("Renaming {:?} to {:?}", &attachment, &attachment_path);
             rename(&attachment, &attachment_path)?;
@@ -555,7 +932,10 @@ impl Config {
     pub fn handle_untracked_message(&self, msg_id: i32, attachments: &Vec<PathBuf>) {
         println!("New message was sent with ID: {}", msg_id);
         if !attachments.is_empty() {
-            println!("   🗑️  Cleaning up {} temporary attachment(s)", attachments.len());
+            println!(
+                "   🗑️  Cleaning up {} temporary attachment(s)",
+                attachments.len()
+            );
         }
         attachments.iter().for_each(|attachment| {
             if attachment.exists() {
@@ -567,6 +947,44 @@ impl Config {
         })
     }
 
+    /// Build a pseudonym for each participant, so each person is shown as `Contact N`
+    ///
+    /// Handles that belong to the same person share a pseudonym.
+    fn pseudonyms(real_participants: &HashMap<i32, i32>) -> HashMap<i32, String> {
+        real_participants
+            .iter()
+            .map(|(handle_id, person_id)| (*handle_id, format!("Contact {}", person_id + 1)))
+            .collect()
+    }
+
+    /// Remove phone numbers and emails from message text if identities are redacted
+    pub fn redact_text(&self, text: String) -> String {
+        if !self.options.redact_identities {
+            return text;
+        }
+        redact_contact_details(&text, CONTACT_REDACTED).into_owned()
+    }
+
+    /// Get the name of a chat to show in exported content
+    ///
+    /// If identities are redacted, this is a generic label instead of the chat's name or participants.
+    pub fn chat_label(&self, chat: &Chat) -> String {
+        if self.options.redact_identities {
+            return format!(
+                "Chat {}",
+                self.real_chatrooms
+                    .get(&chat.rowid)
+                    .map_or(chat.rowid, |id| id + 1)
+            );
+        }
+        self.filename(chat)
+    }
+
+    /// The label used for the database owner in exports, defaulting to [`ME`]
+    pub fn owner_label(&self) -> &str {
+        self.options.custom_name.as_deref().unwrap_or(ME)
+    }
+
     /// Determine who sent a message
     pub fn who<'a, 'b: 'a>(
         &'a self,
@@ -575,12 +993,21 @@ impl Config {
         destination_caller_id: &'b Option<String>,
     ) -> &'a str {
         if is_from_me {
-            if self.options.use_caller_id {
-                return destination_caller_id.as_deref().unwrap_or(ME);
+            if self.options.use_caller_id && !self.options.redact_identities {
+                return destination_caller_id
+                    .as_deref()
+                    .unwrap_or(self.owner_label());
             }
-            return self.options.custom_name.as_deref().unwrap_or(ME);
+            return self.owner_label();
         } else if let Some(handle_id) = handle_id {
-            return match self.participants.get(&handle_id) {
+            if let Some(pseudonym) = self.pseudonyms.get(&handle_id) {
+                return pseudonym;
+            }
+            return match self
+                .contact_names
+                .get(&handle_id)
+                .or_else(|| self.participants.get(&handle_id))
+            {
                 Some(contact) => contact,
                 None => UNKNOWN,
             };
@@ -598,15 +1025,57 @@ impl Config {
             real_chatrooms: HashMap::new(),
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
+            contact_names: HashMap::new(),
             real_participants: HashMap::new(),
+            pseudonyms: HashMap::new(),
             tapbacks: HashMap::new(),
+            empty_chats: HashSet::new(),
             options,
             offset: get_offset(),
             db: Some(connection),
             backup: None,
+            backup_manifest: None,
+            last_exported_rowid: Cell::new(None),
+            exported: RefCell::new(ExportCounts::default()),
         }
     }
 
+    /// Create an empty, writable, in-memory database that uses the same schema as the test database
+    pub fn fake_db(options: &Options) -> Connection {
+        let source = get_connection(&options.db_path).unwrap();
+        let db = Connection::open_in_memory().unwrap();
+        // Tests only insert the rows they need, so related rows may not exist
+        db.execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+
+        // Triggers are skipped because they call functions that only exist in the Messages app
+        let mut statement = source
+            .prepare("SELECT sql FROM sqlite_master WHERE type IN ('table', 'index') AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'")
+            .unwrap();
+        let schema = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap();
+        for sql in schema {
+            db.execute_batch(&sql.unwrap()).unwrap();
+        }
+
+        db
+    }
+
+    /// Create an app that exports to an empty directory named `name` in the temporary directory, reading from a
+    /// [`fake_db()`](Self::fake_db) that holds the rows inserted by `rows`
+    pub fn fake_export(mut options: Options, name: &str, rows: &str) -> Config {
+        options.export_path = std::env::temp_dir().join(name);
+        let _ = remove_dir_all(&options.export_path);
+        create_dir_all(&options.export_path).unwrap();
+
+        let db = Config::fake_db(&options);
+        db.execute_batch(rows).unwrap();
+
+        let mut config = Config::fake_app(options);
+        config.db = Some(db);
+        config
+    }
+
     pub fn fake_message() -> Message {
         Message {
             rowid: i32::default(),
@@ -629,12 +1098,16 @@ impl Config {
             group_action_type: 0,
             associated_message_guid: None,
             associated_message_type: Some(i32::default()),
+            associated_message_range_location: 0,
+            associated_message_range_length: 0,
             balloon_bundle_id: None,
             expressive_send_style_id: None,
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
             associated_message_emoji: None,
+            is_audio_message: false,
+            is_played: None,
             chat_id: None,
             num_attachments: 0,
             deleted_from: None,
@@ -680,3 +1153,185 @@ impl Drop for Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use chrono::Utc;
+    use chrono_tz::Tz;
+    use imessage_database::util::{
+        backup_manifest::{BackupManifest, MANIFEST_DB},
+        dates::{TIMESTAMP_FACTOR, format, get_offset},
+        platform::Platform,
+    };
+    use rusqlite::Connection;
+
+    use crate::app::{options::Options, runtime::Config};
+
+    /// A [`Config`] with a chat last active 400 days ago and a chat last active yesterday
+    fn config_with_stale_and_recent_chats() -> Config {
+        let options = Options::fake_options();
+        let db = Config::fake_db(&options);
+        let now = (Utc::now().timestamp() - get_offset()) * TIMESTAMP_FACTOR;
+        let days_ago = |days: i64| now - days * 86400 * TIMESTAMP_FACTOR;
+        db.execute_batch(&format!(
+            "
+            INSERT INTO message (ROWID, guid, text, date, date_read, date_delivered) VALUES
+                (1, 'a', 'Stale', {}, 0, 0),
+                (2, 'b', 'Old message in a recent chat', {}, 0, 0),
+                (3, 'c', 'Recent', {}, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2), (2, 3);
+            ",
+            days_ago(400),
+            days_ago(500),
+            days_ago(1),
+        ))
+        .unwrap();
+
+        let mut config = Config::fake_app(options);
+        config.db = Some(db);
+        config
+    }
+
+    #[test]
+    fn can_select_recently_active_chats() {
+        let mut config = config_with_stale_and_recent_chats();
+        config.options.active_within_days = Some(30);
+
+        config.resolve_active_chats().unwrap();
+
+        // The recent chat is selected in full, including its old message
+        assert_eq!(
+            config.options.query_context.selected_chat_ids,
+            Some(BTreeSet::from([2]))
+        );
+    }
+
+    #[test]
+    fn can_keep_all_chats_without_window() {
+        let mut config = config_with_stale_and_recent_chats();
+
+        config.resolve_active_chats().unwrap();
+
+        assert_eq!(config.options.query_context.selected_chat_ids, None);
+    }
+
+    #[test]
+    fn cant_select_without_active_chats() {
+        let mut config = config_with_stale_and_recent_chats();
+        config.options.active_within_days = Some(30);
+        config
+            .options
+            .query_context
+            .set_selected_chat_ids(BTreeSet::from([1]));
+
+        assert!(config.resolve_active_chats().is_err());
+    }
+
+    #[test]
+    fn can_link_attachments_under_base_url() {
+        let mut options = Options::fake_options();
+        options.attachment_base_url = Some(String::from("/media/"));
+        let config = Config::fake_app(options);
+
+        let mut attachment = Config::fake_attachment();
+        attachment.copied_path = Some(
+            config
+                .options
+                .export_path
+                .join("attachments/Family - 1/1.jpeg"),
+        );
+
+        assert_eq!(
+            config.message_attachment_link(&attachment),
+            "/media/attachments/Family%20-%201/1.jpeg"
+        );
+    }
+
+    #[test]
+    fn can_link_uncopied_attachments_without_base_url() {
+        let mut options = Options::fake_options();
+        options.attachment_base_url = Some(String::from("/media"));
+        let config = Config::fake_app(options);
+
+        let mut attachment = Config::fake_attachment();
+        attachment.copied_path = Some(PathBuf::from("/elsewhere/1.jpeg"));
+
+        assert_eq!(
+            config.message_attachment_link(&attachment),
+            "/elsewhere/1.jpeg"
+        );
+    }
+    #[test]
+    fn can_resolve_attachment_from_backup_manifest() {
+        let mut options = Options::fake_options();
+        options.platform = Platform::iOS;
+        options.db_path = temp_dir().join("imessage-undeleter-backup-manifest-test");
+        let _ = remove_dir_all(&options.db_path);
+        create_dir_all(&options.db_path).unwrap();
+        Connection::open(options.db_path.join(MANIFEST_DB))
+            .unwrap()
+            .execute_batch(
+                "
+                CREATE TABLE Files (fileID TEXT PRIMARY KEY, domain TEXT, relativePath TEXT, flags INTEGER, file BLOB);
+                INSERT INTO Files (fileID, domain, relativePath, flags) VALUES
+                    ('b1e8a5bdc63d01e1c1c7d0f4e6a2b5c3d4e5f607', 'MediaDomain', 'Library/SMS/Attachments/a1/01/IMG_0001/IMG_0001.jpeg', 1);
+                ",
+            )
+            .unwrap();
+
+        let mut config = Config::fake_app(Options::fake_options());
+        config.backup_manifest = Some(BackupManifest::open(&options.db_path).unwrap());
+        config.options = options;
+
+        let mut attachment = Config::fake_attachment();
+        attachment.filename =
+            Some("~/Library/SMS/Attachments/a1/01/IMG_0001/IMG_0001.jpeg".to_string());
+        assert_eq!(
+            config.attachment_source_path(&attachment),
+            Some(
+                config
+                    .options
+                    .db_path
+                    .join("b1/b1e8a5bdc63d01e1c1c7d0f4e6a2b5c3d4e5f607")
+                    .display()
+                    .to_string()
+            )
+        );
+
+        // Files missing from the manifest fall back to the path derived from their name
+        attachment.filename = Some("~/Library/SMS/Attachments/missing.jpeg".to_string());
+        assert_eq!(
+            config.attachment_source_path(&attachment),
+            attachment.resolved_attachment_path(&Platform::iOS, &config.options.db_path, None)
+        );
+
+        remove_dir_all(&config.options.db_path).unwrap();
+    }
+
+    #[test]
+    fn can_render_dates_in_time_zone() {
+        // 2022-05-17 12:00:00 UTC
+        let stamp = 674481600000000000;
+        let in_zone = |tz| {
+            let mut options = Options::fake_options();
+            options.timezone = Some(tz);
+            Config::fake_app(options)
+        };
+        let new_york = in_zone(Tz::America__New_York);
+        let london = in_zone(Tz::Europe__London);
+
+        assert_eq!(format(&new_york.time(&stamp)), "May 17, 2022  8:00:00 AM");
+        assert_eq!(format(&london.time(&stamp)), "May 17, 2022  1:00:00 PM");
+        assert_eq!(
+            london.time(&stamp).unwrap().to_rfc3339(),
+            "2022-05-17T13:00:00+01:00"
+        );
+    }
+}
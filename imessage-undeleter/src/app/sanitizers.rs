@@ -6,6 +6,7 @@ use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 
 use std::borrow::Cow;
+use std::path::{Component, Path, absolute};
 
 /// Characters disallowed in a filename
 static FILENAME_DISALLOWED_CHARS: LazyLock<HashSet<char>> =
@@ -23,8 +24,12 @@ static HTML_DISALLOWED_CHARS: LazyLock<HashMap<char, &str>> = LazyLock::new(|| {
         (' ', "&nbsp;"),
     ])
 });
+/// Characters that start or end Markdown formatting
+const MARKDOWN_SPECIAL_CHARS: [char; 11] = ['\\', '`', '*', '_', '~', '[', ']', '<', '>', '#', '|'];
 /// The character to replace disallowed chars with
 const FILENAME_REPLACEMENT_CHAR: char = '_';
+/// URL schemes that exports may write as links
+const SAFE_LINK_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
 
 /// Remove unsafe chars in [this list](FILENAME_DISALLOWED_CHARS).
 pub fn sanitize_filename(filename: &str) -> String {
@@ -57,6 +62,136 @@ pub fn sanitize_html(input: &str) -> Cow<str> {
     Cow::Borrowed(input)
 }
 
+/// Build an absolute `file://` URL for a path, percent-encoding everything except unreserved characters and separators
+pub fn file_url(path: &Path) -> String {
+    let path = absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+    percent_encode(&path.to_string_lossy(), &mut url);
+    url
+}
+
+/// Determine if a URL sent in a message uses a scheme that is safe to write as a link, like `https:` or `mailto:`
+///
+/// Other schemes, like `javascript:` or `data:`, could run code when the link is opened from an export.
+pub fn is_safe_link(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        SAFE_LINK_SCHEMES
+            .iter()
+            .any(|safe| scheme.eq_ignore_ascii_case(safe))
+    })
+}
+
+/// Append a relative path to a base URL, percent-encoding each component of the path
+///
+/// The base is used as-is, so it may be an absolute URL like `https://example.com/media` or a path like `/media`.
+pub fn join_url(base: &str, path: &Path) -> String {
+    let mut url = base.trim_end_matches('/').to_string();
+    for component in path.components() {
+        if let Component::Normal(part) = component {
+            url.push('/');
+            percent_encode(&part.to_string_lossy(), &mut url);
+        }
+    }
+    url
+}
+
+/// Percent-encode everything in `text` except unreserved characters and separators
+fn percent_encode(text: &str, url: &mut String) {
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                url.push(char::from(byte));
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+}
+
+/// Quote and escape a string for use as a JSON string literal
+pub fn json_string(input: &str) -> String {
+    let mut out = String::with_capacity(input.len() + 2);
+    out.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Replace phone numbers and email addresses in text with `replacement`
+///
+/// A phone number is a run of digits and `+`, `-`, `(`, `)`, `.`, or space characters containing at least
+/// 10 digits, or at least 7 if it starts with `+`. Shorter runs are kept so that times, dates, and
+/// amounts are not removed. An email address is any word containing `@` followed by a domain with a `.`.
+pub fn redact_contact_details<'a>(input: &'a str, replacement: &str) -> Cow<'a, str> {
+    let mut out = String::with_capacity(input.len());
+    let mut changed = false;
+    let mut rest = input;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // Phone numbers may contain spaces, so check for one before splitting on whitespace
+        let run = rest
+            .find(|c: char| !(c.is_ascii_digit() || "+-(). ".contains(c)))
+            .unwrap_or(rest.len());
+        let number = rest[..run].trim_end_matches(|c: char| !c.is_ascii_digit());
+        let digits = number.chars().filter(char::is_ascii_digit).count();
+        if digits >= 10 || (number.starts_with('+') && digits >= 7) {
+            out.push_str(replacement);
+            rest = &rest[number.len()..];
+            changed = true;
+            continue;
+        }
+
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..word_end];
+        let trimmed = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
+        if trimmed
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+        {
+            out.push_str(replacement);
+            out.push_str(&word[trimmed.len()..]);
+            changed = true;
+        } else {
+            out.push_str(word);
+        }
+        rest = &rest[word_end..];
+    }
+    out.push_str(rest);
+
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Escape characters that Markdown treats as formatting, so text is shown exactly as it was sent
+pub fn sanitize_markdown(input: &str) -> Cow<'_, str> {
+    if !input.contains(MARKDOWN_SPECIAL_CHARS) {
+        return Cow::Borrowed(input);
+    }
+    let mut out = String::with_capacity(input.len() + 8);
+    for c in input.chars() {
+        if MARKDOWN_SPECIAL_CHARS.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
 #[cfg(test)]
 mod filename_sanitization_tests {
     use crate::app::sanitizers::sanitize_filename;
@@ -302,3 +437,177 @@ mod html_sanitization_tests {
         assert_eq!(&sanitize_html("class=\"test\""), "class=&quot;test&quot;");
     }
 }
+
+#[cfg(test)]
+mod file_url_tests {
+    use std::path::Path;
+
+    use crate::app::sanitizers::file_url;
+
+    #[test]
+    fn can_encode_spaces() {
+        assert_eq!(
+            file_url(Path::new(
+                "/Users/me/Library/Messages/Attachments/My Photos/IMG 0001.jpeg"
+            )),
+            "file:///Users/me/Library/Messages/Attachments/My%20Photos/IMG%200001.jpeg"
+        );
+    }
+
+    #[test]
+    fn can_encode_reserved_and_unicode() {
+        assert_eq!(
+            file_url(Path::new("/tmp/a#b?c%d/café.png")),
+            "file:///tmp/a%23b%3Fc%25d/caf%C3%A9.png"
+        );
+    }
+
+    #[test]
+    fn can_make_relative_path_absolute() {
+        let url = file_url(Path::new("relative/file.png"));
+        assert!(url.starts_with("file:///"));
+        assert!(url.ends_with("/relative/file.png"));
+    }
+}
+
+#[cfg(test)]
+mod safe_link_tests {
+    use crate::app::sanitizers::is_safe_link;
+
+    #[test]
+    fn can_allow_web_and_contact_links() {
+        assert!(is_safe_link("https://example.com/a?b=c"));
+        assert!(is_safe_link("HTTP://example.com"));
+        assert!(is_safe_link("mailto:alice@example.com"));
+        assert!(is_safe_link("tel:+15558675309"));
+    }
+
+    #[test]
+    fn cant_allow_other_schemes() {
+        assert!(!is_safe_link("javascript:alert(1)"));
+        assert!(!is_safe_link("JavaScript:alert(1)"));
+        assert!(!is_safe_link(" javascript:alert(1)"));
+        assert!(!is_safe_link("data:text/html,<script>alert(1)</script>"));
+        assert!(!is_safe_link("file:///etc/passwd"));
+        assert!(!is_safe_link("example.com"));
+    }
+}
+
+#[cfg(test)]
+mod join_url_tests {
+    use std::path::Path;
+
+    use crate::app::sanitizers::join_url;
+
+    #[test]
+    fn can_join_base_path() {
+        assert_eq!(
+            join_url("/media", Path::new("attachments/Family - 1/IMG 0001.jpeg")),
+            "/media/attachments/Family%20-%201/IMG%200001.jpeg"
+        );
+    }
+
+    #[test]
+    fn can_join_base_with_trailing_slash() {
+        assert_eq!(
+            join_url(
+                "https://example.com/media/",
+                Path::new("attachments/#1.png")
+            ),
+            "https://example.com/media/attachments/%231.png"
+        );
+    }
+}
+
+#[cfg(test)]
+mod json_string_tests {
+    use crate::app::sanitizers::json_string;
+
+    #[test]
+    fn can_quote_plain_text() {
+        assert_eq!(json_string("hello"), "\"hello\"");
+    }
+
+    #[test]
+    fn can_escape_quotes_and_backslashes() {
+        assert_eq!(json_string("a \"b\" \\ c"), "\"a \\\"b\\\" \\\\ c\"");
+    }
+
+    #[test]
+    fn can_escape_control_characters() {
+        assert_eq!(json_string("a\nb\u{0}"), "\"a\\nb\\u0000\"");
+    }
+
+    #[test]
+    fn handles_emoji() {
+        assert_eq!(json_string("hi 👋"), "\"hi 👋\"");
+    }
+}
+
+#[cfg(test)]
+mod redact_contact_details_tests {
+    use crate::app::sanitizers::redact_contact_details;
+
+    #[test]
+    fn can_redact_phone_numbers() {
+        assert_eq!(
+            redact_contact_details("Call me at (555) 867-5309 tonight", "[x]"),
+            "Call me at [x] tonight"
+        );
+        assert_eq!(
+            redact_contact_details("Text +1 555 867 5309.", "[x]"),
+            "Text [x]."
+        );
+        assert_eq!(redact_contact_details("+44 20 7946", "[x]"), "[x]");
+    }
+
+    #[test]
+    fn can_redact_emails() {
+        assert_eq!(
+            redact_contact_details("Email steve@apple.com, please", "[x]"),
+            "Email [x], please"
+        );
+    }
+
+    #[test]
+    fn can_keep_short_numbers() {
+        let text = "Meet at 12:30 on 2021-05-17, it costs $45.00";
+        assert_eq!(redact_contact_details(text, "[x]"), text);
+    }
+
+    #[test]
+    fn can_keep_plain_text() {
+        assert_eq!(
+            redact_contact_details("  hi @ there ", "[x]"),
+            "  hi @ there "
+        );
+    }
+}
+
+#[cfg(test)]
+mod markdown_sanitization_tests {
+    use crate::app::sanitizers::sanitize_markdown;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(sanitize_markdown("Dinner at 8?"), "Dinner at 8?");
+    }
+
+    #[test]
+    fn can_sanitize_formatting() {
+        assert_eq!(
+            sanitize_markdown("**not bold** and [not](a link)"),
+            "\\*\\*not bold\\*\\* and \\[not\\](a link)"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_html_and_headers() {
+        assert_eq!(sanitize_markdown("# <3"), "\\# \\<3");
+    }
+
+    #[test]
+    fn can_sanitize_backslashes() {
+        assert_eq!(sanitize_markdown("C:\\Users"), "C:\\\\Users");
+    }
+}
@@ -0,0 +1,63 @@
+/*!
+ Contains data structures used to select messages by the content of their text.
+*/
+
+use std::fmt::Display;
+
+use regex::Regex;
+
+/// A regular expression that message text must match to be exported
+///
+/// The expression is compiled once when options are parsed. It is matched against the message body after it is
+/// generated from the `attributedBody`, and before one-time codes or contact details are redacted.
+#[derive(Debug, Clone)]
+pub struct TextFilter {
+    regex: Regex,
+}
+
+impl TextFilter {
+    /// Compile a filter from a user-provided pattern
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+        })
+    }
+
+    /// `true` if the text contains a match for the filter, else `false`
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+impl PartialEq for TextFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+
+impl Eq for TextFilter {}
+
+impl Display for TextFilter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.regex.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::text_filter::TextFilter;
+
+    #[test]
+    fn can_match_pattern() {
+        let filter = TextFilter::new(r"1Z[0-9A-Z]{16}").unwrap();
+
+        assert!(filter.is_match("Your package 1Z999AA10123456784 has shipped"));
+        assert!(!filter.is_match("Your package has shipped"));
+        assert_eq!(filter.to_string(), "1Z[0-9A-Z]{16}");
+    }
+
+    #[test]
+    fn cant_compile_invalid_pattern() {
+        assert!(TextFilter::new("(unclosed").is_err());
+    }
+}
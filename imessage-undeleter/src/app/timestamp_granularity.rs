@@ -0,0 +1,119 @@
+/*!
+ Contains data structures used to describe how much of a timestamp human-readable exports write.
+*/
+
+use std::fmt::Display;
+
+use imessage_database::{error::message::MessageError, util::dates::format};
+
+use chrono::{DateTime, TimeZone};
+
+/// The date format used when only the date is written
+const DATE_FORMAT: &str = "%b %d, %Y";
+/// The time format used when only the time is written
+const TIME_FORMAT: &str = "%-I:%M:%S %p";
+
+/// How much of a message's timestamp human-readable exports write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampGranularity {
+    /// Write the date and the time, like `May 17, 2021  5:00:00 AM`
+    #[default]
+    Full,
+    /// Write only the time, like `5:00:00 AM`, under a heading with the date each time the day changes
+    Time,
+    /// Write only the date, like `May 17, 2021`
+    Date,
+}
+
+impl TimestampGranularity {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(granularity: &str) -> Option<Self> {
+        match granularity.to_lowercase().as_str() {
+            "full" => Some(Self::Full),
+            "time" => Some(Self::Time),
+            "date" => Some(Self::Date),
+            _ => None,
+        }
+    }
+
+    /// Format a message timestamp, or describe why it could not be read
+    pub fn format<T: TimeZone>(&self, date: &Result<DateTime<T>, MessageError>) -> String
+    where
+        T::Offset: Display,
+    {
+        match (self, date) {
+            (TimestampGranularity::Full, _) | (_, Err(_)) => format(date),
+            (TimestampGranularity::Time, Ok(date)) => date.format(TIME_FORMAT).to_string(),
+            (TimestampGranularity::Date, Ok(date)) => date.format(DATE_FORMAT).to_string(),
+        }
+    }
+
+    /// The heading written above a day's messages, or [`None`] if every timestamp already includes the date
+    pub fn day_heading<T: TimeZone>(
+        &self,
+        date: &Result<DateTime<T>, MessageError>,
+    ) -> Option<String>
+    where
+        T::Offset: Display,
+    {
+        match (self, date) {
+            (TimestampGranularity::Time, Ok(date)) => Some(date.format(DATE_FORMAT).to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TimestampGranularity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimestampGranularity::Full => write!(fmt, "full"),
+            TimestampGranularity::Time => write!(fmt, "time"),
+            TimestampGranularity::Date => write!(fmt, "date"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::app::timestamp_granularity::TimestampGranularity;
+
+    #[test]
+    fn can_parse_timestamp_granularity() {
+        assert_eq!(
+            TimestampGranularity::from_cli("TIME"),
+            Some(TimestampGranularity::Time)
+        );
+        assert_eq!(
+            TimestampGranularity::from_cli("date"),
+            Some(TimestampGranularity::Date)
+        );
+        assert_eq!(TimestampGranularity::from_cli("seconds"), None);
+        assert_eq!(TimestampGranularity::default(), TimestampGranularity::Full);
+    }
+
+    #[test]
+    fn can_format_each_granularity() {
+        let date = Ok(Local.with_ymd_and_hms(2021, 5, 17, 17, 4, 9).unwrap());
+
+        assert_eq!(
+            TimestampGranularity::Full.format(&date),
+            "May 17, 2021  5:04:09 PM"
+        );
+        assert_eq!(TimestampGranularity::Time.format(&date), "5:04:09 PM");
+        assert_eq!(TimestampGranularity::Date.format(&date), "May 17, 2021");
+    }
+
+    #[test]
+    fn can_only_write_day_headings_in_time_mode() {
+        let date = Ok(Local.with_ymd_and_hms(2021, 5, 17, 17, 4, 9).unwrap());
+
+        assert_eq!(
+            TimestampGranularity::Time.day_heading(&date).as_deref(),
+            Some("May 17, 2021")
+        );
+        assert_eq!(TimestampGranularity::Full.day_heading(&date), None);
+        assert_eq!(TimestampGranularity::Date.day_heading(&date), None);
+    }
+}
@@ -0,0 +1,99 @@
+/*!
+ Generates colored initial bubbles for participants without a contact photo, like the Messages app does in group chats.
+*/
+
+use crate::app::sanitizers::sanitize_html;
+
+/// Size of the rendered avatar, in pixels
+pub(crate) const AVATAR_SIZE: u32 = 28;
+
+/// Get up to two initials from a participant's display name
+///
+/// Names without any alphanumeric characters, like phone numbers, fall back to `#`.
+pub(crate) fn initials(name: &str) -> String {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphabetic()))
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect();
+
+    if initials.is_empty() {
+        String::from("#")
+    } else {
+        initials
+    }
+}
+
+/// Build a deterministic background color for a handle
+///
+/// The color is derived from an FNV-1a hash of the handle, so the same participant gets the same
+/// color in every export.
+pub(crate) fn avatar_color(handle: &str) -> String {
+    let hash = handle
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+
+    // Keep saturation and lightness fixed so white text is always readable
+    format!("hsl({}, 55%, 45%)", hash % 360)
+}
+
+/// Render an inline SVG avatar with the participant's initials
+pub(crate) fn avatar_svg(name: &str, handle: &str) -> String {
+    format!(
+        "<svg class=\"avatar\" width=\"{AVATAR_SIZE}\" height=\"{AVATAR_SIZE}\" viewBox=\"0 0 {AVATAR_SIZE} {AVATAR_SIZE}\" role=\"img\" aria-label=\"{}\"><circle cx=\"{half}\" cy=\"{half}\" r=\"{half}\" fill=\"{}\"/><text x=\"50%\" y=\"50%\" dy=\".35em\" text-anchor=\"middle\" fill=\"white\" font-size=\"12\" font-family=\"-apple-system, sans-serif\">{}</text></svg>",
+        sanitize_html(name),
+        avatar_color(handle),
+        sanitize_html(&initials(name)),
+        half = AVATAR_SIZE / 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exporters::avatar::{avatar_color, avatar_svg, initials};
+
+    #[test]
+    fn can_get_initials() {
+        assert_eq!(initials("Sarah Connor"), "SC");
+        assert_eq!(initials("cher"), "C");
+        assert_eq!(initials("Mary Jane Watson"), "MJ");
+        assert_eq!(initials("+15558675309"), "#");
+    }
+
+    #[test]
+    fn can_get_deterministic_colors() {
+        assert_eq!(avatar_color("+15558675309"), avatar_color("+15558675309"));
+        assert_eq!(
+            avatar_color("person@example.com"),
+            avatar_color("person@example.com")
+        );
+    }
+
+    #[test]
+    fn can_get_distinct_colors_for_participants() {
+        let handles = [
+            "+15558675309",
+            "+15558675310",
+            "person@example.com",
+            "other@example.com",
+        ];
+        let colors: Vec<String> = handles.iter().map(|h| avatar_color(h)).collect();
+
+        for (idx, color) in colors.iter().enumerate() {
+            assert!(!colors[idx + 1..].contains(color));
+        }
+    }
+
+    #[test]
+    fn can_render_avatar_svg() {
+        let svg = avatar_svg("Sarah <Connor>", "+15558675309");
+
+        assert!(svg.starts_with("<svg class=\"avatar\""));
+        assert!(svg.contains(&avatar_color("+15558675309")));
+        assert!(svg.contains(">SC</text>"));
+        assert!(!svg.contains("<Connor>"));
+    }
+}
@@ -0,0 +1,159 @@
+/*!
+ Writes a list of everyone in the database to `contacts.csv`.
+
+ Each row describes one handle, so a full export can be browsed by person:
+
+ ```csv
+ handle_id,identifier,name,messages,first_message,last_message,services
+ 1,+15558675309,Alice,42,2021-05-17T12:00:00+00:00,2023-01-02T08:30:00+00:00,SMS;iMessage
+ ```
+
+ `name` is the name used for the handle in other exports. `messages` counts messages sent to and received
+ from the handle. Dates are empty for handles with no messages.
+*/
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use csv::Writer;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::tables::handle::{Handle, HandleSummary};
+
+/// The name of the contacts file created in the export directory
+pub const CONTACTS_EXPORT_FILE: &str = "contacts.csv";
+
+/// The header row of the contacts file
+const CONTACTS_HEADER: &str =
+    "handle_id,identifier,name,messages,first_message,last_message,services";
+
+pub struct Contacts<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the contacts file we are writing to
+    pub path: PathBuf,
+}
+
+impl<'a> Exporter<'a> for Contacts<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Contacts {
+            config,
+            path: config.options.export_path.join(CONTACTS_EXPORT_FILE),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn reads_messages(&self) -> bool {
+        false
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let summaries = Handle::summaries(self.config.db())?;
+
+        let mut file = Writer::from_path(&self.path).map_err(IoError::from)?;
+        file.write_record(CONTACTS_HEADER.split(','))
+            .map_err(IoError::from)?;
+        for summary in &summaries {
+            file.write_record(self.format_record(summary))
+                .map_err(IoError::from)?;
+        }
+        file.flush()?;
+
+        eprintln!(
+            "Wrote {} contact(s) to {}",
+            summaries.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+impl Contacts<'_> {
+    /// Build the fields of the row describing a handle, in the order of [`CONTACTS_HEADER`]
+    fn format_record(&self, summary: &HandleSummary) -> [String; 7] {
+        let config = self.config;
+
+        let identifier = config.pseudonyms.get(&summary.rowid).unwrap_or(&summary.id);
+        let name = config.who(Some(summary.rowid), false, &None);
+        let date = |date: Option<i64>| {
+            date.and_then(|date| config.time(&date).ok())
+                .map(|date| date.to_rfc3339())
+                .unwrap_or_default()
+        };
+        let services = summary
+            .services
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        [
+            summary.rowid.to_string(),
+            identifier.to_string(),
+            name.to_string(),
+            summary.messages.to_string(),
+            date(summary.first_message),
+            date(summary.last_message),
+            services,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, remove_dir_all};
+
+    use imessage_database::tables::{handle::Handle, table::Cacheable};
+
+    use crate::{
+        app::{options::Options, runtime::Config},
+        exporters::{
+            contacts::{CONTACTS_HEADER, Contacts},
+            exporter::Exporter,
+        },
+    };
+
+    #[test]
+    fn can_list_every_handle() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-contacts-test",
+            "
+            INSERT INTO handle (ROWID, id, service) VALUES
+                (1, '+15558675309', 'iMessage'),
+                (2, 'bob@example.com', 'iMessage'),
+                (3, '+15550000000', 'SMS');
+            INSERT INTO message (ROWID, guid, text, handle_id, service, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'A', 'Hi', 1, 'iMessage', 0, 642945600000000000, 0, 0),
+                (2, 'B', 'Hello', 1, 'SMS', 1, 642945660000000000, 0, 0),
+                (3, 'C', 'Hey', 2, 'iMessage', 0, 642945720000000000, 0, 0);
+            ",
+        );
+        config.participants = Handle::cache(config.db()).unwrap();
+        config.participants.insert(1, String::from("Alice"));
+
+        let mut contacts = Contacts::new(&config).unwrap();
+        contacts.iter_messages().unwrap();
+
+        let contents = read_to_string(&contacts.path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        assert_eq!(lines[0], CONTACTS_HEADER);
+        // Every handle is listed, even if it has no messages
+        assert_eq!(lines.len() - 1, 3);
+        // Dates depend on the local timezone, so only check the other fields
+        assert!(lines[1].starts_with("1,+15558675309,Alice,2,2021-05-17T"));
+        assert!(lines[1].ends_with(",SMS;iMessage"));
+        assert!(lines[2].starts_with("2,bob@example.com,bob@example.com,1,"));
+        assert!(lines[2].ends_with(",iMessage"));
+        assert_eq!(lines[3], "3,+15550000000,+15550000000,0,,,SMS");
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
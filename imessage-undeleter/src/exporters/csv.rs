@@ -0,0 +1,362 @@
+/*!
+ Writes every message to `messages.csv`, one row per message, for importing into spreadsheets or data frames.
+
+ ```csv
+ rowid,guid,chat_id,date,is_from_me,sender,service,text,num_attachments,variant
+ 42,0355C6E1-D0C8-4212-AA87-DD8AE4FD1203,1,2021-05-17T12:01:00-07:00,true,Me,iMessage,,0,Tapback:Loved
+ 41,F1A8B2C3-D4E5-4F60-8A7B-9C0D1E2F3A4B,1,2021-05-17T12:00:00-07:00,false,+15558675309,iMessage,"Look, a dog! | [attachment]",1,Normal
+ ```
+
+ The columns never change, so scripts can rely on them. Fields that contain commas, quotes, or line breaks are
+ quoted. A message body with several parts, like text followed by a photo, is flattened into a single `text` cell
+ with each part separated by ` | `, and attachments are written as `--attachment-placeholder` describes.
+ `variant` names the kind of message, like `Normal`, `Edited`, `Tapback:Loved`, or `App:URL`. Tapbacks are written
+ as their own rows, and removed tapbacks are marked like `Tapback:Loved:Removed`. Messages are written newest
+ first; with `--max-file-size`, rows are split across numbered volumes like `messages-2.csv`, each with its own
+ header row. With `--since-rowid`, new messages are instead written in ascending `ROWID` order after the rows
+ already in the file, so an export started with `--since-rowid 0` stays in ascending `ROWID` order as it grows.
+*/
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use csv::Writer;
+
+use crate::{
+    app::{
+        attachment_placeholder::{ATTACHMENT_CHAR, AttachmentPlaceholder},
+        error::RuntimeError,
+        runtime::Config,
+    },
+    exporters::{
+        exporter::{Exporter, format_export_text},
+        volume::VolumeWriter,
+    },
+};
+
+use imessage_database::{
+    message_types::variants::{CustomBalloon, TapbackAction, Variant},
+    tables::{attachment::Attachment, messages::Message},
+};
+
+/// The name of the export file created in the export directory
+pub const CSV_EXPORT_FILE: &str = "messages.csv";
+
+/// The columns of the export, in order
+const CSV_COLUMNS: [&str; 10] = [
+    "rowid",
+    "guid",
+    "chat_id",
+    "date",
+    "is_from_me",
+    "sender",
+    "service",
+    "text",
+    "num_attachments",
+    "variant",
+];
+
+/// Written between the parts of a message body when they are flattened into a single cell
+const PART_SEPARATOR: &str = " | ";
+
+// Named like the `TXT` exporter
+#[allow(clippy::upper_case_acronyms)]
+pub struct CSV<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the export we are writing to
+    pub path: PathBuf,
+    /// The export volumes, opened once the export starts
+    file: Option<VolumeWriter>,
+    /// The number of rows written so far
+    rows: usize,
+}
+
+impl<'a> Exporter<'a> for CSV<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(CSV {
+            config,
+            path: config.options.export_path.join(CSV_EXPORT_FILE),
+            file: None,
+            rows: 0,
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn start(&mut self) -> Result<(), RuntimeError> {
+        let header = format_row(&CSV_COLUMNS)?;
+        self.file = Some(self.config.open_volumes(&self.path, &header)?);
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        _attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        if !self.config.matches_text(msg) {
+            return Ok(());
+        }
+
+        let record = self.format_record(msg);
+        let row = format_row(&record)?;
+        if let Some(file) = &mut self.file {
+            file.write_row(msg.rowid, &row)?;
+            self.rows += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let volumes = match self.file.take() {
+            Some(file) => file.finish()?.len(),
+            None => 1,
+        };
+
+        if volumes > 1 {
+            eprintln!(
+                "Wrote {} message(s) to {volumes} volumes starting at {}",
+                self.rows,
+                self.path.display()
+            );
+        } else {
+            eprintln!("Wrote {} message(s) to {}", self.rows, self.path.display());
+        }
+        Ok(())
+    }
+}
+
+impl CSV<'_> {
+    /// Build the fields of the row describing a message, in the order of [`CSV_COLUMNS`]
+    fn format_record(&self, msg: &Message) -> [String; 10] {
+        let config = self.config;
+
+        let date = config
+            .time(&msg.date)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default();
+        let text = format_export_text(config, msg)
+            .map(|text| flatten_parts(&text, config.options.attachment_placeholder))
+            .unwrap_or_default();
+
+        [
+            msg.rowid.to_string(),
+            msg.guid.clone(),
+            msg.chat_id
+                .or(msg.deleted_from)
+                .map(|chat_id| chat_id.to_string())
+                .unwrap_or_default(),
+            date,
+            msg.is_from_me().to_string(),
+            config
+                .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)
+                .to_string(),
+            msg.service.clone().unwrap_or_default(),
+            text,
+            msg.num_attachments.to_string(),
+            variant_label(&msg.variant()),
+        ]
+    }
+}
+
+/// Quote the fields of a row and join them into a single line
+pub(crate) fn format_row<T: AsRef<[u8]>>(fields: &[T]) -> Result<String, RuntimeError> {
+    let mut writer = Writer::from_writer(vec![]);
+    writer.write_record(fields).map_err(IoError::from)?;
+    let row = writer
+        .into_inner()
+        .map_err(|why| IoError::other(why.to_string()))?;
+    Ok(String::from_utf8_lossy(&row).into_owned())
+}
+
+/// Join the parts of a message body into a single line of text
+///
+/// The body is split at each attachment placeholder, and each attachment is written as `placeholder` describes.
+fn flatten_parts(text: &str, placeholder: AttachmentPlaceholder) -> String {
+    let mut parts: Vec<String> = vec![];
+    for (idx, segment) in text.split(ATTACHMENT_CHAR).enumerate() {
+        if idx > 0 {
+            let attachment = placeholder.apply(&ATTACHMENT_CHAR.to_string());
+            if !attachment.is_empty() {
+                parts.push(attachment);
+            }
+        }
+        let segment = segment.trim();
+        if !segment.is_empty() {
+            parts.push(segment.to_string());
+        }
+    }
+    parts.join(PART_SEPARATOR)
+}
+
+/// Describe the kind of a message in a few words, like `Tapback:Loved` or `App:URL`
+fn variant_label(variant: &Variant) -> String {
+    match variant {
+        Variant::Normal => String::from("Normal"),
+        Variant::Edited => String::from("Edited"),
+        Variant::Tapback(_, TapbackAction::Added, tapback) => format!("Tapback:{tapback}"),
+        Variant::Tapback(_, TapbackAction::Removed, tapback) => {
+            format!("Tapback:{tapback}:Removed")
+        }
+        Variant::App(CustomBalloon::Application(bundle_id)) => format!("App:{bundle_id}"),
+        Variant::App(balloon) => format!("App:{balloon:?}"),
+        Variant::SharePlay => String::from("SharePlay"),
+        Variant::Unknown(kind) => format!("Unknown:{kind}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_dir_all;
+
+    use imessage_database::message_types::variants::{
+        CustomBalloon, Tapback, TapbackAction, Variant,
+    };
+
+    use crate::{
+        app::{attachment_placeholder::AttachmentPlaceholder, options::Options, runtime::Config},
+        exporters::{
+            csv::{CSV, CSV_COLUMNS, CSV_EXPORT_FILE, flatten_parts, variant_label},
+            exporter::Exporter,
+        },
+    };
+
+    #[test]
+    fn can_write_row_per_message() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-csv-test",
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, associated_message_guid) VALUES
+                (1, 'A', 'Look, a \"dog\"!\nSo cute \u{FFFC}', 'iMessage', 1, 0, 642945600000000000, 0, 0, 0, NULL),
+                (2, 'B', NULL, 'iMessage', 0, 1, 642945660000000000, 0, 0, 2000, 'p:0/A');
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/IMG_0001.jpeg', 'image/jpeg', 'IMG_0001.jpeg', 100);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);
+            ",
+        );
+        config.participants.insert(1, String::from("+15558675309"));
+
+        let mut csv = CSV::new(&config).unwrap();
+        csv.iter_messages().unwrap();
+
+        let mut reader =
+            csv::Reader::from_path(config.options.export_path.join(CSV_EXPORT_FILE)).unwrap();
+        assert_eq!(reader.headers().unwrap(), &CSV_COLUMNS[..]);
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+
+        // Messages are newest first, and tapbacks are written as their own rows
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[0][1], "B");
+        assert_eq!(&rows[0][4], "true");
+        assert_eq!(&rows[0][5], "Me");
+        assert_eq!(&rows[0][9], "Tapback:Loved");
+        assert_eq!(&rows[1][0], "1");
+        assert_eq!(&rows[1][2], "1");
+        assert_eq!(&rows[1][5], "+15558675309");
+        assert_eq!(&rows[1][7], "Look, a \"dog\"!\nSo cute | [attachment]");
+        assert_eq!(&rows[1][8], "1");
+        assert_eq!(&rows[1][9], "Normal");
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_append_messages_since_rowid() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-csv-since-test",
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type) VALUES
+                (1, 'A', 'First', 'iMessage', 0, 1, 642945600000000000, 0, 0, 0),
+                (2, 'B', 'Second', 'iMessage', 0, 1, 642945660000000000, 0, 0, 0);
+            ",
+        );
+
+        // Starting from ROWID 0 writes every message, oldest ROWID first
+        config.options.since_rowid = Some(0);
+        config.options.query_context.set_since_rowid(0);
+        CSV::new(&config).unwrap().iter_messages().unwrap();
+        assert_eq!(config.last_exported_rowid.get(), Some(2));
+
+        // The next export only writes the new messages, after the rows already in the file and in `ROWID` order
+        // even though the last one was sent first
+        config
+            .db()
+            .execute_batch(
+                "INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type) VALUES
+                    (3, 'C', 'Third', 'iMessage', 0, 1, 642945720000000000, 0, 0, 0),
+                    (4, 'D', 'Fourth', 'iMessage', 0, 1, 642945500000000000, 0, 0, 0);",
+            )
+            .unwrap();
+        config.options.since_rowid = Some(2);
+        config.options.query_context.set_since_rowid(2);
+        CSV::new(&config).unwrap().iter_messages().unwrap();
+        assert_eq!(config.last_exported_rowid.get(), Some(4));
+
+        let mut reader =
+            csv::Reader::from_path(config.options.export_path.join(CSV_EXPORT_FILE)).unwrap();
+        let rows: Vec<(String, String)> = reader
+            .records()
+            .map(|row| {
+                let row = row.unwrap();
+                (row[0].to_string(), row[1].to_string())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (String::from("1"), String::from("A")),
+                (String::from("2"), String::from("B")),
+                (String::from("3"), String::from("C")),
+                (String::from("4"), String::from("D")),
+            ]
+        );
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_flatten_parts() {
+        let text = "\u{FFFC}\u{FFFC} Two photos";
+
+        assert_eq!(
+            flatten_parts(text, AttachmentPlaceholder::Label),
+            "[attachment] | [attachment] | Two photos"
+        );
+        assert_eq!(
+            flatten_parts(text, AttachmentPlaceholder::Strip),
+            "Two photos"
+        );
+        assert_eq!(
+            flatten_parts("Hello", AttachmentPlaceholder::Label),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn can_label_variants() {
+        assert_eq!(variant_label(&Variant::Normal), "Normal");
+        assert_eq!(
+            variant_label(&Variant::Tapback(0, TapbackAction::Added, Tapback::Loved)),
+            "Tapback:Loved"
+        );
+        assert_eq!(
+            variant_label(&Variant::Tapback(
+                0,
+                TapbackAction::Removed,
+                Tapback::Emoji(Some("🔥"))
+            )),
+            "Tapback:🔥:Removed"
+        );
+        assert_eq!(variant_label(&Variant::App(CustomBalloon::URL)), "App:URL");
+        assert_eq!(
+            variant_label(&Variant::App(CustomBalloon::Application("com.example.app"))),
+            "App:com.example.app"
+        );
+        assert_eq!(variant_label(&Variant::Unknown(42)), "Unknown:42");
+    }
+}
@@ -2,19 +2,320 @@ use std::{fs::File, io::BufWriter};
 
 use imessage_database::{
     error::{plist::PlistParseError, table::TableError},
-    message_types::edited::EditedMessage,
+    message_types::{
+        collaboration::SharedItem,
+        edited::{EditedEvent, EditedMessage},
+        poll::Poll,
+        text_effects::TextEffect,
+        variants::PinAction,
+    },
     tables::{
         attachment::Attachment,
         messages::{
             Message,
-            models::{AttachmentMeta, TextAttributes},
+            models::{AttachmentMeta, GroupAction, TextAttributes},
         },
+        table::{Table, UNKNOWN},
     },
 };
+use rusqlite::params_from_iter;
 
-use crate::app::{error::RuntimeError};
+use crate::app::{error::RuntimeError, runtime::Config};
 
 pub(crate) const ATTACHMENT_NO_FILENAME: &str = "Attachment missing name metadata!";
+/// Text that replaces one-time codes when they are redacted
+pub(crate) const ONE_TIME_CODE_REDACTED: &str = "[one-time code]";
+/// Text that replaces phone numbers and emails when identities are redacted
+pub(crate) const CONTACT_REDACTED: &str = "[redacted]";
+/// Label for messages that were recovered from the Recently Deleted collection
+pub(crate) const RECOVERED_LABEL: &str = "[recovered from Recently Deleted]";
+
+/// Copy an attachment into the export directory if requested, so it can be linked from the export
+///
+/// The copy is named after the attachment's `ROWID`, and attachments that were never downloaded are skipped.
+pub(crate) fn copy_attachment(config: &Config, msg: &Message, attachment: &mut Attachment) {
+    if attachment.is_downloaded(
+        &config.options.platform,
+        &config.options.db_path,
+        config.options.attachment_root.as_deref(),
+    ) {
+        config.options.attachment_manager.handle_attachment(
+            msg,
+            attachment,
+            &attachment.rowid.to_string(),
+            config,
+        );
+    }
+}
+
+/// Render a poll's question and the number of votes for each choice on a single line
+pub(crate) fn format_poll(poll: &Poll) -> String {
+    let tallies = poll
+        .options
+        .iter()
+        .map(|option| format!("{}: {}", option.text, option.voters.len()))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match &poll.question {
+        Some(question) => format!("Poll: {question} ({tallies})"),
+        None => format!("Poll: {tallies}"),
+    }
+}
+
+/// Render an item shared from another app on a single line, like `Collaboration: Untitled (Freeform)`
+pub(crate) fn format_shared_item(item: &SharedItem) -> String {
+    let label = if item.is_collaboration {
+        "Collaboration"
+    } else {
+        "Shared"
+    };
+    let title = item
+        .title
+        .as_deref()
+        .or(item.url.as_deref())
+        .unwrap_or("Untitled");
+
+    match &item.app_name {
+        Some(app_name) => format!("{label}: {title} ({app_name})"),
+        None => format!("{label}: {title}"),
+    }
+}
+
+/// Describe whether an outgoing message was read or delivered, like `Read at 3:04 PM` or `Delivered`
+///
+/// Returns `None` for messages sent by other people, and for messages that have no receipt.
+pub(crate) fn format_receipt(msg: &Message, config: &Config) -> Option<String> {
+    if !msg.is_from_me() || msg.is_tapback() || msg.is_announcement() {
+        return None;
+    }
+    if msg.date_read != 0 {
+        let read = config.time(&msg.date_read).ok()?;
+        return Some(format!("Read at {}", read.format("%-I:%M %p")));
+    }
+    (msg.date_delivered != 0).then(|| String::from("Delivered"))
+}
+
+/// Render a message pin event as a system line, quoting the pinned message if it could be found
+pub(crate) fn format_pin_event(
+    sender: &str,
+    action: PinAction,
+    pinned: Option<&Message>,
+) -> String {
+    let verb = match action {
+        PinAction::Pinned => "pinned",
+        PinAction::Unpinned => "unpinned",
+    };
+    match pinned.and_then(|message| message.text.as_deref()) {
+        Some(text) if !text.trim().is_empty() => format!("{sender} {verb} a message: \"{text}\""),
+        _ => format!("{sender} {verb} a message"),
+    }
+}
+
+/// Render a group membership, name, or photo change as a system line, like `Alice added Bob to the conversation`
+///
+/// `target` is the name of the participant that was added or removed, if the action affects one.
+pub(crate) fn format_group_action(
+    sender: &str,
+    action: &GroupAction,
+    target: Option<&str>,
+) -> String {
+    let target = target.unwrap_or(UNKNOWN);
+    match action {
+        GroupAction::ParticipantAdded(_) => format!("{sender} added {target} to the conversation"),
+        GroupAction::ParticipantRemoved(_) => {
+            format!("{sender} removed {target} from the conversation")
+        }
+        GroupAction::NameChange(name) => format!("{sender} named the conversation \"{name}\""),
+        GroupAction::ParticipantLeft => format!("{sender} left the conversation"),
+        GroupAction::GroupIconChanged => format!("{sender} changed the group photo"),
+        GroupAction::GroupIconRemoved => format!("{sender} removed the group photo"),
+    }
+}
+
+/// Get a message's text with one-time codes and contact details redacted, if requested
+///
+/// The message's text must already be generated.
+pub(crate) fn redacted_text(config: &Config, msg: &Message) -> Option<String> {
+    let text = if config.options.redact_one_time_codes {
+        msg.text_with_redacted_codes(ONE_TIME_CODE_REDACTED)
+    } else {
+        msg.text.clone()
+    };
+    text.map(|text| config.redact_text(text))
+}
+
+/// Get one text part of a message body with one-time codes and contact details redacted, if requested
+///
+/// `attributes` are the ranges of `text`, the message's full text, that make up the part.
+pub(crate) fn redacted_part(
+    config: &Config,
+    text: &str,
+    attributes: &[TextAttributes],
+) -> Option<String> {
+    let mut part = String::new();
+    for attribute in attributes {
+        let segment = text.get(attribute.start..attribute.end)?;
+        if config.options.redact_one_time_codes && matches!(attribute.effect, TextEffect::OTP) {
+            part.push_str(ONE_TIME_CODE_REDACTED);
+        } else {
+            part.push_str(segment);
+        }
+    }
+    (!attributes.is_empty()).then(|| config.redact_text(part))
+}
+
+/// Get the text of one version of an edited message part with one-time codes and contact details redacted, if
+/// requested
+pub(crate) fn redacted_edit(config: &Config, event: &EditedEvent) -> Option<String> {
+    let text = if config.options.redact_one_time_codes {
+        event.text_with_redacted_codes(ONE_TIME_CODE_REDACTED)
+    } else {
+        event.text.clone()
+    };
+    text.map(|text| config.redact_text(text))
+}
+
+/// Render the text of a message the way structured exports store it
+///
+/// One-time codes and contact details are redacted if requested, and polls, app balloons, payments, pin events, and
+/// group actions are described in words. The message's text must already be generated.
+pub(crate) fn format_export_text(config: &Config, msg: &Message) -> Option<String> {
+    let mut text = redacted_text(config, msg);
+    if let Some(poll) = msg.poll(config.db()) {
+        text = Some(format_poll(&poll));
+    }
+    if let Some(summary) = msg.app_summary(config.db()) {
+        text = Some(config.redact_text(summary));
+    } else if let Some(item) = msg.shared_item(config.db()) {
+        text = Some(config.redact_text(format_shared_item(&item)));
+    }
+    if let Some(payment) = msg.apple_pay(config.db()) {
+        text = Some(payment.to_string());
+    }
+    if let Some(action) = msg.pin_action() {
+        let pinned = msg.pinned_message(config.db());
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let line = format_pin_event(sender, action, pinned.as_ref());
+        text = Some(config.redact_text(line));
+    }
+    if let Some(action) = msg.group_action() {
+        // `is_from_me()` treats any message with an `other_handle` as a location share, so use the column
+        let sender = config.who(msg.handle_id, msg.is_from_me, &msg.destination_caller_id);
+        // A target handle of `0` is the database owner
+        let target = action
+            .target()
+            .map(|handle| config.who(Some(handle), handle == 0, &msg.destination_caller_id));
+        let line = format_group_action(sender, &action, target);
+        text = Some(config.redact_text(line));
+    }
+    text
+}
+
+/// Defines behavior for iterating over messages from the iMessage database and managing export files
+///
+/// Exporters that read messages receive them one at a time through [`Exporter::write_message()`], so several
+/// exporters can share a single pass over the messages table with [`export_all()`].
+pub trait Exporter<'a> {
+    /// Create new exporter with references to the cached data
+    fn new(config: &'a Config) -> Result<Self, RuntimeError>
+    where
+        Self: Sized;
+    /// The application data the exporter was created with
+    fn config(&self) -> &'a Config;
+    /// `false` if the exporter does not use the messages table, else `true`
+    fn reads_messages(&self) -> bool {
+        true
+    }
+    /// Prepare the export before any messages are written
+    fn start(&mut self) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+    /// Export a single message and the attachments sent with it
+    fn write_message(
+        &mut self,
+        _msg: &Message,
+        _attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+    /// Finish the export once every message has been written
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+    /// Begin iterating over the messages table
+    fn iter_messages(&mut self) -> Result<(), RuntimeError>
+    where
+        Self: Sized,
+    {
+        let config = self.config();
+        export_all(config, &mut [self])
+    }
+}
+
+/// Write several exports in a single pass over the messages table
+///
+/// Each message is read, filtered, and has its text generated once, then handed to every exporter in order, so
+/// all of the exports cover the same messages. The attachments for each message are loaded once and shared, so
+/// an attachment copied by one exporter is already in place for the next.
+pub fn export_all<'a>(
+    config: &'a Config,
+    exporters: &mut [&mut dyn Exporter<'a>],
+) -> Result<(), RuntimeError> {
+    for exporter in exporters.iter_mut() {
+        exporter.start()?;
+    }
+
+    if exporters.iter().any(|exporter| exporter.reads_messages()) {
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+        let mut attachments =
+            Attachment::cache_by_message(config.db(), &config.options.query_context)?;
+
+        let mut statement = Message::stream_rows(config.db(), &config.options.query_context)?;
+        let messages = statement
+            .query_map(
+                params_from_iter(config.options.query_context.predicate_params()),
+                |row| Ok(Message::from_row(row)),
+            )
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if !config.include_chat(msg.chat_id.or(msg.deleted_from)) {
+                continue;
+            }
+
+            let _ = msg.generate_text(config.db());
+            let mut files = msg.attachments_without_query(&mut attachments);
+            for exporter in exporters.iter_mut() {
+                exporter.write_message(&msg, &mut files)?;
+            }
+            if config.last_exported_rowid.get() < Some(msg.rowid) {
+                config.last_exported_rowid.set(Some(msg.rowid));
+            }
+            let mut exported = config.exported.borrow_mut();
+            exported.messages += 1;
+            exported.attachments += files.len() as u64;
+            if let Some(chat_id) = msg.chat_id.or(msg.deleted_from) {
+                exported.chats.insert(chat_id);
+            }
+        }
+    }
+
+    for exporter in exporters.iter_mut() {
+        exporter.finish()?;
+    }
+    Ok(())
+}
 
 /// Defines behavior for formatting message instances to the desired output format
 pub trait Writer<'a> {
@@ -57,4 +358,123 @@ pub trait Writer<'a> {
     /// Format all [`TextAttributes`]s applied to a given set of text
     fn format_attributes(&'a self, text: &'a str, attributes: &'a [TextAttributes]) -> String;
     fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError>;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, remove_dir_all};
+
+    use imessage_database::{
+        message_types::{
+            collaboration::SharedItem,
+            poll::{Poll, PollOption},
+        },
+        tables::chat::Chat,
+    };
+    use rusqlite::Connection;
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        exporters::{
+            exporter::{format_poll, format_shared_item},
+            sqlite::SQLITE_EXPORT_FILE,
+            telegram::TELEGRAM_EXPORT_FILE,
+        },
+    };
+
+    #[test]
+    fn can_export_multiple_formats_in_one_pass() {
+        let mut options = Options::fake_options();
+        options.export_types = vec![ExportType::Sqlite, ExportType::Telegram];
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-multiple-formats-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type) VALUES
+                (1, 'A', 'Hello', 1, 0, 642945600000000000, 0, 0, 0),
+                (2, 'B', 'Hi!', 0, 1, 642945660000000000, 0, 0, 0),
+                (3, 'C', 'Dinner?', 1, 0, 642945720000000000, 0, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice"));
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: String::from("chat1"),
+                service_name: Some(String::from("iMessage")),
+                display_name: None,
+                style: None,
+                group_id: None,
+            },
+        );
+
+        config.start().unwrap();
+
+        // Both exports are written by the same run
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let sqlite_rowids: Vec<i32> = out
+            .prepare("SELECT id FROM messages ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let telegram =
+            read_to_string(config.options.export_path.join(TELEGRAM_EXPORT_FILE)).unwrap();
+        let mut telegram_rowids: Vec<i32> = telegram
+            .split("\"message_rowid\": ")
+            .skip(1)
+            .map(|rest| rest.split(',').next().unwrap().parse().unwrap())
+            .collect();
+        telegram_rowids.sort_unstable();
+
+        assert_eq!(sqlite_rowids, vec![1, 2, 3]);
+        assert_eq!(telegram_rowids, sqlite_rowids);
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_format_poll_tallies() {
+        let poll = Poll {
+            question: Some(String::from("Dinner?")),
+            options: vec![
+                PollOption {
+                    text: String::from("Tacos"),
+                    voters: vec![String::from("+15558675309"), String::from("Me")],
+                },
+                PollOption {
+                    text: String::from("Pizza"),
+                    voters: vec![],
+                },
+            ],
+        };
+
+        assert_eq!(format_poll(&poll), "Poll: Dinner? (Tacos: 2, Pizza: 0)");
+    }
+
+    #[test]
+    fn can_format_shared_items() {
+        let mut item = SharedItem {
+            title: Some(String::from("Untitled")),
+            app_name: Some(String::from("Freeform")),
+            url: Some(String::from("https://www.icloud.com/freeform/REDACTED")),
+            is_collaboration: true,
+        };
+        assert_eq!(
+            format_shared_item(&item),
+            "Collaboration: Untitled (Freeform)"
+        );
+
+        // Without a title or app name, fall back to the link
+        item.title = None;
+        item.app_name = None;
+        item.is_collaboration = false;
+        assert_eq!(
+            format_shared_item(&item),
+            "Shared: https://www.icloud.com/freeform/REDACTED"
+        );
+    }
+}
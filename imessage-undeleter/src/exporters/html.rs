@@ -0,0 +1,967 @@
+/*!
+ Writes each conversation to a self-contained HTML file laid out like the Messages app.
+
+ Messages are written oldest first, with the database owner's messages on the right and everyone else's on the
+ left. Consecutive messages from the same sender are grouped, and every part of a message gets its own bubble:
+ styled text keeps its formatting, attachments are embedded from where they were copied, tapbacks are drawn as
+ badges next to the part they react to, and stickers are drawn on top of the bubble they were placed on. Edited
+ parts list their history in a collapsible section. With `--max-file-size`, a conversation is split into numbered
+ pages that link to each other.
+*/
+
+use std::collections::BTreeMap;
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        runtime::Config,
+        sanitizers::{is_safe_link, sanitize_html},
+    },
+    exporters::{
+        avatar::avatar_svg,
+        exporter::{
+            Exporter, ONE_TIME_CODE_REDACTED, RECOVERED_LABEL, copy_attachment, format_export_text,
+            format_poll, format_receipt,
+        },
+    },
+};
+
+use imessage_database::{
+    message_types::{
+        collaboration::SharedItem,
+        edited::EditStatus,
+        text_effects::{Animation, Style, TextEffect},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{
+            Message,
+            models::{AttachmentMeta, BubbleComponent, TextAttributes},
+        },
+        table::ORPHANED,
+    },
+    util::{grouping::SenderGroups, plist::parse_ns_keyed_archiver},
+};
+
+/// Styles embedded in every page, so each file can be opened on its own
+const STYLE: &str = "<style>
+body { font-family: -apple-system, BlinkMacSystemFont, sans-serif; max-width: 720px; margin: 0 auto; padding: 1em; }
+h1 { text-align: center; font-size: 1.2em; }
+.message { display: flex; flex-direction: column; margin: 2px 0; }
+.message.sent { align-items: flex-end; }
+.message.received { align-items: flex-start; }
+.header { color: #8e8e93; font-size: 0.75em; margin: 0.8em 0.5em 0.2em; }
+.row { display: flex; align-items: flex-end; gap: 6px; max-width: 75%; }
+.avatar, .avatar-spacer { flex-shrink: 0; width: 28px; }
+.parts { display: flex; flex-direction: column; gap: 2px; min-width: 0; }
+.sent .parts { align-items: flex-end; }
+.received .parts { align-items: flex-start; }
+.bubble { position: relative; border-radius: 18px; padding: 7px 12px; white-space: pre-wrap; overflow-wrap: anywhere; }
+.sent .bubble { background: #0b84fe; color: white; }
+.sent .bubble a { color: white; }
+.received .bubble { background: #e9e9eb; color: black; }
+.bubble.media { background: none; padding: 0; }
+.bubble.media img, .bubble.media video { max-width: 100%; border-radius: 18px; }
+.bubble.unsent { background: none; color: #8e8e93; font-style: italic; }
+.bold { font-weight: bold; }
+.italic { font-style: italic; }
+.underline { text-decoration: underline; }
+.strikethrough { text-decoration: line-through; }
+.underline.strikethrough { text-decoration: underline line-through; }
+.mention { font-weight: 600; }
+.transcription { font-size: 0.85em; margin: 4px 0 0; }
+.card { display: block; text-decoration: none; }
+.card img { max-width: 100%; border-radius: 12px; }
+.card .title { display: block; font-weight: 600; }
+.card .detail { display: block; font-size: 0.85em; opacity: 0.8; }
+.sticker { max-width: 160px; }
+.sticker-overlay { position: absolute; width: 64px; left: 85%; top: 15%; transform: translate(-50%, -50%); pointer-events: none; }
+.tapbacks { display: flex; gap: 2px; }
+.tapback { background: #e9e9eb; border: 2px solid white; border-radius: 12px; padding: 0 5px; font-size: 0.8em; }
+.edit-history, .receipt, .recovered { color: #8e8e93; font-size: 0.75em; }
+.edit-history ol { margin: 0; padding-left: 1.5em; }
+.announcement { color: #8e8e93; font-size: 0.75em; text-align: center; margin: 1em 0; }
+.day { color: #8e8e93; font-size: 0.8em; text-align: center; margin: 1.5em 0 0.5em; }
+.volume-link { display: block; text-align: center; margin: 1em 0; }
+</style>";
+
+/// Closes the markup opened by each page's header
+const FOOTER: &str = "</body>\n</html>\n";
+
+/// A rendered message waiting for its conversation to be written oldest first
+struct Entry {
+    /// The class of the message row: `sent`, `received`, or `announcement`
+    kind: &'static str,
+    /// The sender and time, shown above the first message in a group
+    header: String,
+    /// The sender's avatar in group conversations, shown next to the last message in a group
+    avatar: Option<String>,
+    /// The message's bubbles, tapbacks, and notes
+    body: String,
+    /// `true` if this is the latest message in its group
+    ends_group: bool,
+    /// The heading for the day the message was sent, if timestamps leave the date out
+    day: Option<String>,
+}
+
+// Named like the `TXT` exporter
+#[allow(clippy::upper_case_acronyms)]
+pub struct HTML<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Map of deduplicated chat ID to the messages rendered for it so far; orphaned messages use `None`
+    chats: BTreeMap<Option<i32>, Vec<Entry>>,
+    /// Tracks runs of consecutive messages from the same sender in each chat
+    groups: SenderGroups,
+}
+
+impl<'a> Exporter<'a> for HTML<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(HTML {
+            config,
+            chats: BTreeMap::new(),
+            groups: SenderGroups::default(),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        // Tapbacks and stickers are drawn on the message they target
+        if msg.is_tapback() || msg.is_sticker_overlay() || !config.matches_text(msg) {
+            return Ok(());
+        }
+        let chat_id = config.conversation(msg).map(|(_, chat_id)| *chat_id);
+        let day = config
+            .options
+            .timestamp_granularity
+            .day_heading(&config.time(&msg.date));
+
+        let entry = if msg.is_announcement() {
+            Entry {
+                kind: "announcement",
+                header: String::new(),
+                avatar: None,
+                body: self.format_announcement(msg),
+                ends_group: true,
+                day,
+            }
+        } else {
+            for attachment in attachments.iter_mut() {
+                copy_attachment(self.config, msg, attachment);
+            }
+            // Messages are streamed newest first, so a message that starts a group is the latest in its run
+            let ends_group = self.groups.starts_group(msg);
+            let mut body = self.format_parts(msg, attachments.to_vec());
+            if let Some(receipt) =
+                format_receipt(msg, config).filter(|_| ends_group && config.options.show_receipts)
+            {
+                body.push_str(&format!("<p class=\"receipt\">{receipt}</p>"));
+            }
+            if msg.is_deleted() {
+                body.push_str(&format!("<p class=\"recovered\">{RECOVERED_LABEL}</p>"));
+            }
+            Entry {
+                kind: if msg.is_from_me() { "sent" } else { "received" },
+                header: self.format_header(msg),
+                avatar: self.format_avatar(msg),
+                body,
+                ends_group,
+                day,
+            }
+        };
+
+        self.chats.entry(chat_id).or_default().push(entry);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let chats = std::mem::take(&mut self.chats);
+        let mut files = 0;
+        for (chat_id, entries) in chats {
+            let chat = chat_id.and_then(|chat_id| self.config.chatrooms.get(&chat_id));
+            let (filename, title) = match chat {
+                Some(chat) => (self.config.filename(chat), self.config.chat_label(chat)),
+                None => (String::from(ORPHANED), String::from("Orphaned messages")),
+            };
+            let mut path = self.config.options.export_path.join(filename);
+            path.set_extension("html");
+
+            let mut file = self
+                .config
+                .open_volumes(&path, &Self::format_page_header(&title))?
+                .with_footer(FOOTER)
+                .with_navigation(|file, label| {
+                    format!(
+                        "<a class=\"volume-link\" href=\"{}\">{label}</a>\n",
+                        sanitize_html(file)
+                    )
+                });
+            for message in Self::format_entries(&entries) {
+                file.write_message(&message)?;
+            }
+            files += file.finish()?.len();
+        }
+
+        eprintln!(
+            "Wrote {files} HTML file(s) to {}",
+            self.config.options.export_path.display()
+        );
+        Ok(())
+    }
+}
+
+impl HTML<'_> {
+    /// The start of each page of a conversation, up to where the messages begin
+    fn format_page_header(title: &str) -> String {
+        let title = sanitize_html(title);
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n{STYLE}\n</head>\n<body>\n<h1>{title}</h1>\n"
+        )
+    }
+
+    /// Render a conversation's messages oldest first, showing headers and avatars at the edges of each group
+    ///
+    /// If timestamps leave the date out, a heading with the date is written each time the day changes.
+    fn format_entries(entries: &[Entry]) -> Vec<String> {
+        let mut starts_group = true;
+        let mut day = None;
+        let mut messages = Vec::with_capacity(entries.len());
+
+        // Messages are streamed newest first, so walk them backwards
+        for (idx, entry) in entries.iter().enumerate().rev() {
+            if entry.day.is_some() && entry.day != day {
+                day.clone_from(&entry.day);
+                messages.push(format!(
+                    "<h2 class=\"day\">{}</h2>\n",
+                    sanitize_html(day.as_deref().unwrap_or_default())
+                ));
+                starts_group = true;
+            }
+
+            if entry.kind == "announcement" {
+                messages.push(format!("{}\n", entry.body));
+                starts_group = true;
+                continue;
+            }
+
+            // Announcements and day headings between two messages split their group
+            let next_splits_group = idx.checked_sub(1).is_some_and(|next| {
+                entries[next].kind == "announcement" || entries[next].day != entry.day
+            });
+            let ends_group = entry.ends_group || next_splits_group;
+
+            let header = if starts_group {
+                entry.header.as_str()
+            } else {
+                ""
+            };
+            let avatar = match &entry.avatar {
+                Some(avatar) if ends_group => avatar.as_str(),
+                Some(_) => "<span class=\"avatar-spacer\"></span>",
+                None => "",
+            };
+            messages.push(format!(
+                "<div class=\"message {}\">{header}<div class=\"row\">{avatar}<div class=\"parts\">{}</div></div></div>\n",
+                entry.kind, entry.body
+            ));
+            starts_group = ends_group;
+        }
+        messages
+    }
+
+    /// Render the sender and time shown above a group of messages
+    fn format_header(&self, msg: &Message) -> String {
+        let config = self.config;
+        let time = sanitize_html(
+            &config
+                .options
+                .timestamp_granularity
+                .format(&config.time(&msg.date)),
+        )
+        .to_string();
+        if msg.is_from_me() {
+            return format!("<p class=\"header\">{time}</p>");
+        }
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        format!("<p class=\"header\">{} · {time}</p>", sanitize_html(sender))
+    }
+
+    /// Render the avatar shown next to messages from other people in group conversations
+    fn format_avatar(&self, msg: &Message) -> Option<String> {
+        let config = self.config;
+        if msg.is_from_me() || !config.conversation(msg)?.0.is_group() {
+            return None;
+        }
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        Some(avatar_svg(sender, sender))
+    }
+
+    /// Render an announcement, like a group action or an unsent message, as a line across the conversation
+    fn format_announcement(&self, msg: &Message) -> String {
+        let config = self.config;
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let text = match msg.get_announcement() {
+            Some(Announcement::FullyUnsent) => format!("{sender} unsent a message"),
+            Some(Announcement::AudioMessageKept) => format!("{sender} kept an audio message"),
+            _ => format_export_text(config, msg).unwrap_or_default(),
+        };
+        let time = config
+            .options
+            .timestamp_granularity
+            .format(&config.time(&msg.date));
+        format!(
+            "<p class=\"announcement\">{} · {}</p>",
+            sanitize_html(&time),
+            sanitize_html(&text)
+        )
+    }
+
+    /// Render each part of a message as its own bubble, with the tapbacks, stickers, and edits for that part
+    fn format_parts(&self, msg: &Message, mut attachments: Vec<Attachment>) -> String {
+        let config = self.config;
+        let preview = msg.take_link_preview_image(&mut attachments);
+        let rendered = msg.rendered_parts(attachments);
+        if let Some(mismatch) = &rendered.mismatch {
+            eprintln!("Message {}: {mismatch}", msg.guid);
+        }
+        let reactions = config.reactions_to(msg);
+        let text = msg.text.as_deref().unwrap_or_default();
+
+        let mut out = String::new();
+        for (idx, part) in rendered.parts.iter().enumerate() {
+            let (class, content) = match &part.component {
+                BubbleComponent::Text(attributes) => {
+                    let content = self.format_attributes(text, attributes);
+                    if content.trim().is_empty() {
+                        continue;
+                    }
+                    ("text", content)
+                }
+                BubbleComponent::Attachment(meta) => (
+                    "media",
+                    self.format_attachment(part.attachment.as_ref(), meta),
+                ),
+                BubbleComponent::App => ("app", self.format_app(msg, preview.as_ref())),
+                BubbleComponent::Retracted => {
+                    let sender =
+                        config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+                    (
+                        "unsent",
+                        format!("{} unsent this part", sanitize_html(sender)),
+                    )
+                }
+            };
+
+            let on_part: Vec<&Message> = reactions
+                .iter()
+                .filter(|tapback| {
+                    tapback.clean_associated_guid().map_or(0, |(part, _)| part) == idx
+                })
+                .copied()
+                .collect();
+            let stickers: String = on_part
+                .iter()
+                .filter(|tapback| tapback.is_sticker_overlay())
+                .filter_map(|tapback| self.format_sticker_overlay(tapback))
+                .collect();
+            let badges: Vec<String> = on_part
+                .iter()
+                .filter(|tapback| !tapback.is_sticker_overlay())
+                .filter_map(|tapback| self.format_tapback(tapback, msg))
+                .collect();
+
+            let bubble = format!("<div class=\"bubble {class}\">{content}{stickers}</div>");
+            let tapbacks = if badges.is_empty() {
+                vec![]
+            } else {
+                vec![format!("<div class=\"tapbacks\">{}</div>", badges.concat())]
+            };
+            for item in config.options.reaction_placement.arrange(bubble, tapbacks) {
+                out.push_str(&item);
+            }
+            if let Some(history) = self.format_edit_history(msg, idx) {
+                out.push_str(&history);
+            }
+        }
+        out
+    }
+
+    /// Render the ranges of a text part with their styles, links, and mentions
+    fn format_attributes(&self, text: &str, attributes: &[TextAttributes]) -> String {
+        let config = self.config;
+        let mut out = String::new();
+        for attribute in attributes {
+            let Some(segment) = text.get(attribute.start..attribute.end) else {
+                continue;
+            };
+            let escaped = sanitize_html(&config.redact_text(segment.to_string())).to_string();
+            let rendered = match &attribute.effect {
+                TextEffect::Default => escaped,
+                TextEffect::Mention(handle) => format!(
+                    "<span class=\"mention\" title=\"{}\">{escaped}</span>",
+                    sanitize_html(&config.redact_text(handle.to_string()))
+                ),
+                TextEffect::Link(url) if is_safe_link(url) => format!(
+                    "<a href=\"{}\">{escaped}</a>",
+                    sanitize_html(&config.redact_text(url.to_string()))
+                ),
+                TextEffect::Link(_) => escaped,
+                TextEffect::OTP if config.options.redact_one_time_codes => {
+                    sanitize_html(ONE_TIME_CODE_REDACTED).to_string()
+                }
+                TextEffect::OTP => format!("<span class=\"otp\">{escaped}</span>"),
+                TextEffect::Styles(styles) => {
+                    let classes: Vec<&str> = styles
+                        .iter()
+                        .map(|style| match style {
+                            Style::Bold => "bold",
+                            Style::Italic => "italic",
+                            Style::Strikethrough => "strikethrough",
+                            Style::Underline => "underline",
+                        })
+                        .collect();
+                    format!("<span class=\"{}\">{escaped}</span>", classes.join(" "))
+                }
+                TextEffect::Animated(animation) => {
+                    let name = match animation {
+                        Animation::Big => "big",
+                        Animation::Small => "small",
+                        Animation::Shake => "shake",
+                        Animation::Nod => "nod",
+                        Animation::Explode => "explode",
+                        Animation::Ripple => "ripple",
+                        Animation::Bloom => "bloom",
+                        Animation::Jitter => "jitter",
+                        Animation::Unknown(_) => "unknown",
+                    };
+                    format!("<span class=\"animation-{name}\">{escaped}</span>")
+                }
+                TextEffect::Conversion(_) => format!("<span class=\"conversion\">{escaped}</span>"),
+            };
+            out.push_str(&rendered);
+        }
+        out
+    }
+
+    /// Render an attachment inline if the browser can show it, otherwise link to it
+    fn format_attachment(&self, attachment: Option<&Attachment>, meta: &AttachmentMeta) -> String {
+        let config = self.config;
+        let Some(attachment) = attachment else {
+            return String::from("<span class=\"missing\">Attachment missing</span>");
+        };
+        let name =
+            sanitize_html(attachment.filename().or(meta.name).unwrap_or("Attachment")).to_string();
+        if attachment.copied_path.is_none()
+            && !attachment.is_downloaded(
+                &config.options.platform,
+                &config.options.db_path,
+                config.options.attachment_root.as_deref(),
+            )
+        {
+            return format!("<span class=\"missing\">{name} (not downloaded)</span>");
+        }
+
+        let link = sanitize_html(&config.message_attachment_link(attachment)).to_string();
+        if attachment.is_sticker {
+            return format!(
+                "<img class=\"sticker\" src=\"{link}\" alt=\"{name}\" loading=\"lazy\">"
+            );
+        }
+        match attachment.mime_type() {
+            MediaType::Image(_) => {
+                format!("<img src=\"{link}\" alt=\"{name}\" loading=\"lazy\">")
+            }
+            MediaType::Video(_) => {
+                format!("<video controls src=\"{link}\" preload=\"metadata\"></video>")
+            }
+            MediaType::Audio(_) => {
+                let transcription = meta
+                    .transcription
+                    .map(|text| {
+                        format!(
+                            "<p class=\"transcription\">{}</p>",
+                            sanitize_html(&config.redact_text(text.to_string()))
+                        )
+                    })
+                    .unwrap_or_default();
+                format!("<audio controls src=\"{link}\"></audio>{transcription}")
+            }
+            _ => format!("<a href=\"{link}\">{name}</a>"),
+        }
+    }
+
+    /// Render an app balloon, like a link preview, a game, a shared document, a poll, or a payment
+    fn format_app(&self, msg: &Message, preview: Option<&Attachment>) -> String {
+        let config = self.config;
+        let db = config.db();
+
+        if let Some(summary) = msg.app_summary(db) {
+            return format!(
+                "<span class=\"detail\">{}</span>",
+                sanitize_html(&config.redact_text(summary))
+            );
+        }
+        if let Some(item) = msg.shared_item(db) {
+            return self.format_shared_item(&item);
+        }
+        if let Some(poll) = msg.poll(db) {
+            return sanitize_html(&config.redact_text(format_poll(&poll))).to_string();
+        }
+        if let Some(payment) = msg.apple_pay(db) {
+            return format!(
+                "<span class=\"card\"><span class=\"title\">{}</span><span class=\"detail\">Apple Pay</span></span>",
+                sanitize_html(&payment.to_string())
+            );
+        }
+        if msg.is_url()
+            && let Some(payload) = msg.payload_data(db)
+        {
+            let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+            if let Ok(url) = URLMessage::from_map(&payload) {
+                return self.format_link_preview(&url, preview);
+            }
+        }
+        String::from("<span class=\"detail\">App message</span>")
+    }
+
+    /// Render a link preview as a card with its thumbnail, title, and site
+    fn format_link_preview(&self, url: &URLMessage, preview: Option<&Attachment>) -> String {
+        let config = self.config;
+        let href = url.url.or(url.original_url).unwrap_or_default();
+        let image = preview
+            .map(|image| {
+                format!(
+                    "<img src=\"{}\" alt=\"\" loading=\"lazy\">",
+                    sanitize_html(&config.message_attachment_link(image))
+                )
+            })
+            .unwrap_or_default();
+        let title = url.title.unwrap_or(href);
+        let detail = url.site_name.or(url.summary).unwrap_or_default();
+        let card = format!(
+            "{image}<span class=\"title\">{}</span><span class=\"detail\">{}</span>",
+            sanitize_html(&config.redact_text(title.to_string())),
+            sanitize_html(&config.redact_text(detail.to_string()))
+        );
+        if is_safe_link(href) {
+            format!(
+                "<a class=\"card\" href=\"{}\">{card}</a>",
+                sanitize_html(&config.redact_text(href.to_string()))
+            )
+        } else {
+            format!("<span class=\"card\">{card}</span>")
+        }
+    }
+
+    /// Render an item shared from another app as a card with its title and app name
+    fn format_shared_item(&self, item: &SharedItem) -> String {
+        let config = self.config;
+        let label = if item.is_collaboration {
+            "Collaboration"
+        } else {
+            "Shared"
+        };
+        let title = item.title.as_deref().unwrap_or("Untitled");
+        let detail = match &item.app_name {
+            Some(app_name) => format!("{label} · {app_name}"),
+            None => label.to_string(),
+        };
+        let card = format!(
+            "<span class=\"title\">{}</span><span class=\"detail\">{}</span>",
+            sanitize_html(&config.redact_text(title.to_string())),
+            sanitize_html(&detail)
+        );
+        match &item.url {
+            Some(url) if is_safe_link(url) => format!(
+                "<a class=\"card\" href=\"{}\">{card}</a>",
+                sanitize_html(&config.redact_text(url.clone()))
+            ),
+            _ => format!("<span class=\"card\">{card}</span>"),
+        }
+    }
+
+    /// Render a tapback as a badge, naming who reacted and the text they reacted to
+    fn format_tapback(&self, tapback: &Message, target: &Message) -> Option<String> {
+        let Variant::Tapback(_, _, reaction) = tapback.variant() else {
+            return None;
+        };
+        let sender = self.config.who(
+            tapback.handle_id,
+            tapback.is_from_me(),
+            &tapback.destination_caller_id,
+        );
+        let mut title = format!("{reaction} by {sender}");
+        if let Some(text) = tapback.tapback_target_text(target) {
+            title.push_str(&format!(
+                ": \"{}\"",
+                self.config.redact_text(text.to_string())
+            ));
+        }
+        Some(format!(
+            "<span class=\"tapback\" title=\"{}\">{}</span>",
+            sanitize_html(&title),
+            sanitize_html(reaction.emoji())
+        ))
+    }
+
+    /// Render a sticker placed on a bubble at the position the sender dropped it
+    fn format_sticker_overlay(&self, tapback: &Message) -> Option<String> {
+        let config = self.config;
+        let mut overlay = tapback.sticker_overlay(config.db()).ok()??;
+        copy_attachment(config, tapback, &mut overlay.attachment);
+
+        let style = overlay
+            .position
+            .map(|position| {
+                format!(
+                    " style=\"left: {:.1}%; top: {:.1}%; transform: translate(-50%, -50%) rotate({:.3}rad) scale({:.2});\"",
+                    position.x * 100.0,
+                    position.y * 100.0,
+                    position.rotation,
+                    position.scale
+                )
+            })
+            .unwrap_or_default();
+        Some(format!(
+            "<img class=\"sticker-overlay\" src=\"{}\" alt=\"Sticker\"{style}>",
+            sanitize_html(&config.message_attachment_link(&overlay.attachment))
+        ))
+    }
+
+    /// Render the history of an edited part in a collapsible section, oldest version first
+    fn format_edit_history(&self, msg: &Message, part: usize) -> Option<String> {
+        let config = self.config;
+        let edited = msg.edited_parts.as_ref()?.part(part)?;
+        if !matches!(edited.status, EditStatus::Edited) || edited.edit_history.is_empty() {
+            return None;
+        }
+
+        let versions: String = edited
+            .edit_history
+            .iter()
+            .map(|event| {
+                let time = config
+                    .options
+                    .timestamp_granularity
+                    .format(&config.time(&event.date));
+                let text = config.redact_text(event.text.clone().unwrap_or_default());
+                format!(
+                    "<li><time>{}</time> {}</li>",
+                    sanitize_html(&time),
+                    sanitize_html(&text)
+                )
+            })
+            .collect();
+        Some(format!(
+            "<details class=\"edit-history\"><summary>Edited</summary><ol>{versions}</ol></details>"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use imessage_database::{
+        message_types::{
+            collaboration::SharedItem,
+            edited::{EditStatus, EditedEvent, EditedMessage, EditedMessagePart},
+            text_effects::{Style, TextEffect},
+            url::URLMessage,
+        },
+        tables::{
+            chat::Chat,
+            messages::{Message, models::TextAttributes},
+            table::ORPHANED,
+        },
+    };
+
+    use crate::{
+        app::{
+            options::Options, reaction_placement::ReactionPlacement, runtime::Config,
+            timestamp_granularity::TimestampGranularity,
+        },
+        exporters::{exporter::Exporter, html::HTML},
+    };
+
+    /// The path of the file a conversation is written to
+    fn page_path(config: &Config, filename: &str) -> PathBuf {
+        let mut path = config.options.export_path.join(filename);
+        path.set_extension("html");
+        path
+    }
+
+    fn chat(rowid: i32, display_name: &str) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: format!("chat{rowid}"),
+            service_name: Some(String::from("iMessage")),
+            display_name: Some(String::from(display_name)),
+            style: Some(43),
+            group_id: None,
+        }
+    }
+
+    #[test]
+    fn can_write_page_per_chat() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-html-test",
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, associated_message_guid, item_type, group_action_type, group_title) VALUES
+                (1, 'A', 'Dinner?', 'iMessage', 1, 0, 642945600000000000, 0, 0, 0, NULL, 0, 0, NULL),
+                (2, 'B', 'Sure <3', 'iMessage', 0, 1, 642945660000000000, 0, 0, 0, NULL, 0, 0, NULL),
+                (3, 'C', NULL, 'iMessage', 1, 0, 642945720000000000, 0, 0, 2001, 'p:0/B', 0, 0, NULL),
+                (4, 'D', NULL, 'iMessage', 1, 0, 642945780000000000, 0, 0, 0, NULL, 2, 0, 'Dinner Club'),
+                (5, 'E', 'Lost', 'iMessage', 1, 0, 642945840000000000, 0, 0, 0, NULL, 0, 0, NULL);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice"));
+        config.chatrooms.insert(1, chat(1, "Friends"));
+        config.real_chatrooms.insert(1, 1);
+        let mut tapback = Config::fake_message();
+        tapback.guid = String::from("C");
+        tapback.handle_id = Some(1);
+        tapback.associated_message_type = Some(2001);
+        tapback.associated_message_guid = Some(String::from("p:0/B"));
+        config
+            .tapbacks
+            .insert(String::from("B"), HashMap::from([(0, vec![tapback])]));
+
+        let mut html = HTML::new(&config).unwrap();
+        html.iter_messages().unwrap();
+
+        let filename = config.filename(&config.chatrooms[&1]);
+        let page = read_to_string(page_path(&config, &filename)).unwrap();
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<h1>Friends - 1</h1>"));
+        assert!(page.ends_with("</body>\n</html>\n"));
+        // Messages are written oldest first, with the owner's on the right
+        let question = page.find("<div class=\"message received\">").unwrap();
+        let answer = page.find("<div class=\"message sent\">").unwrap();
+        let announcement = page.find("<p class=\"announcement\">").unwrap();
+        assert!(question < answer && answer < announcement);
+        assert!(page.contains("Sure &lt;3"));
+        assert!(page.contains("<svg class=\"avatar\""));
+        // The tapback is a badge on the message it targets, not a message of its own
+        assert!(page.contains(
+            "<div class=\"tapbacks\"><span class=\"tapback\" title=\"Liked by Alice: &quot;Sure &lt;3&quot;\">👍</span></div>"
+        ));
+        assert!(page.contains("Alice named the conversation &quot;Dinner Club&quot;"));
+
+        // Messages without a chat are written to their own page
+        let orphaned = read_to_string(page_path(&config, ORPHANED)).unwrap();
+        assert!(orphaned.contains("Lost"));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_group_consecutive_messages() {
+        let config = Config::fake_app(Options::fake_options());
+        let mut html = HTML::new(&config).unwrap();
+
+        // Streamed newest first, like the exporter receives them
+        let mut messages = vec![];
+        for (rowid, seconds) in [(3, 20), (2, 10), (1, 0)] {
+            let mut message = Config::fake_message();
+            message.rowid = rowid;
+            message.guid = rowid.to_string();
+            message.handle_id = Some(1);
+            message.text = Some(format!("Message {rowid}"));
+            message.date = seconds * 1_000_000_000;
+            messages.push(message);
+        }
+        for message in &messages {
+            html.write_message(message, &mut []).unwrap();
+        }
+
+        let entries = HTML::format_entries(&html.chats[&None]);
+        let headers: Vec<bool> = entries
+            .iter()
+            .map(|entry| entry.contains("<p class=\"header\">"))
+            .collect();
+        assert_eq!(headers, vec![true, false, false]);
+        assert!(entries[0].contains("Message 1"));
+    }
+
+    #[test]
+    fn can_write_day_headings_in_time_mode() {
+        let mut options = Options::fake_options();
+        options.timestamp_granularity = TimestampGranularity::Time;
+        let config = Config::fake_app(options);
+        let mut html = HTML::new(&config).unwrap();
+
+        // Streamed newest first: two messages a minute apart, then one sent a day earlier
+        let mut messages = vec![];
+        for (rowid, date) in [
+            (3, 642945660000000000),
+            (2, 642945600000000000),
+            (1, 642859200000000000),
+        ] {
+            let mut message = Config::fake_message();
+            message.rowid = rowid;
+            message.guid = rowid.to_string();
+            message.handle_id = Some(1);
+            message.text = Some(format!("Message {rowid}"));
+            message.date = date;
+            messages.push(message);
+        }
+        for message in &messages {
+            html.write_message(message, &mut []).unwrap();
+        }
+
+        let entries = HTML::format_entries(&html.chats[&None]);
+        let day = |message: &Message| {
+            format!(
+                "<h2 class=\"day\">{}</h2>\n",
+                config.time(&message.date).unwrap().format("%b %d, %Y")
+            )
+        };
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], day(&messages[2]));
+        assert!(entries[1].contains("Message 1"));
+        assert_eq!(entries[2], day(&messages[1]));
+        // The first message of each day starts a new group
+        assert!(entries[3].contains("<p class=\"header\">"));
+        assert!(entries[3].contains("Message 2"));
+        assert!(!entries[4].contains("<p class=\"header\">"));
+        assert!(!entries.concat().contains("May 17, 2021  "));
+    }
+
+    #[test]
+    fn can_style_text() {
+        let config = Config::fake_app(Options::fake_options());
+        let html = HTML::new(&config).unwrap();
+
+        let text = "Hello bold world";
+        let attributes = vec![
+            TextAttributes::new(0, 6, TextEffect::Default),
+            TextAttributes::new(
+                6,
+                10,
+                TextEffect::Styles(vec![Style::Bold, Style::Underline]),
+            ),
+            TextAttributes::new(10, 16, TextEffect::Link("https://example.com")),
+        ];
+
+        assert_eq!(
+            html.format_attributes(text, &attributes),
+            "Hello <span class=\"bold underline\">bold</span><a href=\"https://example.com\"> world</a>"
+        );
+    }
+
+    #[test]
+    fn cant_link_unsafe_schemes() {
+        let config = Config::fake_app(Options::fake_options());
+        let html = HTML::new(&config).unwrap();
+
+        let text = "Click me";
+        let attributes = vec![TextAttributes::new(
+            0,
+            8,
+            TextEffect::Link("javascript:alert(1)"),
+        )];
+        assert_eq!(html.format_attributes(text, &attributes), "Click me");
+
+        let preview = URLMessage {
+            title: Some("Click me"),
+            summary: None,
+            url: Some("javascript:alert(1)"),
+            original_url: None,
+            item_type: None,
+            images: vec![],
+            icons: vec![],
+            site_name: None,
+            image_attachment_index: None,
+            placeholder: false,
+        };
+        assert_eq!(
+            html.format_link_preview(&preview, None),
+            "<span class=\"card\"><span class=\"title\">Click me</span><span class=\"detail\"></span></span>"
+        );
+
+        let item = SharedItem {
+            title: Some(String::from("Notes")),
+            app_name: None,
+            url: Some(String::from("data:text/html,<script>alert(1)</script>")),
+            is_collaboration: false,
+        };
+        assert!(!html.format_shared_item(&item).contains("href"));
+    }
+
+    #[test]
+    fn can_place_reactions_before_message() {
+        let mut options = Options::fake_options();
+        options.reaction_placement = ReactionPlacement::Before;
+        let mut config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.guid = String::from("A");
+        message.text = Some(String::from("Hi"));
+        let mut tapback = Config::fake_message();
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some(String::from("p:0/A"));
+        config
+            .tapbacks
+            .insert(String::from("A"), HashMap::from([(0, vec![tapback])]));
+        let html = HTML::new(&config).unwrap();
+
+        let parts = html.format_parts(&message, vec![]);
+        assert!(parts.starts_with("<div class=\"tapbacks\">"));
+        assert!(parts.ends_with("Hi</div>"));
+    }
+
+    #[test]
+    fn can_show_edit_history() {
+        let config = Config::fake_app(Options::fake_options());
+        let html = HTML::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.date_edited = 674526582885055488;
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![
+                    EditedEvent {
+                        date: 674526582885055488,
+                        text: Some(String::from("Hello wrld")),
+                        components: None,
+                        guid: None,
+                    },
+                    EditedEvent {
+                        date: 674526592885055488,
+                        text: Some(String::from("Hello world")),
+                        components: None,
+                        guid: None,
+                    },
+                ],
+            }],
+        });
+
+        let history = html.format_edit_history(&message, 0).unwrap();
+        assert!(history.starts_with(
+            "<details class=\"edit-history\"><summary>Edited</summary><ol><li><time>"
+        ));
+        assert!(history.contains("</time> Hello wrld</li>"));
+        assert!(history.contains("</time> Hello world</li>"));
+        assert_eq!(html.format_edit_history(&message, 1), None);
+    }
+}
@@ -0,0 +1,519 @@
+/*!
+ Writes every message to `messages.jsonl` as newline-delimited JSON, one record per line.
+
+ ```json
+ {"type": "message", "guid": "0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", "rowid": 41, "chat_id": 1, "date": "2021-05-17T12:00:00-07:00", "sender": "+15558675309", "is_from_me": false, "service": "iMessage", "text": "Look! \u{FFFC}", "body": [{"type": "text", "text": "Look! "}, {"type": "attachment", "name": "IMG_0001.jpeg", "transcription": null}], "tapbacks": [{"sender": "Me", "part": 0, "reaction": "Loved", "action": "Added"}], "edits": [], "attachments": ["IMG_0001.jpeg"]}
+ ```
+
+ `type` is `message` for normal messages, `unsent` for messages whose every part was unsent, and `announcement` for
+ group actions, pins, and other events shown in the middle of a conversation. Tapbacks are written on the message
+ they react to instead of as their own records. `body` lists the message's components in display order: `text`,
+ `attachment`, `app` for app balloons, and `retracted` for unsent parts. `edits` holds the history of each edited
+ part, and `attachments` the name of each attachment. Messages are written newest first; with `--max-file-size`,
+ records are split across numbered volumes like `messages-2.jsonl`. With `--since-rowid`, new messages are instead
+ written in ascending `ROWID` order after the records already in the file.
+
+ With `--merge-db-path`, the messages from each extra database are merged with the export database's and written
+ oldest first in a single timeline instead. Because `ROWID`s differ between databases, merged records identify the
+ chat by its canonical identifier and the sender by their handle, and note the index of the `source` database:
+
+ ```json
+ {"type": "message", "guid": "0355C6E1-D0C8-4212-AA87-DD8AE4FD1203", "source": 1, "chat": "+15558675309", "date": "2021-05-17T12:00:00-07:00", "sender": "+15558675309", "is_from_me": false, "service": "iMessage", "text": "Look!"}
+ ```
+*/
+
+use std::path::PathBuf;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config, sanitizers::json_string},
+    exporters::{
+        exporter::{Exporter, format_export_text, redacted_part, redacted_text},
+        message_json::{date, edit_history, string},
+        volume::VolumeWriter,
+    },
+};
+
+use imessage_database::{
+    message_types::variants::Variant,
+    tables::{
+        attachment::Attachment,
+        messages::{Message, models::BubbleComponent},
+        table::{AttributedBody, get_connection},
+    },
+    util::merge::{MergedMessage, MergedMessages},
+};
+use rusqlite::Connection;
+
+/// The name of the export file created in the export directory
+pub const JSON_EXPORT_FILE: &str = "messages.jsonl";
+
+// Named like the `TXT` exporter
+#[allow(clippy::upper_case_acronyms)]
+pub struct JSON<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the export we are writing to
+    pub path: PathBuf,
+    /// The export volumes, opened once the export starts
+    file: Option<VolumeWriter>,
+    /// The number of records written so far
+    records: usize,
+}
+
+impl<'a> Exporter<'a> for JSON<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(JSON {
+            config,
+            path: config.options.export_path.join(JSON_EXPORT_FILE),
+            file: None,
+            records: 0,
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn start(&mut self) -> Result<(), RuntimeError> {
+        self.file = Some(self.config.open_volumes(&self.path, "")?);
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        // Tapbacks are written with the message they target
+        if msg.is_tapback() || !self.config.matches_text(msg) {
+            return Ok(());
+        }
+
+        let record = self.format_record(msg, attachments);
+        if let Some(file) = &mut self.file {
+            file.write_row(msg.rowid, &format!("{record}\n"))?;
+            self.records += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        if let Some(file) = self.file.take() {
+            file.finish()?;
+        }
+
+        eprintln!(
+            "Wrote {} message(s) to {}",
+            self.records,
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+impl JSON<'_> {
+    /// Write the messages from the export database and every `--merge-db-path` database as one timeline
+    pub fn export_merged(&mut self) -> Result<(), RuntimeError> {
+        let config = self.config;
+        let others = config
+            .options
+            .merge_db_paths
+            .iter()
+            .map(|path| get_connection(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dbs: Vec<&Connection> = std::iter::once(config.db()).chain(&others).collect();
+        let merged = MergedMessages::from_databases(&dbs, &config.options.query_context)?;
+
+        self.start()?;
+        for merged in merged.timeline() {
+            // Tapbacks cannot be matched to their targets across databases
+            if merged.message.is_tapback() || !config.matches_text(&merged.message) {
+                continue;
+            }
+            let record = self.format_merged_record(merged);
+            if let Some(file) = &mut self.file {
+                file.write_message(&format!("{record}\n"))?;
+                self.records += 1;
+            }
+        }
+        self.finish()
+    }
+
+    /// Build the JSON object describing a message read from one of several merged databases, on a single line
+    fn format_merged_record(&self, merged: &MergedMessage) -> String {
+        let config = self.config;
+        let msg = &merged.message;
+
+        let kind = if msg.is_fully_unsent() {
+            "unsent"
+        } else if msg.is_announcement() {
+            "announcement"
+        } else {
+            "message"
+        };
+        let sender = if msg.is_from_me {
+            Some(config.who(None, true, &msg.destination_caller_id))
+        } else {
+            merged.sender.as_deref()
+        };
+        let sender = sender.map(|sender| config.redact_text(sender.to_string()));
+        let chat = merged
+            .chat
+            .as_ref()
+            .map(|chat| config.redact_text(chat.clone()));
+
+        format!(
+            "{{\"type\": {}, \"guid\": {}, \"source\": {}, \"chat\": {}, \"date\": {}, \"sender\": {}, \"is_from_me\": {}, \"service\": {}, \"text\": {}}}",
+            json_string(kind),
+            json_string(&msg.guid),
+            merged.source,
+            string(chat.as_deref()),
+            date(config, msg.date),
+            string(sender.as_deref()),
+            msg.is_from_me,
+            string(msg.service.as_deref()),
+            string(redacted_text(config, msg).as_deref())
+        )
+    }
+
+    /// Build the JSON object describing a message, on a single line
+    fn format_record(&self, msg: &Message, attachments: &[Attachment]) -> String {
+        let config = self.config;
+
+        let kind = if msg.is_fully_unsent() {
+            "unsent"
+        } else if msg.is_announcement() {
+            "announcement"
+        } else {
+            "message"
+        };
+        let attachments: Vec<String> = attachments
+            .iter()
+            .map(|attachment| string(attachment.filename()))
+            .collect();
+
+        format!(
+            "{{\"type\": {}, \"guid\": {}, \"rowid\": {}, \"chat_id\": {}, \"date\": {}, \"sender\": {}, \"is_from_me\": {}, \"service\": {}, \"text\": {}, \"body\": [{}], \"tapbacks\": [{}], \"edits\": [{}], \"attachments\": [{}]}}",
+            json_string(kind),
+            json_string(&msg.guid),
+            msg.rowid,
+            msg.chat_id
+                .or(msg.deleted_from)
+                .map_or_else(|| String::from("null"), |chat_id| chat_id.to_string()),
+            date(config, msg.date),
+            json_string(config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)),
+            msg.is_from_me(),
+            string(msg.service.as_deref()),
+            string(format_export_text(config, msg).as_deref()),
+            self.format_body(msg).join(", "),
+            self.format_tapbacks(msg).join(", "),
+            self.format_edits(msg).join(", "),
+            attachments.join(", ")
+        )
+    }
+
+    /// Describe each component of the message body, in display order
+    fn format_body(&self, msg: &Message) -> Vec<String> {
+        let config = self.config;
+        let text = msg.text.as_deref().unwrap_or_default();
+        msg.body()
+            .iter()
+            .map(|component| match component {
+                BubbleComponent::Text(attributes) => {
+                    let part = redacted_part(config, text, attributes);
+                    format!(
+                        "{{\"type\": \"text\", \"text\": {}}}",
+                        string(part.as_deref())
+                    )
+                }
+                BubbleComponent::Attachment(meta) => format!(
+                    "{{\"type\": \"attachment\", \"name\": {}, \"transcription\": {}}}",
+                    string(meta.name),
+                    string(
+                        meta.transcription
+                            .map(|text| config.redact_text(text.to_string()))
+                            .as_deref()
+                    )
+                ),
+                BubbleComponent::App => String::from("{\"type\": \"app\"}"),
+                BubbleComponent::Retracted => String::from("{\"type\": \"retracted\"}"),
+            })
+            .collect()
+    }
+
+    /// Describe the tapbacks applied to each part of the message, oldest first
+    fn format_tapbacks(&self, msg: &Message) -> Vec<String> {
+        let config = self.config;
+        let mut tapbacks: Vec<(&usize, &Message)> = config
+            .tapbacks
+            .get(&msg.guid)
+            .iter()
+            .flat_map(|parts| parts.iter())
+            .flat_map(|(part, tapbacks)| tapbacks.iter().map(move |tapback| (part, tapback)))
+            .collect();
+        tapbacks.sort_by_key(|(part, tapback)| (**part, tapback.date));
+
+        tapbacks
+            .iter()
+            .filter_map(|(part, tapback)| match tapback.variant() {
+                Variant::Tapback(_, action, reaction) => Some(format!(
+                    "{{\"sender\": {}, \"part\": {part}, \"reaction\": {}, \"action\": {}}}",
+                    json_string(config.who(
+                        tapback.handle_id,
+                        tapback.is_from_me(),
+                        &tapback.destination_caller_id
+                    )),
+                    json_string(&reaction.to_string()),
+                    json_string(&format!("{action:?}"))
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Describe the history of each edited or unsent part of the message
+    fn format_edits(&self, msg: &Message) -> Vec<String> {
+        edit_history(self.config, msg)
+            .into_iter()
+            .map(|edited| {
+                let history: Vec<String> = edited
+                    .versions
+                    .iter()
+                    .map(|(date, text)| format!("{{\"date\": {date}, \"text\": {text}}}"))
+                    .collect();
+                format!(
+                    "{{\"part\": {}, \"status\": {}, \"history\": [{}]}}",
+                    edited.part,
+                    edited.status,
+                    history.join(", ")
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{read_to_string, remove_dir_all},
+    };
+
+    use imessage_database::message_types::edited::{
+        EditStatus, EditedEvent, EditedMessage, EditedMessagePart,
+    };
+    use serde_json::{Value, json};
+
+    use crate::{
+        app::{options::Options, runtime::Config},
+        exporters::{
+            exporter::Exporter,
+            json::{JSON, JSON_EXPORT_FILE},
+        },
+    };
+
+    #[test]
+    fn can_write_record_per_message() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-json-test",
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, associated_message_guid, item_type, group_action_type, group_title) VALUES
+                (1, 'A', 'Look! \u{FFFC}', 'iMessage', 1, 0, 642945600000000000, 0, 0, 0, NULL, 0, 0, NULL),
+                (2, 'B', NULL, 'iMessage', 0, 1, 642945660000000000, 0, 0, 2000, 'p:0/A', 0, 0, NULL),
+                (3, 'C', NULL, 'iMessage', 1, 0, 642945720000000000, 0, 0, 0, NULL, 2, 0, 'Dinner Club');
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/IMG_0001.jpeg', 'image/jpeg', 'IMG_0001.jpeg', 100);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);
+            ",
+        );
+        config.participants.insert(1, String::from("+15558675309"));
+        let mut tapback = Config::fake_message();
+        tapback.guid = String::from("B");
+        tapback.is_from_me = true;
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some(String::from("p:0/A"));
+        config
+            .tapbacks
+            .insert(String::from("A"), HashMap::from([(0, vec![tapback])]));
+
+        let mut json = JSON::new(&config).unwrap();
+        json.iter_messages().unwrap();
+
+        let contents = read_to_string(config.options.export_path.join(JSON_EXPORT_FILE)).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        // The tapback is written on the message it targets, and messages are newest first
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "{\"type\": \"announcement\", \"guid\": \"C\", \"rowid\": 3, \"chat_id\": 1, "
+        ));
+        assert!(lines[1].starts_with(
+            "{\"type\": \"message\", \"guid\": \"A\", \"rowid\": 1, \"chat_id\": 1, "
+        ));
+        assert!(lines[1].contains(
+            "\"sender\": \"+15558675309\", \"is_from_me\": false, \"service\": \"iMessage\", "
+        ));
+        assert!(lines[1].contains(", \"body\": [{\"type\": \"text\", \"text\": \"Look! \"}, {\"type\": \"attachment\", \"name\": null, \"transcription\": null}], "));
+        assert!(lines[1].ends_with(", \"tapbacks\": [{\"sender\": \"Me\", \"part\": 0, \"reaction\": \"Loved\", \"action\": \"Added\"}], \"edits\": [], \"attachments\": [\"IMG_0001.jpeg\"]}"));
+
+        // Every line is a complete JSON object
+        let records: Vec<Value> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[0]["type"], "announcement");
+        assert_eq!(records[1]["rowid"], 1);
+        assert_eq!(
+            records[1]["tapbacks"],
+            json!([{ "sender": "Me", "part": 0, "reaction": "Loved", "action": "Added" }])
+        );
+        assert_eq!(records[1]["attachments"], json!(["IMG_0001.jpeg"]));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_merge_databases() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-json-merge-test",
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier, service_name, style) VALUES (1, 'iMessage;-;+15558675309', '+15558675309', 'iMessage', 45);
+            INSERT INTO handle (ROWID, id, service) VALUES (1, '+15558675309', 'iMessage');
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, item_type, group_action_type) VALUES
+                (1, 'A', 'Hello', 'iMessage', 1, 0, 642945600000000000, 0, 0, 0, 0, 0),
+                (2, 'B', 'Hi!', 'iMessage', 0, 1, 642945660000000000, 0, 0, 0, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2);
+            "
+        );
+
+        // An older backup that still has a message deleted since, with different `ROWID`s
+        let older = Config::fake_db(&config.options);
+        older.execute_batch(
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier, service_name, style) VALUES (3, 'iMessage;-;+15558675309', '+15558675309', 'iMessage', 45);
+            INSERT INTO handle (ROWID, id, service) VALUES (5, '+15558675309', 'iMessage');
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, item_type, group_action_type) VALUES
+                (10, 'A', 'Hello', 'iMessage', 5, 0, 642945600000000000, 0, 0, 0, 0, 0),
+                (11, 'OLD', 'Deleted since', 'iMessage', 5, 0, 642945500000000000, 0, 0, 0, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (3, 10), (3, 11);
+            ",
+        )
+        .unwrap();
+        let older_path = config.options.export_path.join("older.db");
+        older
+            .execute("VACUUM INTO ?1", [older_path.to_str().unwrap()])
+            .unwrap();
+        config.options.merge_db_paths = vec![older_path];
+
+        let mut json = JSON::new(&config).unwrap();
+        json.export_merged().unwrap();
+
+        let contents = read_to_string(config.options.export_path.join(JSON_EXPORT_FILE)).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        // `A` is in both databases, and the merged timeline is oldest first
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with(
+            "{\"type\": \"message\", \"guid\": \"OLD\", \"source\": 1, \"chat\": \"+15558675309\", "
+        ));
+        assert!(lines[0].ends_with(
+            ", \"sender\": \"+15558675309\", \"is_from_me\": false, \"service\": \"iMessage\", \"text\": \"Deleted since\"}"
+        ));
+        assert!(lines[1].starts_with("{\"type\": \"message\", \"guid\": \"A\", \"source\": 0, "));
+        assert!(lines[2].starts_with("{\"type\": \"message\", \"guid\": \"B\", \"source\": 0, "));
+        assert!(lines[2].contains(", \"sender\": \"Me\", \"is_from_me\": true, "));
+
+        let records: Vec<Value> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[0]["text"], "Deleted since");
+        assert_eq!(records[2]["is_from_me"], true);
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_mark_unsent_messages() {
+        let config = Config::fake_app(Options::fake_options());
+        let json = JSON::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.guid = String::from("UNSENT");
+        message.date_edited = 674526582885055488;
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Unsent,
+                edit_history: vec![],
+            }],
+        });
+
+        let record = json.format_record(&message, &[]);
+
+        assert!(record.starts_with("{\"type\": \"unsent\", \"guid\": \"UNSENT\", "));
+        assert!(record.contains(", \"text\": null, "));
+        assert!(
+            record.contains(
+                ", \"edits\": [{\"part\": 0, \"status\": \"Unsent\", \"history\": []}], "
+            )
+        );
+
+        let record: Value = serde_json::from_str(&record).unwrap();
+        assert_eq!(record["text"], Value::Null);
+    }
+
+    #[test]
+    fn can_redact_body_and_edits() {
+        let mut options = Options::fake_options();
+        options.redact_identities = true;
+        let config = Config::fake_app(options);
+        let json = JSON::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.text = Some(String::from("Call me at +1 555 867 5309"));
+        message.date_edited = 674526582885055488;
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![
+                    EditedEvent {
+                        date: 674526582885055488,
+                        text: Some(String::from("Call me at (555) 867-5309")),
+                        components: None,
+                        guid: None,
+                    },
+                    EditedEvent {
+                        date: 674526582885055489,
+                        text: Some(String::from("Call me at +1 555 867 5309")),
+                        components: None,
+                        guid: None,
+                    },
+                ],
+            }],
+        });
+
+        let record = json.format_record(&message, &[]);
+
+        assert!(!record.contains("867"));
+        assert!(record.contains(", \"text\": \"Call me at [redacted]\", "));
+        assert!(
+            record.contains(
+                ", \"body\": [{\"type\": \"text\", \"text\": \"Call me at [redacted]\"}], "
+            )
+        );
+        assert!(record.contains("\"status\": \"Edited\", \"history\": [{\"date\": "));
+        assert!(record.contains(", \"text\": \"Call me at [redacted]\"}, {\"date\": "));
+
+        let record: Value = serde_json::from_str(&record).unwrap();
+        let history = record["edits"][0]["history"].as_array().unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(
+            history
+                .iter()
+                .all(|event| event["text"] == "Call me at [redacted]")
+        );
+    }
+}
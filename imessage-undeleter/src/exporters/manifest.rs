@@ -0,0 +1,371 @@
+/*!
+ Writes a catalog of every attachment in the database to `media_manifest.csv`.
+
+ Each row describes one attachment, so media can be indexed without opening the files:
+
+ ```csv
+ path,media_type,size_bytes,date,sender,chat,date_taken,width,height,message_rowid,attachment_rowid
+ /Users/me/Library/Messages/Attachments/3d/13/ABC/IMG_0001.jpeg,image/jpeg,2048,2021-05-17T12:00:00+00:00,Alice,Family - 1,2021-05-16T09:30:00,4032,3024,41,7
+ ```
+
+ With `--max-file-size`, the manifest is split into numbered volumes like `media_manifest-2.csv`, each with its own
+ header row. The attachments of one message are always written to the same volume. Messages are written newest
+ first, except with `--since-rowid`, where the attachments of new messages are written in ascending message `ROWID`
+ order after the rows already in the manifest.
+
+ `date` is when the message that contains the attachment was sent. Attachments whose file path is unknown
+ list their transfer name instead. `date_taken`, `width`, and `height` are read from the image's EXIF data
+ and are empty when it is unavailable or the `exif` feature is disabled. `message_rowid` and `attachment_rowid` are the `ROWID`s of the message and
+ attachment in the source database.
+*/
+
+use std::path::PathBuf;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::{csv::format_row, exporter::Exporter, volume::VolumeWriter},
+};
+
+use imessage_database::tables::{attachment::Attachment, messages::Message, table::ORPHANED};
+
+/// The name of the manifest file created in the export directory
+pub const MANIFEST_EXPORT_FILE: &str = "media_manifest.csv";
+
+/// The header row of the manifest
+const MANIFEST_HEADER: &str = "path,media_type,size_bytes,date,sender,chat,date_taken,width,height,message_rowid,attachment_rowid";
+
+pub struct Manifest<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the manifest we are writing to
+    pub path: PathBuf,
+    /// The manifest volumes, opened once the export starts
+    file: Option<VolumeWriter>,
+    /// The number of attachments written so far
+    rows: usize,
+}
+
+impl<'a> Exporter<'a> for Manifest<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Manifest {
+            config,
+            path: config.options.export_path.join(MANIFEST_EXPORT_FILE),
+            file: None,
+            rows: 0,
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn start(&mut self) -> Result<(), RuntimeError> {
+        self.file = Some(
+            self.config
+                .open_volumes(&self.path, &format!("{MANIFEST_HEADER}\n"))?,
+        );
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let chat_id = msg.chat_id.or(msg.deleted_from);
+        if attachments.is_empty() {
+            return Ok(());
+        }
+
+        let mut rows = String::new();
+        for attachment in attachments.iter() {
+            rows.push_str(&format_row(&self.format_record(msg, attachment, chat_id))?);
+        }
+        if let Some(file) = &mut self.file {
+            file.write_row(msg.rowid, &rows)?;
+            self.rows += attachments.len();
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let volumes = match self.file.take() {
+            Some(file) => file.finish()?.len(),
+            None => 1,
+        };
+
+        if volumes > 1 {
+            eprintln!(
+                "Wrote {} attachment(s) to {volumes} volumes starting at {}",
+                self.rows,
+                self.path.display()
+            );
+        } else {
+            eprintln!(
+                "Wrote {} attachment(s) to {}",
+                self.rows,
+                self.path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Manifest<'_> {
+    /// Build the fields of the manifest row describing an attachment, in the order of [`MANIFEST_HEADER`]
+    fn format_record(
+        &self,
+        msg: &Message,
+        attachment: &Attachment,
+        chat_id: Option<i32>,
+    ) -> [String; 11] {
+        let config = self.config;
+
+        let resolved = config.attachment_source_path(attachment);
+        #[cfg(feature = "exif")]
+        let (date_taken, width, height) = {
+            let exif = resolved
+                .as_deref()
+                .and_then(|path| {
+                    crate::app::exif::ImageMetadata::from_attachment(
+                        attachment,
+                        std::path::Path::new(path),
+                    )
+                })
+                .unwrap_or_default();
+            (
+                exif.date_taken.unwrap_or_default(),
+                exif.width
+                    .map(|width| width.to_string())
+                    .unwrap_or_default(),
+                exif.height
+                    .map(|height| height.to_string())
+                    .unwrap_or_default(),
+            )
+        };
+        #[cfg(not(feature = "exif"))]
+        let (date_taken, width, height) = (String::new(), String::new(), String::new());
+        let path = resolved
+            .or_else(|| attachment.transfer_name.clone())
+            .unwrap_or_default();
+        let date = config
+            .time(&msg.date)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default();
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let chat = chat_id
+            .and_then(|chat_id| config.chatrooms.get(&chat_id))
+            .map_or_else(|| String::from(ORPHANED), |chat| config.chat_label(chat));
+
+        [
+            path,
+            attachment.mime_type().as_mime_type(),
+            attachment.total_bytes.to_string(),
+            date,
+            sender.to_string(),
+            chat,
+            date_taken,
+            width,
+            height,
+            msg.rowid.to_string(),
+            attachment.rowid.to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::current_dir,
+        fs::{read_to_string, remove_dir_all},
+    };
+
+    use imessage_database::tables::chat::Chat;
+
+    use crate::{
+        app::{options::Options, runtime::Config},
+        exporters::{
+            exporter::Exporter,
+            manifest::{MANIFEST_HEADER, Manifest},
+            volume::volume_path,
+        },
+    };
+
+    #[test]
+    fn can_write_row_per_attachment() {
+        let exif_fixture = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/exif/DateTimeOriginal.jpg");
+
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-manifest-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'A', '\u{FFFC}\u{FFFC}', 1, 0, 642945600000000000, 0, 0),
+                (2, 'B', 'No attachments', 0, 1, 642945660000000000, 0, 0),
+                (3, 'C', '\u{FFFC}\u{FFFC}', 0, 1, 642945720000000000, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/one.png', 'image/png', 'one.png', 100),
+                (2, 'a2', 'a2', NULL, 'video/mp4', 'two, final.mp4', 200),
+                (3, 'a3', 'a3', '/tmp/three.heic', 'image/heic', 'three.heic', 300);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1), (1, 2), (3, 3), (3, 4);
+            ",
+        );
+        config.db().execute(
+            "INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES (4, 'a4', 'a4', ?1, 'image/jpeg', 'photo.jpg', 115)",
+            [exif_fixture.to_str().unwrap()],
+        )
+        .unwrap();
+        config.participants.insert(1, String::from("Alice"));
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: String::from("+15558675309"),
+                service_name: Some(String::from("iMessage")),
+                display_name: Some(String::from("Family")),
+                style: None,
+                group_id: None,
+            },
+        );
+
+        let mut manifest = Manifest::new(&config).unwrap();
+        manifest.iter_messages().unwrap();
+
+        let contents = read_to_string(&manifest.path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        assert_eq!(lines[0], MANIFEST_HEADER);
+        assert_eq!(lines.len() - 1, 4);
+        // Dates depend on the local timezone, so only check the other fields
+        let row = |start: &str, end: &str| {
+            lines
+                .iter()
+                .any(|line| line.starts_with(start) && line.ends_with(end))
+        };
+        assert!(row(
+            "/tmp/one.png,image/png,100,",
+            ",Alice,Family - 1,,,,1,1"
+        ));
+        assert!(row(
+            "\"two, final.mp4\",video/mp4,200,",
+            ",Alice,Family - 1,,,,1,2"
+        ));
+        assert!(row(
+            "/tmp/three.heic,image/heic,300,",
+            ",Me,Family - 1,,,,3,3"
+        ));
+        #[cfg(feature = "exif")]
+        assert!(row(
+            &format!("{},image/jpeg,115,", exif_fixture.display()),
+            ",Me,Family - 1,2021-05-17T12:00:00,4032,3024,3,4"
+        ));
+        #[cfg(not(feature = "exif"))]
+        assert!(row(
+            &format!("{},image/jpeg,115,", exif_fixture.display()),
+            ",Me,Family - 1,,,,3,4"
+        ));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_redact_chat_names() {
+        let mut options = Options::fake_options();
+        options.redact_identities = true;
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-manifest-redact-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'A', '\u{FFFC}', 0, 1, 642945600000000000, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/one.png', 'image/png', 'one.png', 100);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);
+            ",
+        );
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: String::from("+15558675309"),
+                service_name: Some(String::from("iMessage")),
+                display_name: Some(String::from("Family")),
+                style: None,
+                group_id: None,
+            },
+        );
+
+        let mut manifest = Manifest::new(&config).unwrap();
+        manifest.iter_messages().unwrap();
+
+        let contents = read_to_string(&manifest.path).unwrap();
+        assert!(!contents.contains("Family"));
+        assert!(!contents.contains("5558675309"));
+        assert!(contents.lines().nth(1).unwrap().ends_with(",Chat 1,,,,1,1"));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_split_into_volumes() {
+        let mut options = Options::fake_options();
+        // Room for the header and about one row
+        options.max_file_size = Some(MANIFEST_HEADER.len() as u64 + 80);
+        let config = Config::fake_export(
+            options,
+            "imessage-undeleter-manifest-volumes-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'A', '\u{FFFC}\u{FFFC}', 1, 0, 642945600000000000, 0, 0),
+                (2, 'B', '\u{FFFC}', 0, 1, 642945660000000000, 0, 0),
+                (3, 'C', '\u{FFFC}', 0, 1, 642945720000000000, 0, 0);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/one.png', 'image/png', 'one.png', 100),
+                (2, 'a2', 'a2', '/tmp/two.png', 'image/png', 'two.png', 200),
+                (3, 'a3', 'a3', '/tmp/three.png', 'image/png', 'three.png', 300),
+                (4, 'a4', 'a4', '/tmp/four.png', 'image/png', 'four.png', 400);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1), (1, 2), (2, 3), (3, 4);
+            ",
+        );
+
+        let mut manifest = Manifest::new(&config).unwrap();
+        manifest.iter_messages().unwrap();
+
+        let volumes: Vec<String> = (1..=3)
+            .map(|volume| read_to_string(volume_path(&manifest.path, volume)).unwrap())
+            .collect();
+        assert!(!volume_path(&manifest.path, 4).exists());
+
+        // Every volume has a header, and messages are written newest first
+        assert!(
+            volumes
+                .iter()
+                .all(|volume| volume.starts_with(MANIFEST_HEADER))
+        );
+        assert!(
+            volumes[0]
+                .lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("/tmp/four.png,")
+        );
+        assert!(
+            volumes[1]
+                .lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("/tmp/three.png,")
+        );
+        // Both attachments of the first message stay together, even though they exceed the limit
+        assert_eq!(volumes[2].lines().count(), 3);
+        assert!(volumes[2].contains("/tmp/one.png,") && volumes[2].contains("/tmp/two.png,"));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
@@ -0,0 +1,588 @@
+/*!
+ Writes each conversation to a Markdown file that can be pasted into notes apps or read in any Markdown viewer.
+
+ ```md
+ **Alice**
+ > May 17, 2021 12:00:00 PM
+
+ Dinner at **8**?
+
+ ![IMG_0001.jpeg](<attachments/1/1.jpeg>)
+
+ *❤️ Me*
+ ```
+
+ Messages are written oldest first. Each one starts with its sender in bold and its timestamp in a blockquote.
+ Bold, italic, and strikethrough text keeps its formatting, links and link previews become Markdown links, and
+ attachments are embedded as images or linked relative to the export directory. With `--max-file-size`, a
+ conversation is split into numbered files that link to each other.
+*/
+
+use std::collections::BTreeMap;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config, sanitizers::sanitize_markdown},
+    exporters::exporter::{
+        Exporter, ONE_TIME_CODE_REDACTED, RECOVERED_LABEL, copy_attachment, format_export_text,
+        format_poll, format_shared_item,
+    },
+};
+
+use imessage_database::{
+    message_types::{
+        text_effects::{Style, TextEffect},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{
+            Message,
+            models::{AttachmentMeta, BubbleComponent, TextAttributes},
+        },
+        table::ORPHANED,
+    },
+    util::plist::parse_ns_keyed_archiver,
+};
+
+/// A message rendered for a conversation, kept until the conversation is written in order
+struct Entry {
+    /// The rendered message
+    text: String,
+    /// The heading for the day the message was sent, if timestamps leave the date out
+    day: Option<String>,
+}
+
+pub struct MD<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Map of deduplicated chat ID to the messages rendered for it so far; orphaned messages use `None`
+    chats: BTreeMap<Option<i32>, Vec<Entry>>,
+}
+
+impl<'a> Exporter<'a> for MD<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(MD {
+            config,
+            chats: BTreeMap::new(),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        // Tapbacks are written with the message they target
+        if msg.is_tapback() || msg.is_sticker_overlay() || !config.matches_text(msg) {
+            return Ok(());
+        }
+        let chat_id = config.conversation(msg).map(|(_, chat_id)| *chat_id);
+
+        let text = if msg.is_announcement() {
+            self.format_announcement(msg)
+        } else {
+            for attachment in attachments.iter_mut() {
+                copy_attachment(config, msg, attachment);
+            }
+            self.format_message(msg, attachments.to_vec())
+        };
+        let day = config
+            .options
+            .timestamp_granularity
+            .day_heading(&config.time(&msg.date));
+
+        self.chats
+            .entry(chat_id)
+            .or_default()
+            .push(Entry { text, day });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let chats = std::mem::take(&mut self.chats);
+        let mut files = 0;
+        for (chat_id, entries) in chats {
+            let chat = chat_id.and_then(|chat_id| self.config.chatrooms.get(&chat_id));
+            let (filename, title) = match chat {
+                Some(chat) => (self.config.filename(chat), self.config.chat_label(chat)),
+                None => (String::from(ORPHANED), String::from("Orphaned messages")),
+            };
+            let mut path = self.config.options.export_path.join(filename);
+            path.set_extension("md");
+
+            let mut file = self
+                .config
+                .open_volumes(&path, &format!("# {}\n\n", sanitize_markdown(&title)))?
+                .with_navigation(|file, label| {
+                    format!("[{label}](<{}>)\n\n", link_destination(file))
+                });
+            for message in Self::format_entries(&entries) {
+                file.write_message(&message)?;
+            }
+            files += file.finish()?.len();
+        }
+
+        eprintln!(
+            "Wrote {files} Markdown file(s) to {}",
+            self.config.options.export_path.display()
+        );
+        Ok(())
+    }
+}
+
+impl MD<'_> {
+    /// Order a conversation's messages oldest first, with a heading each time the day changes if timestamps leave
+    /// the date out
+    fn format_entries(entries: &[Entry]) -> Vec<String> {
+        let mut day = None;
+        let mut messages = Vec::with_capacity(entries.len());
+
+        // Messages are streamed newest first, so walk them backwards
+        for entry in entries.iter().rev() {
+            if entry.day.is_some() && entry.day != day {
+                day.clone_from(&entry.day);
+                messages.push(format!(
+                    "## {}\n\n",
+                    sanitize_markdown(day.as_deref().unwrap_or_default())
+                ));
+            }
+            messages.push(entry.text.clone());
+        }
+        messages
+    }
+
+    /// Render a message's sender, time, parts, and tapbacks
+    fn format_message(&self, msg: &Message, mut attachments: Vec<Attachment>) -> String {
+        let config = self.config;
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let mut out = format!(
+            "**{}**\n> {}\n\n",
+            sanitize_markdown(sender),
+            self.format_time(msg)
+        );
+
+        let preview = msg.take_link_preview_image(&mut attachments);
+        let rendered = msg.rendered_parts(attachments);
+        if let Some(mismatch) = &rendered.mismatch {
+            eprintln!("Message {}: {mismatch}", msg.guid);
+        }
+        let text = msg.text.as_deref().unwrap_or_default();
+
+        for (idx, part) in rendered.parts.iter().enumerate() {
+            let mut content = match &part.component {
+                BubbleComponent::Text(attributes) => {
+                    let content = self.format_attributes(text, attributes);
+                    if content.trim().is_empty() {
+                        continue;
+                    }
+                    content
+                }
+                BubbleComponent::Attachment(meta) => {
+                    self.format_attachment(part.attachment.as_ref(), meta)
+                }
+                BubbleComponent::App => self.format_app(msg, preview.as_ref()),
+                BubbleComponent::Retracted => {
+                    format!("*{} unsent this part*", sanitize_markdown(sender))
+                }
+            };
+            if msg.is_part_edited(idx) && !matches!(part.component, BubbleComponent::Retracted) {
+                content.push_str(" *(edited)*");
+            }
+            out.push_str(&content);
+            out.push_str("\n\n");
+        }
+
+        if let Some(tapbacks) = self.format_tapbacks(msg) {
+            out.push_str(&tapbacks);
+            out.push_str("\n\n");
+        }
+        if msg.is_deleted() {
+            out.push_str(&format!("*{}*\n\n", sanitize_markdown(RECOVERED_LABEL)));
+        }
+        out
+    }
+
+    /// Render the time a message was sent with the requested granularity
+    fn format_time(&self, msg: &Message) -> String {
+        let config = self.config;
+        config
+            .options
+            .timestamp_granularity
+            .format(&config.time(&msg.date))
+    }
+
+    /// Render an announcement, like a group action or an unsent message, as an italic line
+    fn format_announcement(&self, msg: &Message) -> String {
+        let config = self.config;
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let text = match msg.get_announcement() {
+            Some(Announcement::FullyUnsent) => format!("{sender} unsent a message"),
+            Some(Announcement::AudioMessageKept) => format!("{sender} kept an audio message"),
+            _ => format_export_text(config, msg).unwrap_or_default(),
+        };
+        format!(
+            "> {}\n\n*{}*\n\n",
+            self.format_time(msg),
+            sanitize_markdown(&text)
+        )
+    }
+
+    /// Render the ranges of a text part with their styles and links
+    fn format_attributes(&self, text: &str, attributes: &[TextAttributes]) -> String {
+        let config = self.config;
+        let mut out = String::new();
+        for attribute in attributes {
+            let Some(segment) = text.get(attribute.start..attribute.end) else {
+                continue;
+            };
+            let escaped = sanitize_markdown(&config.redact_text(segment.to_string())).to_string();
+            let rendered = match &attribute.effect {
+                TextEffect::Link(url) => format!(
+                    "[{escaped}](<{}>)",
+                    link_destination(&config.redact_text(url.to_string()))
+                ),
+                TextEffect::OTP if config.options.redact_one_time_codes => {
+                    sanitize_markdown(ONE_TIME_CODE_REDACTED).to_string()
+                }
+                TextEffect::Styles(styles) => {
+                    // Markdown has no underline, so underlined text is written plainly
+                    let marker: String = styles
+                        .iter()
+                        .filter_map(|style| match style {
+                            Style::Bold => Some("**"),
+                            Style::Italic => Some("*"),
+                            Style::Strikethrough => Some("~~"),
+                            Style::Underline => None,
+                        })
+                        .collect();
+                    emphasize(&escaped, &marker)
+                }
+                _ => escaped,
+            };
+            out.push_str(&rendered);
+        }
+        // Keep line breaks inside a part instead of joining the lines into one paragraph
+        out.replace('\n', "  \n")
+    }
+
+    /// Embed an image attachment, or link to any other kind of attachment
+    fn format_attachment(&self, attachment: Option<&Attachment>, meta: &AttachmentMeta) -> String {
+        let config = self.config;
+        let Some(attachment) = attachment else {
+            return String::from("*Attachment missing*");
+        };
+        let name = sanitize_markdown(attachment.filename().or(meta.name).unwrap_or("Attachment"))
+            .to_string();
+        if attachment.copied_path.is_none()
+            && !attachment.is_downloaded(
+                &config.options.platform,
+                &config.options.db_path,
+                config.options.attachment_root.as_deref(),
+            )
+        {
+            return format!("*{name} (not downloaded)*");
+        }
+
+        let link = link_destination(&config.message_attachment_link(attachment));
+        match attachment.mime_type() {
+            MediaType::Image(_) => format!("![{name}](<{link}>)"),
+            MediaType::Audio(_) => match meta.transcription {
+                Some(transcription) => format!(
+                    "[{name}](<{link}>)\n> {}",
+                    sanitize_markdown(&config.redact_text(transcription.to_string()))
+                ),
+                None => format!("[{name}](<{link}>)"),
+            },
+            _ => format!("[{name}](<{link}>)"),
+        }
+    }
+
+    /// Render an app balloon, like a link preview, a game, a shared document, a poll, or a payment
+    fn format_app(&self, msg: &Message, preview: Option<&Attachment>) -> String {
+        let config = self.config;
+        let db = config.db();
+
+        if let Some(summary) = msg.app_summary(db) {
+            return sanitize_markdown(&config.redact_text(summary)).to_string();
+        }
+        if let Some(item) = msg.shared_item(db) {
+            let line = config.redact_text(format_shared_item(&item));
+            return match &item.url {
+                Some(url) => format!(
+                    "[{}](<{}>)",
+                    sanitize_markdown(&line),
+                    link_destination(&config.redact_text(url.clone()))
+                ),
+                None => sanitize_markdown(&line).to_string(),
+            };
+        }
+        if let Some(poll) = msg.poll(db) {
+            return sanitize_markdown(&config.redact_text(format_poll(&poll))).to_string();
+        }
+        if let Some(payment) = msg.apple_pay(db) {
+            return sanitize_markdown(&payment.to_string()).to_string();
+        }
+        if msg.is_url()
+            && let Some(payload) = msg.payload_data(db)
+        {
+            let payload = parse_ns_keyed_archiver(&payload).unwrap_or(payload);
+            if let Ok(url) = URLMessage::from_map(&payload) {
+                return self.format_link_preview(&url, preview);
+            }
+        }
+        String::from("*App message*")
+    }
+
+    /// Render a link preview as `[title](url)`, with its thumbnail if one was sent
+    fn format_link_preview(&self, url: &URLMessage, preview: Option<&Attachment>) -> String {
+        let config = self.config;
+        let href = url.url.or(url.original_url).unwrap_or_default();
+        let title = url.title.unwrap_or(href);
+        let link = format!(
+            "[{}](<{}>)",
+            sanitize_markdown(&config.redact_text(title.to_string())),
+            link_destination(&config.redact_text(href.to_string()))
+        );
+        match preview {
+            Some(image) => format!(
+                "![](<{}>)\n{link}",
+                link_destination(&config.message_attachment_link(image))
+            ),
+            None => link,
+        }
+    }
+
+    /// Render the tapbacks on a message as an italic line, like `*❤️ Alice, 👍 Me*`
+    fn format_tapbacks(&self, msg: &Message) -> Option<String> {
+        let config = self.config;
+        let tapbacks: Vec<String> = config
+            .reactions_to(msg)
+            .iter()
+            .filter_map(|tapback| {
+                let Variant::Tapback(_, _, reaction) = tapback.variant() else {
+                    return None;
+                };
+                let sender = config.who(
+                    tapback.handle_id,
+                    tapback.is_from_me(),
+                    &tapback.destination_caller_id,
+                );
+                Some(format!(
+                    "{} {}",
+                    reaction.emoji(),
+                    sanitize_markdown(sender)
+                ))
+            })
+            .collect();
+        (!tapbacks.is_empty()).then(|| format!("*{}*", tapbacks.join(", ")))
+    }
+}
+
+/// Wrap text in an emphasis marker like `**`, keeping surrounding whitespace outside of it
+///
+/// Markdown ignores markers that are next to whitespace on the inside, so `** bold**` would not be bold.
+fn emphasize(text: &str, marker: &str) -> String {
+    let trimmed = text.trim();
+    if marker.is_empty() || trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + trimmed.len();
+    let closing: String = marker.chars().rev().collect();
+    format!(
+        "{}{marker}{trimmed}{closing}{}",
+        &text[..start],
+        &text[end..]
+    )
+}
+
+/// Escape a link destination for use inside `<` and `>`, which allows it to contain spaces
+fn link_destination(url: &str) -> String {
+    url.replace('<', "%3C")
+        .replace('>', "%3E")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use imessage_database::{
+        message_types::text_effects::{Style, TextEffect},
+        tables::{
+            chat::Chat,
+            messages::{Message, models::TextAttributes},
+            table::ORPHANED,
+        },
+    };
+
+    use crate::{
+        app::{options::Options, runtime::Config, timestamp_granularity::TimestampGranularity},
+        exporters::{
+            exporter::Exporter,
+            markdown::{MD, emphasize},
+        },
+    };
+
+    /// The path of the file a conversation is written to
+    fn file_path(config: &Config, filename: &str) -> PathBuf {
+        let mut path = config.options.export_path.join(filename);
+        path.set_extension("md");
+        path
+    }
+
+    #[test]
+    fn can_write_file_per_chat() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-markdown-test",
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, associated_message_guid, item_type, group_action_type, group_title) VALUES
+                (1, 'A', 'Dinner?', 'iMessage', 1, 0, 642945600000000000, 0, 0, 0, NULL, 0, 0, NULL),
+                (2, 'B', 'Sure *8*', 'iMessage', 0, 1, 642945660000000000, 0, 0, 0, NULL, 0, 0, NULL),
+                (3, 'C', NULL, 'iMessage', 1, 0, 642945720000000000, 0, 0, 2001, 'p:0/B', 0, 0, NULL),
+                (4, 'D', NULL, 'iMessage', 1, 0, 642945780000000000, 0, 0, 0, NULL, 2, 0, 'Dinner Club'),
+                (5, 'E', 'Lost', 'iMessage', 1, 0, 642945840000000000, 0, 0, 0, NULL, 0, 0, NULL);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice"));
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: String::from("chat1"),
+                service_name: Some(String::from("iMessage")),
+                display_name: Some(String::from("Friends")),
+                style: Some(43),
+                group_id: None,
+            },
+        );
+        config.real_chatrooms.insert(1, 1);
+        let mut tapback = Config::fake_message();
+        tapback.guid = String::from("C");
+        tapback.handle_id = Some(1);
+        tapback.associated_message_type = Some(2001);
+        tapback.associated_message_guid = Some(String::from("p:0/B"));
+        config
+            .tapbacks
+            .insert(String::from("B"), HashMap::from([(0, vec![tapback])]));
+
+        let mut md = MD::new(&config).unwrap();
+        md.iter_messages().unwrap();
+
+        let filename = config.filename(&config.chatrooms[&1]);
+        let file = read_to_string(file_path(&config, &filename)).unwrap();
+
+        assert!(file.starts_with("# Friends - 1\n\n"));
+        // Messages are written oldest first, with the sender in bold and the time in a blockquote
+        let question = file.find("**Alice**\n> ").unwrap();
+        let answer = file.find("**Me**\n> ").unwrap();
+        let announcement = file
+            .find("*Alice named the conversation \"Dinner Club\"*")
+            .unwrap();
+        assert!(question < answer && answer < announcement);
+        assert!(file.contains("Dinner?\n\n"));
+        // Text that looks like Markdown is escaped, and the tapback is written on the message it targets
+        assert!(file.contains("Sure \\*8\\*\n\n*👍 Alice*\n\n"));
+
+        // Messages without a chat are written to their own file
+        let orphaned = read_to_string(file_path(&config, ORPHANED)).unwrap();
+        assert!(orphaned.contains("Lost"));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_write_day_headings_in_time_mode() {
+        let mut options = Options::fake_options();
+        options.timestamp_granularity = TimestampGranularity::Time;
+        let config = Config::fake_app(options);
+        let mut md = MD::new(&config).unwrap();
+
+        // Streamed newest first: two messages a minute apart, then one sent a day earlier
+        let mut messages = vec![];
+        for (rowid, date) in [
+            (3, 642945660000000000),
+            (2, 642945600000000000),
+            (1, 642859200000000000),
+        ] {
+            let mut message = Config::fake_message();
+            message.rowid = rowid;
+            message.guid = rowid.to_string();
+            message.text = Some(format!("Message {rowid}"));
+            message.date = date;
+            messages.push(message);
+        }
+        for message in &messages {
+            md.write_message(message, &mut []).unwrap();
+        }
+
+        let entries = MD::format_entries(&md.chats[&None]);
+        let day = |message: &Message| {
+            format!(
+                "## {}\n\n",
+                config.time(&message.date).unwrap().format("%b %d, %Y")
+            )
+        };
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], day(&messages[2]));
+        assert!(entries[1].contains("Message 1"));
+        assert_eq!(entries[2], day(&messages[1]));
+        assert!(entries[3].contains("Message 2"));
+        assert!(entries[4].contains("Message 3"));
+
+        // Timestamps that include the date need no headings
+        let default = Config::fake_app(Options::fake_options());
+        let mut md = MD::new(&default).unwrap();
+        for message in &messages {
+            md.write_message(message, &mut []).unwrap();
+        }
+        assert_eq!(MD::format_entries(&md.chats[&None]).len(), 3);
+    }
+
+    #[test]
+    fn can_style_text() {
+        let config = Config::fake_app(Options::fake_options());
+        let md = MD::new(&config).unwrap();
+
+        let text = "Bold, italic, gone, and a link";
+        let attributes = vec![
+            TextAttributes::new(0, 4, TextEffect::Styles(vec![Style::Bold])),
+            TextAttributes::new(4, 6, TextEffect::Default),
+            TextAttributes::new(6, 12, TextEffect::Styles(vec![Style::Italic])),
+            TextAttributes::new(12, 14, TextEffect::Default),
+            TextAttributes::new(
+                14,
+                18,
+                TextEffect::Styles(vec![Style::Strikethrough, Style::Underline]),
+            ),
+            TextAttributes::new(18, 26, TextEffect::Default),
+            TextAttributes::new(26, 30, TextEffect::Link("https://example.com/a b")),
+        ];
+
+        assert_eq!(
+            md.format_attributes(text, &attributes),
+            "**Bold**, *italic*, ~~gone~~, and a [link](<https://example.com/a b>)"
+        );
+    }
+
+    #[test]
+    fn can_emphasize_around_whitespace() {
+        assert_eq!(emphasize(" bold ", "**"), " **bold** ");
+        assert_eq!(emphasize("both", "***"), "***both***");
+        assert_eq!(emphasize("  ", "**"), "  ");
+        assert_eq!(emphasize("plain", ""), "plain");
+    }
+}
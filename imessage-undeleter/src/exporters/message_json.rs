@@ -0,0 +1,414 @@
+/*!
+ Renders a single message, looked up by GUID, as a pretty-printed JSON document for bug reports.
+
+ ```json
+ {
+   "rowid": 1,
+   "guid": "0355C6E1-D0C8-4212-AA87-DD8AE4FD1203",
+   "chat_id": 1,
+   "sender": "Alice",
+   "is_from_me": false,
+   "service": "iMessage",
+   "date": "2021-05-17T12:00:00-07:00",
+   "date_delivered": null,   // `null` if the database does not record the date
+   "date_read": null,
+   "date_edited": null,
+   "text": "Hello",
+   "variant": "Normal",
+   "is_deleted": false,
+   "reply_to_guid": null,
+   "edits": [
+     { "part": 0, "status": "Edited", "history": [
+       { "date": "2021-05-17T12:00:00-07:00", "text": "Helo" }
+     ] }
+   ],
+   "attachments": [   // `sticker_source` labels stickers, like `Memoji (surprised)`, and is `null` otherwise
+     { "rowid": 1, "transfer_name": "IMG_0001.jpeg", "mime_type": "image/jpeg", "total_bytes": 2048, "is_sticker": false, "sticker_source": null, "is_downloaded": true, "path": "/Users/me/Library/Messages/Attachments/IMG_0001.jpeg",
+       "exif": { "date_taken": "2021-05-16T09:30:00", "width": 4032, "height": 3024 } }   // `null` if the image has no EXIF data or the `exif` feature is disabled
+   ],
+   "tapbacks": [
+     { "guid": "…", "sender": "Me", "part": 0, "reaction": "Loved", "action": "Added" }
+   ]
+ }
+ ```
+*/
+
+#[cfg(feature = "exif")]
+use std::path::Path;
+
+#[cfg(feature = "exif")]
+use crate::app::exif::ImageMetadata;
+use crate::{
+    app::{error::RuntimeError, runtime::Config, sanitizers::json_string},
+    exporters::exporter::{redacted_edit, redacted_text},
+};
+
+use imessage_database::{
+    message_types::variants::Variant,
+    tables::{attachment::Attachment, messages::Message},
+};
+
+/// Load the message with `guid` and render everything known about it as JSON
+pub fn message_json(config: &Config, guid: &str) -> Result<String, RuntimeError> {
+    let mut msg = Message::from_guid(guid, config.db())?;
+    let _ = msg.generate_text(config.db());
+    let attachments = Attachment::from_message(config.db(), &msg)?;
+
+    let mut out = String::from("{\n");
+    out.push_str(&format!("  \"rowid\": {},\n", msg.rowid));
+    out.push_str(&format!("  \"guid\": {},\n", json_string(&msg.guid)));
+    out.push_str(&format!(
+        "  \"chat_id\": {},\n",
+        number(msg.chat_id.or(msg.deleted_from))
+    ));
+    out.push_str(&format!(
+        "  \"sender\": {},\n",
+        json_string(config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id))
+    ));
+    out.push_str(&format!("  \"is_from_me\": {},\n", msg.is_from_me()));
+    out.push_str(&format!(
+        "  \"service\": {},\n",
+        string(msg.service.as_deref())
+    ));
+    out.push_str(&format!("  \"date\": {},\n", date(config, msg.date)));
+    out.push_str(&format!(
+        "  \"date_delivered\": {},\n",
+        date(config, msg.date_delivered)
+    ));
+    out.push_str(&format!(
+        "  \"date_read\": {},\n",
+        date(config, msg.date_read)
+    ));
+    out.push_str(&format!(
+        "  \"date_edited\": {},\n",
+        date(config, msg.date_edited)
+    ));
+    out.push_str(&format!(
+        "  \"text\": {},\n",
+        string(redacted_text(config, &msg).as_deref())
+    ));
+    out.push_str(&format!(
+        "  \"variant\": {},\n",
+        json_string(&variant_name(&msg.variant()))
+    ));
+    out.push_str(&format!("  \"is_deleted\": {},\n", msg.is_deleted()));
+    out.push_str(&format!(
+        "  \"reply_to_guid\": {},\n",
+        string(msg.thread_originator_guid.as_deref())
+    ));
+
+    let edits: Vec<String> = edit_history(config, &msg)
+        .into_iter()
+        .map(|edited| {
+            let history: Vec<String> = edited
+                .versions
+                .iter()
+                .map(|(date, text)| format!("      {{ \"date\": {date}, \"text\": {text} }}"))
+                .collect();
+            format!(
+                "    {{ \"part\": {}, \"status\": {}, \"history\": {} }}",
+                edited.part,
+                edited.status,
+                block("    ", '[', ']', &history)
+            )
+        })
+        .collect();
+    out.push_str(&format!(
+        "  \"edits\": {},\n",
+        block("  ", '[', ']', &edits)
+    ));
+
+    let attachments: Vec<String> = attachments
+        .iter()
+        .map(|attachment| {
+            let path = config.attachment_source_path(attachment);
+            #[cfg(feature = "exif")]
+            let exif = path
+                .as_deref()
+                .and_then(|path| ImageMetadata::from_attachment(attachment, Path::new(path)))
+                .map_or_else(
+                    || String::from("null"),
+                    |exif| {
+                        format!(
+                            "{{ \"date_taken\": {}, \"width\": {}, \"height\": {} }}",
+                            string(exif.date_taken.as_deref()),
+                            number(exif.width),
+                            number(exif.height)
+                        )
+                    },
+                );
+            #[cfg(not(feature = "exif"))]
+            let exif = "null";
+            format!(
+                "    {{ \"rowid\": {}, \"transfer_name\": {}, \"mime_type\": {}, \"total_bytes\": {}, \"is_sticker\": {}, \"sticker_source\": {}, \"is_downloaded\": {}, \"path\": {}, \"exif\": {exif} }}",
+                attachment.rowid,
+                string(attachment.transfer_name.as_deref()),
+                string(attachment.mime_type.as_deref()),
+                attachment.total_bytes,
+                attachment.is_sticker,
+                string(
+                    attachment
+                        .is_sticker
+                        .then(|| attachment.get_sticker_source(config.db()))
+                        .flatten()
+                        .map(|source| source.to_string())
+                        .as_deref()
+                ),
+                attachment.is_downloaded(
+                    &config.options.platform,
+                    &config.options.db_path,
+                    config.options.attachment_root.as_deref(),
+                ),
+                string(path.as_deref())
+            )
+        })
+        .collect();
+    out.push_str(&format!(
+        "  \"attachments\": {},\n",
+        block("  ", '[', ']', &attachments)
+    ));
+
+    let mut tapbacks: Vec<(&usize, &Message)> = config
+        .tapbacks
+        .get(&msg.guid)
+        .iter()
+        .flat_map(|parts| parts.iter())
+        .flat_map(|(part, tapbacks)| tapbacks.iter().map(move |tapback| (part, tapback)))
+        .collect();
+    tapbacks.sort_by_key(|(part, tapback)| (**part, tapback.date));
+    let tapbacks: Vec<String> = tapbacks
+        .iter()
+        .filter_map(|(part, tapback)| match tapback.variant() {
+            Variant::Tapback(_, action, reaction) => Some(format!(
+                "    {{ \"guid\": {}, \"sender\": {}, \"part\": {part}, \"reaction\": {}, \"action\": {} }}",
+                json_string(&tapback.guid),
+                json_string(config.who(
+                    tapback.handle_id,
+                    tapback.is_from_me(),
+                    &tapback.destination_caller_id
+                )),
+                json_string(&reaction.to_string()),
+                json_string(&format!("{action:?}"))
+            )),
+            _ => None,
+        })
+        .collect();
+    out.push_str(&format!(
+        "  \"tapbacks\": {}\n}}\n",
+        block("  ", '[', ']', &tapbacks)
+    ));
+
+    Ok(out)
+}
+
+/// The history of an edited or unsent message part, ready to be written as JSON
+pub(crate) struct EditHistory {
+    /// The index of the part in the message body
+    pub part: usize,
+    /// How the part was changed, as a JSON string
+    pub status: String,
+    /// The date and text of each version of the part, oldest first, as JSON values
+    pub versions: Vec<(String, String)>,
+}
+
+/// Describe the history of each edited or unsent part of a message, redacting each version's text like the
+/// message's own text
+pub(crate) fn edit_history(config: &Config, msg: &Message) -> Vec<EditHistory> {
+    msg.edited_parts
+        .iter()
+        .flat_map(|edited| edited.parts.iter().enumerate())
+        .map(|(part, edited)| EditHistory {
+            part,
+            status: json_string(&format!("{:?}", edited.status)),
+            versions: edited
+                .edit_history
+                .iter()
+                .map(|event| {
+                    (
+                        date(config, event.date),
+                        string(redacted_edit(config, event).as_deref()),
+                    )
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The name of a variant without the data it carries, like `Normal` or `App`
+fn variant_name(variant: &Variant) -> String {
+    let debug = format!("{variant:?}");
+    match debug.find(['(', ' ', '{']) {
+        Some(end) => debug[..end].to_string(),
+        None => debug,
+    }
+}
+
+/// Format a message timestamp as an RFC 3339 JSON string, or `null` if it is unset
+pub(crate) fn date(config: &Config, stamp: i64) -> String {
+    if stamp == 0 {
+        return String::from("null");
+    }
+    config.time(&stamp).map_or_else(
+        |_| String::from("null"),
+        |date| json_string(&date.to_rfc3339()),
+    )
+}
+
+/// Format an optional string as a JSON string or `null`
+pub(crate) fn string(value: Option<&str>) -> String {
+    value.map_or_else(|| String::from("null"), json_string)
+}
+
+/// Format an optional number as a JSON number or `null`
+fn number<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| String::from("null"), |value| value.to_string())
+}
+
+/// Wrap indented items in a JSON array or object, one item per line, closing at `indent`
+fn block(indent: &str, open: char, close: char, items: &[String]) -> String {
+    if items.is_empty() {
+        return format!("{open}{close}");
+    }
+    format!("{open}\n{}\n{indent}{close}", items.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{Value, json};
+
+    use crate::{
+        app::{options::Options, runtime::Config},
+        exporters::message_json::message_json,
+    };
+
+    /// The only message in the test database
+    const GUID: &str = "0355C6E1-D0C8-4212-AA87-DD8AE4FD1203";
+
+    #[test]
+    fn can_export_known_guid() {
+        let config = Config::fake_app(Options::fake_options());
+
+        let json = message_json(&config, GUID).unwrap();
+
+        // Record IDs match the source database
+        let (message_rowid, attachment_rowid): (i32, i32) = config
+            .db()
+            .query_row(
+                "SELECT message_id, attachment_id FROM message_attachment_join WHERE message_id = (SELECT ROWID FROM message WHERE guid = ?1)",
+                [GUID],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(json.starts_with(&format!("{{\n  \"rowid\": {message_rowid},\n")));
+        assert!(json.contains(&format!("{{ \"rowid\": {attachment_rowid}, ")));
+        assert!(json.starts_with("{\n  \"rowid\": 452567,\n"));
+        assert!(json.contains(&format!("  \"guid\": \"{GUID}\",\n")));
+        assert!(json.contains("  \"service\": \"iMessage\",\n"));
+        assert!(json.contains(
+            "  \"text\": \"I’m going to try to eat as quick as possible and then come over\",\n"
+        ));
+        assert!(json.contains("  \"variant\": \"Normal\",\n"));
+        assert!(json.contains("  \"edits\": [],\n"));
+        assert!(json.contains("{ \"rowid\": 1, \"transfer_name\": \"289D9E83-33EE-4153-AF13-43DB31792C6F.heic\", \"mime_type\": \"image/heic\", \"total_bytes\": 24560, \"is_sticker\": true, \"sticker_source\": \"com.freepeople.iosapp-production.stickers\", \"is_downloaded\": false, "));
+        // The attachment file is not on disk, so there is no EXIF data
+        assert!(json.contains(", \"exif\": null }"));
+        assert!(json.ends_with("  \"tapbacks\": []\n}\n"));
+
+        // The output is a single JSON document
+        let record: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(record["rowid"], message_rowid);
+        assert_eq!(record["attachments"][0]["rowid"], attachment_rowid);
+        assert_eq!(record["attachments"][0]["exif"], Value::Null);
+    }
+
+    #[test]
+    fn can_export_source_rowids() {
+        let options = Options::fake_options();
+        let db = Config::fake_db(&options);
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (4242, 'KNOWN', 'Hello', 'iMessage', 0, 1, 642945600000000000, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 4242);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (77, 'a77', 'a77', '/missing/IMG_0001.jpeg', 'image/jpeg', 'IMG_0001.jpeg', 100);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (4242, 77);
+            ",
+        )
+        .unwrap();
+        let mut config = Config::fake_app(options);
+        config.db = Some(db);
+
+        let json = message_json(&config, "KNOWN").unwrap();
+
+        let rowid: i32 = config
+            .db()
+            .query_row(
+                "SELECT ROWID FROM message WHERE guid = 'KNOWN'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rowid, 4242);
+        assert!(json.starts_with("{\n  \"rowid\": 4242,\n  \"guid\": \"KNOWN\",\n"));
+        assert!(json.contains("{ \"rowid\": 77, \"transfer_name\": \"IMG_0001.jpeg\", "));
+    }
+
+    #[test]
+    fn can_redact_text() {
+        let mut options = Options::fake_options();
+        options.redact_identities = true;
+        let db = Config::fake_db(&options);
+        db.execute_batch(
+            "
+            INSERT INTO message (ROWID, guid, text, service, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (4242, 'KNOWN', 'Call me at +1 555 867 5309', 'iMessage', 0, 1, 642945600000000000, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 4242);
+            ",
+        )
+        .unwrap();
+        let mut config = Config::fake_app(options);
+        config.db = Some(db);
+
+        let json = message_json(&config, "KNOWN").unwrap();
+
+        assert!(!json.contains("867"));
+        assert!(json.contains("  \"text\": \"Call me at [redacted]\",\n"));
+
+        let record: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(record["text"], "Call me at [redacted]");
+    }
+
+    #[test]
+    fn can_include_tapbacks() {
+        let mut config = Config::fake_app(Options::fake_options());
+        let mut tapback = Config::fake_message();
+        tapback.guid = String::from("TAPBACK");
+        tapback.is_from_me = true;
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some(format!("p:0/{GUID}"));
+        config
+            .tapbacks
+            .insert(GUID.to_string(), HashMap::from([(0, vec![tapback])]));
+
+        let json = message_json(&config, GUID).unwrap();
+
+        assert!(json.contains(
+            "  \"tapbacks\": [\n    { \"guid\": \"TAPBACK\", \"sender\": \"Me\", \"part\": 0, \"reaction\": \"Loved\", \"action\": \"Added\" }\n  ]\n"
+        ));
+
+        let record: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            record["tapbacks"],
+            json!([{ "guid": "TAPBACK", "sender": "Me", "part": 0, "reaction": "Loved", "action": "Added" }])
+        );
+    }
+
+    #[test]
+    fn cant_export_missing_guid() {
+        let config = Config::fake_app(Options::fake_options());
+        assert!(message_json(&config, "missing").is_err());
+    }
+}
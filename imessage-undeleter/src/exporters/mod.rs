@@ -1,2 +1,15 @@
+pub mod avatar;
+pub mod contacts;
+pub mod csv;
 pub mod exporter;
+pub mod html;
+pub mod json;
+pub mod manifest;
+pub mod markdown;
+pub mod message_json;
+pub mod on_this_day;
+pub mod sqlite;
+pub mod stats;
+pub mod telegram;
 pub mod txt;
+pub mod volume;
@@ -0,0 +1,464 @@
+/*!
+ Writes a plain text report of the messages sent on a single calendar day across every year.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use chrono::{Datelike, Local, NaiveDate, Utc};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::exporter::{
+        Exporter, RECOVERED_LABEL, format_pin_event, format_poll, format_receipt,
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Variant,
+    tables::{messages::Message, table::Table},
+    util::{
+        dates::{TIMESTAMP_FACTOR, format},
+        grouping::SenderGroups,
+    },
+};
+use rusqlite::params_from_iter;
+
+pub struct OnThisDay<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// The calendar day the report covers, as `(month, day)`
+    pub calendar_day: (u32, u32),
+    /// Path to the report we are writing to
+    pub path: PathBuf,
+}
+
+impl<'a> Exporter<'a> for OnThisDay<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let (month, day) =
+            config
+                .options
+                .query_context
+                .on_this_day
+                .ok_or(RuntimeError::InvalidOptions(String::from(
+                    "No calendar day selected for the report!",
+                )))?;
+
+        let mut path = config.options.export_path.clone();
+        path.push(format!("on_this_day_{month:02}-{day:02}"));
+        path.set_extension("txt");
+
+        Ok(OnThisDay {
+            config,
+            calendar_day: (month, day),
+            path,
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        // Map of year to the formatted messages sent on that day
+        let mut years: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+        // Messages recovered from Recently Deleted, if they are listed separately
+        let mut recovered: Vec<String> = vec![];
+        let mut current_message_row = -1;
+        // Messages are streamed newest first, so a message that starts a group is the latest in its run
+        let mut runs = SenderGroups::new(i64::MAX);
+
+        let mut statement = Message::stream_rows(config.db(), &config.options.query_context)?;
+        let messages = statement
+            .query_map(
+                params_from_iter(config.options.query_context.predicate_params()),
+                |row| Ok(Message::from_row(row)),
+            )
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row || msg.is_tapback() {
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            let _ = msg.generate_text(config.db());
+            if !config.matches_text(&msg) {
+                continue;
+            }
+            let Ok(date) = config.time(&msg.date) else {
+                continue;
+            };
+
+            // Keep each message and its reactions together so they stay in order when the year is reversed
+            let reactions = config
+                .reactions_to(&msg)
+                .into_iter()
+                .filter_map(|tapback| self.format_reaction(tapback))
+                .collect();
+            let mut line = self.format_line(&msg);
+            let latest_in_run = runs.starts_group(&msg);
+            if let Some(receipt) = format_receipt(&msg, config)
+                .filter(|_| latest_in_run && config.options.show_receipts)
+            {
+                line.push_str(&format!(" [{receipt}]"));
+            }
+            let line = config
+                .options
+                .reaction_placement
+                .arrange(line, reactions)
+                .join("\n");
+            if config.options.separate_recovered && msg.is_deleted() {
+                recovered.push(line);
+            } else {
+                years.entry(date.year()).or_default().push(line);
+            }
+        }
+
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        let (month, day) = self.calendar_day;
+        let title = NaiveDate::from_ymd_opt(2000, month, day)
+            .map(|date| date.format("%B %-d").to_string())
+            .unwrap_or_else(|| format!("{month:02}-{day:02}"));
+        writeln!(file, "On this day: {title}")?;
+
+        if years.is_empty() && recovered.is_empty() {
+            writeln!(file, "\nNo messages were sent on this day.")?;
+        }
+
+        // Count years in the same time zone that message dates are read in
+        let now = (Utc::now().timestamp() - config.offset) * TIMESTAMP_FACTOR;
+        let this_year = config
+            .time(&now)
+            .map_or_else(|_| Local::now().year(), |now| now.year());
+        // Messages are streamed newest first, so reverse each year to read in order
+        for (year, lines) in &years {
+            let years_ago = this_year - year;
+            writeln!(
+                file,
+                "\n== {year} ({}) ==",
+                match years_ago {
+                    0 => String::from("this year"),
+                    1 => String::from("1 year ago"),
+                    n => format!("{n} years ago"),
+                }
+            )?;
+            for line in lines.iter().rev() {
+                writeln!(file, "{line}")?;
+            }
+        }
+
+        if !recovered.is_empty() {
+            writeln!(file, "\n== Recovered from Recently Deleted ==")?;
+            for line in recovered.iter().rev() {
+                writeln!(file, "{line}")?;
+            }
+        }
+
+        file.flush()?;
+        eprintln!("Wrote report to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl OnThisDay<'_> {
+    /// Format a tapback as an indented line under or over the message it targets
+    fn format_reaction(&self, tapback: &Message) -> Option<String> {
+        let Variant::Tapback(_, _, reaction) = tapback.variant() else {
+            return None;
+        };
+        let sender = self.config.who(
+            tapback.handle_id,
+            tapback.is_from_me(),
+            &tapback.destination_caller_id,
+        );
+        Some(format!("    {reaction} by {sender}"))
+    }
+
+    /// Format a single message as one line of the report
+    fn format_line(&self, msg: &Message) -> String {
+        let sender = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        let chat = self
+            .config
+            .conversation(msg)
+            .map(|(chat, _)| format!(" in {}", self.config.chat_label(chat)))
+            .unwrap_or_default();
+
+        let mut body = match (msg.poll(self.config.db()), msg.pin_action()) {
+            (Some(poll), _) => format_poll(&poll),
+            (None, Some(action)) => format_pin_event(
+                sender,
+                action,
+                msg.pinned_message(self.config.db()).as_ref(),
+            ),
+            (None, None) => self
+                .config
+                .options
+                .attachment_placeholder
+                .apply(msg.text.as_deref().unwrap_or_default()),
+        };
+        body = self.config.redact_text(body);
+        if let Some(played) = msg.voice_message_played() {
+            if !body.is_empty() {
+                body.push(' ');
+            }
+            body.push_str(if played {
+                "[voice message (played)]"
+            } else {
+                "[voice message]"
+            });
+        } else if msg.has_attachments() {
+            if !body.is_empty() {
+                body.push(' ');
+            }
+            body.push_str(&format!(
+                "[{} attachment{}]",
+                msg.num_attachments,
+                if msg.num_attachments == 1 { "" } else { "s" }
+            ));
+        }
+
+        // Show what each edit changed instead of every full version of the message
+        if let Some(edited) = &msg.edited_parts {
+            let edits: Vec<String> = edited
+                .parts
+                .iter()
+                .flat_map(|part| part.diffs().map(|diff| diff.describe()))
+                .collect();
+            if !edits.is_empty() {
+                body.push_str(&format!(" (edited: {})", edits.join("; ")));
+            }
+        }
+
+        // Recovered messages no longer appear in the Messages app, so call them out
+        if msg.is_deleted() {
+            if !body.is_empty() {
+                body.push(' ');
+            }
+            body.push_str(RECOVERED_LABEL);
+        }
+
+        format!(
+            "{} | {sender}{chat}: {body}",
+            format(&self.config.time(&msg.date))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+    use imessage_database::{
+        tables::{messages::Message, table::Cacheable},
+        util::dates::utc_offset_on_day,
+    };
+
+    use crate::{
+        app::{options::Options, reaction_placement::ReactionPlacement, runtime::Config},
+        exporters::{exporter::Exporter, on_this_day::OnThisDay},
+    };
+
+    #[test]
+    fn can_write_report_across_years() {
+        let mut options = Options::fake_options();
+        options.query_context.set_on_this_day(5, 17).unwrap();
+        // Dates are at noon UTC so the local calendar day matches in most time zones
+        let config = Config::fake_export(
+            options,
+            "imessage-undeleter-on-this-day-test",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'a', 'First year', 1, 642945600000000000, 0, 0),
+                (2, 'b', 'Second year', 1, 674481600000000000, 0, 0),
+                (3, 'c', 'Wrong day', 1, 674568000000000000, 0, 0);
+            ",
+        );
+
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        assert!(contents.starts_with("On this day: May 17"));
+        assert!(contents.contains("== 2021 ("));
+        assert!(contents.contains("== 2022 ("));
+        assert!(contents.find("First year").unwrap() < contents.find("Second year").unwrap());
+        assert!(!contents.contains("Wrong day"));
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_match_day_in_selected_timezone() {
+        let mut options = Options::fake_options();
+        options.timezone = Some(Tz::Asia__Tokyo);
+        options.query_context.set_on_this_day(5, 17).unwrap();
+        options
+            .query_context
+            .set_utc_offset(utc_offset_on_day(Tz::Asia__Tokyo, 5, 17));
+        // 2021-05-16 23:00 UTC is already May 17th in Tokyo, and 2021-05-17 16:00 UTC is May 18th
+        let config = Config::fake_export(
+            options,
+            "imessage-undeleter-on-this-day-test-timezone",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'a', 'Tokyo morning', 1, 642898800000000000, 0, 0),
+                (2, 'b', 'Tokyo next day', 1, 642960000000000000, 0, 0);
+            ",
+        );
+
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        assert!(contents.contains("== 2021 ("));
+        assert!(contents.contains("Tokyo morning"));
+        assert!(!contents.contains("Tokyo next day"));
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_label_recovered_messages() {
+        let mut options = Options::fake_options();
+        options.query_context.set_on_this_day(5, 17).unwrap();
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-on-this-day-test-recovered",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'a', 'Still here', 1, 642945600000000000, 0, 0),
+                (2, 'b', 'Deleted', 1, 642945660000000000, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1);
+            INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date) VALUES (1, 2, 1);
+            ",
+        );
+
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        assert!(contents.contains("Deleted [recovered from Recently Deleted]"));
+        assert!(!contents.contains("Still here [recovered"));
+        assert!(!contents.contains("== Recovered from Recently Deleted =="));
+
+        // Recovered messages move to their own section
+        config.options.separate_recovered = true;
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        let section = contents
+            .find("== Recovered from Recently Deleted ==")
+            .unwrap();
+        assert!(contents.find("Still here").unwrap() < section);
+        assert!(contents.find("Deleted [recovered").unwrap() > section);
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_place_reactions_before_or_after() {
+        let mut options = Options::fake_options();
+        options.query_context.set_on_this_day(5, 17).unwrap();
+        // Alice likes the message, then changes her tapback to a love
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-on-this-day-test-reactions",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (1, '00000000-0000-0000-0000-000000000001', 'Earlier', 0, 1, 642945500000000000, 0, 0, NULL, 0),
+                (2, '00000000-0000-0000-0000-000000000002', 'Reacted', 0, 1, 642945600000000000, 0, 0, NULL, 0),
+                (3, '00000000-0000-0000-0000-000000000003', '', 1, 0, 642945610000000000, 0, 0, 'p:0/00000000-0000-0000-0000-000000000002', 2001),
+                (4, '00000000-0000-0000-0000-000000000004', '', 1, 0, 642945620000000000, 0, 0, 'p:0/00000000-0000-0000-0000-000000000002', 2000),
+                (5, '00000000-0000-0000-0000-000000000005', 'Later', 0, 1, 642945700000000000, 0, 0, NULL, 0);
+            ",
+        );
+        config.tapbacks = Message::cache(config.db()).unwrap();
+        config.participants.insert(1, String::from("Alice"));
+
+        let report_lines = |config: &Config| {
+            let mut report = OnThisDay::new(config).unwrap();
+            report.iter_messages().unwrap();
+            let contents = std::fs::read_to_string(&report.path).unwrap();
+            contents
+                .lines()
+                .skip_while(|line| !line.starts_with("== "))
+                .skip(1)
+                .map(|line| {
+                    line.split_once(": ")
+                        .map_or(line, |(_, body)| body)
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            report_lines(&config),
+            vec!["Earlier", "Reacted", "    Loved by Alice", "Later"]
+        );
+
+        config.options.reaction_placement = ReactionPlacement::Before;
+        assert_eq!(
+            report_lines(&config),
+            vec!["Earlier", "    Loved by Alice", "Reacted", "Later"]
+        );
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_annotate_latest_outgoing_message() {
+        let mut options = Options::fake_options();
+        options.query_context.set_on_this_day(5, 17).unwrap();
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-on-this-day-test-receipts",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'a', 'First', 0, 1, 642945600000000000, 642945700000000000, 642945610000000000),
+                (2, 'b', 'Second', 0, 1, 642945610000000000, 642945700000000000, 642945620000000000),
+                (3, 'c', 'Reply', 1, 0, 642945800000000000, 0, 0),
+                (4, 'd', 'Third', 0, 1, 642945900000000000, 0, 642945910000000000);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4);
+            ",
+        );
+
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        assert!(!contents.contains("[Read at"));
+
+        config.options.show_receipts = true;
+        let mut report = OnThisDay::new(&config).unwrap();
+        report.iter_messages().unwrap();
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        let line = |text: &str| {
+            contents
+                .lines()
+                .find(|line| line.contains(&format!(": {text}")))
+                .unwrap()
+                .to_string()
+        };
+
+        // Only the last message in each run of outgoing messages is annotated
+        assert!(line("First").ends_with(": First"));
+        assert!(line("Second").contains(": Second [Read at "));
+        assert!(line("Reply").ends_with(": Reply"));
+        assert!(line("Third").ends_with(": Third [Delivered]"));
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
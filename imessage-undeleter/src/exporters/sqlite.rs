@@ -0,0 +1,711 @@
+/*!
+ Exports parsed, denormalized message data into a new SQLite database.
+
+ The output is intended for ad-hoc SQL analysis, so it does not attempt to mirror Apple's schema.
+ All dates are stored as RFC 3339 strings in the local time zone, or `NULL` if the event never happened.
+
+ ```sql
+ -- Contacts that appear in the export
+ CREATE TABLE handles (
+     id INTEGER PRIMARY KEY,       -- The `ROWID` of the handle in the source database
+     identifier TEXT NOT NULL,     -- Phone number or email address
+     person_id INTEGER NOT NULL    -- Shared by handles that represent the same contact
+ );
+
+ -- Conversations
+ CREATE TABLE chats (
+     id INTEGER PRIMARY KEY,       -- The `ROWID` of the chat in the source database
+     identifier TEXT NOT NULL,     -- The chat's unique identifier
+     service TEXT,                 -- iMessage, SMS, etc.
+     name TEXT,                    -- Custom name given to the chat, if any
+     conversation_id INTEGER NOT NULL -- Shared by chats with the same participants
+ );
+
+ -- Members of each conversation
+ CREATE TABLE chat_participants (
+     chat_id INTEGER NOT NULL REFERENCES chats (id),
+     handle_id INTEGER NOT NULL REFERENCES handles (id),
+     PRIMARY KEY (chat_id, handle_id)
+ );
+
+ -- Messages, excluding reactions
+ CREATE TABLE messages (
+     id INTEGER PRIMARY KEY,       -- The `ROWID` of the message in the source database
+     guid TEXT NOT NULL UNIQUE,
+     chat_id INTEGER REFERENCES chats (id),
+     sequence INTEGER,             -- Position of the message within its chat, starting at 1
+     handle_id INTEGER REFERENCES handles (id),
+     sender TEXT NOT NULL,         -- Resolved display name of the sender
+     is_from_me INTEGER NOT NULL,
+     service TEXT,
+     subject TEXT,
+     text TEXT,                    -- Polls and pin events are stored as a single line describing them
+     sent_at TEXT,
+     delivered_at TEXT,
+     read_at TEXT,
+     edited_at TEXT,
+     reply_to_guid TEXT,           -- The GUID of the message this is a threaded reply to
+     voice_message_played INTEGER, -- NULL if the message is not a voice message or played status is unknown
+     is_deleted INTEGER NOT NULL   -- Message is in the recently deleted collection
+ );
+
+ -- Files sent with messages
+ CREATE TABLE attachments (
+     id INTEGER PRIMARY KEY,       -- The `ROWID` of the attachment in the source database
+     message_id INTEGER NOT NULL REFERENCES messages (id),
+     path TEXT,                    -- Resolved path to the file on disk, or a `file://` URL if linked
+     transfer_name TEXT,
+     mime_type TEXT,
+     total_bytes INTEGER NOT NULL,
+     is_sticker INTEGER NOT NULL
+ );
+
+ -- Tapbacks applied to messages
+ CREATE TABLE reactions (
+     id INTEGER PRIMARY KEY,       -- The `ROWID` of the reaction in the source database
+     message_id INTEGER REFERENCES messages (id), -- NULL if the target is not in the export
+     target_guid TEXT NOT NULL,
+     part_index INTEGER NOT NULL,  -- The index of the message part the reaction applies to
+     handle_id INTEGER REFERENCES handles (id),
+     sender TEXT NOT NULL,
+     is_from_me INTEGER NOT NULL,
+     reaction TEXT NOT NULL,       -- Loved, Liked, an emoji, etc.
+     is_removed INTEGER NOT NULL,  -- Reaction removes a prior reaction
+     sent_at TEXT
+ );
+
+ -- Messages that were recovered from the Recently Deleted collection
+ CREATE VIEW recovered_messages AS SELECT * FROM messages WHERE is_deleted = 1;
+ ```
+*/
+
+use std::{collections::HashMap, path::PathBuf};
+
+use rusqlite::{Connection, params};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::exporter::{Exporter, format_export_text},
+};
+
+use imessage_database::{
+    message_types::variants::{TapbackAction, Variant},
+    tables::{attachment::Attachment, messages::Message},
+};
+
+/// The name of the database file created in the export directory
+pub const SQLITE_EXPORT_FILE: &str = "messages.db";
+
+const SCHEMA: &str = "
+CREATE TABLE handles (
+    id INTEGER PRIMARY KEY,
+    identifier TEXT NOT NULL,
+    person_id INTEGER NOT NULL
+);
+CREATE TABLE chats (
+    id INTEGER PRIMARY KEY,
+    identifier TEXT NOT NULL,
+    service TEXT,
+    name TEXT,
+    conversation_id INTEGER NOT NULL
+);
+CREATE TABLE chat_participants (
+    chat_id INTEGER NOT NULL REFERENCES chats (id),
+    handle_id INTEGER NOT NULL REFERENCES handles (id),
+    PRIMARY KEY (chat_id, handle_id)
+);
+CREATE TABLE messages (
+    id INTEGER PRIMARY KEY,
+    guid TEXT NOT NULL UNIQUE,
+    chat_id INTEGER REFERENCES chats (id),
+    sequence INTEGER,
+    handle_id INTEGER REFERENCES handles (id),
+    sender TEXT NOT NULL,
+    is_from_me INTEGER NOT NULL,
+    service TEXT,
+    subject TEXT,
+    text TEXT,
+    sent_at TEXT,
+    delivered_at TEXT,
+    read_at TEXT,
+    edited_at TEXT,
+    reply_to_guid TEXT,
+    voice_message_played INTEGER,
+    is_deleted INTEGER NOT NULL
+);
+CREATE TABLE attachments (
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    path TEXT,
+    transfer_name TEXT,
+    mime_type TEXT,
+    total_bytes INTEGER NOT NULL,
+    is_sticker INTEGER NOT NULL
+);
+CREATE TABLE reactions (
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER REFERENCES messages (id),
+    target_guid TEXT NOT NULL,
+    part_index INTEGER NOT NULL,
+    handle_id INTEGER REFERENCES handles (id),
+    sender TEXT NOT NULL,
+    is_from_me INTEGER NOT NULL,
+    reaction TEXT NOT NULL,
+    is_removed INTEGER NOT NULL,
+    sent_at TEXT
+);
+CREATE INDEX messages_idx_chat_id_sent_at ON messages (chat_id, sent_at);
+CREATE INDEX messages_idx_handle_id ON messages (handle_id);
+CREATE INDEX attachments_idx_message_id ON attachments (message_id);
+CREATE INDEX reactions_idx_message_id ON reactions (message_id);
+CREATE INDEX reactions_idx_target_guid ON reactions (target_guid);
+CREATE VIEW recovered_messages AS SELECT * FROM messages WHERE is_deleted = 1;
+";
+
+pub struct SQLite<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Connection to the database we are writing to
+    pub out: Connection,
+    /// Path to the database we are writing to
+    pub path: PathBuf,
+    /// Map of message ROWID to its position within its chat
+    sequence: HashMap<i32, u64>,
+}
+
+impl<'a> Exporter<'a> for SQLite<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let path = config.options.export_path.join(SQLITE_EXPORT_FILE);
+        if path.exists() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Export database {} already exists!",
+                path.display()
+            )));
+        }
+
+        let out = Connection::open(&path)?;
+        // The source database can reference rows that are not exported, i.e. handles for deleted contacts
+        out.execute_batch("PRAGMA foreign_keys = OFF;")?;
+        out.execute_batch(SCHEMA)?;
+
+        Ok(SQLite {
+            config,
+            out,
+            path,
+            sequence: HashMap::new(),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn start(&mut self) -> Result<(), RuntimeError> {
+        eprintln!("Exporting to {}...", self.path.display());
+
+        // Every row is written in one transaction, which is committed once all messages are written
+        self.out.execute_batch("BEGIN;")?;
+        let config = self.config;
+
+        // Contacts and conversations come straight from the application cache
+        for (handle_id, identifier) in &config.participants {
+            // Handle ID 0 is the database owner, which is represented by `is_from_me`
+            if *handle_id == 0 {
+                continue;
+            }
+            self.out.execute(
+                "INSERT INTO handles (id, identifier, person_id) VALUES (?1, ?2, ?3)",
+                params![
+                    handle_id,
+                    config.pseudonyms.get(handle_id).unwrap_or(identifier),
+                    config.real_participants.get(handle_id).unwrap_or(handle_id)
+                ],
+            )?;
+        }
+
+        for (chat_id, chat) in &config.chatrooms {
+            if !config.include_chat(Some(*chat_id)) {
+                continue;
+            }
+            let (identifier, name) = if config.options.redact_identities {
+                (config.chat_label(chat), None)
+            } else {
+                (chat.chat_identifier.clone(), chat.display_name())
+            };
+            self.out.execute(
+                "INSERT INTO chats (id, identifier, service, name, conversation_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    chat_id,
+                    identifier,
+                    chat.service_name,
+                    name,
+                    config.real_chatrooms.get(chat_id).unwrap_or(chat_id)
+                ],
+            )?;
+            if let Some(participants) = config.chatroom_participants.get(chat_id) {
+                for handle_id in participants {
+                    self.out.execute(
+                        "INSERT OR IGNORE INTO chat_participants (chat_id, handle_id) VALUES (?1, ?2)",
+                        params![chat_id, handle_id],
+                    )?;
+                }
+            }
+        }
+
+        self.sequence = Message::cache_chat_sequence(config.db())?;
+        Ok(())
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        // Tapbacks have no text, but are kept so reactions to matching messages are exported
+        if !msg.is_tapback() && !config.matches_text(msg) {
+            return Ok(());
+        }
+        let text = format_export_text(config, msg);
+        let sender = config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+
+        if let Variant::Tapback(part_index, action, tapback) = msg.variant() {
+            if let Some((_, target_guid)) = msg.clean_associated_guid() {
+                self.out.execute(
+                    "INSERT INTO reactions (id, target_guid, part_index, handle_id, sender, is_from_me, reaction, is_removed, sent_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        msg.rowid,
+                        target_guid,
+                        part_index,
+                        msg.handle_id.filter(|id| *id != 0),
+                        sender,
+                        msg.is_from_me(),
+                        tapback.to_string(),
+                        matches!(action, TapbackAction::Removed),
+                        Self::timestamp(config, msg.date),
+                    ],
+                )?;
+            }
+            return Ok(());
+        }
+
+        self.out.execute(
+            "INSERT INTO messages (id, guid, chat_id, sequence, handle_id, sender, is_from_me, service, subject, text, sent_at, delivered_at, read_at, edited_at, reply_to_guid, voice_message_played, is_deleted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+            params![
+                msg.rowid,
+                msg.guid,
+                msg.chat_id.or(msg.deleted_from),
+                self.sequence.get(&msg.rowid),
+                msg.handle_id.filter(|id| *id != 0),
+                sender,
+                msg.is_from_me(),
+                msg.service,
+                msg.subject,
+                text,
+                Self::timestamp(config, msg.date),
+                Self::timestamp(config, msg.date_delivered),
+                Self::timestamp(config, msg.date_read),
+                Self::timestamp(config, msg.date_edited),
+                msg.thread_originator_guid,
+                msg.voice_message_played(),
+                msg.is_deleted(),
+            ],
+        )?;
+
+        for attachment in attachments.iter() {
+            self.out.execute(
+                "INSERT INTO attachments (id, message_id, path, transfer_name, mime_type, total_bytes, is_sticker)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    attachment.rowid,
+                    msg.rowid,
+                    config.message_attachment_link(attachment),
+                    attachment.transfer_name,
+                    attachment.mime_type,
+                    attachment.total_bytes,
+                    attachment.is_sticker,
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        // Link reactions to the messages they were applied to, now that all messages exist
+        self.out.execute(
+            "UPDATE reactions SET message_id = (SELECT m.id FROM messages m WHERE m.guid = reactions.target_guid)",
+            [],
+        )?;
+
+        self.out.execute_batch("COMMIT;")?;
+        eprintln!("Export complete!");
+        Ok(())
+    }
+}
+
+impl SQLite<'_> {
+    /// Format a date from the iMessage table, treating empty dates as `NULL`
+    fn timestamp(config: &Config, date: i64) -> Option<String> {
+        if date == 0 {
+            return None;
+        }
+        config.time(&date).ok().map(|date| date.to_rfc3339())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        env::temp_dir,
+        fs::create_dir_all,
+        path::Path,
+    };
+
+    use imessage_database::{
+        message_types::variants::UNKNOWN_EMOJI_TAPBACK,
+        tables::{chat::Chat, table::Cacheable},
+    };
+    use rusqlite::Connection;
+
+    use crate::{
+        app::{
+            compatibility::attachment_manager::AttachmentManagerMode, options::Options,
+            runtime::Config, sanitizers::file_url, text_filter::TextFilter,
+        },
+        exporters::{
+            exporter::Exporter,
+            sqlite::{SQLITE_EXPORT_FILE, SQLite},
+        },
+    };
+
+    #[test]
+    fn can_export_known_message() {
+        let mut options = Options::fake_options();
+        options.export_path = temp_dir().join("imessage-undeleter-sqlite-test");
+        let _ = std::fs::remove_dir_all(&options.export_path);
+        create_dir_all(&options.export_path).unwrap();
+
+        let config = Config::fake_app(options);
+        let mut exporter = SQLite::new(&config).unwrap();
+        exporter.iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let (guid, is_from_me, num_attachments): (String, bool, i64) = out
+            .query_row(
+                "SELECT m.guid, m.is_from_me, (SELECT COUNT(*) FROM attachments a WHERE a.message_id = m.id)
+                 FROM messages m WHERE m.id = 452567",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+
+        assert_eq!(guid, "0355C6E1-D0C8-4212-AA87-DD8AE4FD1203");
+        assert!(!is_from_me);
+        assert_eq!(num_attachments, 1);
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn cant_overwrite_existing_export() {
+        let mut options = Options::fake_options();
+        options.export_path = temp_dir().join("imessage-undeleter-sqlite-test-existing");
+        create_dir_all(&options.export_path).unwrap();
+        std::fs::write(options.export_path.join(SQLITE_EXPORT_FILE), "").unwrap();
+
+        let config = Config::fake_app(options);
+        assert!(SQLite::new(&config).is_err());
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_filter_messages_by_regex() {
+        let mut options = Options::fake_options();
+        options.match_regex = Some(TextFilter::new(r"\b1Z[0-9A-Z]{16}\b").unwrap());
+        let config = Config::fake_export(
+            options,
+            "imessage-undeleter-sqlite-test-match-regex",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, date, date_read, date_delivered) VALUES
+                (1, 'A1A1A1A1-0000-0000-0000-000000000000', 'Your package 1Z999AA10123456784 has shipped', 0, 1, 0, 0),
+                (2, 'other', 'Your package has shipped', 0, 2, 0, 0);
+            INSERT INTO message (ROWID, guid, text, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (3, 'tapback', NULL, 1, 3, 0, 0, 'p:0/A1A1A1A1-0000-0000-0000-000000000000', 2000);
+            ",
+        );
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let guids: Vec<String> = out
+            .prepare("SELECT guid FROM messages ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let reactions: i64 = out
+            .query_row("SELECT COUNT(*) FROM reactions", [], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(guids, vec!["A1A1A1A1-0000-0000-0000-000000000000"]);
+        // Reactions are kept even though they have no text
+        assert_eq!(reactions, 1);
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_skip_announcement_only_chats() {
+        let mut options = Options::fake_options();
+        options.skip_empty_chats = true;
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-sqlite-test-empty-chats",
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'a', 'announcements'), (2, 'b', 'people');
+            INSERT INTO message (ROWID, guid, item_type, group_title, date, date_read, date_delivered) VALUES (1, 'm1', 2, 'New Name', 1, 0, 0);
+            INSERT INTO message (ROWID, guid, item_type, text, date, date_read, date_delivered) VALUES (2, 'm2', 0, 'Hello', 2, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2);
+            ",
+        );
+        config.chatrooms = Chat::cache(config.db()).unwrap();
+        config.empty_chats = Config::find_empty_chats(config.db()).unwrap();
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let chats: Vec<i32> = out
+            .prepare("SELECT id FROM chats")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let messages: Vec<String> = out
+            .prepare("SELECT guid FROM messages")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(chats, vec![2]);
+        assert_eq!(messages, vec!["m2"]);
+
+        let sequence: i64 = out
+            .query_row(
+                "SELECT sequence FROM messages WHERE guid = 'm2'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(sequence, 1);
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_use_custom_owner_label() {
+        let mut options = Options::fake_options();
+        options.custom_name = Some(String::from("Alex"));
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-sqlite-test-owner-label",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (1, 'sent', 'Hello', 1, 0, 1, 0, 0),
+                (2, 'received', 'Hi', 0, 1, 2, 0, 0);
+            ",
+        );
+        config.participants.insert(1, String::from("+15558675309"));
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let sender = |guid: &str| -> String {
+            out.query_row(
+                "SELECT sender FROM messages WHERE guid = ?1",
+                [guid],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(sender("sent"), "Alex");
+        assert_eq!(sender("received"), "+15558675309");
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_export_emoji_tapbacks() {
+        let config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-sqlite-test-emoji-tapback",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (1, 'F0B6E5A4-7C2D-4E0B-9F3A-1D2C3B4A5E6F', 'Dinner?', 1, 0, 1, 0, 0);
+            INSERT INTO message (ROWID, guid, is_from_me, handle_id, associated_message_guid, associated_message_type, associated_message_emoji, date, date_read, date_delivered) VALUES
+                (2, 'emoji', 0, 1, 'p:0/F0B6E5A4-7C2D-4E0B-9F3A-1D2C3B4A5E6F', 2006, '🔥', 2, 0, 0),
+                (3, 'missing', 0, 1, 'p:0/F0B6E5A4-7C2D-4E0B-9F3A-1D2C3B4A5E6F', 2006, NULL, 3, 0, 0);
+            ",
+        );
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let reaction = |id: i32| -> String {
+            out.query_row(
+                "SELECT reaction FROM reactions WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(reaction(2), "🔥");
+        assert_eq!(reaction(3), UNKNOWN_EMOJI_TAPBACK);
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_export_pin_events() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-sqlite-test-pin-events",
+            "
+            INSERT INTO message (ROWID, guid, text, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (1, '5D3B0C2E-8F1A-4B6C-9D7E-0A1B2C3D4E5F', 'Gate code is 1234', 0, 1, 1, 0, 0);
+            INSERT INTO message (ROWID, guid, item_type, group_action_type, associated_message_guid, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (2, 'pinned', 7, 0, '5D3B0C2E-8F1A-4B6C-9D7E-0A1B2C3D4E5F', 0, 1, 2, 0, 0),
+                (3, 'unpinned', 7, 1, NULL, 1, 0, 3, 0, 0);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice"));
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let text = |guid: &str| -> String {
+            out.query_row("SELECT text FROM messages WHERE guid = ?1", [guid], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+
+        assert_eq!(
+            text("pinned"),
+            "Alice pinned a message: \"Gate code is 1234\""
+        );
+        // The pinned message is not recorded, so it cannot be quoted
+        assert_eq!(text("unpinned"), "Me unpinned a message");
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_export_group_membership_events() {
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-sqlite-test-group-events",
+            "
+            INSERT INTO message (ROWID, guid, item_type, group_action_type, other_handle, group_title, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (1, 'added', 1, 0, 2, NULL, 0, 1, 1, 0, 0),
+                (2, 'removed', 1, 1, 1, NULL, 1, 0, 2, 0, 0),
+                (3, 'renamed', 2, 0, NULL, 'Dinner Club', 0, 2, 3, 0, 0);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice"));
+        config.participants.insert(2, String::from("Bob"));
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let text = |guid: &str| -> String {
+            out.query_row("SELECT text FROM messages WHERE guid = ?1", [guid], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+
+        assert_eq!(text("added"), "Alice added Bob to the conversation");
+        assert_eq!(text("removed"), "Me removed Alice from the conversation");
+        assert_eq!(
+            text("renamed"),
+            "Bob named the conversation \"Dinner Club\""
+        );
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_redact_identities_and_keep_attachments() {
+        let mut options = Options::fake_options();
+        options.redact_identities = true;
+        options.attachment_manager.mode = AttachmentManagerMode::Link;
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-sqlite-test-redact-identities",
+            "
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'c', '+15558675309');
+            INSERT INTO message (ROWID, guid, text, is_from_me, handle_id, date, date_read, date_delivered) VALUES
+                (1, 'm1', 'Call me at +1 (555) 867-5309 or alice@example.com \u{FFFC}', 0, 1, 1, 0, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (1, 'a1', 'a1', '/tmp/holiday.png', 'image/png', 'holiday.png', 100);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);
+            ",
+        );
+        config.chatrooms = Chat::cache(config.db()).unwrap();
+        config.participants.insert(1, String::from("Alice"));
+        config.real_participants.insert(1, 0);
+        config.pseudonyms = HashMap::from([(1, String::from("Contact 1"))]);
+        config.chatroom_participants.insert(1, BTreeSet::from([1]));
+
+        SQLite::new(&config).unwrap().iter_messages().unwrap();
+
+        let out = Connection::open(config.options.export_path.join(SQLITE_EXPORT_FILE)).unwrap();
+        let (sender, text): (String, String) = out
+            .query_row(
+                "SELECT sender, text FROM messages WHERE guid = 'm1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let handle: String = out
+            .query_row("SELECT identifier FROM handles WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let chat: String = out
+            .query_row("SELECT identifier FROM chats WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let path: String = out
+            .query_row("SELECT path FROM attachments WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+
+        assert_eq!(sender, "Contact 1");
+        assert_eq!(handle, "Contact 1");
+        assert_eq!(chat, "Chat 1");
+        assert!(!text.contains("867-5309"));
+        assert!(!text.contains("alice@example.com"));
+        assert!(text.starts_with("Call me at [redacted] or [redacted]"));
+        // The attachment still points at the real file
+        assert_eq!(path, file_url(Path::new("/tmp/holiday.png")));
+        // File names are only changed by anonymization
+        assert_eq!(config.filename(&config.chatrooms[&1]), "Alice");
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
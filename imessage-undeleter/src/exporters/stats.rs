@@ -0,0 +1,366 @@
+/*!
+ Writes a machine-readable summary of the conversations in the database to `stats.json`.
+
+ ```json
+ {
+   "totals": {
+     "messages": 3,        // Messages, excluding reactions
+     "sent": 1,
+     "received": 2,
+     "attachments": 0,
+     "reactions": 1,       // Reactions that were added, excluding removals
+     "deleted": 0,         // Messages in the recently deleted collection
+     "chats": 1
+   },
+   "participants": [
+     { "name": "Alice", "messages": 2, "reactions": 1 },
+     { "name": "Me", "messages": 1, "reactions": 0 }
+   ],
+   "reactions": { "Loved": 1 },
+   "busiest_days": [
+     { "date": "2021-05-17", "messages": 3 }
+   ],
+   "response_seconds": {
+     "owner": { "replies": 1, "mean": 60, "median": 60 },    // Time for the database owner to reply
+     "contacts": { "replies": 1, "mean": 120, "median": 120 } // `null` if nobody replied
+   }
+ }
+ ```
+
+ Response times are measured with [`ResponseTimes`]: a reply is the first message after a message from the other
+ side of the same chat, so messages between two contacts in a group chat are not counted.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config, sanitizers::json_string},
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    message_types::variants::{TapbackAction, Variant},
+    tables::{attachment::Attachment, messages::Message},
+    util::response_time::{ResponseStats, ResponseTimes},
+};
+
+/// The name of the report file created in the export directory
+pub const STATS_EXPORT_FILE: &str = "stats.json";
+
+/// The number of days listed in the busiest days section
+const BUSIEST_DAYS: usize = 10;
+
+/// Message and reaction counts for a single participant
+#[derive(Debug, Default)]
+struct ParticipantStats {
+    messages: u64,
+    reactions: u64,
+}
+
+/// Running totals collected while streaming messages
+#[derive(Debug, Default)]
+struct Totals {
+    messages: u64,
+    sent: u64,
+    received: u64,
+    attachments: u64,
+    reactions: u64,
+    deleted: u64,
+    chats: HashSet<i32>,
+}
+
+pub struct Stats<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the report we are writing to
+    pub path: PathBuf,
+    totals: Totals,
+    /// Map of sender name to their activity
+    participants: HashMap<String, ParticipantStats>,
+    /// Map of reaction name to the number of times it was added
+    reactions: HashMap<String, u64>,
+    /// Map of calendar day to the number of messages sent on it
+    days: HashMap<String, u64>,
+    /// The time between each message and its reply, in every chat
+    response_times: ResponseTimes,
+}
+
+impl<'a> Exporter<'a> for Stats<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Stats {
+            config,
+            path: config.options.export_path.join(STATS_EXPORT_FILE),
+            totals: Totals::default(),
+            participants: HashMap::new(),
+            reactions: HashMap::new(),
+            days: HashMap::new(),
+            response_times: ResponseTimes::default(),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        _attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let config = self.config;
+        let chat_id = msg.chat_id.or(msg.deleted_from);
+        let sender = config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)
+            .to_string();
+
+        if let Variant::Tapback(_, action, tapback) = msg.variant() {
+            if matches!(action, TapbackAction::Added) {
+                self.totals.reactions += 1;
+                *self.reactions.entry(tapback.to_string()).or_default() += 1;
+                self.participants.entry(sender).or_default().reactions += 1;
+            }
+            return Ok(());
+        }
+
+        let totals = &mut self.totals;
+        totals.messages += 1;
+        if msg.is_from_me() {
+            totals.sent += 1;
+        } else {
+            totals.received += 1;
+        }
+        totals.attachments += u64::try_from(msg.num_attachments).unwrap_or(0);
+        if msg.is_deleted() {
+            totals.deleted += 1;
+        }
+        if let Ok(date) = config.time(&msg.date) {
+            *self
+                .days
+                .entry(date.format("%Y-%m-%d").to_string())
+                .or_default() += 1;
+        }
+
+        self.participants.entry(sender).or_default().messages += 1;
+        if let Some(chat_id) = chat_id {
+            totals.chats.insert(chat_id);
+        }
+        self.response_times.record(msg);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let Stats {
+            path,
+            totals,
+            participants,
+            reactions,
+            days,
+            response_times,
+            ..
+        } = self;
+
+        let responses = response_times.overall();
+        let mut file = BufWriter::new(File::create(&path)?);
+        write!(
+            file,
+            "{}",
+            Self::render(
+                totals,
+                participants,
+                reactions,
+                days,
+                &responses.owner,
+                &responses.contacts
+            )
+        )?;
+        file.flush()?;
+
+        eprintln!("Wrote statistics to {}", path.display());
+        Ok(())
+    }
+}
+
+impl Stats<'_> {
+    /// Build the JSON document for the collected statistics
+    fn render(
+        totals: &Totals,
+        participants: &HashMap<String, ParticipantStats>,
+        reactions: &HashMap<String, u64>,
+        days: &HashMap<String, u64>,
+        owner_responses: &ResponseStats,
+        contact_responses: &ResponseStats,
+    ) -> String {
+        let mut out = String::from("{\n");
+
+        out.push_str(&format!(
+            "  \"totals\": {{\n    \"messages\": {},\n    \"sent\": {},\n    \"received\": {},\n    \"attachments\": {},\n    \"reactions\": {},\n    \"deleted\": {},\n    \"chats\": {}\n  }},\n",
+            totals.messages,
+            totals.sent,
+            totals.received,
+            totals.attachments,
+            totals.reactions,
+            totals.deleted,
+            totals.chats.len()
+        ));
+
+        // Most active participants first
+        let mut participants: Vec<(&String, &ParticipantStats)> = participants.iter().collect();
+        participants.sort_by(|a, b| b.1.messages.cmp(&a.1.messages).then(a.0.cmp(b.0)));
+        let participants: Vec<String> = participants
+            .iter()
+            .map(|(name, stats)| {
+                format!(
+                    "    {{ \"name\": {}, \"messages\": {}, \"reactions\": {} }}",
+                    json_string(name),
+                    stats.messages,
+                    stats.reactions
+                )
+            })
+            .collect();
+        out.push_str(&format!(
+            "  \"participants\": {},\n",
+            Self::block('[', ']', &participants)
+        ));
+
+        // Sorted by name so the output is stable between runs
+        let reactions: BTreeMap<&String, &u64> = reactions.iter().collect();
+        let reactions: Vec<String> = reactions
+            .iter()
+            .map(|(reaction, count)| format!("    {}: {count}", json_string(reaction)))
+            .collect();
+        out.push_str(&format!(
+            "  \"reactions\": {},\n",
+            Self::block('{', '}', &reactions)
+        ));
+
+        let mut days: Vec<(&String, &u64)> = days.iter().collect();
+        days.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let days: Vec<String> = days
+            .iter()
+            .take(BUSIEST_DAYS)
+            .map(|(date, count)| {
+                format!(
+                    "    {{ \"date\": {}, \"messages\": {count} }}",
+                    json_string(date)
+                )
+            })
+            .collect();
+        out.push_str(&format!(
+            "  \"busiest_days\": {},\n",
+            Self::block('[', ']', &days)
+        ));
+
+        out.push_str(&format!(
+            "  \"response_seconds\": {{\n    \"owner\": {},\n    \"contacts\": {}\n  }}\n}}\n",
+            Self::responses(owner_responses),
+            Self::responses(contact_responses)
+        ));
+
+        out
+    }
+
+    /// Wrap indented items in a JSON array or object, one item per line
+    fn block(open: char, close: char, items: &[String]) -> String {
+        if items.is_empty() {
+            return format!("{open}{close}");
+        }
+        format!("{open}\n{}\n  {close}", items.join(",\n"))
+    }
+
+    /// Format the response times in one direction as a JSON object
+    fn responses(stats: &ResponseStats) -> String {
+        let number =
+            |value: Option<i64>| value.map_or_else(|| String::from("null"), |v| v.to_string());
+        format!(
+            "{{ \"replies\": {}, \"mean\": {}, \"median\": {} }}",
+            stats.count,
+            number(stats.mean),
+            number(stats.median)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use crate::{
+        app::{options::Options, runtime::Config},
+        exporters::{exporter::Exporter, stats::Stats},
+    };
+
+    #[test]
+    fn can_write_stats_report() {
+        // Dates are at noon UTC, one minute apart
+        let mut config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-stats-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type) VALUES
+                (1, 'A', 'Hello', 1, 0, 642945600000000000, 0, 0, 0),
+                (2, 'B', 'Hi', 0, 1, 642945660000000000, 0, 0, 0),
+                (3, 'C', 'How are you?', 1, 0, 642945780000000000, 0, 0, 0);
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (4, 'D', 'Loved \"Hi\"', 1, 0, 642945720000000000, 0, 0, 'p:0/B', 2000);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4);
+            ",
+        );
+        config.participants.insert(1, String::from("Alice \"Al\""));
+
+        let mut report = Stats::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        let stats: Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(
+            stats["totals"],
+            json!({ "messages": 3, "sent": 1, "received": 2, "attachments": 0, "reactions": 1, "deleted": 0, "chats": 1 })
+        );
+        assert_eq!(
+            stats["participants"],
+            json!([
+                { "name": "Alice \"Al\"", "messages": 2, "reactions": 1 },
+                { "name": "Me", "messages": 1, "reactions": 0 }
+            ])
+        );
+        assert_eq!(stats["reactions"], json!({ "Loved": 1 }));
+        assert_eq!(stats["busiest_days"][0]["messages"], 3);
+        assert_eq!(
+            stats["response_seconds"],
+            json!({
+                "owner": { "replies": 1, "mean": 60, "median": 60 },
+                "contacts": { "replies": 1, "mean": 120, "median": 120 }
+            })
+        );
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn can_write_empty_stats_report() {
+        let config = Config::fake_export(
+            Options::fake_options(),
+            "imessage-undeleter-stats-test-empty",
+            "",
+        );
+
+        let mut report = Stats::new(&config).unwrap();
+        report.iter_messages().unwrap();
+
+        let contents = std::fs::read_to_string(&report.path).unwrap();
+        let stats: Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(stats["totals"]["messages"], 0);
+        assert_eq!(stats["participants"], json!([]));
+        assert_eq!(
+            stats["response_seconds"]["owner"],
+            json!({ "replies": 0, "mean": null, "median": null })
+        );
+
+        std::fs::remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
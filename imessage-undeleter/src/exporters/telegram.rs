@@ -0,0 +1,492 @@
+/*!
+ Writes every conversation to `result.json`, in the shape Telegram Desktop uses for its chat exports.
+
+ ```json
+ {
+   "about": "Exported from iMessage",
+   "chats": {
+     "about": "This page lists all chats from this export.",
+     "list": [
+       {
+         "name": "Family - 1",
+         "type": "private_group",   // `personal_chat` for conversations with one other person
+         "id": 1,
+         "messages": [
+           { "id": 1, "type": "message", "message_rowid": 41, "date": "2021-05-17T12:00:00", "date_unixtime": "1621278000", "from": "Alice", "from_id": "user1", "text": "Look!", "text_entities": [{ "type": "plain", "text": "Look!" }], "attachment_rowid": 7, "photo": "attachments/tmp/7.jpeg", "width": 4032, "height": 3024 },
+           { "id": 2, "type": "message", "message_rowid": 42, "date": "2021-05-17T12:01:00", "date_unixtime": "1621278060", "from": "Me", "from_id": "user0", "reply_to_message_id": 1, "text": "Nice", "text_entities": [{ "type": "plain", "text": "Nice" }], "reactions": [{ "type": "emoji", "count": 1, "emoji": "❤️" }] }
+         ]
+       }
+     ]
+   }
+ }
+ ```
+
+ Messages are listed oldest first and numbered from 1 within each chat. `message_rowid` and `attachment_rowid` are
+ not part of Telegram's format; they hold the `ROWID`s from the iMessage database so records can be traced back to it. Telegram messages carry at most one
+ file, so each additional attachment is written as its own message. Attachments are copied according to
+ `--copy-method` and referenced relative to the export directory, or under `--attachment-base-url` if it is
+ set; if they are not copied, the original location is used instead. Attachments that are not downloaded, like files offloaded to iCloud, use Telegram's
+ placeholder for files that were not exported. Tapbacks are written as reactions on the message they target.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config, sanitizers::json_string},
+    exporters::exporter::{Exporter, format_export_text},
+};
+
+use imessage_database::{
+    message_types::variants::{Tapback, Variant},
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+    },
+};
+
+/// The name of the export file created in the export directory
+pub const TELEGRAM_EXPORT_FILE: &str = "result.json";
+
+/// Telegram's placeholder for files that are not part of an export, used for attachments that are not on disk
+const FILE_NOT_DOWNLOADED: &str =
+    "(File not included. Change data exporting settings to download.)";
+
+/// A rendered message waiting for its chat to be sorted and numbered
+struct Entry {
+    date: i64,
+    rowid: i32,
+    guid: String,
+    reply_to: Option<String>,
+    /// Fields shared by every Telegram message built from the same iMessage
+    header: String,
+    /// Text, media, and reaction fields
+    body: String,
+}
+
+pub struct Telegram<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Path to the export we are writing to
+    pub path: PathBuf,
+    /// Map of chat ID to the messages rendered for it so far
+    chats: BTreeMap<i32, Vec<Entry>>,
+}
+
+impl<'a> Exporter<'a> for Telegram<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        Ok(Telegram {
+            config,
+            path: config.options.export_path.join(TELEGRAM_EXPORT_FILE),
+            chats: BTreeMap::new(),
+        })
+    }
+
+    fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    fn write_message(
+        &mut self,
+        msg: &Message,
+        attachments: &mut [Attachment],
+    ) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        // Telegram messages must belong to a chat, and tapbacks are written as reactions
+        let Some(chat_id) = msg.chat_id.or(msg.deleted_from) else {
+            return Ok(());
+        };
+        if msg.is_tapback() || msg.is_announcement() || !config.matches_text(msg) {
+            return Ok(());
+        }
+        let text = format_export_text(config, msg);
+
+        let header = self.format_header(msg);
+        let mut files = attachments.iter_mut();
+
+        // The text and first attachment share a message, and the reactions go with them
+        let mut body = Self::format_text(text.as_deref().unwrap_or_default());
+        if let Some(attachment) = files.next() {
+            body.push_str(&self.format_media(msg, attachment));
+        }
+        body.push_str(&self.format_reactions(msg));
+        let mut entries = vec![Entry {
+            date: msg.date,
+            rowid: msg.rowid,
+            guid: msg.guid.clone(),
+            reply_to: msg.thread_originator_guid.clone(),
+            header: header.clone(),
+            body,
+        }];
+
+        for attachment in files {
+            let mut body = Self::format_text("");
+            body.push_str(&self.format_media(msg, attachment));
+            entries.push(Entry {
+                date: msg.date,
+                rowid: msg.rowid,
+                guid: msg.guid.clone(),
+                reply_to: None,
+                header: header.clone(),
+                body,
+            });
+        }
+
+        self.chats.entry(chat_id).or_default().extend(entries);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        let chats = std::mem::take(&mut self.chats);
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        write!(file, "{}", self.render(chats))?;
+        file.flush()?;
+
+        eprintln!("Wrote Telegram export to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl Telegram<'_> {
+    /// Build the JSON document for every chat
+    fn render(&self, chats: BTreeMap<i32, Vec<Entry>>) -> String {
+        let config = self.config;
+
+        let chats: Vec<String> = chats
+            .into_iter()
+            .filter_map(|(chat_id, mut entries)| {
+                let chat = config.chatrooms.get(&chat_id)?;
+                let is_group = config
+                    .chatroom_participants
+                    .get(&chat_id)
+                    .is_some_and(|participants| participants.len() > 1);
+
+                // Messages are streamed newest first, but Telegram lists them oldest first
+                entries.sort_by_key(|entry| (entry.date, entry.rowid));
+                let mut ids: HashMap<&str, usize> = HashMap::new();
+                for (idx, entry) in entries.iter().enumerate() {
+                    ids.entry(&entry.guid).or_insert(idx + 1);
+                }
+
+                let messages: Vec<String> = entries
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, entry)| {
+                        let reply = entry
+                            .reply_to
+                            .as_deref()
+                            .and_then(|guid| ids.get(guid))
+                            .map(|id| format!(", \"reply_to_message_id\": {id}"))
+                            .unwrap_or_default();
+                        format!(
+                            "          {{ \"id\": {}, {}{reply}, {} }}",
+                            idx + 1,
+                            entry.header,
+                            entry.body
+                        )
+                    })
+                    .collect();
+
+                Some(format!(
+                    "      {{\n        \"name\": {},\n        \"type\": {},\n        \"id\": {chat_id},\n        \"messages\": {}\n      }}",
+                    json_string(&config.chat_label(chat)),
+                    json_string(if is_group {
+                        "private_group"
+                    } else {
+                        "personal_chat"
+                    }),
+                    Self::block("        ", &messages)
+                ))
+            })
+            .collect();
+
+        format!(
+            "{{\n  \"about\": \"Exported from iMessage\",\n  \"chats\": {{\n    \"about\": \"This page lists all chats from this export.\",\n    \"list\": {}\n  }}\n}}\n",
+            Self::block("    ", &chats)
+        )
+    }
+
+    /// Format the fields that describe when and by whom a message was sent
+    fn format_header(&self, msg: &Message) -> String {
+        let config = self.config;
+        let (date, unixtime) = config.time(&msg.date).map_or_else(
+            |_| (String::new(), 0),
+            |date| {
+                (
+                    date.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    date.timestamp(),
+                )
+            },
+        );
+        let from_id = if msg.is_from_me() {
+            0
+        } else {
+            msg.handle_id
+                .map(|id| *config.real_participants.get(&id).unwrap_or(&id))
+                .unwrap_or_default()
+        };
+
+        format!(
+            "\"type\": \"message\", \"message_rowid\": {}, \"date\": {}, \"date_unixtime\": {}, \"from\": {}, \"from_id\": {}",
+            msg.rowid,
+            json_string(&date),
+            json_string(&unixtime.to_string()),
+            json_string(config.who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)),
+            json_string(&format!("user{from_id}"))
+        )
+    }
+
+    /// Format message text as both a plain string and a list of text entities
+    fn format_text(text: &str) -> String {
+        // Attachments are written as media, so their placeholders are not part of the text
+        let text = text.replace('\u{FFFC}', "");
+        let text = text.trim();
+        if text.is_empty() {
+            return String::from("\"text\": \"\", \"text_entities\": []");
+        }
+        format!(
+            "\"text\": {0}, \"text_entities\": [{{ \"type\": \"plain\", \"text\": {0} }}]",
+            json_string(text)
+        )
+    }
+
+    /// Copy an attachment if requested and format the fields Telegram uses to reference it
+    fn format_media(&self, msg: &Message, attachment: &mut Attachment) -> String {
+        let config = self.config;
+        let path = if attachment.is_downloaded(
+            &config.options.platform,
+            &config.options.db_path,
+            config.options.attachment_root.as_deref(),
+        ) {
+            config.options.attachment_manager.handle_attachment(
+                msg,
+                attachment,
+                &attachment.rowid.to_string(),
+                config,
+            );
+            json_string(&config.message_attachment_link(attachment))
+        } else {
+            json_string(FILE_NOT_DOWNLOADED)
+        };
+        let mime_type = json_string(&attachment.mime_type().as_mime_type());
+        let media = if attachment.is_sticker {
+            format!(", \"file\": {path}, \"media_type\": \"sticker\", \"mime_type\": {mime_type}")
+        } else {
+            match attachment.mime_type() {
+                MediaType::Image(_) => {
+                    #[cfg(feature = "exif")]
+                    let dimensions = attachment
+                        .copied_path
+                        .clone()
+                        .or_else(|| config.attachment_source_path(attachment).map(PathBuf::from))
+                        .and_then(|source| {
+                            crate::app::exif::ImageMetadata::from_attachment(
+                                attachment,
+                                std::path::Path::new(&source),
+                            )
+                        })
+                        .and_then(|exif| Some((exif.width?, exif.height?)))
+                        .map(|(width, height)| {
+                            format!(", \"width\": {width}, \"height\": {height}")
+                        })
+                        .unwrap_or_default();
+                    #[cfg(not(feature = "exif"))]
+                    let dimensions = "";
+                    format!(", \"photo\": {path}{dimensions}")
+                }
+                MediaType::Video(_) => format!(
+                    ", \"file\": {path}, \"media_type\": \"video_file\", \"mime_type\": {mime_type}"
+                ),
+                MediaType::Audio(_) => format!(
+                    ", \"file\": {path}, \"media_type\": {}, \"mime_type\": {mime_type}",
+                    json_string(if msg.is_audio_message {
+                        "voice_message"
+                    } else {
+                        "audio_file"
+                    })
+                ),
+                _ => format!(", \"file\": {path}, \"mime_type\": {mime_type}"),
+            }
+        };
+        format!(", \"attachment_rowid\": {}{media}", attachment.rowid)
+    }
+
+    /// Format the tapbacks still applied to a message as Telegram reactions, in the order they were first used
+    fn format_reactions(&self, msg: &Message) -> String {
+        let reactions = self.config.reactions_to(msg);
+        let mut counts: Vec<(Tapback, usize)> = vec![];
+        for reaction in &reactions {
+            let Variant::Tapback(_, _, tapback) = reaction.variant() else {
+                continue;
+            };
+            match counts.iter_mut().find(|(existing, _)| *existing == tapback) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((tapback, 1)),
+            }
+        }
+        if counts.is_empty() {
+            return String::new();
+        }
+
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(tapback, count)| {
+                format!(
+                    "{{ \"type\": \"emoji\", \"count\": {count}, \"emoji\": {} }}",
+                    json_string(tapback.emoji())
+                )
+            })
+            .collect();
+        format!(", \"reactions\": [{}]", counts.join(", "))
+    }
+
+    /// Wrap indented items in a JSON array, one item per line, closing at `indent`
+    fn block(indent: &str, items: &[String]) -> String {
+        if items.is_empty() {
+            return String::from("[]");
+        }
+        format!("[\n{}\n{indent}]", items.join(",\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        env::current_dir,
+        fs::{read_to_string, remove_dir_all},
+    };
+
+    use imessage_database::tables::{chat::Chat, messages::Message, table::Cacheable};
+    use serde_json::Value;
+
+    use crate::{
+        app::{
+            compatibility::attachment_manager::AttachmentManagerMode, options::Options,
+            runtime::Config,
+        },
+        exporters::{exporter::Exporter, telegram::Telegram},
+    };
+
+    #[test]
+    fn can_write_telegram_export() {
+        let mut options = Options::fake_options();
+        options.attachment_manager.mode = AttachmentManagerMode::Clone;
+        let photo = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/exif/DateTimeOriginal.jpg");
+
+        let mut config = Config::fake_export(
+            options,
+            "imessage-undeleter-telegram-test",
+            "
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_type, thread_originator_guid) VALUES
+                (1, 'A', 'Look! \u{FFFC}', 1, 0, 642945600000000000, 0, 0, 0, NULL),
+                (2, 'B1B1B1B1-0000-0000-0000-000000000000', 'Nice', 0, 1, 642945660000000000, 0, 0, 0, 'A'),
+                (4, 'D', 'Elsewhere \u{FFFC}', 2, 0, 642945600000000000, 0, 0, 0, NULL);
+            INSERT INTO message (ROWID, guid, text, handle_id, is_from_me, date, date_read, date_delivered, associated_message_guid, associated_message_type) VALUES
+                (3, 'C', 'Loved \"Nice\"', 1, 0, 642945720000000000, 0, 0, 'p:0/B1B1B1B1-0000-0000-0000-000000000000', 2000);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (2, 4);
+            INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1), (4, 2);
+            INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES
+                (2, 'a2', 'a2', '/imessage-undeleter-missing/clip.mp4', 'video/mp4', 'clip.mp4', 2048);
+            ",
+        );
+        config.db().execute(
+            "INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type, transfer_name, total_bytes) VALUES (1, 'a1', 'a1', ?1, 'image/jpeg', 'photo.jpg', 115)",
+            [photo.to_str().unwrap()],
+        )
+        .unwrap();
+        config.tapbacks = Message::cache(config.db()).unwrap();
+        config.participants.insert(1, String::from("Alice"));
+        config.participants.insert(2, String::from("Bob"));
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([1, 2]));
+        config.chatroom_participants.insert(2, BTreeSet::from([2]));
+        for (rowid, name) in [(1, Some("Family")), (2, None)] {
+            config.chatrooms.insert(
+                rowid,
+                Chat {
+                    rowid,
+                    chat_identifier: format!("chat{rowid}"),
+                    service_name: Some(String::from("iMessage")),
+                    display_name: name.map(String::from),
+                    style: None,
+                    group_id: None,
+                },
+            );
+        }
+
+        let mut exporter = Telegram::new(&config).unwrap();
+        exporter.iter_messages().unwrap();
+        let contents = read_to_string(&exporter.path).unwrap();
+
+        // Top-level structure
+        assert!(contents.starts_with(
+            "{\n  \"about\": \"Exported from iMessage\",\n  \"chats\": {\n    \"about\": \"This page lists all chats from this export.\",\n    \"list\": [\n      {\n        \"name\": \"Family - 1\",\n        \"type\": \"private_group\",\n        \"id\": 1,\n        \"messages\": [\n"
+        ));
+        assert!(contents.contains("        \"type\": \"personal_chat\",\n        \"id\": 2,\n"));
+        assert!(contents.ends_with("      }\n    ]\n  }\n}\n"));
+
+        // The whole file is a single JSON document
+        let export: Value = serde_json::from_str(&contents).unwrap();
+        let chats = export["chats"]["list"].as_array().unwrap();
+        assert_eq!(chats.len(), 2);
+        assert_eq!(chats[0]["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(chats[1]["messages"][0]["text"], "Elsewhere");
+
+        // Sample message mapping; dates depend on the local timezone
+        let first = contents
+            .lines()
+            .find(|line| {
+                line.contains("\"id\": 1, \"type\": \"message\"") && line.contains("Alice")
+            })
+            .unwrap();
+        assert!(first.contains("\"from\": \"Alice\", \"from_id\": \"user1\", \"text\": \"Look!\", \"text_entities\": [{ \"type\": \"plain\", \"text\": \"Look!\" }], \"attachment_rowid\": 1, \"photo\": \"attachments/tmp/1.jpg\""));
+        #[cfg(feature = "exif")]
+        assert!(
+            first.contains(
+                "\"photo\": \"attachments/tmp/1.jpg\", \"width\": 4032, \"height\": 3024 }"
+            )
+        );
+        #[cfg(not(feature = "exif"))]
+        assert!(first.contains("\"photo\": \"attachments/tmp/1.jpg\" }"));
+
+        let reply = contents
+            .lines()
+            .find(|line| line.contains("\"id\": 2, \"type\": \"message\""))
+            .unwrap();
+        assert!(reply.contains("\"from\": \"Me\", \"from_id\": \"user0\", \"reply_to_message_id\": 1, \"text\": \"Nice\""));
+        assert!(reply.ends_with(
+            "\"reactions\": [{ \"type\": \"emoji\", \"count\": 1, \"emoji\": \"❤️\" }] }"
+        ));
+        assert!(!contents.contains("Loved"));
+
+        // Records can be traced back to the source database
+        let rowid: i32 = config
+            .db()
+            .query_row("SELECT ROWID FROM message WHERE guid = 'A'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(first.contains(&format!(
+            "\"type\": \"message\", \"message_rowid\": {rowid}, "
+        )));
+        assert!(reply.contains("\"message_rowid\": 2, "));
+
+        // Files that are not on disk are not linked
+        assert!(contents.contains(
+            "\"text\": \"Elsewhere\", \"text_entities\": [{ \"type\": \"plain\", \"text\": \"Elsewhere\" }], \"attachment_rowid\": 2, \"file\": \"(File not included. Change data exporting settings to download.)\", \"media_type\": \"video_file\", \"mime_type\": \"video/mp4\" }"
+        ));
+
+        remove_dir_all(&config.options.export_path).unwrap();
+    }
+}
@@ -1,24 +1,151 @@
-use std::{
-    collections::{
-        HashMap,
-    },
-    fs::File,
-    io::BufWriter,
-};
+use std::{collections::HashMap, fs::File, io::BufWriter};
 
 use crate::app::{
-        error::RuntimeError,
-        runtime::Config,
-    };
+    error::RuntimeError, runtime::Config, timestamp_granularity::TimestampGranularity,
+};
 
 use imessage_database::{
     error::table::TableError,
+    message_types::variants::Variant,
     tables::{
         messages::Message,
         table::{ORPHANED, Table},
     },
-    util::dates::format,
 };
+use rusqlite::params_from_iter;
+
+/// Where the timestamp goes in a message header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampPlacement {
+    /// `Sender: timestamp`
+    AfterSender,
+    /// `timestamp: Sender`
+    BeforeSender,
+}
+
+/// How tapbacks are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactionStyle {
+    /// The name of the tapback, i.e. `Loved`
+    Name,
+    /// The emoji shown on the tapback, i.e. `❤️`
+    Emoji,
+}
+
+/// Formatting choices for the text written by [`TXT`]
+///
+/// The defaults match the output of previous versions. Each setter consumes and returns the options:
+///
+/// ```ignore
+/// let formatting = TXTFormatting::default()
+///     .with_separator(" | ")
+///     .with_timestamp_placement(TimestampPlacement::BeforeSender);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TXTFormatting {
+    /// Text written before each message header
+    pub header_prefix: String,
+    /// Text written between the sender and the timestamp
+    pub separator: String,
+    /// Where the timestamp goes in a message header
+    pub timestamp_placement: TimestampPlacement,
+    /// How much of the timestamp goes in a message header
+    pub timestamp_granularity: TimestampGranularity,
+    /// Include how long it took for a message to be read
+    pub show_read_time: bool,
+    /// How tapbacks are written
+    pub reaction_style: ReactionStyle,
+    /// Write a deleted tapback as the reaction it applied instead of the text the database stores for it
+    pub label_reactions: bool,
+    /// If set, the maximum number of characters in a line of message text
+    pub wrap_width: Option<usize>,
+    /// Text written between wrapped lines
+    pub line_break: String,
+}
+
+impl Default for TXTFormatting {
+    fn default() -> Self {
+        Self {
+            header_prefix: String::from("==="),
+            separator: String::from(":"),
+            timestamp_placement: TimestampPlacement::AfterSender,
+            timestamp_granularity: TimestampGranularity::Full,
+            show_read_time: true,
+            reaction_style: ReactionStyle::Name,
+            label_reactions: false,
+            wrap_width: None,
+            line_break: String::from("\n"),
+        }
+    }
+}
+
+impl TXTFormatting {
+    /// Set the text written before each message header
+    #[must_use]
+    pub fn with_header_prefix(mut self, header_prefix: &str) -> Self {
+        self.header_prefix = header_prefix.to_string();
+        self
+    }
+
+    /// Set the text written between the sender and the timestamp
+    #[must_use]
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Set where the timestamp goes in a message header
+    #[must_use]
+    pub fn with_timestamp_placement(mut self, timestamp_placement: TimestampPlacement) -> Self {
+        self.timestamp_placement = timestamp_placement;
+        self
+    }
+
+    /// Set how much of the timestamp goes in a message header
+    #[must_use]
+    pub fn with_timestamp_granularity(
+        mut self,
+        timestamp_granularity: TimestampGranularity,
+    ) -> Self {
+        self.timestamp_granularity = timestamp_granularity;
+        self
+    }
+
+    /// Set whether to include how long it took for a message to be read
+    #[must_use]
+    pub fn with_read_time(mut self, show_read_time: bool) -> Self {
+        self.show_read_time = show_read_time;
+        self
+    }
+
+    /// Set how tapbacks are written
+    #[must_use]
+    pub fn with_reaction_style(mut self, reaction_style: ReactionStyle) -> Self {
+        self.reaction_style = reaction_style;
+        self
+    }
+
+    /// Set whether a deleted tapback is written as the reaction it applied
+    #[must_use]
+    pub fn with_reaction_labels(mut self, label_reactions: bool) -> Self {
+        self.label_reactions = label_reactions;
+        self
+    }
+
+    /// Set the maximum number of characters in a line of message text
+    #[must_use]
+    pub fn with_wrap_width(mut self, wrap_width: usize) -> Self {
+        self.wrap_width = Some(wrap_width);
+        self
+    }
+
+    /// Set the text written between wrapped lines, i.e. `<br>` when the text is written into HTML
+    #[must_use]
+    pub fn with_line_break(mut self, line_break: &str) -> Self {
+        self.line_break = line_break.to_string();
+        self
+    }
+}
 
 pub struct TXT<'a> {
     /// Data that is setup from the application's runtime
@@ -26,6 +153,10 @@ pub struct TXT<'a> {
     /// Handles to files we want to write messages to
     /// Map of resolved chatroom file location to a buffered writer
     pub files: HashMap<String, BufWriter<File>>,
+    /// Formatting choices for the text we write
+    pub formatting: TXTFormatting,
+    /// The heading for the day of the last message passed to [`TXT::format_day_heading()`]
+    pub last_day: Option<String>,
 }
 
 impl<'a> TXT<'a> {
@@ -37,9 +168,22 @@ impl<'a> TXT<'a> {
         Ok(TXT {
             config,
             files: HashMap::new(),
+            formatting: TXTFormatting::default()
+                .with_timestamp_granularity(config.options.timestamp_granularity),
+            last_day: None,
         })
     }
 
+    /// Create an exporter that uses custom formatting
+    pub fn with_formatting(
+        config: &'a Config,
+        formatting: TXTFormatting,
+    ) -> Result<Self, RuntimeError> {
+        let mut txt = Self::new(config)?;
+        txt.formatting = formatting;
+        Ok(txt)
+    }
+
     pub fn iter_messages(&mut self) -> Result<HashMap<i32, Message>, RuntimeError> {
         // Keep track of current message ROWID
         let mut current_message_row = -1;
@@ -48,7 +192,10 @@ impl<'a> TXT<'a> {
             Message::stream_rows(self.config.db(), &self.config.options.query_context)?;
 
         let messages = statement
-            .query_map([], |row| Ok(Message::from_row(row)))
+            .query_map(
+                params_from_iter(self.config.options.query_context.predicate_params()),
+                |row| Ok(Message::from_row(row)),
+            )
             .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
 
         let mut msgs: HashMap<i32, Message> = HashMap::new();
@@ -61,15 +208,120 @@ impl<'a> TXT<'a> {
                 continue;
             }
             current_message_row = msg.rowid;
-            msgs.insert(msg.rowid, msg).map(|o|println!("{}", o.rowid));
+            msgs.insert(msg.rowid, msg).map(|o| println!("{}", o.rowid));
         }
         Ok(msgs)
     }
 }
 
 impl TXT<'_> {
+    /// Format the header written before a message, containing its sender and timestamp
+    pub fn format_header(&self, sender: &str, message: &Message) -> String {
+        let time = self.get_time(message);
+        let TXTFormatting {
+            header_prefix,
+            separator,
+            ..
+        } = &self.formatting;
+        match self.formatting.timestamp_placement {
+            TimestampPlacement::AfterSender => format!("{header_prefix}{sender}{separator}{time}"),
+            TimestampPlacement::BeforeSender => format!("{header_prefix}{time}{separator}{sender}"),
+        }
+    }
+
+    /// Format a heading with the date if a message was sent on a different day than the last one passed here
+    ///
+    /// Headings are only written when timestamps leave the date out.
+    pub fn format_day_heading(&mut self, message: &Message) -> Option<String> {
+        let day = self
+            .formatting
+            .timestamp_granularity
+            .day_heading(&self.config.time(&message.date))?;
+        if self.last_day.as_ref() == Some(&day) {
+            return None;
+        }
+        self.last_day = Some(day.clone());
+        Some(day)
+    }
+
+    /// Format the reaction a tapback applies, or `None` if the message is not a tapback
+    pub fn format_tapback(&self, message: &Message) -> Option<String> {
+        let Variant::Tapback(_, _, tapback) = message.variant() else {
+            return None;
+        };
+        Some(match self.formatting.reaction_style {
+            ReactionStyle::Name => tapback.to_string(),
+            ReactionStyle::Emoji => tapback.emoji().to_string(),
+        })
+    }
+
+    /// Format the reaction a deleted tapback applied, if [`TXTFormatting::label_reactions`] is set
+    ///
+    /// Otherwise tapbacks are written with the text the database stores for them, like any other message.
+    pub fn format_tapback_label(&self, message: &Message) -> Option<String> {
+        self.format_tapback(message)
+            .filter(|_| self.formatting.label_reactions)
+    }
+
+    /// Prepare message text for output, rewriting attachment placeholders and wrapping lines
+    pub fn format_text(&self, text: &str) -> String {
+        self.wrap_text(&self.config.options.attachment_placeholder.apply(text))
+    }
+
+    /// Hard-wrap message text at word boundaries, if a wrap width is set
+    ///
+    /// Existing line breaks are kept, and words longer than the width are split across lines. Every line break in
+    /// wrapped text is written as [`TXTFormatting::line_break`].
+    pub fn wrap_text(&self, text: &str) -> String {
+        let Some(width) = self.formatting.wrap_width.filter(|width| *width > 0) else {
+            return text.to_string();
+        };
+
+        let mut lines: Vec<String> = vec![];
+        for line in text.lines() {
+            let mut current = String::new();
+            let mut current_len = 0;
+            for word in line.split(' ').filter(|word| !word.is_empty()) {
+                let mut word: Vec<char> = word.chars().collect();
+
+                // Break words that cannot fit on a line by themselves
+                while word.len() > width {
+                    if current_len > 0 {
+                        lines.push(std::mem::take(&mut current));
+                        current_len = 0;
+                    }
+                    lines.push(word.drain(..width).collect());
+                }
+                if word.is_empty() {
+                    continue;
+                }
+
+                if current_len == 0 {
+                    current.extend(&word);
+                    current_len = word.len();
+                } else if current_len + 1 + word.len() <= width {
+                    current.push(' ');
+                    current.extend(&word);
+                    current_len += 1 + word.len();
+                } else {
+                    lines.push(std::mem::take(&mut current));
+                    current.extend(&word);
+                    current_len = word.len();
+                }
+            }
+            lines.push(current);
+        }
+        lines.join(&self.formatting.line_break)
+    }
+
     pub fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
+        let mut date = self
+            .formatting
+            .timestamp_granularity
+            .format(&self.config.time(&message.date));
+        if !self.formatting.show_read_time {
+            return date;
+        }
         let read_after = message.time_until_read(&self.config.offset);
         if let Some(time) = read_after {
             if !time.is_empty() {
@@ -83,4 +335,255 @@ impl TXT<'_> {
         }
         date
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::read};
+
+    use imessage_database::{tables::messages::Message, util::streamtyped::parse};
+
+    use crate::{
+        app::{
+            attachment_placeholder::AttachmentPlaceholder, options::Options, runtime::Config,
+            timestamp_granularity::TimestampGranularity,
+        },
+        exporters::txt::{ReactionStyle, TXT, TXTFormatting, TimestampPlacement},
+    };
+
+    #[test]
+    fn can_format_header_with_defaults() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt = TXT::new(&config).unwrap();
+
+        let message = Config::fake_message();
+        let time = txt.get_time(&message);
+
+        assert_eq!(
+            txt.format_header("Sender", &message),
+            format!("===Sender:{time}")
+        );
+    }
+
+    #[test]
+    fn can_format_header_with_custom_formatting() {
+        let config = Config::fake_app(Options::fake_options());
+        let default = TXT::new(&config).unwrap();
+        let custom = TXT::with_formatting(
+            &config,
+            TXTFormatting::default()
+                .with_header_prefix("")
+                .with_separator(" | ")
+                .with_timestamp_placement(TimestampPlacement::BeforeSender),
+        )
+        .unwrap();
+
+        let message = Config::fake_message();
+        let time = default.get_time(&message);
+
+        assert_eq!(
+            custom.format_header("Sender", &message),
+            format!("{time} | Sender")
+        );
+        assert_ne!(
+            custom.format_header("Sender", &message),
+            default.format_header("Sender", &message)
+        );
+    }
+
+    #[test]
+    fn can_omit_date_in_time_only_mode() {
+        let mut options = Options::fake_options();
+        options.timestamp_granularity = TimestampGranularity::Time;
+        let config = Config::fake_app(options);
+        let txt = TXT::new(&config).unwrap();
+        let default_config = Config::fake_app(Options::fake_options());
+        let default = TXT::new(&default_config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.date = 674526582885055488;
+
+        let date = message.date(&config.offset).unwrap();
+        let header = txt.format_header("Sender", &message);
+        assert_eq!(header, format!("===Sender:{}", date.format("%-I:%M:%S %p")));
+        assert!(!header.contains(&date.format("%b %d, %Y").to_string()));
+        assert!(
+            default
+                .format_header("Sender", &message)
+                .contains(&date.format("%b %d, %Y").to_string())
+        );
+    }
+
+    #[test]
+    fn can_write_day_heading_when_day_changes() {
+        let mut options = Options::fake_options();
+        options.timestamp_granularity = TimestampGranularity::Time;
+        let config = Config::fake_app(options);
+        let mut txt = TXT::new(&config).unwrap();
+        let default_config = Config::fake_app(Options::fake_options());
+        let mut default = TXT::new(&default_config).unwrap();
+
+        let mut first = Config::fake_message();
+        first.date = 642859200000000000;
+        let mut same_day = Config::fake_message();
+        same_day.date = 642859260000000000;
+        let mut next_day = Config::fake_message();
+        next_day.date = 642945600000000000;
+
+        let day = |message: &Message| {
+            config
+                .time(&message.date)
+                .unwrap()
+                .format("%b %d, %Y")
+                .to_string()
+        };
+        assert_eq!(txt.format_day_heading(&first), Some(day(&first)));
+        assert_eq!(txt.format_day_heading(&same_day), None);
+        assert_eq!(txt.format_day_heading(&next_day), Some(day(&next_day)));
+        // Full timestamps already include the date
+        assert_eq!(default.format_day_heading(&first), None);
+    }
+
+    #[test]
+    fn can_hide_read_time() {
+        let config = Config::fake_app(Options::fake_options());
+        let default = TXT::new(&config).unwrap();
+        let custom =
+            TXT::with_formatting(&config, TXTFormatting::default().with_read_time(false)).unwrap();
+
+        let mut message = Config::fake_message();
+        message.date = 674526582885055488;
+        message.date_read = 674530231992568192;
+
+        assert!(default.get_time(&message).contains("(Read by "));
+        assert!(!custom.get_time(&message).contains("(Read by "));
+    }
+
+    #[test]
+    fn can_format_tapback_styles() {
+        let config = Config::fake_app(Options::fake_options());
+        let default = TXT::new(&config).unwrap();
+        let custom = TXT::with_formatting(
+            &config,
+            TXTFormatting::default().with_reaction_style(ReactionStyle::Emoji),
+        )
+        .unwrap();
+
+        let mut message = Config::fake_message();
+        assert_eq!(default.format_tapback(&message), None);
+
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some(String::from("p:0/A"));
+
+        assert_eq!(default.format_tapback(&message).as_deref(), Some("Loved"));
+        assert_eq!(custom.format_tapback(&message).as_deref(), Some("❤️"));
+        // Custom emoji tapbacks show the emoji in either style
+        message.associated_message_type = Some(2006);
+        message.associated_message_emoji = Some(String::from("🔥"));
+
+        assert_eq!(default.format_tapback(&message).as_deref(), Some("🔥"));
+        assert_eq!(custom.format_tapback(&message).as_deref(), Some("🔥"));
+    }
+
+    #[test]
+    fn can_opt_into_reaction_labels() {
+        let config = Config::fake_app(Options::fake_options());
+        let default = TXT::new(&config).unwrap();
+        let custom =
+            TXT::with_formatting(&config, TXTFormatting::default().with_reaction_labels(true))
+                .unwrap();
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some(String::from("p:0/A"));
+
+        assert_eq!(default.format_tapback_label(&message), None);
+        assert_eq!(
+            custom.format_tapback_label(&message).as_deref(),
+            Some("Loved")
+        );
+    }
+
+    #[test]
+    fn can_wrap_long_message() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt =
+            TXT::with_formatting(&config, TXTFormatting::default().with_wrap_width(40)).unwrap();
+
+        let bytes = read(
+            current_dir()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .join("imessage-database/test_data/typedstream/LongMessage"),
+        )
+        .unwrap();
+        let text = parse(bytes).unwrap();
+
+        let wrapped = txt.wrap_text(&text);
+
+        assert!(wrapped.lines().count() > text.lines().count());
+        assert!(wrapped.lines().all(|line| line.chars().count() <= 40));
+        // Only whitespace changes
+        assert_eq!(
+            wrapped.split_whitespace().collect::<Vec<_>>(),
+            text.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn can_split_words_longer_than_width() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt =
+            TXT::with_formatting(&config, TXTFormatting::default().with_wrap_width(4)).unwrap();
+
+        assert_eq!(txt.wrap_text("a abcdefghij b"), "a\nabcd\nefgh\nij b");
+    }
+
+    #[test]
+    fn can_wrap_with_html_line_breaks() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt = TXT::with_formatting(
+            &config,
+            TXTFormatting::default()
+                .with_wrap_width(5)
+                .with_line_break("<br>"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            txt.format_text("one two three\nfour"),
+            "one<br>two<br>three<br>four"
+        );
+        // Unwrapped text is written as it is stored
+        let default = TXT::new(&config).unwrap();
+        assert_eq!(default.format_text("one two\nthree"), "one two\nthree");
+    }
+
+    #[test]
+    fn can_label_attachment_placeholders() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt = TXT::new(&config).unwrap();
+
+        assert_eq!(txt.format_text("Look! \u{FFFC}"), "Look! [attachment]");
+    }
+
+    #[test]
+    fn can_keep_attachment_placeholders() {
+        let mut options = Options::fake_options();
+        options.attachment_placeholder = AttachmentPlaceholder::Keep;
+        let config = Config::fake_app(options);
+        let txt = TXT::new(&config).unwrap();
+
+        assert_eq!(txt.format_text("Look! \u{FFFC}"), "Look! \u{FFFC}");
+    }
+
+    #[test]
+    fn can_skip_wrapping_by_default() {
+        let config = Config::fake_app(Options::fake_options());
+        let txt = TXT::new(&config).unwrap();
+
+        let text = "word ".repeat(100);
+        assert_eq!(txt.wrap_text(&text), text);
+    }
+}
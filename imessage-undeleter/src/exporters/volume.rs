@@ -0,0 +1,419 @@
+/*!
+ Writes an export file in numbered volumes, starting a new file when the current one grows past a size limit.
+
+ The first volume keeps the requested name and later volumes add their number before the extension, so
+ `media_manifest.csv` is followed by `media_manifest-2.csv`, `media_manifest-3.csv`, and so on. Files are only
+ split between messages: everything written for one message stays in the same volume, even if that alone
+ exceeds the limit. Formats that need closing markup can set a footer, and formats that support links can add
+ navigation between neighboring volumes. Writers can also hold messages until they finish and then write them in
+ ascending `ROWID` order, so an incremental export continues the file in the order its messages were added.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::app::error::RuntimeError;
+
+/// Builds a link to a neighboring volume from its file name and a label like `Next`
+pub type VolumeLink = fn(&str, &str) -> String;
+
+pub struct VolumeWriter {
+    /// Path to the first volume
+    path: PathBuf,
+    /// If set, the size in bytes after which a new volume is started
+    max_bytes: Option<u64>,
+    /// Text written at the start of every volume, like a CSV header row
+    header: String,
+    /// Text written at the end of every volume, like closing HTML tags
+    footer: String,
+    /// If set, used to link each volume to the ones before and after it
+    link: Option<VolumeLink>,
+    /// The volume we are currently writing to
+    file: BufWriter<File>,
+    /// The number of the current volume, starting at 1
+    volume: usize,
+    /// The number of bytes written to the current volume
+    written: u64,
+    /// If true, no messages have been written to the current volume yet
+    empty: bool,
+    /// If true, the current volume was written by an earlier export and still ends with its footer
+    resumed: bool,
+    /// If set, the output for each message `ROWID`, held until the writer finishes
+    pending: Option<BTreeMap<i32, String>>,
+}
+
+impl VolumeWriter {
+    /// Create the first volume at `path` and write its header
+    pub fn create(path: &Path, max_bytes: Option<u64>, header: &str) -> Result<Self, RuntimeError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(header.as_bytes())?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            header: header.to_string(),
+            footer: String::new(),
+            link: None,
+            file,
+            volume: 1,
+            written: header.len() as u64,
+            empty: true,
+            resumed: false,
+            pending: None,
+        })
+    }
+
+    /// Continue the volumes an earlier export wrote at `path`, or create the first volume if there are none
+    ///
+    /// Messages are appended to the last existing volume without writing the header again, and the footer the
+    /// earlier export ended that volume with is replaced once a new message is written.
+    pub fn append(path: &Path, max_bytes: Option<u64>, header: &str) -> Result<Self, RuntimeError> {
+        let mut volume = 1;
+        while volume_path(path, volume + 1).exists() {
+            volume += 1;
+        }
+        let current = volume_path(path, volume);
+        if !current.exists() {
+            return Self::create(path, max_bytes, header);
+        }
+
+        let file = OpenOptions::new().append(true).open(&current)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            header: header.to_string(),
+            footer: String::new(),
+            link: None,
+            file: BufWriter::new(file),
+            volume,
+            written,
+            empty: false,
+            resumed: true,
+            pending: None,
+        })
+    }
+
+    /// Set the text written at the end of every volume
+    #[must_use]
+    pub fn with_footer(mut self, footer: &str) -> Self {
+        self.footer = footer.to_string();
+        self
+    }
+
+    /// Link each volume to the previous and next volumes, placing the links before the footer and after the header
+    #[must_use]
+    pub fn with_navigation(mut self, link: VolumeLink) -> Self {
+        self.link = Some(link);
+        self
+    }
+
+    /// Hold messages written with [`Self::write_row()`] until the writer finishes, then write them in ascending `ROWID`
+    /// order
+    ///
+    /// A message written more than once is only kept once.
+    #[must_use]
+    pub fn sorted_by_rowid(mut self) -> Self {
+        self.pending = Some(BTreeMap::new());
+        self
+    }
+
+    /// Write the output for the message with `rowid`, or hold it until the writer finishes if it is sorted by `ROWID`
+    pub fn write_row(&mut self, rowid: i32, text: &str) -> Result<(), RuntimeError> {
+        match &mut self.pending {
+            Some(pending) => {
+                pending.insert(rowid, text.to_string());
+                Ok(())
+            }
+            None => self.write_message(text),
+        }
+    }
+
+    /// Write the output for a single message, starting a new volume first if it would not fit in the current one
+    pub fn write_message(&mut self, text: &str) -> Result<(), RuntimeError> {
+        if self.resumed {
+            self.strip_footer()?;
+        }
+        if let Some(max_bytes) = self.max_bytes
+            && !self.empty
+            && self.written + text.len() as u64 > max_bytes
+        {
+            self.next_volume()?;
+        }
+        self.file.write_all(text.as_bytes())?;
+        self.written += text.len() as u64;
+        self.empty = false;
+        Ok(())
+    }
+
+    /// Flush the current volume, returning the paths of every volume written
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, RuntimeError> {
+        for text in self.pending.take().unwrap_or_default().into_values() {
+            self.write_message(&text)?;
+        }
+        // A resumed volume that received no messages still ends with its footer
+        if !self.resumed {
+            self.file.write_all(self.footer.as_bytes())?;
+        }
+        self.file.flush()?;
+        Ok((1..=self.volume)
+            .map(|volume| volume_path(&self.path, volume))
+            .collect())
+    }
+
+    /// Close the current volume and open the next one
+    fn next_volume(&mut self) -> Result<(), RuntimeError> {
+        if let Some(link) = self.link {
+            let next = link(&self.volume_name(self.volume + 1), "Next");
+            self.file.write_all(next.as_bytes())?;
+        }
+        self.file.write_all(self.footer.as_bytes())?;
+        self.file.flush()?;
+
+        self.volume += 1;
+        self.file = BufWriter::new(File::create(volume_path(&self.path, self.volume))?);
+        self.file.write_all(self.header.as_bytes())?;
+        self.written = self.header.len() as u64;
+        if let Some(link) = self.link {
+            let previous = link(&self.volume_name(self.volume - 1), "Previous");
+            self.file.write_all(previous.as_bytes())?;
+            self.written += previous.len() as u64;
+        }
+        self.empty = true;
+        Ok(())
+    }
+
+    /// Remove the footer an earlier export ended the current volume with, so new messages are written before it
+    fn strip_footer(&mut self) -> Result<(), RuntimeError> {
+        self.resumed = false;
+        let footer_len = self.footer.len() as u64;
+        if footer_len == 0 || self.written < footer_len {
+            return Ok(());
+        }
+
+        let mut tail = vec![0; self.footer.len()];
+        let mut file = File::open(volume_path(&self.path, self.volume))?;
+        file.seek(SeekFrom::Start(self.written - footer_len))?;
+        file.read_exact(&mut tail)?;
+        if tail == self.footer.as_bytes() {
+            self.written -= footer_len;
+            self.file.get_ref().set_len(self.written)?;
+        }
+        Ok(())
+    }
+
+    /// The file name of a numbered volume, for linking between volumes in the same directory
+    fn volume_name(&self, volume: usize) -> String {
+        volume_path(&self.path, volume)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// The path to a numbered volume of the file at `path`
+pub fn volume_path(path: &Path, volume: usize) -> PathBuf {
+    if volume <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let mut name = format!("{stem}-{volume}");
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+        path::Path,
+    };
+
+    use crate::exporters::volume::{VolumeWriter, volume_path};
+
+    #[test]
+    fn can_name_volumes() {
+        let path = Path::new("/tmp/export/media_manifest.csv");
+        assert_eq!(volume_path(path, 1), path);
+        assert_eq!(
+            volume_path(path, 2),
+            Path::new("/tmp/export/media_manifest-2.csv")
+        );
+        assert_eq!(
+            volume_path(Path::new("/tmp/export/notes"), 3),
+            Path::new("/tmp/export/notes-3")
+        );
+    }
+
+    #[test]
+    fn can_split_at_message_boundaries() {
+        let dir = temp_dir().join("imessage-undeleter-volume-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut writer = VolumeWriter::create(&dir.join("out.txt"), Some(16), "head\n").unwrap();
+        writer.write_message("one\n").unwrap();
+        writer.write_message("two\n").unwrap();
+        // Does not fit after the first two messages, so it starts the second volume
+        writer.write_message("three\n").unwrap();
+        // Larger than the limit on its own, but never split
+        writer.write_message("a very long message\n").unwrap();
+        let volumes = writer.finish().unwrap();
+
+        assert_eq!(volumes.len(), 3);
+        assert_eq!(read_to_string(&volumes[0]).unwrap(), "head\none\ntwo\n");
+        assert_eq!(read_to_string(&volumes[1]).unwrap(), "head\nthree\n");
+        assert_eq!(
+            read_to_string(&volumes[2]).unwrap(),
+            "head\na very long message\n"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_link_between_volumes() {
+        let dir = temp_dir().join("imessage-undeleter-volume-navigation-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut writer = VolumeWriter::create(&dir.join("chat.html"), Some(8), "<body>")
+            .unwrap()
+            .with_footer("</body>")
+            .with_navigation(|file, label| format!("[{label}: {file}]"));
+        writer.write_message("one").unwrap();
+        writer.write_message("two").unwrap();
+        writer.write_message("three").unwrap();
+        let volumes = writer.finish().unwrap();
+
+        assert_eq!(
+            read_to_string(&volumes[0]).unwrap(),
+            "<body>one[Next: chat-2.html]</body>"
+        );
+        assert_eq!(
+            read_to_string(&volumes[1]).unwrap(),
+            "<body>[Previous: chat.html]two[Next: chat-3.html]</body>"
+        );
+        assert_eq!(
+            read_to_string(&volumes[2]).unwrap(),
+            "<body>[Previous: chat-2.html]three</body>"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_append_to_earlier_volumes() {
+        let dir = temp_dir().join("imessage-undeleter-volume-append-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("chat.html");
+
+        let mut writer = VolumeWriter::create(&path, Some(16), "<body>")
+            .unwrap()
+            .with_footer("</body>");
+        writer.write_message("one").unwrap();
+        writer.write_message("two").unwrap();
+        writer.write_message("three").unwrap();
+        writer.finish().unwrap();
+
+        // New messages continue the last volume, before its footer
+        let mut writer = VolumeWriter::append(&path, Some(16), "<body>")
+            .unwrap()
+            .with_footer("</body>");
+        writer.write_message("four").unwrap();
+        writer.write_message("five").unwrap();
+        let volumes = writer.finish().unwrap();
+
+        assert_eq!(volumes.len(), 3);
+        assert_eq!(read_to_string(&volumes[0]).unwrap(), "<body>onetwo</body>");
+        assert_eq!(
+            read_to_string(&volumes[1]).unwrap(),
+            "<body>threefour</body>"
+        );
+        assert_eq!(read_to_string(&volumes[2]).unwrap(), "<body>five</body>");
+
+        // Appending nothing leaves the files as they were
+        VolumeWriter::append(&path, Some(16), "<body>")
+            .unwrap()
+            .with_footer("</body>")
+            .finish()
+            .unwrap();
+        assert_eq!(read_to_string(&volumes[2]).unwrap(), "<body>five</body>");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_append_without_earlier_export() {
+        let dir = temp_dir().join("imessage-undeleter-volume-append-new-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut writer = VolumeWriter::append(&dir.join("out.csv"), None, "head\n").unwrap();
+        writer.write_message("one\n").unwrap();
+        let volumes = writer.finish().unwrap();
+
+        assert_eq!(read_to_string(&volumes[0]).unwrap(), "head\none\n");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_sort_rows_by_rowid() {
+        let dir = temp_dir().join("imessage-undeleter-volume-sorted-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("out.csv");
+
+        let mut writer = VolumeWriter::create(&path, None, "head\n").unwrap();
+        writer.write_row(2, "two\n").unwrap();
+        writer.write_row(1, "one\n").unwrap();
+        writer.finish().unwrap();
+
+        // Rows are held until the writer finishes, and a repeated message is only written once
+        let mut writer = VolumeWriter::append(&path, None, "head\n")
+            .unwrap()
+            .sorted_by_rowid();
+        writer.write_row(5, "five\n").unwrap();
+        writer.write_row(3, "three\n").unwrap();
+        writer.write_row(5, "five\n").unwrap();
+        writer.write_row(4, "four\n").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "head\ntwo\none\n");
+        writer.finish().unwrap();
+
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "head\ntwo\none\nthree\nfour\nfive\n"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_write_single_volume_without_limit() {
+        let dir = temp_dir().join("imessage-undeleter-volume-unlimited-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        let mut writer = VolumeWriter::create(&dir.join("out.txt"), None, "").unwrap();
+        for _ in 0..100 {
+            writer.write_message("message\n").unwrap();
+        }
+        let volumes = writer.finish().unwrap();
+
+        assert_eq!(volumes, vec![dir.join("out.txt")]);
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
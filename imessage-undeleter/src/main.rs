@@ -5,33 +5,64 @@ mod exporters;
 
 pub use exporters::txt::TXT;
 
+use std::path::PathBuf;
+
+use imessage_database::util::dirs::default_db_path;
+
 use app::{
-    options::{Options, from_command_line},
+    error::error_chain,
+    options::{
+        DEFAULT_OUTPUT_DIR, OPTION_DB_PATH, OPTION_EXPORT_ALL, OPTION_EXPORT_PATH, Options,
+        from_command_line,
+    },
+    quick_export::export_everything,
     runtime::Config,
 };
 
 fn main() {
     // Get args from command line
     let args = from_command_line();
+
+    // Export everything with the default settings, if requested
+    if args.get_flag(OPTION_EXPORT_ALL) {
+        let db_path = args
+            .get_one::<String>(OPTION_DB_PATH)
+            .map_or_else(default_db_path, PathBuf::from);
+        let export_path = args.get_one::<String>(OPTION_EXPORT_PATH).map_or_else(
+            || PathBuf::from(format!("./{DEFAULT_OUTPUT_DIR}")),
+            PathBuf::from,
+        );
+        if let Err(why) = export_everything(&db_path, &export_path) {
+            eprintln!("Unable to export: {}", error_chain(&why));
+        }
+        return;
+    }
+
     // Create application options
     let options = Options::from_args(&args);
 
     // Create app state and start
     match options {
         Err(why) => {
-            eprintln!("{why}");
+            eprintln!("{}", error_chain(&why));
         },
         Ok(options) => match Config::new(options) {
             Ok(mut app) => {
                 // Resolve the filtered contacts, if provided
                 app.resolve_filtered_handles();
 
+                // Keep only recently active chats, if requested
+                if let Err(why) = app.resolve_active_chats() {
+                    eprintln!("Invalid configuration: {}", error_chain(&why));
+                    return;
+                }
+
                 if let Err(why) = app.start() {
-                    eprintln!("Unable to start: {why}");
+                    eprintln!("Unable to start: {}", error_chain(&why));
                 }
             }
             Err(why) => {
-                eprintln!("Invalid configuration: {why}");
+                eprintln!("Invalid configuration: {}", error_chain(&why));
             }
         }
     }